@@ -19,6 +19,7 @@ import {
     Modal,
     Stack,
     Rating,
+    Textarea,
 } from '@mantine/core';
 import { IconAlertCircle, IconPackage } from '@tabler/icons-react';
 
@@ -28,6 +29,7 @@ import { IconAlertCircle, IconPackage } from '@tabler/icons-react';
 function RatingModal({ orderId, opened, onClose, onRated }) {
     const [qualityRating, setQualityRating] = useState(0);
     const [communicationRating, setCommunicationRating] = useState(0);
+    const [review, setReview] = useState('');
     const [isSubmitting, setIsSubmitting] = useState(false);
 
     const handleSubmit = async () => {
@@ -40,6 +42,7 @@ function RatingModal({ orderId, opened, onClose, onRated }) {
             await api.rateOrder(orderId, {
                 quality_rating: qualityRating,
                 communication_rating: communicationRating,
+                review: review || null,
             });
             alert("Thank you for your feedback!");
             onRated(); // 通知父组件刷新
@@ -56,6 +59,7 @@ function RatingModal({ orderId, opened, onClose, onRated }) {
     const handleClose = () => {
         setQualityRating(0);
         setCommunicationRating(0);
+        setReview('');
         onClose();
     };
 
@@ -70,6 +74,15 @@ function RatingModal({ orderId, opened, onClose, onRated }) {
                     <Text fw={500}>Communication & Service</Text>
                     <Rating value={communicationRating} onChange={setCommunicationRating} size="lg" />
                 </div>
+                <Textarea
+                    label="Review (optional)"
+                    placeholder="How was working with this supplier?"
+                    value={review}
+                    onChange={(e) => setReview(e.currentTarget.value)}
+                    maxLength={2000}
+                    autosize
+                    minRows={3}
+                />
                 <Button onClick={handleSubmit} loading={isSubmitting} mt="md">
                     Submit Rating
                 </Button>
@@ -154,8 +167,8 @@ function OrdersPage() {
             data={[
                 { value: 'PENDING_CONFIRMATION', label: 'Pending Confirmation', disabled: true },
                 { value: 'IN_PRODUCTION', label: 'In Production' },
-                { value: 'SHIPPED', label: 'Shipped' },
-                { value: 'COMPLETED', label: 'Completed' },
+                { value: 'SHIPPED', label: 'Shipped', disabled: true }, // 发货记录覆盖全部数量后自动变更
+                { value: 'COMPLETED', label: 'Completed', disabled: true }, // 收货检验完毕后自动完成
             ]}
         />
     );
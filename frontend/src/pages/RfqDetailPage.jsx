@@ -44,7 +44,7 @@ function CreateQuoteForm({ rfqId, onQuoteSubmitted }) {
         e.preventDefault();
         setIsSubmitting(true);
         try {
-            await api.createQuote(rfqId, { price: parseFloat(price), lead_time_days: parseInt(lead_time_days), notes });
+            await api.createQuote(rfqId, { price: String(price), lead_time_days: parseInt(lead_time_days), notes });
             alert('Quote submitted successfully!');
             onQuoteSubmitted();
         } catch (error) {
@@ -0,0 +1,5 @@
+-- 授标时未中标的报价会被标记为 REJECTED，这里保存拒绝原因和匿名价格反馈
+ALTER TABLE `quotes`
+    ADD COLUMN `rejection_reason` VARCHAR(500) NULL AFTER `status`,
+    ADD COLUMN `price_feedback` VARCHAR(255) NULL COMMENT '例如: You were 12% above the winning bid' AFTER `rejection_reason`,
+    ADD COLUMN `rejected_at` TIMESTAMP NULL AFTER `price_feedback`;
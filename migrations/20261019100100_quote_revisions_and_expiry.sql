@@ -0,0 +1,8 @@
+-- 报价版本号与有效期，同一供应商对同一RFQ只保留一个有效(SUBMITTED)报价，修改时版本号+1
+ALTER TABLE `quotes`
+    ADD COLUMN `revision` INT NOT NULL DEFAULT 1 AFTER `notes`,
+    ADD COLUMN `expires_at` TIMESTAMP NULL AFTER `revision`,
+    ADD COLUMN `updated_at` TIMESTAMP NULL DEFAULT NULL ON UPDATE CURRENT_TIMESTAMP AFTER `created_at`;
+
+-- 供应商“我的报价”列表按公司和状态查询
+CREATE INDEX `idx_quotes_supplier_status` ON `quotes` (`supplier_company_id`, `status`);
@@ -0,0 +1,19 @@
+-- 多币种支持：报价和订单带币种，公司设置报表币种，汇率表统一折算
+ALTER TABLE `quotes`
+    ADD COLUMN `currency` CHAR(3) NOT NULL DEFAULT 'USD' AFTER `price`;
+
+ALTER TABLE `purchase_orders`
+    ADD COLUMN `currency` CHAR(3) NOT NULL DEFAULT 'USD' AFTER `total_amount`;
+
+ALTER TABLE `companies`
+    ADD COLUMN `reporting_currency` CHAR(3) NOT NULL DEFAULT 'USD' AFTER `description`;
+
+-- 汇率表：usd_rate 表示 1 单位该币种折合多少美元，换算时 amount * rate(from) / rate(to)
+CREATE TABLE `fx_rates` (
+    `currency` CHAR(3) PRIMARY KEY,
+    `usd_rate` DECIMAL(18, 8) NOT NULL,
+    `source` VARCHAR(50) NOT NULL DEFAULT 'MANUAL' COMMENT '例如: FILE, ADMIN',
+    `updated_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP
+) ENGINE=InnoDB;
+
+INSERT INTO `fx_rates` (`currency`, `usd_rate`, `source`) VALUES ('USD', 1, 'SEED');
@@ -0,0 +1,22 @@
+-- 供应商对RFQ的意向：准备报价 / 不参与(附原因)
+CREATE TABLE `rfq_supplier_responses` (
+    `rfq_id` INT NOT NULL,
+    `supplier_company_id` INT NOT NULL,
+    `response` ENUM('INTEND_TO_QUOTE', 'DECLINED') NOT NULL,
+    `reason_code` ENUM('CAPACITY', 'CAPABILITY', 'PRICE', 'TIMELINE') NULL COMMENT '仅 DECLINED 时填写',
+    `comment` VARCHAR(500) NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `updated_at` TIMESTAMP NULL DEFAULT NULL ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (`rfq_id`, `supplier_company_id`),
+    FOREIGN KEY (`rfq_id`) REFERENCES `rfqs`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`supplier_company_id`) REFERENCES `companies`(`id`) ON DELETE CASCADE
+) ENGINE=InnoDB;
+
+-- 记录匹配服务为每个RFQ命中的能力标签，用于按能力统计供应商的拒绝历史
+CREATE TABLE `rfq_matched_capabilities` (
+    `rfq_id` INT NOT NULL,
+    `capability_id` INT NOT NULL,
+    PRIMARY KEY (`rfq_id`, `capability_id`),
+    FOREIGN KEY (`rfq_id`) REFERENCES `rfqs`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`capability_id`) REFERENCES `capabilities`(`id`) ON DELETE CASCADE
+) ENGINE=InnoDB;
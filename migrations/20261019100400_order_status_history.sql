@@ -0,0 +1,19 @@
+-- 采购订单状态变更历史，记录操作人、时间和备注
+CREATE TABLE `order_status_history` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `order_id` INT NOT NULL,
+    `from_status` VARCHAR(30) NULL COMMENT '订单创建时为空',
+    `to_status` VARCHAR(30) NOT NULL,
+    `actor_user_id` INT NULL COMMENT '系统自动变更时为空',
+    `actor_company_id` INT NULL,
+    `comment` VARCHAR(500) NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (`order_id`) REFERENCES `purchase_orders`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`actor_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`actor_company_id`) REFERENCES `companies`(`id`) ON DELETE SET NULL,
+    INDEX `idx_order_status_history_order` (`order_id`, `created_at`)
+) ENGINE=InnoDB;
+
+-- 已有订单补一条初始记录
+INSERT INTO `order_status_history` (`order_id`, `from_status`, `to_status`, `created_at`)
+SELECT `id`, NULL, 'PENDING_CONFIRMATION', `created_at` FROM `purchase_orders`;
@@ -0,0 +1,71 @@
+-- 订单取消和争议处理
+ALTER TABLE `purchase_orders`
+    MODIFY `status` ENUM('PENDING_CONFIRMATION', 'IN_PRODUCTION', 'SHIPPED', 'COMPLETED', 'CANCELLED') NOT NULL DEFAULT 'PENDING_CONFIRMATION',
+    MODIFY `payment_status` ENUM('UNPAID', 'PAID', 'FAILED', 'REFUND_PENDING') NOT NULL DEFAULT 'UNPAID';
+
+-- 取消申请：由一方发起，另一方同意后订单才会取消
+CREATE TABLE `order_cancellation_requests` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `order_id` INT NOT NULL,
+    `requested_by_company_id` INT NOT NULL,
+    `requested_by_user_id` INT NULL,
+    `reason` VARCHAR(1000) NOT NULL,
+    `status` ENUM('PENDING', 'ACCEPTED', 'REJECTED') NOT NULL DEFAULT 'PENDING',
+    `responded_by_user_id` INT NULL,
+    `response_comment` VARCHAR(1000) NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `responded_at` TIMESTAMP NULL,
+    FOREIGN KEY (`order_id`) REFERENCES `purchase_orders`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`requested_by_company_id`) REFERENCES `companies`(`id`),
+    FOREIGN KEY (`requested_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`responded_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    INDEX `idx_cancellation_order_status` (`order_id`, `status`)
+) ENGINE=InnoDB;
+
+-- 争议：订单双方都可以发起，由管理员仲裁
+CREATE TABLE `disputes` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `order_id` INT NOT NULL,
+    `opened_by_company_id` INT NOT NULL,
+    `opened_by_user_id` INT NULL,
+    `reason` VARCHAR(2000) NOT NULL,
+    `status` ENUM('OPEN', 'RESOLVED') NOT NULL DEFAULT 'OPEN',
+    `resolution` ENUM('REFUND', 'REWORK', 'CLOSED') NULL,
+    `resolution_note` VARCHAR(2000) NULL,
+    `refund_amount` DECIMAL(12, 2) NULL COMMENT '仅在 REFUND 时有值，币种与订单一致',
+    `resolved_by_user_id` INT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `resolved_at` TIMESTAMP NULL,
+    FOREIGN KEY (`order_id`) REFERENCES `purchase_orders`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`opened_by_company_id`) REFERENCES `companies`(`id`),
+    FOREIGN KEY (`opened_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`resolved_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    INDEX `idx_disputes_order_status` (`order_id`, `status`),
+    INDEX `idx_disputes_status` (`status`, `created_at`)
+) ENGINE=InnoDB;
+
+-- 争议沟通记录，管理员也可以留言
+CREATE TABLE `dispute_messages` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `dispute_id` INT NOT NULL,
+    `sender_user_id` INT NULL,
+    `sender_company_id` INT NULL,
+    `is_admin` BOOLEAN NOT NULL DEFAULT FALSE,
+    `content` TEXT NOT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (`dispute_id`) REFERENCES `disputes`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`sender_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`sender_company_id`) REFERENCES `companies`(`id`) ON DELETE SET NULL
+) ENGINE=InnoDB;
+
+-- 争议证据附件
+CREATE TABLE `dispute_attachments` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `dispute_id` INT NOT NULL,
+    `uploaded_by_user_id` INT NULL,
+    `original_filename` VARCHAR(255) NOT NULL,
+    `stored_path` VARCHAR(255) NOT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (`dispute_id`) REFERENCES `disputes`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`uploaded_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL
+) ENGINE=InnoDB;
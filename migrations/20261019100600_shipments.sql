@@ -0,0 +1,22 @@
+-- 订单数量：从RFQ复制，分批发货时用来判断是否已全部发出
+ALTER TABLE `purchase_orders` ADD COLUMN `quantity` INT NOT NULL DEFAULT 0 AFTER `supplier_company_id`;
+
+UPDATE `purchase_orders` po JOIN `rfqs` r ON po.`rfq_id` = r.`id` SET po.`quantity` = r.`quantity`;
+
+-- 发货记录：一个订单可以分多批发货
+CREATE TABLE `shipments` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `order_id` INT NOT NULL,
+    `carrier` VARCHAR(100) NOT NULL,
+    `tracking_number` VARCHAR(100) NOT NULL,
+    `shipped_quantity` INT NOT NULL,
+    `estimated_arrival` DATE NULL,
+    `notes` VARCHAR(1000) NULL,
+    `packing_list_filename` VARCHAR(255) NULL,
+    `packing_list_path` VARCHAR(255) NULL,
+    `created_by_user_id` INT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (`order_id`) REFERENCES `purchase_orders`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`created_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    INDEX `idx_shipments_order` (`order_id`, `created_at`)
+) ENGINE=InnoDB;
@@ -0,0 +1,55 @@
+-- 采购方收货和来料检验
+CREATE TABLE `goods_receipts` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `order_id` INT NOT NULL,
+    `shipment_id` INT NULL COMMENT '对应的发货批次，可为空',
+    `received_quantity` INT NOT NULL,
+    `accepted_quantity` INT NOT NULL,
+    `rejected_quantity` INT NOT NULL,
+    `notes` VARCHAR(1000) NULL,
+    `received_by_user_id` INT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (`order_id`) REFERENCES `purchase_orders`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`shipment_id`) REFERENCES `shipments`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`received_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    INDEX `idx_goods_receipts_order` (`order_id`)
+) ENGINE=InnoDB;
+
+-- 不合格品报告(NCR)：收货有拒收时创建，供应商回复纠正措施，采购方确认后关闭
+CREATE TABLE `ncrs` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `order_id` INT NOT NULL,
+    `goods_receipt_id` INT NOT NULL,
+    `supplier_company_id` INT NOT NULL,
+    `defect_category` ENUM('DIMENSIONAL', 'MATERIAL', 'SURFACE_FINISH', 'FUNCTIONAL', 'PACKAGING', 'DOCUMENTATION', 'OTHER') NOT NULL,
+    `description` VARCHAR(2000) NOT NULL,
+    `rejected_quantity` INT NOT NULL,
+    `status` ENUM('OPEN', 'RESPONDED', 'CLOSED') NOT NULL DEFAULT 'OPEN',
+    `root_cause` VARCHAR(2000) NULL,
+    `corrective_action` VARCHAR(2000) NULL,
+    `responded_by_user_id` INT NULL,
+    `responded_at` TIMESTAMP NULL,
+    `closed_by_user_id` INT NULL,
+    `closing_comment` VARCHAR(1000) NULL,
+    `closed_at` TIMESTAMP NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (`order_id`) REFERENCES `purchase_orders`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`goods_receipt_id`) REFERENCES `goods_receipts`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`supplier_company_id`) REFERENCES `companies`(`id`),
+    FOREIGN KEY (`responded_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`closed_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    INDEX `idx_ncrs_order` (`order_id`),
+    INDEX `idx_ncrs_supplier` (`supplier_company_id`, `created_at`)
+) ENGINE=InnoDB;
+
+-- NCR 缺陷照片
+CREATE TABLE `ncr_photos` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `ncr_id` INT NOT NULL,
+    `uploaded_by_user_id` INT NULL,
+    `original_filename` VARCHAR(255) NOT NULL,
+    `stored_path` VARCHAR(255) NOT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (`ncr_id`) REFERENCES `ncrs`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`uploaded_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL
+) ENGINE=InnoDB;
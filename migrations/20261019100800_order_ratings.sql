@@ -0,0 +1,6 @@
+-- 评价：rate_order 一直在写 communication_rating，但这个列从来没有建过
+ALTER TABLE `purchase_orders`
+    ADD COLUMN `communication_rating` TINYINT UNSIGNED NULL COMMENT '1-5 star rating' AFTER `quality_rating`,
+    ADD COLUMN `review_text` VARCHAR(2000) NULL AFTER `communication_rating`,
+    ADD COLUMN `rated_at` TIMESTAMP NULL AFTER `review_text`,
+    ADD INDEX `idx_purchase_orders_supplier_rated` (`supplier_company_id`, `rated_at`);
@@ -0,0 +1,21 @@
+-- 采购订单编号
+ALTER TABLE `purchase_orders` ADD COLUMN `po_number` VARCHAR(30) NULL UNIQUE AFTER `id`;
+
+UPDATE `purchase_orders` SET `po_number` = CONCAT('PO-', YEAR(`created_at`), '-', LPAD(`id`, 6, '0'));
+
+-- 订单相关的正式文件（PO、发票等）。生成后不再修改，sha256 用于下载时校验
+CREATE TABLE `documents` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `order_id` INT NOT NULL,
+    `document_type` VARCHAR(30) NOT NULL,
+    `document_number` VARCHAR(50) NOT NULL,
+    `version` INT NOT NULL DEFAULT 1,
+    `filename` VARCHAR(255) NOT NULL,
+    `stored_path` VARCHAR(255) NOT NULL,
+    `content_type` VARCHAR(100) NOT NULL,
+    `size_bytes` INT NOT NULL,
+    `sha256` CHAR(64) NOT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (`order_id`) REFERENCES `purchase_orders`(`id`) ON DELETE CASCADE,
+    UNIQUE KEY `uq_documents_order_type_version` (`order_id`, `document_type`, `version`)
+) ENGINE=InnoDB;
@@ -0,0 +1,63 @@
+-- 供应商开票
+-- 每个公司独立的发票流水号，在事务中加锁递增，保证连续不重复
+CREATE TABLE `invoice_sequences` (
+    `company_id` INT PRIMARY KEY,
+    `next_number` INT NOT NULL DEFAULT 1,
+    FOREIGN KEY (`company_id`) REFERENCES `companies`(`id`) ON DELETE CASCADE
+) ENGINE=InnoDB;
+
+CREATE TABLE `invoices` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `invoice_number` VARCHAR(40) NOT NULL,
+    `order_id` INT NOT NULL,
+    `shipment_id` INT NULL COMMENT '按发货批次开票时有值',
+    `supplier_company_id` INT NOT NULL,
+    `buyer_company_id` INT NOT NULL,
+    `quantity` INT NOT NULL,
+    `currency` CHAR(3) NOT NULL,
+    `subtotal` DECIMAL(12, 2) NOT NULL,
+    `tax_total` DECIMAL(12, 2) NOT NULL,
+    `total` DECIMAL(12, 2) NOT NULL,
+    `issue_date` DATE NOT NULL,
+    `due_date` DATE NOT NULL,
+    `notes` VARCHAR(1000) NULL,
+    `payment_status` ENUM('UNPAID', 'PAID', 'FAILED', 'REFUND_PENDING') NOT NULL DEFAULT 'UNPAID',
+    `stripe_session_id` VARCHAR(255) NULL,
+    `paid_at` TIMESTAMP NULL,
+    `created_by_user_id` INT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (`order_id`) REFERENCES `purchase_orders`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`shipment_id`) REFERENCES `shipments`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`supplier_company_id`) REFERENCES `companies`(`id`),
+    FOREIGN KEY (`buyer_company_id`) REFERENCES `companies`(`id`),
+    FOREIGN KEY (`created_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    UNIQUE KEY `uq_invoices_supplier_number` (`supplier_company_id`, `invoice_number`),
+    UNIQUE KEY `uq_invoices_shipment` (`shipment_id`),
+    INDEX `idx_invoices_order` (`order_id`),
+    INDEX `idx_invoices_stripe_session` (`stripe_session_id`)
+) ENGINE=InnoDB;
+
+CREATE TABLE `invoice_lines` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `invoice_id` INT NOT NULL,
+    `description` VARCHAR(500) NOT NULL,
+    `quantity` INT NOT NULL,
+    `unit_price` DECIMAL(14, 4) NOT NULL,
+    `line_total` DECIMAL(12, 2) NOT NULL,
+    FOREIGN KEY (`invoice_id`) REFERENCES `invoices`(`id`) ON DELETE CASCADE
+) ENGINE=InnoDB;
+
+CREATE TABLE `invoice_tax_lines` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `invoice_id` INT NOT NULL,
+    `name` VARCHAR(50) NOT NULL,
+    `rate` DECIMAL(5, 2) NOT NULL COMMENT '百分比，例如 19.00',
+    `taxable_amount` DECIMAL(12, 2) NOT NULL,
+    `tax_amount` DECIMAL(12, 2) NOT NULL,
+    FOREIGN KEY (`invoice_id`) REFERENCES `invoices`(`id`) ON DELETE CASCADE
+) ENGINE=InnoDB;
+
+-- 一个订单可以有多张发票，文件按编号区分
+ALTER TABLE `documents`
+    DROP INDEX `uq_documents_order_type_version`,
+    ADD UNIQUE KEY `uq_documents_order_type_number_version` (`order_id`, `document_type`, `document_number`, `version`);
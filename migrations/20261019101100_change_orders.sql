@@ -0,0 +1,36 @@
+-- 承诺交期：授标时按报价交货周期计算，之后只能通过变更单修改
+ALTER TABLE `purchase_orders`
+    ADD COLUMN `promised_delivery_date` DATE NULL AFTER `currency`,
+    ADD COLUMN `amendment_number` INT NOT NULL DEFAULT 0 AFTER `promised_delivery_date`;
+
+UPDATE `purchase_orders` po JOIN `quotes` q ON po.`quote_id` = q.`id`
+SET po.`promised_delivery_date` = DATE_ADD(DATE(po.`created_at`), INTERVAL q.`lead_time_days` DAY);
+
+-- 变更单：一方提出数量、单价、交期的修改，另一方同意后生效，生效时分配修订号
+CREATE TABLE `change_orders` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `order_id` INT NOT NULL,
+    `proposed_by_company_id` INT NOT NULL,
+    `proposed_by_user_id` INT NULL,
+    `reason` VARCHAR(1000) NOT NULL,
+    `old_quantity` INT NOT NULL,
+    `new_quantity` INT NULL,
+    `old_unit_price` DECIMAL(14, 4) NOT NULL,
+    `new_unit_price` DECIMAL(14, 4) NULL,
+    `old_delivery_date` DATE NULL,
+    `new_delivery_date` DATE NULL,
+    `old_total_amount` DECIMAL(12, 2) NOT NULL,
+    `new_total_amount` DECIMAL(12, 2) NULL COMMENT '同意时重新计算',
+    `status` ENUM('PENDING', 'ACCEPTED', 'REJECTED', 'WITHDRAWN') NOT NULL DEFAULT 'PENDING',
+    `amendment_number` INT NULL COMMENT '同意后才分配',
+    `responded_by_user_id` INT NULL,
+    `response_comment` VARCHAR(1000) NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `responded_at` TIMESTAMP NULL,
+    FOREIGN KEY (`order_id`) REFERENCES `purchase_orders`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`proposed_by_company_id`) REFERENCES `companies`(`id`),
+    FOREIGN KEY (`proposed_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`responded_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    UNIQUE KEY `uq_change_orders_amendment` (`order_id`, `amendment_number`),
+    INDEX `idx_change_orders_order_status` (`order_id`, `status`)
+) ENGINE=InnoDB;
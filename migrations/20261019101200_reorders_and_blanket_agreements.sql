@@ -0,0 +1,49 @@
+-- 再订货和框架协议下的订单沿用原报价的条款，一个报价可以对应多个订单
+ALTER TABLE `purchase_orders`
+    DROP FOREIGN KEY `purchase_orders_ibfk_1`,
+    DROP INDEX `quote_id`,
+    ADD INDEX `idx_purchase_orders_quote` (`quote_id`),
+    ADD CONSTRAINT `fk_purchase_orders_quote` FOREIGN KEY (`quote_id`) REFERENCES `quotes`(`id`);
+
+-- 框架协议：买卖双方约定单价和总数量，在有效期内分批下达订单
+CREATE TABLE `blanket_agreements` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `agreement_number` VARCHAR(30) NULL UNIQUE,
+    `buyer_company_id` INT NOT NULL,
+    `supplier_company_id` INT NOT NULL,
+    `source_order_id` INT NOT NULL COMMENT '协议基于的历史订单，零件和报价条款从这里来',
+    `rfq_id` INT NOT NULL,
+    `quote_id` INT NOT NULL,
+    `unit_price` DECIMAL(14, 4) NOT NULL,
+    `currency` VARCHAR(3) NOT NULL,
+    `total_quantity` INT NOT NULL,
+    `start_date` DATE NOT NULL,
+    `end_date` DATE NOT NULL,
+    `notes` VARCHAR(1000) NULL,
+    `status` ENUM('PROPOSED', 'ACTIVE', 'REJECTED', 'CLOSED') NOT NULL DEFAULT 'PROPOSED',
+    `proposed_by_company_id` INT NOT NULL,
+    `proposed_by_user_id` INT NULL,
+    `responded_by_user_id` INT NULL,
+    `response_comment` VARCHAR(1000) NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `responded_at` TIMESTAMP NULL,
+    `closed_at` TIMESTAMP NULL,
+    FOREIGN KEY (`buyer_company_id`) REFERENCES `companies`(`id`),
+    FOREIGN KEY (`supplier_company_id`) REFERENCES `companies`(`id`),
+    FOREIGN KEY (`source_order_id`) REFERENCES `purchase_orders`(`id`),
+    FOREIGN KEY (`rfq_id`) REFERENCES `rfqs`(`id`),
+    FOREIGN KEY (`quote_id`) REFERENCES `quotes`(`id`),
+    FOREIGN KEY (`proposed_by_company_id`) REFERENCES `companies`(`id`),
+    FOREIGN KEY (`proposed_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`responded_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    INDEX `idx_blanket_buyer` (`buyer_company_id`, `status`),
+    INDEX `idx_blanket_supplier` (`supplier_company_id`, `status`)
+) ENGINE=InnoDB;
+
+-- 订单来源：再订货的原订单，或者框架协议下的分批订单（release）
+ALTER TABLE `purchase_orders`
+    ADD COLUMN `source_order_id` INT NULL AFTER `rfq_id`,
+    ADD COLUMN `blanket_agreement_id` INT NULL AFTER `source_order_id`,
+    ADD CONSTRAINT `fk_purchase_orders_source` FOREIGN KEY (`source_order_id`) REFERENCES `purchase_orders`(`id`),
+    ADD CONSTRAINT `fk_purchase_orders_blanket` FOREIGN KEY (`blanket_agreement_id`) REFERENCES `blanket_agreements`(`id`),
+    ADD INDEX `idx_purchase_orders_blanket` (`blanket_agreement_id`, `status`);
@@ -0,0 +1,15 @@
+-- 交期跟踪：定时任务按承诺交期标记有风险/已延期的订单，全部发货时记录准时或延期
+ALTER TABLE `purchase_orders`
+    ADD COLUMN `delivery_status` ENUM('ON_TRACK', 'AT_RISK', 'LATE') NOT NULL DEFAULT 'ON_TRACK' AFTER `amendment_number`,
+    ADD COLUMN `delivery_outcome` ENUM('ON_TIME', 'LATE') NULL AFTER `delivery_status`,
+    ADD COLUMN `delivered_on` DATE NULL AFTER `delivery_outcome`,
+    ADD COLUMN `days_late` INT NULL AFTER `delivered_on`,
+    ADD INDEX `idx_purchase_orders_delivery` (`status`, `promised_delivery_date`);
+
+-- 已经全部发货的历史订单按最后一批发货日期补记结果
+UPDATE `purchase_orders` po
+JOIN (SELECT `order_id`, DATE(MAX(`created_at`)) AS `shipped_on` FROM `shipments` GROUP BY `order_id`) s ON s.`order_id` = po.`id`
+SET po.`delivered_on` = s.`shipped_on`,
+    po.`days_late` = GREATEST(DATEDIFF(s.`shipped_on`, po.`promised_delivery_date`), 0),
+    po.`delivery_outcome` = IF(s.`shipped_on` <= po.`promised_delivery_date`, 'ON_TIME', 'LATE')
+WHERE po.`status` IN ('SHIPPED', 'COMPLETED') AND po.`promised_delivery_date` IS NOT NULL;
@@ -0,0 +1,29 @@
+-- 支付渠道事件日志：每个Webhook事件都落库，按 (provider, event_id) 去重
+CREATE TABLE `payment_events` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `provider` VARCHAR(20) NOT NULL,
+    `event_id` VARCHAR(255) NOT NULL,
+    `event_type` VARCHAR(100) NOT NULL,
+    `payload` MEDIUMTEXT NOT NULL,
+    `status` ENUM('RECEIVED', 'PROCESSED', 'IGNORED', 'FAILED') NOT NULL DEFAULT 'RECEIVED',
+    `detail` VARCHAR(1000) NULL COMMENT '处理结果说明或失败原因',
+    `attempts` INT NOT NULL DEFAULT 1,
+    `received_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `processed_at` TIMESTAMP NULL,
+    UNIQUE KEY `uq_payment_events_provider_event` (`provider`, `event_id`),
+    INDEX `idx_payment_events_status` (`status`)
+) ENGINE=InnoDB;
+
+-- 支付成功后记下渠道的付款ID，退款和拒付事件靠它找到订单或发票
+ALTER TABLE `purchase_orders`
+    MODIFY `payment_status` ENUM('UNPAID', 'PAID', 'FAILED', 'REFUND_PENDING', 'PARTIALLY_REFUNDED', 'REFUNDED') NOT NULL DEFAULT 'UNPAID',
+    ADD COLUMN `payment_reference` VARCHAR(255) NULL AFTER `stripe_session_id`,
+    ADD COLUMN `chargeback_opened_at` TIMESTAMP NULL AFTER `payment_reference`,
+    ADD INDEX `idx_purchase_orders_stripe_session` (`stripe_session_id`),
+    ADD INDEX `idx_purchase_orders_payment_reference` (`payment_reference`);
+
+ALTER TABLE `invoices`
+    MODIFY `payment_status` ENUM('UNPAID', 'PAID', 'FAILED', 'REFUND_PENDING', 'PARTIALLY_REFUNDED', 'REFUNDED') NOT NULL DEFAULT 'UNPAID',
+    ADD COLUMN `payment_reference` VARCHAR(255) NULL AFTER `stripe_session_id`,
+    ADD COLUMN `chargeback_opened_at` TIMESTAMP NULL AFTER `payment_reference`,
+    ADD INDEX `idx_invoices_payment_reference` (`payment_reference`);
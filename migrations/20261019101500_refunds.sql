@@ -0,0 +1,33 @@
+-- 退款：买方申请，供应商或管理员通过支付渠道退款；可以关联争议或取消申请
+CREATE TABLE `refunds` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `order_id` INT NOT NULL,
+    `invoice_id` INT NULL COMMENT '按发票付款的订单，退的是某张发票的付款',
+    `dispute_id` INT NULL,
+    `cancellation_request_id` INT NULL,
+    `amount` DECIMAL(12, 2) NOT NULL,
+    `currency` CHAR(3) NOT NULL,
+    `reason` VARCHAR(1000) NOT NULL,
+    `status` ENUM('REQUESTED', 'PROCESSING', 'SUCCEEDED', 'FAILED', 'REJECTED') NOT NULL DEFAULT 'REQUESTED',
+    `provider_refund_id` VARCHAR(255) NULL,
+    `failure_reason` VARCHAR(1000) NULL,
+    `requested_by_company_id` INT NOT NULL,
+    `requested_by_user_id` INT NULL,
+    `processed_by_user_id` INT NULL,
+    `response_comment` VARCHAR(1000) NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `processed_at` TIMESTAMP NULL,
+    FOREIGN KEY (`order_id`) REFERENCES `purchase_orders`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`invoice_id`) REFERENCES `invoices`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`dispute_id`) REFERENCES `disputes`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`cancellation_request_id`) REFERENCES `order_cancellation_requests`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`requested_by_company_id`) REFERENCES `companies`(`id`),
+    FOREIGN KEY (`requested_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`processed_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    INDEX `idx_refunds_order` (`order_id`),
+    INDEX `idx_refunds_status` (`status`, `created_at`)
+) ENGINE=InnoDB;
+
+-- 累计已退金额，由退款记录和渠道的退款事件更新
+ALTER TABLE `purchase_orders` ADD COLUMN `refunded_amount` DECIMAL(12, 2) NOT NULL DEFAULT 0 AFTER `payment_status`;
+ALTER TABLE `invoices` ADD COLUMN `refunded_amount` DECIMAL(12, 2) NOT NULL DEFAULT 0 AFTER `payment_status`;
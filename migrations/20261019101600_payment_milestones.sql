@@ -0,0 +1,29 @@
+-- 分期付款计划：比如授标后付定金，发货后付尾款。每期单独支付，全部付清订单才算已付
+CREATE TABLE `payment_milestones` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `order_id` INT NOT NULL,
+    `sequence` INT NOT NULL,
+    `label` VARCHAR(100) NOT NULL,
+    `percentage` DECIMAL(5, 2) NOT NULL,
+    `amount` DECIMAL(12, 2) NOT NULL,
+    `currency` CHAR(3) NOT NULL,
+    `due_trigger` ENUM('AWARD', 'SHIPPED', 'ACCEPTED') NOT NULL COMMENT '订单到达这个状态后才能付款',
+    `payment_status` ENUM('UNPAID', 'PAID', 'FAILED', 'REFUND_PENDING', 'PARTIALLY_REFUNDED', 'REFUNDED') NOT NULL DEFAULT 'UNPAID',
+    `refunded_amount` DECIMAL(12, 2) NOT NULL DEFAULT 0,
+    `stripe_session_id` VARCHAR(255) NULL,
+    `payment_reference` VARCHAR(255) NULL,
+    `chargeback_opened_at` TIMESTAMP NULL,
+    `paid_at` TIMESTAMP NULL,
+    `created_by_user_id` INT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (`order_id`) REFERENCES `purchase_orders`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`created_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    UNIQUE KEY `uq_payment_milestones_order_sequence` (`order_id`, `sequence`),
+    INDEX `idx_payment_milestones_stripe_session` (`stripe_session_id`),
+    INDEX `idx_payment_milestones_payment_reference` (`payment_reference`)
+) ENGINE=InnoDB;
+
+-- 退款也可以针对某一期付款
+ALTER TABLE `refunds`
+    ADD COLUMN `milestone_id` INT NULL AFTER `invoice_id`,
+    ADD FOREIGN KEY (`milestone_id`) REFERENCES `payment_milestones`(`id`) ON DELETE SET NULL;
@@ -0,0 +1,41 @@
+-- 支付对账：定时向支付渠道核对还没付款但有支付会话的订单、发票和分期，修正丢失Webhook造成的状态不一致
+CREATE TABLE `payment_reconciliation_runs` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `provider` VARCHAR(50) NOT NULL,
+    `report_date` DATE NOT NULL,
+    `checked` INT NOT NULL DEFAULT 0,
+    `fixed` INT NOT NULL DEFAULT 0,
+    `flagged` INT NOT NULL DEFAULT 0,
+    `triggered_by_user_id` INT NULL COMMENT '管理员手动触发时记录，定时任务为空',
+    `started_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `finished_at` TIMESTAMP NULL,
+    FOREIGN KEY (`triggered_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    INDEX `idx_reconciliation_runs_report_date` (`report_date`)
+) ENGINE=InnoDB;
+
+-- 每次对账检查过的每一笔付款
+CREATE TABLE `payment_reconciliation_items` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `run_id` INT NOT NULL,
+    `order_id` INT NOT NULL,
+    `invoice_id` INT NULL,
+    `milestone_id` INT NULL,
+    `stripe_session_id` VARCHAR(255) NOT NULL,
+    `local_status` VARCHAR(30) NOT NULL,
+    `provider_status` VARCHAR(30) NULL,
+    `expected_amount` DECIMAL(12, 2) NOT NULL,
+    `provider_amount` DECIMAL(12, 2) NULL,
+    `currency` CHAR(3) NOT NULL,
+    `outcome` ENUM('FIXED', 'EXPIRED', 'PENDING', 'IN_SYNC', 'MISMATCH') NOT NULL,
+    `detail` VARCHAR(1000) NULL,
+    `resolved_at` TIMESTAMP NULL COMMENT '管理员处理完不一致后标记',
+    `resolved_by_user_id` INT NULL,
+    `resolution_note` VARCHAR(1000) NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (`run_id`) REFERENCES `payment_reconciliation_runs`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`order_id`) REFERENCES `purchase_orders`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`invoice_id`) REFERENCES `invoices`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`milestone_id`) REFERENCES `payment_milestones`(`id`) ON DELETE SET NULL,
+    FOREIGN KEY (`resolved_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    INDEX `idx_reconciliation_items_outcome` (`outcome`, `resolved_at`)
+) ENGINE=InnoDB;
@@ -0,0 +1,32 @@
+-- 账期付款：供应商给某个买方开账期（net-30/60），买方有平台管理员核定的信用额度，按发票线下付款
+CREATE TABLE `payment_terms` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `buyer_company_id` INT NOT NULL,
+    `supplier_company_id` INT NOT NULL,
+    `net_days` INT NOT NULL,
+    `updated_by_user_id` INT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `updated_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    FOREIGN KEY (`buyer_company_id`) REFERENCES `companies`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`supplier_company_id`) REFERENCES `companies`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`updated_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    UNIQUE KEY `uq_payment_terms_buyer_supplier` (`buyer_company_id`, `supplier_company_id`)
+) ENGINE=InnoDB;
+
+-- 信用额度按买方的报表币种计算，NULL 表示不能赊账
+ALTER TABLE `companies`
+    ADD COLUMN `credit_limit` DECIMAL(14, 2) NULL AFTER `reporting_currency`;
+
+-- 下单时的账期快照，NULL 表示在线付款
+ALTER TABLE `purchase_orders`
+    ADD COLUMN `payment_terms_days` INT NULL AFTER `currency`,
+    ADD COLUMN `remittance_reference` VARCHAR(255) NULL AFTER `payment_reference`,
+    ADD COLUMN `marked_paid_by_user_id` INT NULL AFTER `remittance_reference`,
+    ADD FOREIGN KEY (`marked_paid_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL;
+
+ALTER TABLE `invoices`
+    ADD COLUMN `remittance_reference` VARCHAR(255) NULL AFTER `payment_reference`,
+    ADD COLUMN `marked_paid_by_user_id` INT NULL AFTER `remittance_reference`,
+    ADD COLUMN `last_reminder_sent_on` DATE NULL COMMENT '最近一次到期/逾期提醒的日期',
+    ADD FOREIGN KEY (`marked_paid_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    ADD INDEX `idx_invoices_due` (`payment_status`, `due_date`);
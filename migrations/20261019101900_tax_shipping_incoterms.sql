@@ -0,0 +1,41 @@
+-- 税费、运费和贸易术语：订单总额 = 货款小计 + 运费 + 税额
+-- 税率按买方所在国家/地区配置，region 为空字符串表示全国统一税率
+CREATE TABLE `tax_rates` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `country_code` CHAR(2) NOT NULL,
+    `region` VARCHAR(100) NOT NULL DEFAULT '',
+    `name` VARCHAR(50) NOT NULL COMMENT '税种名称，例如 VAT、GST、Sales Tax',
+    `rate` DECIMAL(5, 2) NOT NULL COMMENT '百分比',
+    `updated_by_user_id` INT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `updated_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    FOREIGN KEY (`updated_by_user_id`) REFERENCES `users`(`id`) ON DELETE SET NULL,
+    UNIQUE KEY `uq_tax_rates_location` (`country_code`, `region`)
+) ENGINE=InnoDB;
+
+ALTER TABLE `companies`
+    ADD COLUMN `country_code` CHAR(2) NULL COMMENT 'ISO 3166-1 alpha-2' AFTER `city`,
+    ADD COLUMN `region` VARCHAR(100) NULL COMMENT '州/省，用于地区税率' AFTER `country_code`;
+
+-- 报价的 price 仍是整单货款，运费单独报
+ALTER TABLE `quotes`
+    ADD COLUMN `shipping_cost` DECIMAL(12, 2) NOT NULL DEFAULT 0.00 AFTER `currency`,
+    ADD COLUMN `incoterm` VARCHAR(3) NULL AFTER `shipping_cost`,
+    ADD COLUMN `incoterm_place` VARCHAR(100) NULL COMMENT '指定地点，例如 FOB Shanghai' AFTER `incoterm`;
+
+-- 下单时的税率快照，之后改税率不影响已下的订单
+ALTER TABLE `purchase_orders`
+    ADD COLUMN `subtotal_amount` DECIMAL(12, 2) NOT NULL DEFAULT 0.00 AFTER `quantity`,
+    ADD COLUMN `shipping_amount` DECIMAL(12, 2) NOT NULL DEFAULT 0.00 AFTER `subtotal_amount`,
+    ADD COLUMN `tax_name` VARCHAR(50) NULL AFTER `shipping_amount`,
+    ADD COLUMN `tax_rate` DECIMAL(5, 2) NOT NULL DEFAULT 0.00 AFTER `tax_name`,
+    ADD COLUMN `tax_amount` DECIMAL(12, 2) NOT NULL DEFAULT 0.00 AFTER `tax_rate`,
+    ADD COLUMN `incoterm` VARCHAR(3) NULL AFTER `currency`,
+    ADD COLUMN `incoterm_place` VARCHAR(100) NULL AFTER `incoterm`;
+
+-- 以前的订单总额就是货款
+UPDATE `purchase_orders` SET `subtotal_amount` = `total_amount`;
+
+-- 发票小计里包含的运费，第一张发票收全部运费
+ALTER TABLE `invoices`
+    ADD COLUMN `shipping_total` DECIMAL(12, 2) NOT NULL DEFAULT 0.00 AFTER `subtotal`;
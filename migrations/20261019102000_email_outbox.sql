@@ -0,0 +1,25 @@
+-- 邮件发件箱：业务代码只负责写入，后台任务负责投递、失败重试（指数退避），多次失败后转为 DEAD 等管理员处理
+CREATE TABLE `email_outbox` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `recipient` VARCHAR(255) NOT NULL,
+    `subject` VARCHAR(255) NOT NULL,
+    `body` TEXT NOT NULL,
+    `status` ENUM('PENDING', 'SENT', 'DEAD') NOT NULL DEFAULT 'PENDING',
+    `attempts` INT NOT NULL DEFAULT 0,
+    `next_attempt_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `last_error` VARCHAR(1000) NULL,
+    `transport` VARCHAR(20) NULL COMMENT '最后一次投递用的通道',
+    `sent_at` TIMESTAMP NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `updated_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    INDEX `idx_email_outbox_due` (`status`, `next_attempt_at`)
+) ENGINE=InnoDB;
+
+CREATE TABLE `email_outbox_attachments` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `email_id` INT NOT NULL,
+    `filename` VARCHAR(255) NOT NULL,
+    `content_type` VARCHAR(100) NOT NULL,
+    `content` LONGBLOB NOT NULL,
+    FOREIGN KEY (`email_id`) REFERENCES `email_outbox`(`id`) ON DELETE CASCADE
+) ENGINE=InnoDB;
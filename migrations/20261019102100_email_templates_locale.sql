@@ -0,0 +1,6 @@
+-- 邮件模板本地化：用户的邮件语言，发件箱同时保存HTML正文
+ALTER TABLE `users`
+    ADD COLUMN `locale` VARCHAR(10) NOT NULL DEFAULT 'en' COMMENT '邮件语言：en 或 zh-CN' AFTER `full_name`;
+
+ALTER TABLE `email_outbox`
+    ADD COLUMN `html_body` MEDIUMTEXT NULL COMMENT '为空时只发纯文本' AFTER `body`;
@@ -0,0 +1,56 @@
+-- 通知偏好：按事件类别和通道（站内、邮件、Webhook、每日摘要）开关，没有记录的用默认值
+-- PAYMENT、DISPUTE、SYSTEM 是事务性通知，站内和邮件不能关闭，也不受免打扰时段影响
+CREATE TABLE `notification_preferences` (
+    `user_id` INT NOT NULL,
+    `category` VARCHAR(20) NOT NULL,
+    `channel` ENUM('IN_APP', 'EMAIL', 'WEBHOOK', 'DIGEST') NOT NULL,
+    `enabled` BOOLEAN NOT NULL,
+    `updated_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    PRIMARY KEY (`user_id`, `category`, `channel`),
+    FOREIGN KEY (`user_id`) REFERENCES `users`(`id`) ON DELETE CASCADE
+) ENGINE=InnoDB;
+
+-- 免打扰时段用用户本地时间，utc_offset_minutes 是相对UTC的偏移
+CREATE TABLE `notification_settings` (
+    `user_id` INT PRIMARY KEY,
+    `quiet_hours_start` TIME NULL,
+    `quiet_hours_end` TIME NULL,
+    `utc_offset_minutes` INT NOT NULL DEFAULT 0,
+    `webhook_url` VARCHAR(500) NULL,
+    `webhook_secret` VARCHAR(64) NULL COMMENT '用于给Webhook请求签名',
+    `updated_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    FOREIGN KEY (`user_id`) REFERENCES `users`(`id`) ON DELETE CASCADE
+) ENGINE=InnoDB;
+
+ALTER TABLE `notifications`
+    ADD COLUMN `category` VARCHAR(20) NULL AFTER `recipient_user_id`;
+
+-- 待投递的Webhook，和邮件发件箱一样失败后退避重试
+CREATE TABLE `notification_webhook_deliveries` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `user_id` INT NOT NULL,
+    `url` VARCHAR(500) NOT NULL,
+    `payload` TEXT NOT NULL,
+    `status` ENUM('PENDING', 'SENT', 'DEAD') NOT NULL DEFAULT 'PENDING',
+    `attempts` INT NOT NULL DEFAULT 0,
+    `next_attempt_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `last_error` VARCHAR(1000) NULL,
+    `response_status` INT NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `updated_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    FOREIGN KEY (`user_id`) REFERENCES `users`(`id`) ON DELETE CASCADE,
+    INDEX `idx_webhook_deliveries_due` (`status`, `next_attempt_at`)
+) ENGINE=InnoDB;
+
+-- 等待汇总进每日摘要邮件的通知
+CREATE TABLE `notification_digest_items` (
+    `id` INT AUTO_INCREMENT PRIMARY KEY,
+    `user_id` INT NOT NULL,
+    `category` VARCHAR(20) NOT NULL,
+    `message` TEXT NOT NULL,
+    `link_url` VARCHAR(255) NULL,
+    `created_at` TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    `sent_at` TIMESTAMP NULL,
+    FOREIGN KEY (`user_id`) REFERENCES `users`(`id`) ON DELETE CASCADE,
+    INDEX `idx_digest_items_pending` (`user_id`, `sent_at`)
+) ENGINE=InnoDB;
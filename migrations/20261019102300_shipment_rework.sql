@@ -0,0 +1,3 @@
+-- 争议判返工后，之前的发货和收货记录作废，重新发货和收货从零开始计数
+ALTER TABLE `shipments` ADD COLUMN `superseded_at` TIMESTAMP NULL AFTER `created_by_user_id`;
+ALTER TABLE `goods_receipts` ADD COLUMN `superseded_at` TIMESTAMP NULL AFTER `received_by_user_id`;
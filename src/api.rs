@@ -1,5 +1,5 @@
 use actix_web::web;
-use crate::handlers::{auth_handler, rfq_handler, quote_handler, order_handler, auth_middleware::Auth, company_handler, user_handler, analytics_handler, payment_handler, admin_handler, capability_handler, notification_handler, ws_handler, annotation_handler};
+use crate::handlers::{auth_handler, rfq_handler, quote_handler, order_handler, auth_middleware::Auth, company_handler, user_handler, analytics_handler, payment_handler, admin_handler, capability_handler, notification_handler, ws_handler, annotation_handler, dispute_handler, shipment_handler, receipt_handler, document_handler, invoice_handler, change_order_handler, blanket_agreement_handler, refund_handler, payment_schedule_handler, payment_terms_handler};
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     // 公开路由，不需要登录
@@ -19,7 +19,9 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .route("/{rfq_id}/attachments", web::get().to(rfq_handler::get_attachments))
             .route("/{rfq_id}/quotes", web::post().to(quote_handler::post_quote))
             .route("/{rfq_id}/quotes", web::get().to(quote_handler::get_quotes))
-            .route("/{rfq_id}/messages", web::get().to(rfq_handler::get_messages)),
+            .route("/{rfq_id}/messages", web::get().to(rfq_handler::get_messages))
+            .route("/{rfq_id}/response", web::post().to(rfq_handler::post_rfq_response))
+            .route("/{rfq_id}/responses", web::get().to(rfq_handler::get_rfq_responses)),
 
     );
 
@@ -27,6 +29,8 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api/quotes")
             .wrap(Auth)
+            .route("/mine", web::get().to(quote_handler::get_my_quotes))
+            .route("/{quote_id}", web::put().to(quote_handler::put_revise_quote))
             .route("/{quote_id}/accept", web::post().to(quote_handler::post_accept_quote)),
     );
 
@@ -36,17 +40,106 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .wrap(Auth)
             .route("", web::get().to(order_handler::get_orders))
             .route("/{order_id}/status", web::patch().to(order_handler::patch_order_status))
+            .route("/{order_id}/history", web::get().to(order_handler::get_order_history))
+            .route("/{order_id}/rate", web::post().to(order_handler::post_order_rating))
+            .route("/{order_id}/cancellation-requests", web::post().to(order_handler::post_cancellation_request))
+            .route("/{order_id}/cancellation-requests", web::get().to(order_handler::get_cancellation_requests))
+            .route("/{order_id}/cancellation-requests/{request_id}", web::put().to(order_handler::put_cancellation_response))
+            .route("/{order_id}/disputes", web::post().to(dispute_handler::post_dispute))
+            .route("/{order_id}/disputes", web::get().to(dispute_handler::get_order_disputes))
+            .route("/{order_id}/shipments", web::post().to(shipment_handler::post_shipment))
+            .route("/{order_id}/shipments", web::get().to(shipment_handler::get_shipments))
+            .route("/{order_id}/shipments/{shipment_id}/packing-list", web::get().to(shipment_handler::download_packing_list))
+            .route("/{order_id}/receipts", web::post().to(receipt_handler::post_goods_receipt))
+            .route("/{order_id}/receipts", web::get().to(receipt_handler::get_goods_receipts))
+            .route("/{order_id}/ncrs", web::get().to(receipt_handler::get_order_ncrs))
+            .route("/{order_id}/documents", web::get().to(document_handler::get_order_documents))
+            .route("/{order_id}/invoices", web::post().to(invoice_handler::post_invoice))
+            .route("/{order_id}/invoices", web::get().to(invoice_handler::get_order_invoices))
+            .route("/{order_id}/change-orders", web::post().to(change_order_handler::post_change_order))
+            .route("/{order_id}/change-orders", web::get().to(change_order_handler::get_change_orders))
+            .route("/{order_id}/change-orders/{change_order_id}", web::put().to(change_order_handler::put_change_order_response))
+            .route("/{order_id}/change-orders/{change_order_id}/withdraw", web::put().to(change_order_handler::put_withdraw_change_order))
+            .route("/{order_id}/refunds", web::post().to(refund_handler::post_refund))
+            .route("/{order_id}/refunds", web::get().to(refund_handler::get_refunds))
+            .route("/{order_id}/refunds/{refund_id}", web::put().to(refund_handler::put_refund_response))
+            .route("/{order_id}/payment-schedule", web::put().to(payment_schedule_handler::put_payment_schedule))
+            .route("/{order_id}/payment-schedule", web::get().to(payment_schedule_handler::get_payment_schedule))
+            .route("/{order_id}/payment-milestones/{milestone_id}/create-checkout-session", web::post().to(payment_handler::create_milestone_session))
+            .route("/{order_id}/mark-paid", web::post().to(payment_terms_handler::post_mark_order_paid))
+            .route("/{order_id}/reorder", web::get().to(order_handler::get_reorder_preview))
+            .route("/{order_id}/reorder", web::post().to(order_handler::post_reorder))
         // --- 新增 ---
             .route("/{order_id}/create-checkout-session", web::post().to(payment_handler::create_session)),
 
     );
 
+    // 争议详情、留言和证据，订单双方和管理员可访问
+    cfg.service(
+        web::scope("/api/disputes")
+            .wrap(Auth)
+            .route("/{dispute_id}", web::get().to(dispute_handler::get_dispute))
+            .route("/{dispute_id}/messages", web::post().to(dispute_handler::post_dispute_message))
+            .route("/{dispute_id}/attachments", web::post().to(dispute_handler::post_dispute_attachments))
+            .route("/{dispute_id}/attachments/{attachment_id}/download", web::get().to(dispute_handler::download_dispute_attachment)),
+    );
+
+    // 不合格品报告(NCR)
+    cfg.service(
+        web::scope("/api/ncrs")
+            .wrap(Auth)
+            .route("/{ncr_id}", web::get().to(receipt_handler::get_ncr))
+            .route("/{ncr_id}/photos", web::post().to(receipt_handler::post_ncr_photos))
+            .route("/{ncr_id}/photos/{photo_id}/download", web::get().to(receipt_handler::download_ncr_photo))
+            .route("/{ncr_id}/response", web::put().to(receipt_handler::put_ncr_response))
+            .route("/{ncr_id}/close", web::put().to(receipt_handler::put_close_ncr)),
+    );
+
+    // 订单正式文件下载
+    cfg.service(
+        web::scope("/api/documents")
+            .wrap(Auth)
+            .route("/{document_id}/download", web::get().to(document_handler::download_document)),
+    );
+
+    // 框架协议和分批下达
+    cfg.service(
+        web::scope("/api/blanket-agreements")
+            .wrap(Auth)
+            .route("", web::post().to(blanket_agreement_handler::post_agreement))
+            .route("", web::get().to(blanket_agreement_handler::get_agreements))
+            .route("/{agreement_id}", web::get().to(blanket_agreement_handler::get_agreement))
+            .route("/{agreement_id}/response", web::put().to(blanket_agreement_handler::put_agreement_response))
+            .route("/{agreement_id}/close", web::put().to(blanket_agreement_handler::put_close_agreement))
+            .route("/{agreement_id}/releases", web::post().to(blanket_agreement_handler::post_release)),
+    );
+
+    // 发票详情和按发票付款
+    cfg.service(
+        web::scope("/api/invoices")
+            .wrap(Auth)
+            .route("/{invoice_id}", web::get().to(invoice_handler::get_invoice))
+            .route("/{invoice_id}/create-checkout-session", web::post().to(payment_handler::create_invoice_session))
+            .route("/{invoice_id}/mark-paid", web::post().to(payment_terms_handler::post_mark_invoice_paid)),
+    );
+
+    // 账期和买方信用额度
+    cfg.service(
+        web::scope("/api/payment-terms")
+            .wrap(Auth)
+            .route("", web::get().to(payment_terms_handler::get_payment_terms))
+            .route("", web::put().to(payment_terms_handler::put_payment_terms))
+            .route("/credit", web::get().to(payment_terms_handler::get_my_credit))
+            .route("/{buyer_company_id}", web::delete().to(payment_terms_handler::delete_payment_terms)),
+    );
+
     // --- 新增受保护的User路由 ---
     cfg.service(
         web::scope("/api/users")
             .wrap(Auth)
             .route("/me", web::get().to(user_handler::get_me)) // GET /api/users/me
-            .route("/me/password", web::put().to(user_handler::update_password)), // PUT /api/users/me/password
+            .route("/me/password", web::put().to(user_handler::update_password)) // PUT /api/users/me/password
+            .route("/me/locale", web::put().to(user_handler::update_locale)),
     );
 
     // --- 受保护的Company路由 ---
@@ -54,7 +147,8 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         web::scope("/api/companies")
             .wrap(Auth) // 查看和修改都需要登录
             .route("/{company_id}", web::get().to(company_handler::get_profile))
-            .route("/{company_id}", web::put().to(company_handler::update_profile)),
+            .route("/{company_id}", web::put().to(company_handler::update_profile))
+            .route("/{company_id}/scorecard", web::get().to(company_handler::get_scorecard)),
     );
 
     // --- 受保护的Analytics路由 ---
@@ -85,8 +179,28 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .wrap(Auth) // 使用普通Auth中间件确保用户已登录
             .route("/companies", web::get().to(admin_handler::get_all_companies))
             .route("/companies/{id}/verify", web::put().to(admin_handler::put_verify_company))
+            .route("/companies/{id}/credit-limit", web::put().to(admin_handler::put_credit_limit))
+            .route("/companies/{id}/credit", web::get().to(admin_handler::get_credit_summary))
             .route("/users", web::get().to(admin_handler::get_all_users))
-            .route("/users/{id}/status", web::put().to(admin_handler::put_update_user_status)),
+            .route("/users/{id}/status", web::put().to(admin_handler::put_update_user_status))
+            .route("/fx-rates", web::get().to(admin_handler::get_fx_rates))
+            .route("/fx-rates", web::put().to(admin_handler::put_fx_rates))
+            .route("/tax-rates", web::get().to(admin_handler::get_tax_rates))
+            .route("/tax-rates", web::put().to(admin_handler::put_tax_rate))
+            .route("/tax-rates/{id}", web::delete().to(admin_handler::delete_tax_rate))
+            .route("/email-outbox", web::get().to(admin_handler::get_email_outbox))
+            .route("/email-outbox/{id}/retry", web::post().to(admin_handler::post_retry_email))
+            .route("/email-templates", web::get().to(admin_handler::get_email_templates))
+            .route("/email-templates/{name}/preview", web::get().to(admin_handler::get_email_template_preview))
+            .route("/disputes", web::get().to(admin_handler::get_disputes))
+            .route("/disputes/{id}/resolve", web::put().to(admin_handler::put_resolve_dispute))
+            .route("/payment-events", web::get().to(admin_handler::get_payment_events))
+            .route("/refunds", web::get().to(admin_handler::get_refunds))
+            .route("/reconciliation/runs", web::post().to(admin_handler::post_reconciliation_run))
+            .route("/reconciliation/runs", web::get().to(admin_handler::get_reconciliation_runs))
+            .route("/reconciliation/items", web::get().to(admin_handler::get_reconciliation_items))
+            .route("/reconciliation/items/{id}/resolve", web::put().to(admin_handler::put_resolve_reconciliation_item))
+            .route("/reconciliation/reports/{date}", web::get().to(admin_handler::download_reconciliation_report)),
     );
 
     // Capabilities
@@ -103,6 +217,9 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         web::scope("/api/notifications")
             .wrap(Auth)
             .route("", web::get().to(notification_handler::get_notifications))
+            .route("/preferences", web::get().to(notification_handler::get_preferences))
+            .route("/preferences", web::put().to(notification_handler::put_preferences))
+            .route("/settings", web::put().to(notification_handler::put_settings))
             .route("/{id}/read", web::put().to(notification_handler::put_mark_as_read))
             .route("/read-all", web::put().to(notification_handler::put_mark_all_as_read)),
     );
@@ -1,5 +1,7 @@
-use crate::{errors::AppError, models::user::Claims, services::admin_service};
-use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use crate::{errors::AppError, models::{dispute::ResolveDisputeDto, email::{OutboxListParams, TemplatePreviewParams}, fx::UpdateFxRatesDto, payment::PaymentEventListParams, payment_terms::SetCreditLimitDto, reconciliation::{ReconciliationItemListParams, ResolveReconciliationItemDto}, refund::RefundListParams, tax::SetTaxRateDto, user::Claims}, services::{admin_service, chat_server::ChatServer, dispute_service, email_service, email_template, fx_service, payment_provider::PaymentProvider, payment_service, payment_terms_service, reconciliation_service, refund_service, tax_service}};
+use actix::Addr;
+use actix_web::{http::header, web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use chrono::NaiveDate;
 use sqlx::MySqlPool;
 use serde::Deserialize;
 
@@ -8,6 +10,11 @@ pub struct UserStatusUpdate {
     is_active: bool,
 }
 
+#[derive(Deserialize)]
+pub struct DisputeListParams {
+    status: Option<String>,
+}
+
 // 权限检查辅助
 fn check_admin(req: &HttpRequest) -> Result<Claims, AppError> {
     let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
@@ -44,4 +51,198 @@ pub async fn put_update_user_status(
     check_admin(&req)?;
     admin_service::update_user_status(pool.get_ref(), user_id.into_inner(), dto.is_active).await?;
     Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "User status updated successfully" })))
-}
\ No newline at end of file
+}
+pub async fn get_fx_rates(pool: web::Data<MySqlPool>, req: HttpRequest) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let rates = fx_service::list_rates(pool.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(rates))
+}
+
+pub async fn put_fx_rates(
+    pool: web::Data<MySqlPool>,
+    dto: web::Json<UpdateFxRatesDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let updated = fx_service::upsert_rates(pool.get_ref(), &dto.rates, "ADMIN").await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "FX rates updated successfully", "updated": updated })))
+}
+
+pub async fn get_disputes(
+    pool: web::Data<MySqlPool>,
+    params: web::Query<DisputeListParams>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let disputes = dispute_service::list_disputes(pool.get_ref(), params.into_inner().status).await?;
+    Ok(HttpResponse::Ok().json(disputes))
+}
+
+pub async fn put_resolve_dispute(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    dispute_id: web::Path<i32>,
+    dto: web::Json<ResolveDisputeDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = check_admin(&req)?;
+    dispute_service::resolve_dispute(pool.get_ref(), chat_server.get_ref(), dispute_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Dispute resolved successfully" })))
+}
+
+pub async fn get_payment_events(
+    pool: web::Data<MySqlPool>,
+    params: web::Query<PaymentEventListParams>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let events = payment_service::list_payment_events(pool.get_ref(), params.into_inner().status).await?;
+    Ok(HttpResponse::Ok().json(events))
+}
+
+pub async fn get_refunds(
+    pool: web::Data<MySqlPool>,
+    params: web::Query<RefundListParams>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let refunds = refund_service::list_refunds(pool.get_ref(), params.into_inner().status).await?;
+    Ok(HttpResponse::Ok().json(refunds))
+}
+
+pub async fn post_reconciliation_run(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    provider: web::Data<dyn PaymentProvider>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = check_admin(&req)?;
+    let run = reconciliation_service::run_reconciliation(pool.get_ref(), chat_server.get_ref(), provider.get_ref(), Some(claims.sub)).await?;
+    Ok(HttpResponse::Ok().json(run))
+}
+
+pub async fn get_reconciliation_runs(pool: web::Data<MySqlPool>, req: HttpRequest) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let runs = reconciliation_service::list_runs(pool.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(runs))
+}
+
+pub async fn get_reconciliation_items(
+    pool: web::Data<MySqlPool>,
+    params: web::Query<ReconciliationItemListParams>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let params = params.into_inner();
+    let items = reconciliation_service::list_items(pool.get_ref(), params.outcome, params.unresolved).await?;
+    Ok(HttpResponse::Ok().json(items))
+}
+
+pub async fn put_resolve_reconciliation_item(
+    pool: web::Data<MySqlPool>,
+    item_id: web::Path<i32>,
+    dto: web::Json<ResolveReconciliationItemDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = check_admin(&req)?;
+    reconciliation_service::resolve_item(pool.get_ref(), item_id.into_inner(), dto.into_inner().note, &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Mismatch marked as resolved" })))
+}
+
+// 日期格式 YYYY-MM-DD
+pub async fn download_reconciliation_report(
+    pool: web::Data<MySqlPool>,
+    date: web::Path<String>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+        .map_err(|_| AppError::BadRequest("Date must be in YYYY-MM-DD format.".to_string()))?;
+    let csv = reconciliation_service::daily_report_csv(pool.get_ref(), date).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(header::ContentDisposition::attachment(format!("payment-reconciliation-{}.csv", date)))
+        .body(csv))
+}
+
+pub async fn put_credit_limit(
+    pool: web::Data<MySqlPool>,
+    company_id: web::Path<i32>,
+    dto: web::Json<SetCreditLimitDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let summary = payment_terms_service::set_credit_limit(pool.get_ref(), company_id.into_inner(), dto.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+pub async fn get_credit_summary(
+    pool: web::Data<MySqlPool>,
+    company_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = check_admin(&req)?;
+    let summary = payment_terms_service::get_credit_summary(pool.get_ref(), company_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+pub async fn get_tax_rates(pool: web::Data<MySqlPool>, req: HttpRequest) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let rates = tax_service::list_tax_rates(pool.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(rates))
+}
+
+pub async fn put_tax_rate(
+    pool: web::Data<MySqlPool>,
+    dto: web::Json<SetTaxRateDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = check_admin(&req)?;
+    let rate = tax_service::set_tax_rate(pool.get_ref(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(rate))
+}
+
+pub async fn delete_tax_rate(
+    pool: web::Data<MySqlPool>,
+    tax_rate_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    tax_service::delete_tax_rate(pool.get_ref(), tax_rate_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Tax rate deleted successfully" })))
+}
+
+pub async fn get_email_outbox(
+    pool: web::Data<MySqlPool>,
+    params: web::Query<OutboxListParams>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let emails = email_service::list_outbox(pool.get_ref(), params.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(emails))
+}
+
+pub async fn post_retry_email(
+    pool: web::Data<MySqlPool>,
+    email_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let email = email_service::retry_email(pool.get_ref(), email_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(email))
+}
+
+pub async fn get_email_templates(req: HttpRequest) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    Ok(HttpResponse::Ok().json(email_template::list_templates()))
+}
+
+pub async fn get_email_template_preview(
+    template_name: web::Path<String>,
+    params: web::Query<TemplatePreviewParams>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    check_admin(&req)?;
+    let email = email_template::preview_template(&template_name, params.into_inner().locale)?;
+    Ok(HttpResponse::Ok().json(email))
+}
@@ -0,0 +1,76 @@
+use actix::Addr;
+use crate::{
+    errors::AppError,
+    models::{
+        blanket_agreement::{CreateBlanketAgreementDto, CreateReleaseDto, RespondBlanketAgreementDto},
+        user::Claims,
+    },
+    services::{blanket_agreement_service, chat_server::ChatServer},
+};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use sqlx::MySqlPool;
+
+pub async fn post_agreement(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    dto: web::Json<CreateBlanketAgreementDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let agreement_id = blanket_agreement_service::propose_agreement(pool.get_ref(), chat_server.get_ref(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Created().json(serde_json::json!({ "message": "Blanket agreement proposed successfully", "agreement_id": agreement_id })))
+}
+
+pub async fn get_agreements(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let agreements = blanket_agreement_service::get_agreements(pool.get_ref(), &claims).await?;
+    Ok(HttpResponse::Ok().json(agreements))
+}
+
+pub async fn get_agreement(
+    pool: web::Data<MySqlPool>,
+    agreement_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let agreement = blanket_agreement_service::get_agreement_detail(pool.get_ref(), agreement_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(agreement))
+}
+
+pub async fn put_agreement_response(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    agreement_id: web::Path<i32>,
+    dto: web::Json<RespondBlanketAgreementDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    blanket_agreement_service::respond_to_agreement(pool.get_ref(), chat_server.get_ref(), agreement_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Blanket agreement answered successfully" })))
+}
+
+pub async fn put_close_agreement(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    agreement_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    blanket_agreement_service::close_agreement(pool.get_ref(), chat_server.get_ref(), agreement_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Blanket agreement closed successfully" })))
+}
+
+pub async fn post_release(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    agreement_id: web::Path<i32>,
+    dto: web::Json<CreateReleaseDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let (order_id, po_number) = blanket_agreement_service::create_release(pool.get_ref(), chat_server.get_ref(), agreement_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Created().json(serde_json::json!({ "message": "Release created successfully", "order_id": order_id, "po_number": po_number })))
+}
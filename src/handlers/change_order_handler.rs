@@ -0,0 +1,56 @@
+use actix::Addr;
+use crate::{
+    errors::AppError,
+    models::{change_order::{CreateChangeOrderDto, RespondChangeOrderDto}, user::Claims},
+    services::{change_order_service, chat_server::ChatServer, payment_provider::PaymentProvider},
+};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use sqlx::MySqlPool;
+
+pub async fn post_change_order(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    order_id: web::Path<i32>,
+    dto: web::Json<CreateChangeOrderDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let change_order_id = change_order_service::propose_change_order(pool.get_ref(), chat_server.get_ref(), order_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Created().json(serde_json::json!({ "message": "Change order proposed successfully", "change_order_id": change_order_id })))
+}
+
+pub async fn get_change_orders(
+    pool: web::Data<MySqlPool>,
+    order_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let changes = change_order_service::get_change_orders(pool.get_ref(), order_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(changes))
+}
+
+pub async fn put_change_order_response(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    provider: web::Data<dyn PaymentProvider>,
+    path: web::Path<(i32, i32)>,
+    dto: web::Json<RespondChangeOrderDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let (order_id, change_order_id) = path.into_inner();
+    change_order_service::respond_to_change_order(pool.get_ref(), chat_server.get_ref(), provider.get_ref(), order_id, change_order_id, dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Change order answered successfully" })))
+}
+
+pub async fn put_withdraw_change_order(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    path: web::Path<(i32, i32)>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let (order_id, change_order_id) = path.into_inner();
+    change_order_service::withdraw_change_order(pool.get_ref(), chat_server.get_ref(), order_id, change_order_id, &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Change order withdrawn successfully" })))
+}
@@ -1,7 +1,7 @@
 use crate::{
     errors::AppError,
     models::{company::UpdateCompanyDto, user::Claims},
-    services::company_service,
+    services::{company_service, rating_service},
 };
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
 use sqlx::MySqlPool;
@@ -14,6 +14,14 @@ pub async fn get_profile(
     Ok(HttpResponse::Ok().json(profile))
 }
 
+pub async fn get_scorecard(
+    pool: web::Data<MySqlPool>,
+    company_id: web::Path<i32>,
+) -> Result<impl Responder, AppError> {
+    let scorecard = rating_service::get_supplier_scorecard(pool.get_ref(), company_id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(scorecard))
+}
+
 pub async fn update_profile(
     pool: web::Data<MySqlPool>,
     company_id: web::Path<i32>,
@@ -0,0 +1,78 @@
+use crate::{
+    errors::AppError,
+    models::{dispute::{CreateDisputeDto, DisputeMessageDto}, user::Claims},
+    services::{chat_server::ChatServer, dispute_service},
+};
+use actix::Addr;
+use actix_multipart::Multipart;
+use actix_web::{http::header, web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use sqlx::MySqlPool;
+
+pub async fn post_dispute(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    order_id: web::Path<i32>,
+    dto: web::Json<CreateDisputeDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let dispute_id = dispute_service::open_dispute(pool.get_ref(), chat_server.get_ref(), order_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Created().json(serde_json::json!({ "message": "Dispute opened successfully", "dispute_id": dispute_id })))
+}
+
+pub async fn get_order_disputes(
+    pool: web::Data<MySqlPool>,
+    order_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let disputes = dispute_service::get_disputes_for_order(pool.get_ref(), order_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(disputes))
+}
+
+pub async fn get_dispute(
+    pool: web::Data<MySqlPool>,
+    dispute_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let detail = dispute_service::get_dispute_detail(pool.get_ref(), dispute_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(detail))
+}
+
+pub async fn post_dispute_message(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    dispute_id: web::Path<i32>,
+    dto: web::Json<DisputeMessageDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let message = dispute_service::add_message(pool.get_ref(), chat_server.get_ref(), dispute_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Created().json(message))
+}
+
+pub async fn post_dispute_attachments(
+    pool: web::Data<MySqlPool>,
+    dispute_id: web::Path<i32>,
+    payload: Multipart,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let attachments = dispute_service::add_attachments(pool.get_ref(), dispute_id.into_inner(), payload, &claims).await?;
+    Ok(HttpResponse::Created().json(attachments))
+}
+
+pub async fn download_dispute_attachment(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<(i32, i32)>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let (dispute_id, attachment_id) = path.into_inner();
+    let (attachment, content_type, content) = dispute_service::download_attachment(pool.get_ref(), dispute_id, attachment_id, &claims).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(header::ContentDisposition::attachment(attachment.original_filename))
+        .body(content))
+}
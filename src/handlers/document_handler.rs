@@ -0,0 +1,26 @@
+use crate::{errors::AppError, models::user::Claims, services::document_service};
+use actix_web::{http::header, web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use sqlx::MySqlPool;
+
+pub async fn get_order_documents(
+    pool: web::Data<MySqlPool>,
+    order_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let documents = document_service::get_documents_for_order(pool.get_ref(), order_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(documents))
+}
+
+pub async fn download_document(
+    pool: web::Data<MySqlPool>,
+    document_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let (document, content) = document_service::download_document(pool.get_ref(), document_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(document.content_type.as_str())
+        .insert_header(header::ContentDisposition::attachment(document.filename))
+        .body(content))
+}
@@ -0,0 +1,41 @@
+use actix::Addr;
+use crate::{
+    errors::AppError,
+    models::{invoice::CreateInvoiceDto, user::Claims},
+    services::{chat_server::ChatServer, invoice_service, payment_provider::PaymentProvider},
+};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use sqlx::MySqlPool;
+
+pub async fn post_invoice(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    provider: web::Data<dyn PaymentProvider>,
+    order_id: web::Path<i32>,
+    dto: web::Json<CreateInvoiceDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let invoice_id = invoice_service::create_invoice(pool.get_ref(), chat_server.get_ref(), provider.get_ref(), order_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Created().json(serde_json::json!({ "message": "Invoice issued successfully", "invoice_id": invoice_id })))
+}
+
+pub async fn get_order_invoices(
+    pool: web::Data<MySqlPool>,
+    order_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let invoices = invoice_service::get_invoices_for_order(pool.get_ref(), order_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(invoices))
+}
+
+pub async fn get_invoice(
+    pool: web::Data<MySqlPool>,
+    invoice_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let invoice = invoice_service::get_invoice_detail(pool.get_ref(), invoice_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(invoice))
+}
@@ -14,3 +14,13 @@ pub mod capability_handler;
 pub mod notification_handler;
 pub mod ws_handler;
 pub mod annotation_handler;
+pub(crate) mod dispute_handler;
+pub(crate) mod shipment_handler;
+pub(crate) mod receipt_handler;
+pub(crate) mod document_handler;
+pub(crate) mod invoice_handler;
+pub(crate) mod change_order_handler;
+pub(crate) mod blanket_agreement_handler;
+pub(crate) mod refund_handler;
+pub(crate) mod payment_schedule_handler;
+pub(crate) mod payment_terms_handler;
@@ -1,4 +1,11 @@
-use crate::{errors::AppError, models::user::Claims, services::notification_service};
+use crate::{
+    errors::AppError,
+    models::{
+        notification_preference::{UpdateNotificationSettingsDto, UpdatePreferencesDto},
+        user::Claims,
+    },
+    services::{notification_preference_service, notification_service},
+};
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
 use sqlx::MySqlPool;
 
@@ -51,3 +58,31 @@ pub async fn put_mark_all_as_read(
     notification_service::mark_all_as_read(pool.get_ref(), &claims).await?;
     Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "All notifications marked as read" })))
 }
+
+/// 当前用户的通知偏好：每个类别在各通道上的开关、免打扰时段和Webhook
+pub async fn get_preferences(pool: web::Data<MySqlPool>, req: HttpRequest) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let preferences = notification_preference_service::get_preferences(pool.get_ref(), &claims).await?;
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+pub async fn put_preferences(
+    pool: web::Data<MySqlPool>,
+    dto: web::Json<UpdatePreferencesDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let preferences = notification_preference_service::update_preferences(pool.get_ref(), &claims, dto.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+/// 免打扰时段和Webhook地址
+pub async fn put_settings(
+    pool: web::Data<MySqlPool>,
+    dto: web::Json<UpdateNotificationSettingsDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let preferences = notification_preference_service::update_settings(pool.get_ref(), &claims, dto.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(preferences))
+}
@@ -1,7 +1,8 @@
+use actix::Addr;
 use crate::{
     errors::AppError,
-    models::{order::UpdateOrderStatusDto, user::Claims},
-    services::order_service,
+    models::{order::{CreateCancellationRequestDto, ReorderDto, RespondCancellationDto, UpdateOrderStatusDto}, user::Claims},
+    services::{chat_server::ChatServer, order_service, payment_provider::PaymentProvider},
 };
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
 use sqlx::MySqlPool;use crate::models::rating::RateOrderDto; // <-- 导入
@@ -18,15 +19,62 @@ pub async fn get_orders(
 
 pub async fn patch_order_status(
     pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
     order_id: web::Path<i32>,
     dto: web::Json<UpdateOrderStatusDto>,
     req: HttpRequest,
 ) -> Result<impl Responder, AppError> {
     let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
-    order_service::update_order_status(pool.get_ref(), order_id.into_inner(), dto.into_inner(), &claims).await?;
+    order_service::update_order_status(pool.get_ref(), chat_server.get_ref(), order_id.into_inner(), dto.into_inner(), &claims).await?;
     Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Order status updated successfully" })))
 }
 
+pub async fn get_order_history(
+    pool: web::Data<MySqlPool>,
+    order_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let history = order_service::get_order_history(pool.get_ref(), order_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(history))
+}
+
+pub async fn post_cancellation_request(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    order_id: web::Path<i32>,
+    dto: web::Json<CreateCancellationRequestDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let request_id = order_service::request_cancellation(pool.get_ref(), chat_server.get_ref(), order_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Created().json(serde_json::json!({ "message": "Cancellation requested successfully", "request_id": request_id })))
+}
+
+pub async fn get_cancellation_requests(
+    pool: web::Data<MySqlPool>,
+    order_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let requests = order_service::get_cancellation_requests(pool.get_ref(), order_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(requests))
+}
+
+pub async fn put_cancellation_response(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    provider: web::Data<dyn PaymentProvider>,
+    path: web::Path<(i32, i32)>,
+    dto: web::Json<RespondCancellationDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let (order_id, request_id) = path.into_inner();
+    order_service::respond_to_cancellation(pool.get_ref(), chat_server.get_ref(), provider.get_ref(), order_id, request_id, dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Cancellation request answered successfully" })))
+}
+
 pub async fn post_order_rating(
     pool: web::Data<MySqlPool>,
     order_id: web::Path<i32>,
@@ -36,4 +84,27 @@ pub async fn post_order_rating(
     let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
     order_service::rate_order(pool.get_ref(), order_id.into_inner(), dto.into_inner(), &claims).await?;
     Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Order rated successfully" })))
-}
\ No newline at end of file
+}
+
+pub async fn get_reorder_preview(
+    pool: web::Data<MySqlPool>,
+    order_id: web::Path<i32>,
+    query: web::Query<ReorderDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let preview = order_service::preview_reorder(pool.get_ref(), order_id.into_inner(), query.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(preview))
+}
+
+pub async fn post_reorder(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    order_id: web::Path<i32>,
+    dto: web::Json<ReorderDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let (new_order_id, po_number) = order_service::reorder(pool.get_ref(), chat_server.get_ref(), order_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Created().json(serde_json::json!({ "message": "Reorder placed successfully", "order_id": new_order_id, "po_number": po_number })))
+}
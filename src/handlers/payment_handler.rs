@@ -1,50 +1,55 @@
+use actix::Addr;
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
 use sqlx::MySqlPool;
 use crate::{
     errors::AppError,
     models::{user::Claims, payment::CheckoutSessionResponse},
-    services::payment_service,
+    services::{chat_server::ChatServer, payment_provider::PaymentProvider, payment_service},
 };
-use std::env;
-use stripe::{EventObject, EventType, Webhook};
 
 pub async fn create_session(
     pool: web::Data<MySqlPool>,
+    provider: web::Data<dyn PaymentProvider>,
     order_id: web::Path<i32>,
     req: HttpRequest,
 ) -> Result<impl Responder, AppError> {
     let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
-    let session_id = payment_service::create_checkout_session(pool.get_ref(), order_id.into_inner(), &claims).await?;
-    Ok(HttpResponse::Ok().json(CheckoutSessionResponse { session_id }))
+    let session = payment_service::create_checkout_session(pool.get_ref(), provider.get_ref(), order_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(CheckoutSessionResponse { session_id: session.session_id, checkout_url: session.url }))
 }
 
-// 用 async-stripe 的API处理Webhook，一定要谨慎
+pub async fn create_invoice_session(
+    pool: web::Data<MySqlPool>,
+    provider: web::Data<dyn PaymentProvider>,
+    invoice_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let session = payment_service::create_invoice_checkout_session(pool.get_ref(), provider.get_ref(), invoice_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(CheckoutSessionResponse { session_id: session.session_id, checkout_url: session.url }))
+}
+
+pub async fn create_milestone_session(
+    pool: web::Data<MySqlPool>,
+    provider: web::Data<dyn PaymentProvider>,
+    path: web::Path<(i32, i32)>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let (order_id, milestone_id) = path.into_inner();
+    let session = payment_service::create_milestone_checkout_session(pool.get_ref(), provider.get_ref(), order_id, milestone_id, &claims).await?;
+    Ok(HttpResponse::Ok().json(CheckoutSessionResponse { session_id: session.session_id, checkout_url: session.url }))
+}
+
+// 验签和事件解析交给支付渠道，一定要谨慎
 pub async fn handle_webhook(
     pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    provider: web::Data<dyn PaymentProvider>,
     payload: String,
     req: HttpRequest,
 ) -> Result<impl Responder, AppError> {
     let signature = req.headers().get("Stripe-Signature").and_then(|h| h.to_str().ok()).unwrap_or_default();
-    let webhook_secret = env::var("STRIPE_WEBHOOK_SECRET").expect("STRIPE_WEBHOOK_SECRET must be set");
-
-    let event = Webhook::construct_event(&payload, signature, &webhook_secret)
-        .map_err(|e| AppError::BadRequest(format!("Invalid Stripe signature: {}", e)))?;
-
-    // 检查事件类型
-    if event.type_ == EventType::CheckoutSessionCompleted {
-        // 用 `if let` 模式匹配来安全地提取会话对象
-        if let EventObject::CheckoutSession(session) = event.data.object {
-            log::info!("Checkout session {} completed!", session.id);
-
-            let session_id = session.id.to_string();
-
-            // 更新数据库
-            sqlx::query("UPDATE purchase_orders SET payment_status = 'PAID' WHERE stripe_session_id = ?")
-                .bind(session_id)
-                .execute(pool.get_ref())
-                .await?;
-        }
-    }
-
+    payment_service::process_webhook(pool.get_ref(), chat_server.get_ref(), provider.get_ref(), &payload, signature).await?;
     Ok(HttpResponse::Ok())
-}
\ No newline at end of file
+}
@@ -0,0 +1,31 @@
+use actix::Addr;
+use crate::{
+    errors::AppError,
+    models::{payment_schedule::SetPaymentScheduleDto, user::Claims},
+    services::{chat_server::ChatServer, payment_provider::PaymentProvider, payment_schedule_service},
+};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use sqlx::MySqlPool;
+
+pub async fn put_payment_schedule(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    provider: web::Data<dyn PaymentProvider>,
+    order_id: web::Path<i32>,
+    dto: web::Json<SetPaymentScheduleDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let schedule = payment_schedule_service::set_payment_schedule(pool.get_ref(), chat_server.get_ref(), provider.get_ref(), order_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(schedule))
+}
+
+pub async fn get_payment_schedule(
+    pool: web::Data<MySqlPool>,
+    order_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let schedule = payment_schedule_service::get_payment_schedule(pool.get_ref(), order_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(schedule))
+}
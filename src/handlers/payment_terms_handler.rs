@@ -0,0 +1,69 @@
+use actix::Addr;
+use crate::{
+    errors::AppError,
+    models::{payment_terms::{MarkPaidDto, SetPaymentTermsDto}, user::Claims},
+    services::{chat_server::ChatServer, payment_provider::PaymentProvider, payment_terms_service},
+};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use sqlx::MySqlPool;
+
+pub async fn put_payment_terms(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    dto: web::Json<SetPaymentTermsDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let terms = payment_terms_service::set_payment_terms(pool.get_ref(), chat_server.get_ref(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(terms))
+}
+
+pub async fn get_payment_terms(pool: web::Data<MySqlPool>, req: HttpRequest) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let terms = payment_terms_service::list_payment_terms(pool.get_ref(), &claims).await?;
+    Ok(HttpResponse::Ok().json(terms))
+}
+
+pub async fn delete_payment_terms(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    buyer_company_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    payment_terms_service::remove_payment_terms(pool.get_ref(), chat_server.get_ref(), buyer_company_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Payment terms removed successfully" })))
+}
+
+// 买方查看自己的信用额度
+pub async fn get_my_credit(pool: web::Data<MySqlPool>, req: HttpRequest) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let summary = payment_terms_service::get_credit_summary(pool.get_ref(), claims.company_id, &claims).await?;
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+pub async fn post_mark_order_paid(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    provider: web::Data<dyn PaymentProvider>,
+    order_id: web::Path<i32>,
+    dto: web::Json<MarkPaidDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    payment_terms_service::mark_paid(pool.get_ref(), chat_server.get_ref(), provider.get_ref(), order_id.into_inner(), None, dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Order marked as paid" })))
+}
+
+pub async fn post_mark_invoice_paid(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    provider: web::Data<dyn PaymentProvider>,
+    invoice_id: web::Path<i32>,
+    dto: web::Json<MarkPaidDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    payment_terms_service::mark_invoice_paid(pool.get_ref(), chat_server.get_ref(), provider.get_ref(), invoice_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Invoice marked as paid" })))
+}
@@ -1,13 +1,21 @@
 use actix::Addr;
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use serde::Deserialize;
 use sqlx::MySqlPool;
 
 use crate::{
     errors::AppError,
-    models::{quote::CreateQuoteDto, user::Claims},
+    models::{quote::{AcceptQuoteDto, CreateQuoteDto}, user::Claims},
     services::{chat_server::ChatServer, quote_service},
 };
 
+#[derive(Debug, Deserialize)]
+pub struct MyQuotesParams {
+    status: Option<String>,
+    page: Option<u32>,
+    page_size: Option<u32>,
+}
+
 /// 供应方(Supplier)为某个RFQ提交新报价的quote
 /// POST /api/rfqs/{rfq_id}/quotes
 pub async fn post_quote(
@@ -60,12 +68,14 @@ pub async fn get_quotes(
     Ok(HttpResponse::Ok().json(quotes))
 }
 
-/// 处理采购方(Buyer)接受某个报价的请求
+/// 处理采购方(Buyer)接受某个报价的请求，其余报价会被自动拒绝
 /// POST /api/quotes/{quote_id}/accept
+/// 请求体可选: { "rejection_reason": "...", "share_price_feedback": true }
 pub async fn post_accept_quote(
     pool: web::Data<MySqlPool>,
     chat_server:  web::Data<Addr<ChatServer>>,
     quote_id: web::Path<i32>,
+    dto: Option<web::Json<AcceptQuoteDto>>,
     req: HttpRequest,
 ) -> Result<impl Responder, AppError> {
     // 提取用户信息
@@ -81,8 +91,49 @@ pub async fn post_accept_quote(
             pool.get_ref(),
             chat_server.get_ref(),
             quote_id.into_inner(),
+            dto.map(|d| d.into_inner()).unwrap_or_default(),
             &claims).await?;
 
     // 返回 200 OK 和新创建的采购订单ID
     Ok(HttpResponse::Ok().json(serde_json::json!({ "purchase_order_id": po_id })))
-}
\ No newline at end of file
+}
+/// 供应方(Supplier)修改自己仍有效的报价
+/// PUT /api/quotes/{quote_id}
+pub async fn put_revise_quote(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    quote_id: web::Path<i32>,
+    dto: web::Json<CreateQuoteDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let revision = quote_service::revise_quote(
+        pool.get_ref(),
+        chat_server.get_ref(),
+        quote_id.into_inner(),
+        dto.into_inner(),
+        &claims,
+    )
+        .await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "revision": revision })))
+}
+
+/// 供应方(Supplier)查看自己提交过的报价
+/// GET /api/quotes/mine?status=SUBMITTED&page=1&page_size=20
+pub async fn get_my_quotes(
+    pool: web::Data<MySqlPool>,
+    params: web::Query<MyQuotesParams>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let params = params.into_inner();
+    let page = quote_service::get_quotes_for_supplier(
+        pool.get_ref(),
+        &claims,
+        params.status,
+        params.page.unwrap_or(1),
+        params.page_size.unwrap_or(20),
+    )
+        .await?;
+    Ok(HttpResponse::Ok().json(page))
+}
@@ -0,0 +1,101 @@
+use crate::{
+    errors::AppError,
+    models::{receipt::{CloseNcrDto, CreateGoodsReceiptDto, NcrResponseDto}, user::Claims},
+    services::{chat_server::ChatServer, receipt_service},
+};
+use actix::Addr;
+use actix_multipart::Multipart;
+use actix_web::{http::header, web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use sqlx::MySqlPool;
+
+pub async fn post_goods_receipt(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    order_id: web::Path<i32>,
+    dto: web::Json<CreateGoodsReceiptDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let receipt_id = receipt_service::create_goods_receipt(pool.get_ref(), chat_server.get_ref(), order_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Created().json(serde_json::json!({ "message": "Goods receipt recorded successfully", "receipt_id": receipt_id })))
+}
+
+pub async fn get_goods_receipts(
+    pool: web::Data<MySqlPool>,
+    order_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let receipts = receipt_service::get_receipts_for_order(pool.get_ref(), order_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(receipts))
+}
+
+pub async fn get_order_ncrs(
+    pool: web::Data<MySqlPool>,
+    order_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let ncrs = receipt_service::get_ncrs_for_order(pool.get_ref(), order_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(ncrs))
+}
+
+pub async fn get_ncr(
+    pool: web::Data<MySqlPool>,
+    ncr_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let ncr = receipt_service::get_ncr_detail(pool.get_ref(), ncr_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(ncr))
+}
+
+pub async fn post_ncr_photos(
+    pool: web::Data<MySqlPool>,
+    ncr_id: web::Path<i32>,
+    payload: Multipart,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let photos = receipt_service::add_ncr_photos(pool.get_ref(), ncr_id.into_inner(), payload, &claims).await?;
+    Ok(HttpResponse::Created().json(photos))
+}
+
+pub async fn download_ncr_photo(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<(i32, i32)>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let (ncr_id, photo_id) = path.into_inner();
+    let (photo, content_type, content) = receipt_service::download_ncr_photo(pool.get_ref(), ncr_id, photo_id, &claims).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(header::ContentDisposition::attachment(photo.original_filename))
+        .body(content))
+}
+
+pub async fn put_ncr_response(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    ncr_id: web::Path<i32>,
+    dto: web::Json<NcrResponseDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    receipt_service::respond_to_ncr(pool.get_ref(), chat_server.get_ref(), ncr_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Corrective action submitted successfully" })))
+}
+
+pub async fn put_close_ncr(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    ncr_id: web::Path<i32>,
+    dto: Option<web::Json<CloseNcrDto>>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let dto = dto.map(|d| d.into_inner()).unwrap_or(CloseNcrDto { comment: None });
+    receipt_service::close_ncr(pool.get_ref(), chat_server.get_ref(), ncr_id.into_inner(), dto, &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Non-conformance report closed successfully" })))
+}
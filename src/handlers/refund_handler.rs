@@ -0,0 +1,45 @@
+use actix::Addr;
+use crate::{
+    errors::AppError,
+    models::{refund::{CreateRefundDto, RespondRefundDto}, user::Claims},
+    services::{chat_server::ChatServer, payment_provider::PaymentProvider, refund_service},
+};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use sqlx::MySqlPool;
+
+pub async fn post_refund(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    provider: web::Data<dyn PaymentProvider>,
+    order_id: web::Path<i32>,
+    dto: web::Json<CreateRefundDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let refund = refund_service::create_refund(pool.get_ref(), chat_server.get_ref(), provider.get_ref(), order_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Created().json(refund))
+}
+
+pub async fn get_refunds(
+    pool: web::Data<MySqlPool>,
+    order_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let refunds = refund_service::get_refunds_for_order(pool.get_ref(), order_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(refunds))
+}
+
+pub async fn put_refund_response(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    provider: web::Data<dyn PaymentProvider>,
+    path: web::Path<(i32, i32)>,
+    dto: web::Json<RespondRefundDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let (order_id, refund_id) = path.into_inner();
+    let refund = refund_service::respond_to_refund(pool.get_ref(), chat_server.get_ref(), provider.get_ref(), order_id, refund_id, dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(refund))
+}
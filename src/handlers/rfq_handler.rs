@@ -1,7 +1,7 @@
 use actix::Addr;
 use crate::{
     errors::AppError,
-    models::user::Claims,
+    models::{rfq::RfqResponseDto, user::Claims},
     services::rfq_service,
 };
 use serde::Deserialize;
@@ -70,4 +70,30 @@ pub async fn get_messages(
 ) -> Result<impl Responder, AppError> {
     let messages = rfq_service::get_messages_for_rfq(pool.get_ref(), rfq_id.into_inner()).await?;
     Ok(HttpResponse::Ok().json(messages))
-}
\ No newline at end of file
+}
+
+/// 供应商表态：准备报价 / 不参与
+/// POST /api/rfqs/{rfq_id}/response
+pub async fn post_rfq_response(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    rfq_id: web::Path<i32>,
+    dto: web::Json<RfqResponseDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    rfq_service::respond_to_rfq(pool.get_ref(), chat_server.get_ref(), rfq_id.into_inner(), dto.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Response recorded" })))
+}
+
+/// 采购方查看供应商表态
+/// GET /api/rfqs/{rfq_id}/responses
+pub async fn get_rfq_responses(
+    pool: web::Data<MySqlPool>,
+    rfq_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let responses = rfq_service::get_responses_for_rfq(pool.get_ref(), rfq_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(responses))
+}
@@ -0,0 +1,45 @@
+use crate::{
+    errors::AppError,
+    models::user::Claims,
+    services::{chat_server::ChatServer, shipment_service},
+};
+use actix::Addr;
+use actix_multipart::Multipart;
+use actix_web::{http::header, web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use sqlx::MySqlPool;
+
+pub async fn post_shipment(
+    pool: web::Data<MySqlPool>,
+    chat_server: web::Data<Addr<ChatServer>>,
+    order_id: web::Path<i32>,
+    payload: Multipart,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let shipment_id = shipment_service::create_shipment(pool.get_ref(), chat_server.get_ref(), order_id.into_inner(), payload, &claims).await?;
+    Ok(HttpResponse::Created().json(serde_json::json!({ "message": "Shipment recorded successfully", "shipment_id": shipment_id })))
+}
+
+pub async fn get_shipments(
+    pool: web::Data<MySqlPool>,
+    order_id: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let shipments = shipment_service::get_shipments_for_order(pool.get_ref(), order_id.into_inner(), &claims).await?;
+    Ok(HttpResponse::Ok().json(shipments))
+}
+
+pub async fn download_packing_list(
+    pool: web::Data<MySqlPool>,
+    path: web::Path<(i32, i32)>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let (order_id, shipment_id) = path.into_inner();
+    let (filename, content_type, content) = shipment_service::download_packing_list(pool.get_ref(), order_id, shipment_id, &claims).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(header::ContentDisposition::attachment(filename))
+        .body(content))
+}
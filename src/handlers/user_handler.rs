@@ -1,6 +1,6 @@
 use crate::{
     errors::AppError,
-    models::user::{ChangePasswordDto, Claims},
+    models::user::{ChangePasswordDto, Claims, UpdateLocaleDto},
     services::user_service,
 };
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
@@ -25,3 +25,12 @@ pub async fn update_password(
     Ok(HttpResponse::Ok().json(serde_json::json!({ "message": "Password updated successfully" })))
 }
 
+pub async fn update_locale(
+    pool: web::Data<MySqlPool>,
+    dto: web::Json<UpdateLocaleDto>,
+    req: HttpRequest,
+) -> Result<impl Responder, AppError> {
+    let claims = req.extensions().get::<Claims>().cloned().ok_or(AppError::AuthError)?;
+    let profile = user_service::update_locale(pool.get_ref(), &claims, dto.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(profile))
+}
@@ -12,6 +12,7 @@ use crate::{
     },
     utils::auth_utils,
 };
+use crate::models::notification_preference::NotificationCategory;
 use querystring::querify;
 use sqlx::MySqlPool;
 use crate::services::chat_server::{RtcCallAccepted, RtcCallRequest, RtcSignal};
@@ -169,6 +170,7 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
                                                 if owner_id != current_user_id { // 不给自己发通知
                                                     let _ = NotificationBuilder::new(
                                                         owner_id,
+                                                        NotificationCategory::Message,
                                                         format!("New message from {} in RFQ #{}", &user_full_name, rfq_id)
                                                     )
                                                         .with_link(format!("/rfqs/{}", rfq_id))
@@ -37,11 +37,31 @@ async fn main() -> std::io::Result<()> {
     let config = Config::from_env();
     let pool = config.db_pool().await;
     log::info!("Database pool created successfully.");
+    // 如果配置了汇率文件，启动时导入汇率表
+    if let Ok(fx_file) = env::var("FX_RATES_FILE") {
+        match services::fx_service::load_rates_from_file(&pool, &fx_file).await {
+            Ok(count) => log::info!("Loaded {} FX rates from {}", count, fx_file),
+            Err(e) => log::error!("Failed to load FX rates from {}: {:?}", fx_file, e),
+        }
+    }
     // 获取服务地址和端口
     let server_addr = env::var("SERVER_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".to_string());
     log::info!("Server starting at http://{}", server_addr);
     // 在 HttpServer::new 之前，启动ChatServer Actor，此处顺序不对会让ChatServer炸掉
     let chat_server = ChatServer::default().start();
+    // 后台交期检查
+    services::delivery_service::spawn_delivery_monitor(pool.clone(), chat_server.clone());
+    // 支付渠道（PAYMENT_PROVIDER=mock 时不连Stripe）
+    let payment_provider = services::payment_provider::provider_from_env();
+    // 后台支付对账，补上丢失的Webhook
+    services::reconciliation_service::spawn_reconciliation_job(pool.clone(), chat_server.clone(), payment_provider.clone());
+    // 账期发票到期和逾期提醒
+    services::payment_terms_service::spawn_payment_reminders(pool.clone(), chat_server.clone());
+    // 邮件发件箱投递（EMAIL_TRANSPORT=file 时写文件或打印到终端）
+    services::email_service::spawn_email_outbox(pool.clone(), services::email_transport::transport_from_env());
+    // 通知Webhook投递和每日摘要
+    services::notification_channel_service::spawn_notification_channels(pool.clone());
+    let payment_provider = web::Data::from(payment_provider);
     // 启动HTTP服务器
     HttpServer::new(move || {
         // 配置CORS（跨域资源共享）
@@ -55,6 +75,8 @@ async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(pool.clone()))
             //将ChatServer的地址共享给所有处理器
             .app_data(web::Data::new(chat_server.clone()))
+            // 支付渠道
+            .app_data(payment_provider.clone())
             // 启用日志中间件
             .wrap(Logger::default())
             // 启用CORS中间件
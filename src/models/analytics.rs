@@ -1,29 +1,41 @@
 use serde::Serialize;
 use sqlx::{types::Decimal, FromRow};
+use crate::models::{delivery::DeliveryPerformance, money, receipt::SupplierQuality};
 
-// 自定义Decimal的序列化，我觉得我应该去提issue
-mod decimal_as_string {
-    use super::*;
-    pub fn serialize<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
-    where S: serde::Serializer,
-    {
-        serializer.serialize_str(&value.to_string())
-    }
-}
-
+// 金额类字段都已折算到公司的报表币种(currency)
 #[derive(Debug, Serialize, FromRow)]
 pub struct BuyerStats {
     pub total_orders: i64, // 用 i64 以防订单数非常多
-    #[serde(with = "decimal_as_string")]
+    // 含运费和税的订单总额，以及其中的运费和税额
+    #[sqlx(skip)]
+    #[serde(with = "money::decimal_as_string")]
     pub total_spent: Decimal,
+    #[sqlx(skip)]
+    #[serde(with = "money::decimal_as_string")]
+    pub total_shipping: Decimal,
+    #[sqlx(skip)]
+    #[serde(with = "money::decimal_as_string")]
+    pub total_tax: Decimal,
     pub distinct_suppliers: i64,
+    // 有退款的订单数和累计退款金额
+    pub refunded_orders: i64,
+    #[sqlx(skip)]
+    #[serde(with = "money::decimal_as_string")]
+    pub total_refunded: Decimal,
+    #[sqlx(skip)]
+    pub currency: String,
+    // 缺汇率没能折算、没有计入上面合计的币种
+    #[sqlx(skip)]
+    pub unconverted_currencies: Vec<String>,
 }
 
-#[derive(Debug, Serialize, FromRow)]
+#[derive(Debug, Serialize)]
 pub struct SpendingBySupplier {
     pub supplier_name: String,
-    #[serde(with = "decimal_as_string")]
+    #[serde(with = "money::decimal_as_string")]
     pub total: Decimal,
+    pub currency: String,
+    pub unconverted_currencies: Vec<String>,
 }
 
 /// 用于供应方(Supplier)仪表盘的统计数据结构
@@ -31,6 +43,28 @@ pub struct SpendingBySupplier {
 pub struct SupplierStats {
     pub total_quotes_submitted: i64,
     pub accepted_quotes: i64,
-    #[serde(with = "decimal_as_string")]
+    // 供应商对RFQ的表态统计
+    pub intents_to_quote: i64,
+    pub declined_rfqs: i64,
+    // 营收 = 货款 + 运费，代收的税额单独统计
+    #[sqlx(skip)]
+    #[serde(with = "money::decimal_as_string")]
     pub total_revenue: Decimal,
-}
\ No newline at end of file
+    #[sqlx(skip)]
+    #[serde(with = "money::decimal_as_string")]
+    pub tax_collected: Decimal,
+    pub refunded_orders: i64,
+    #[sqlx(skip)]
+    #[serde(with = "money::decimal_as_string")]
+    pub total_refunded: Decimal,
+    #[sqlx(skip)]
+    pub currency: String,
+    #[sqlx(skip)]
+    pub unconverted_currencies: Vec<String>,
+    // 收货检验和NCR历史
+    #[sqlx(skip)]
+    pub quality: SupplierQuality,
+    // 准时交付情况
+    #[sqlx(skip)]
+    pub delivery: DeliveryPerformance,
+}
@@ -0,0 +1,73 @@
+// src/models/blanket_agreement.rs
+use crate::models::{money, order::PurchaseOrder};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Decimal, FromRow};
+
+/// 基于一张历史订单提出框架协议，零件、供应商和币种沿用该订单
+#[derive(Debug, Deserialize)]
+pub struct CreateBlanketAgreementDto {
+    pub source_order_id: i32,
+    #[serde(deserialize_with = "money::amount_from_str_or_number")]
+    pub unit_price: Decimal,
+    pub total_quantity: i32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespondBlanketAgreementDto {
+    pub accept: bool,
+    pub comment: Option<String>,
+}
+
+/// 在协议下下达一批订单
+#[derive(Debug, Deserialize)]
+pub struct CreateReleaseDto {
+    pub quantity: i32,
+    // 为空时按原报价的交货周期计算
+    pub delivery_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct BlanketAgreement {
+    pub id: i32,
+    pub agreement_number: Option<String>,
+    pub buyer_company_id: i32,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub buyer_name: String,
+    pub supplier_company_id: i32,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub supplier_name: String,
+    pub source_order_id: i32,
+    pub rfq_id: i32,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub rfq_title: String,
+    pub quote_id: i32,
+    #[serde(with = "money::unit_price_as_string")]
+    pub unit_price: Decimal,
+    pub currency: String,
+    pub total_quantity: i32,
+    // 已下达数量，不含已取消的订单
+    #[sqlx(default)]
+    pub released_quantity: i64,
+    #[sqlx(default)]
+    pub remaining_quantity: i64,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub notes: Option<String>,
+    pub status: String,
+    pub proposed_by_company_id: i32,
+    pub response_comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub responded_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlanketAgreementDetail {
+    #[serde(flatten)]
+    pub agreement: BlanketAgreement,
+    pub releases: Vec<PurchaseOrder>,
+}
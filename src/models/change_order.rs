@@ -0,0 +1,51 @@
+// src/models/change_order.rs
+use crate::models::money;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Decimal, FromRow};
+
+/// 变更单，至少要修改数量、单价、交期中的一项
+#[derive(Debug, Deserialize)]
+pub struct CreateChangeOrderDto {
+    pub new_quantity: Option<i32>,
+    #[serde(default, deserialize_with = "money::option_amount_from_str_or_number")]
+    pub new_unit_price: Option<Decimal>,
+    pub new_delivery_date: Option<NaiveDate>,
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespondChangeOrderDto {
+    pub accept: bool,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ChangeOrder {
+    pub id: i32,
+    pub order_id: i32,
+    pub proposed_by_company_id: i32,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub proposed_by_company_name: String,
+    pub proposed_by_user_id: Option<i32>,
+    pub reason: String,
+    pub old_quantity: i32,
+    pub new_quantity: Option<i32>,
+    #[serde(with = "money::unit_price_as_string")]
+    pub old_unit_price: Decimal,
+    #[serde(with = "money::option_unit_price_as_string")]
+    pub new_unit_price: Option<Decimal>,
+    pub old_delivery_date: Option<NaiveDate>,
+    pub new_delivery_date: Option<NaiveDate>,
+    // 变更前后的货款小计，不含运费和税
+    #[serde(with = "money::decimal_as_string")]
+    pub old_total_amount: Decimal,
+    #[serde(with = "money::option_decimal_as_string")]
+    pub new_total_amount: Option<Decimal>,
+    pub status: String,
+    pub amendment_number: Option<i32>,
+    pub responded_by_user_id: Option<i32>,
+    pub response_comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub responded_at: Option<DateTime<Utc>>,
+}
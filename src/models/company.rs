@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use chrono::{DateTime, Utc};
+use crate::models::{money::Currency, rating::SupplierScorecard};
 
 #[derive(Debug, Serialize, FromRow)]
 pub struct CompanyProfile {
@@ -8,13 +9,26 @@ pub struct CompanyProfile {
     pub name: String,
     pub company_type: String,
     pub city: Option<String>,
+    // 税率按国家和地区匹配
+    pub country_code: Option<String>,
+    pub region: Option<String>,
     pub description: Option<String>,
+    // 分析报表和报价比较使用的币种
+    pub reporting_currency: String,
     pub created_at: DateTime<Utc>,
     pub is_verified: bool,
+    // 只有供应商才有评分卡
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scorecard: Option<SupplierScorecard>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateCompanyDto {
-    // 目前只允许更新简介
+    // 目前只允许更新简介、报表币种和所在国家/地区
     pub description: String,
+    pub reporting_currency: Option<Currency>,
+    // 不传表示不修改；传了国家时地区一起更新
+    pub country_code: Option<String>,
+    pub region: Option<String>,
 }
\ No newline at end of file
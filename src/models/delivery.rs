@@ -0,0 +1,33 @@
+// src/models/delivery.rs
+use serde::Serialize;
+
+/// 未发完货的订单相对承诺交期的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    OnTrack,
+    AtRisk,
+    Late,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::OnTrack => "ON_TRACK",
+            DeliveryStatus::AtRisk => "AT_RISK",
+            DeliveryStatus::Late => "LATE",
+        }
+    }
+}
+
+/// 供应商交期表现，来自全部发货时记录的准时/延期结果
+#[derive(Debug, Serialize, Default)]
+pub struct DeliveryPerformance {
+    pub delivered_orders: i64,
+    pub on_time_orders: i64,
+    pub late_orders: i64,
+    // 0-100，没有已交付订单时为空
+    pub on_time_rate: Option<f64>,
+    pub average_days_late: Option<f64>,
+    // 当前未发完货且已经延期的订单
+    pub currently_late: i64,
+}
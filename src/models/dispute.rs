@@ -0,0 +1,115 @@
+// src/models/dispute.rs
+use crate::models::money;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Decimal, FromRow};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateDisputeDto {
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisputeMessageDto {
+    pub content: String,
+}
+
+/// 管理员仲裁结果：REFUND 退款 / REWORK 返工 / CLOSED 直接关闭
+#[derive(Debug, Deserialize)]
+pub struct ResolveDisputeDto {
+    pub resolution: String,
+    pub note: Option<String>,
+    // 仅 REFUND 需要，币种与订单一致
+    #[serde(default, deserialize_with = "option_amount")]
+    pub refund_amount: Option<Decimal>,
+}
+
+fn option_amount<'de, D>(deserializer: D) -> Result<Option<Decimal>, D::Error>
+where D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Amount(#[serde(deserialize_with = "money::amount_from_str_or_number")] Decimal);
+    Ok(Option::<Amount>::deserialize(deserializer)?.map(|a| a.0))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisputeResolution {
+    Refund,
+    Rework,
+    Closed,
+}
+
+impl DisputeResolution {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DisputeResolution::Refund => "REFUND",
+            DisputeResolution::Rework => "REWORK",
+            DisputeResolution::Closed => "CLOSED",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "REFUND" => Some(DisputeResolution::Refund),
+            "REWORK" => Some(DisputeResolution::Rework),
+            "CLOSED" => Some(DisputeResolution::Closed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Dispute {
+    pub id: i32,
+    pub order_id: i32,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub rfq_title: String,
+    pub opened_by_company_id: i32,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub opened_by_company_name: String,
+    pub opened_by_user_id: Option<i32>,
+    pub reason: String,
+    pub status: String,
+    pub resolution: Option<String>,
+    pub resolution_note: Option<String>,
+    #[serde(with = "money::option_decimal_as_string")]
+    pub refund_amount: Option<Decimal>,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub currency: String,
+    pub resolved_by_user_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DisputeMessage {
+    pub id: i32,
+    pub dispute_id: i32,
+    pub sender_user_id: Option<i32>,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub sender_name: Option<String>,
+    pub sender_company_id: Option<i32>,
+    pub is_admin: bool,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DisputeAttachment {
+    pub id: i32,
+    pub dispute_id: i32,
+    pub uploaded_by_user_id: Option<i32>,
+    pub original_filename: String,
+    #[serde(skip_serializing)] // 文件只能通过下载接口获取
+    pub stored_path: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 争议详情：争议本身 + 沟通记录 + 证据
+#[derive(Debug, Serialize)]
+pub struct DisputeDetail {
+    #[serde(flatten)]
+    pub dispute: Dispute,
+    pub messages: Vec<DisputeMessage>,
+    pub attachments: Vec<DisputeAttachment>,
+}
@@ -0,0 +1,25 @@
+// src/models/document.rs
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+
+pub const DOCUMENT_TYPE_PURCHASE_ORDER: &str = "PURCHASE_ORDER";
+pub const DOCUMENT_TYPE_INVOICE_PDF: &str = "INVOICE_PDF";
+pub const DOCUMENT_TYPE_INVOICE_UBL: &str = "INVOICE_UBL";
+
+/// 订单的正式文件，生成后不可修改
+#[derive(Debug, Serialize, FromRow)]
+pub struct Document {
+    pub id: i32,
+    pub order_id: i32,
+    pub document_type: String,
+    pub document_number: String,
+    pub version: i32,
+    pub filename: String,
+    #[serde(skip_serializing)] // 文件只能通过下载接口获取
+    pub stored_path: String,
+    pub content_type: String,
+    pub size_bytes: i32,
+    pub sha256: String,
+    pub created_at: DateTime<Utc>,
+}
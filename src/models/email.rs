@@ -0,0 +1,106 @@
+// src/models/email.rs
+// 邮件发件箱
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// 邮件附件
+#[derive(Debug, Clone, FromRow)]
+pub struct EmailAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+/// 发件箱里的一封邮件。status：PENDING 等待投递或重试，SENT 已发出，DEAD 重试次数用完
+#[derive(Debug, Serialize, FromRow)]
+pub struct OutboxEmail {
+    pub id: i32,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    pub html_body: Option<String>,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub transport: Option<String>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OutboxListParams {
+    pub status: Option<String>,
+}
+
+/// 邮件语言，用户没设置或设置了不支持的语言时用英文
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    ZhCn,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::ZhCn];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::ZhCn => "zh-CN",
+        }
+    }
+
+    /// 接受 en、en-US、zh、zh-CN、zh_cn 这类写法
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_lowercase().replace('_', "-");
+        match s.split('-').next() {
+            Some("en") => Some(Locale::En),
+            Some("zh") => Some(Locale::ZhCn),
+            _ => None,
+        }
+    }
+
+    /// 数据库里存的语言
+    pub fn from_stored(s: &str) -> Self {
+        Self::parse(s).unwrap_or_default()
+    }
+}
+
+/// 渲染好的邮件：纯文本和HTML两个版本
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmailTemplateInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub variables: Vec<&'static str>,
+    pub locales: Vec<&'static str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TemplatePreviewParams {
+    pub locale: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_parse() {
+        assert_eq!(Locale::parse("zh_cn"), Some(Locale::ZhCn));
+        assert_eq!(Locale::parse(" en-US "), Some(Locale::En));
+        assert_eq!(Locale::parse("fr"), None);
+        assert_eq!(Locale::from_stored("fr"), Locale::En);
+        for locale in Locale::ALL {
+            assert_eq!(Locale::parse(locale.as_str()), Some(locale));
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// src/models/fx.rs
+use crate::{errors::AppError, models::money::{amount_from_str_or_number, Currency}};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::{types::Decimal, FromRow};
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct FxRate {
+    pub currency: String,
+    #[serde(serialize_with = "rate_as_string")]
+    pub usd_rate: Decimal,
+    pub source: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 管理员更新汇率，例如 { "rates": { "CNY": "0.1389", "EUR": "1.0850" } }
+#[derive(Debug, Deserialize)]
+pub struct UpdateFxRatesDto {
+    #[serde(deserialize_with = "deserialize_rates")]
+    pub rates: HashMap<Currency, Decimal>,
+}
+
+// 汇率保留完整精度输出
+fn rate_as_string<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+where S: Serializer,
+{
+    serializer.collect_str(value)
+}
+
+/// 解析 { "CNY": "0.1389", "EUR": 1.085 } 这样的汇率表，管理员接口和汇率文件共用
+pub fn deserialize_rates<'de, D>(deserializer: D) -> Result<HashMap<Currency, Decimal>, D::Error>
+where D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Rate(#[serde(deserialize_with = "amount_from_str_or_number")] Decimal);
+
+    let raw = HashMap::<Currency, Rate>::deserialize(deserializer)?;
+    Ok(raw.into_iter().map(|(currency, rate)| (currency, rate.0)).collect())
+}
+
+/// 内存中的汇率表，所有币种都相对美元报价
+#[derive(Debug, Clone, Default)]
+pub struct FxTable {
+    usd_rates: HashMap<Currency, Decimal>,
+}
+
+impl FxTable {
+    pub fn new(usd_rates: HashMap<Currency, Decimal>) -> Self {
+        let mut usd_rates = usd_rates;
+        usd_rates.insert(Currency::USD, Decimal::ONE);
+        Self { usd_rates }
+    }
+
+    fn rate(&self, currency: Currency) -> Result<Decimal, AppError> {
+        self.usd_rates
+            .get(&currency)
+            .copied()
+            .filter(|r| *r > Decimal::ZERO)
+            .ok_or_else(|| AppError::BadRequest(format!("No FX rate configured for {}", currency)))
+    }
+
+    /// 把金额从一个币种折算到另一个币种，结果保留两位小数
+    pub fn convert(&self, amount: Decimal, from: Currency, to: Currency) -> Result<Decimal, AppError> {
+        if from == to {
+            return Ok(amount);
+        }
+        let converted = amount * self.rate(from)? / self.rate(to)?;
+        Ok(converted.round_dp(2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_fx_conversion() {
+        let table = FxTable::new(HashMap::from([
+            (Currency::CNY, dec("0.14")),
+            (Currency::EUR, dec("1.10")),
+        ]));
+
+        assert_eq!(table.convert(dec("100.00"), Currency::USD, Currency::USD).unwrap(), dec("100.00"));
+        assert_eq!(table.convert(dec("1000.00"), Currency::CNY, Currency::USD).unwrap(), dec("140.00"));
+        assert_eq!(table.convert(dec("110.00"), Currency::USD, Currency::EUR).unwrap(), dec("100.00"));
+        assert_eq!(table.convert(dec("1000.00"), Currency::CNY, Currency::EUR).unwrap(), dec("127.27"));
+    }
+
+    #[test]
+    fn test_deserialize_rates() {
+        let dto: UpdateFxRatesDto = serde_json::from_str(r#"{"rates":{"CNY":"0.1389","EUR":1.085}}"#).unwrap();
+        assert_eq!(dto.rates.get(&Currency::CNY), Some(&dec("0.1389")));
+        assert_eq!(dto.rates.get(&Currency::EUR), Some(&dec("1.085")));
+
+        assert!(serde_json::from_str::<UpdateFxRatesDto>(r#"{"rates":{"GBP":"1.2"}}"#).is_err());
+    }
+
+    #[test]
+    fn test_missing_rate_is_an_error() {
+        let table = FxTable::new(HashMap::new());
+        assert!(table.convert(dec("10.00"), Currency::CNY, Currency::USD).is_err());
+    }
+}
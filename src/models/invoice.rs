@@ -0,0 +1,88 @@
+// src/models/invoice.rs
+use crate::models::{document::Document, money};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Decimal, FromRow};
+
+#[derive(Debug, Deserialize)]
+pub struct TaxLineDto {
+    pub name: String,
+    // 百分比，例如 "19" 表示 19%
+    #[serde(deserialize_with = "money::amount_from_str_or_number")]
+    pub rate: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateInvoiceDto {
+    // 按发货批次开票；为空时按 quantity 开票，quantity 也为空则开剩余全部数量
+    pub shipment_id: Option<i32>,
+    pub quantity: Option<i32>,
+    #[serde(default)]
+    pub tax_lines: Vec<TaxLineDto>,
+    // 付款期限，默认30天
+    pub due_in_days: Option<u32>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Invoice {
+    pub id: i32,
+    pub invoice_number: String,
+    pub order_id: i32,
+    pub shipment_id: Option<i32>,
+    pub supplier_company_id: i32,
+    pub buyer_company_id: i32,
+    pub quantity: i32,
+    pub currency: String,
+    #[serde(with = "money::decimal_as_string")]
+    pub subtotal: Decimal,
+    #[serde(with = "money::decimal_as_string")]
+    pub tax_total: Decimal,
+    #[serde(with = "money::decimal_as_string")]
+    pub total: Decimal,
+    pub issue_date: NaiveDate,
+    pub due_date: NaiveDate,
+    pub notes: Option<String>,
+    pub payment_status: String,
+    // 线下付款的汇款凭证号
+    pub remittance_reference: Option<String>,
+    #[serde(with = "money::decimal_as_string")]
+    pub refunded_amount: Decimal,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct InvoiceLine {
+    pub id: i32,
+    pub invoice_id: i32,
+    pub description: String,
+    pub quantity: i32,
+    #[serde(with = "money::unit_price_as_string")]
+    pub unit_price: Decimal,
+    #[serde(with = "money::decimal_as_string")]
+    pub line_total: Decimal,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct InvoiceTaxLine {
+    pub id: i32,
+    pub invoice_id: i32,
+    pub name: String,
+    #[serde(with = "money::decimal_as_string")]
+    pub rate: Decimal,
+    #[serde(with = "money::decimal_as_string")]
+    pub taxable_amount: Decimal,
+    #[serde(with = "money::decimal_as_string")]
+    pub tax_amount: Decimal,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvoiceDetail {
+    #[serde(flatten)]
+    pub invoice: Invoice,
+    pub lines: Vec<InvoiceLine>,
+    pub tax_lines: Vec<InvoiceTaxLine>,
+    // PDF 和 UBL XML，通过 /api/documents/{id}/download 下载
+    pub documents: Vec<Document>,
+}
@@ -2,6 +2,8 @@ pub mod user;
 pub mod rfq;
 pub mod quote;
 pub mod order;
+pub mod money;
+pub(crate) mod fx;
 pub(crate) mod chat;
 pub(crate) mod company;
 pub(crate) mod analytics;
@@ -10,3 +12,18 @@ pub(crate) mod capability;
 pub(crate) mod notification;
 pub(crate) mod annotation;
 pub(crate) mod rating;
+pub(crate) mod dispute;
+pub(crate) mod shipment;
+pub(crate) mod receipt;
+pub(crate) mod document;
+pub(crate) mod invoice;
+pub(crate) mod change_order;
+pub(crate) mod blanket_agreement;
+pub(crate) mod delivery;
+pub(crate) mod refund;
+pub(crate) mod payment_schedule;
+pub(crate) mod reconciliation;
+pub(crate) mod payment_terms;
+pub(crate) mod tax;
+pub(crate) mod email;
+pub(crate) mod notification_preference;
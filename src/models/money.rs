@@ -0,0 +1,271 @@
+// src/models/money.rs
+// 统一的金额类型：金额用Decimal精确表示，永远不经过f64，和币种一起传递
+use crate::errors::AppError;
+use num_traits::ToPrimitive;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::types::Decimal;
+use std::fmt;
+use std::str::FromStr;
+
+/// 单笔金额上限，对应数据库中的 DECIMAL(12, 2): 9,999,999,999.99
+pub const MAX_AMOUNT: Decimal = Decimal::from_parts(0xD4A5_0FFF, 0xE8, 0, false, 2);
+/// 金额最多保留两位小数
+pub const MAX_DECIMAL_PLACES: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Currency {
+    #[default]
+    USD,
+    CNY,
+    EUR,
+}
+
+impl Currency {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::USD => "USD",
+            Currency::CNY => "CNY",
+            Currency::EUR => "EUR",
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "USD" => Ok(Currency::USD),
+            "CNY" => Ok(Currency::CNY),
+            "EUR" => Ok(Currency::EUR),
+            other => Err(AppError::BadRequest(format!("Unsupported currency: {}", other))),
+        }
+    }
+}
+
+/// 金额按商业惯例四舍五入到两位小数（Decimal 默认的 round_dp 是银行家舍入）
+pub fn round_amount(amount: Decimal) -> Decimal {
+    amount.round_dp_with_strategy(MAX_DECIMAL_PLACES, rust_decimal::RoundingStrategy::MidpointAwayFromZero)
+}
+
+/// 带币种的金额。构造时校验：必须为正数、最多两位小数、不超过上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Money {
+    amount: Decimal,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount: Decimal, currency: Currency) -> Result<Self, AppError> {
+        let amount = amount.normalize();
+        if amount <= Decimal::ZERO {
+            return Err(AppError::BadRequest("Amount must be greater than zero.".to_string()));
+        }
+        if amount.scale() > MAX_DECIMAL_PLACES {
+            return Err(AppError::BadRequest("Amount can have at most 2 decimal places.".to_string()));
+        }
+        if amount > MAX_AMOUNT {
+            return Err(AppError::BadRequest(format!("Amount must not exceed {}.", MAX_AMOUNT)));
+        }
+        Ok(Self { amount, currency })
+    }
+
+    pub fn parse(amount: &str, currency: Currency) -> Result<Self, AppError> {
+        let amount = Decimal::from_str(amount.trim())
+            .map_err(|_| AppError::BadRequest("Invalid amount format".to_string()))?;
+        Self::new(amount, currency)
+    }
+
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    /// 转换为最小货币单位（分），Stripe等支付接口使用
+    pub fn to_minor_units(&self) -> Result<i64, AppError> {
+        (self.amount * Decimal::ONE_HUNDRED)
+            .to_i64()
+            .ok_or_else(|| AppError::InternalServerError(format!("Amount {} is out of range", self.amount)))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2} {}", self.amount, self.currency)
+    }
+}
+
+// 序列化为 {"amount": "12.30", "currency": "USD"}
+impl Serialize for Money {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer,
+    {
+        #[derive(Serialize)]
+        struct Repr<'a> {
+            amount: String,
+            currency: &'a str,
+        }
+        Repr { amount: format!("{:.2}", self.amount), currency: self.currency.code() }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Repr {
+            #[serde(deserialize_with = "amount_from_str_or_number")]
+            amount: Decimal,
+            #[serde(default)]
+            currency: Currency,
+        }
+        let repr = Repr::deserialize(deserializer)?;
+        Money::new(repr.amount, repr.currency).map_err(de::Error::custom)
+    }
+}
+
+/// 金额统一序列化为两位小数的字符串，替代各个model里重复的 decimal_as_string
+pub mod decimal_as_string {
+    use super::*;
+    pub fn serialize<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer,
+    {
+        serializer.serialize_str(&format!("{:.2}", value))
+    }
+}
+
+/// 可选金额，None 序列化为 null
+pub mod option_decimal_as_string {
+    use super::*;
+    pub fn serialize<S>(value: &Option<Decimal>, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer,
+    {
+        match value {
+            Some(v) => serializer.serialize_str(&format!("{:.2}", v)),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+/// 单价保留四位小数，例如整单总价折算出来的单价
+pub mod unit_price_as_string {
+    use super::*;
+    pub fn serialize<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer,
+    {
+        serializer.serialize_str(&format!("{:.4}", value))
+    }
+}
+
+pub mod option_unit_price_as_string {
+    use super::*;
+    pub fn serialize<S>(value: &Option<Decimal>, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer,
+    {
+        match value {
+            Some(v) => serializer.serialize_str(&format!("{:.4}", v)),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+/// 从JSON读取金额，推荐传字符串 "12.34"；也兼容数字 12.34（按最短十进制表示解析，不做浮点运算）
+pub fn amount_from_str_or_number<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where D: Deserializer<'de>,
+{
+    struct AmountVisitor;
+
+    impl de::Visitor<'_> for AmountVisitor {
+        type Value = Decimal;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a decimal amount as a string or number")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Decimal, E> {
+            Decimal::from_str(v.trim()).map_err(|_| E::custom("invalid amount format"))
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Decimal, E> {
+            Ok(Decimal::from(v))
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Decimal, E> {
+            Ok(Decimal::from(v))
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<Decimal, E> {
+            if !v.is_finite() {
+                return Err(E::custom("invalid amount format"));
+            }
+            // f64 的 Display 输出的是能还原该值的最短十进制串，例如 0.1 -> "0.1"
+            Decimal::from_str(&v.to_string()).map_err(|_| E::custom("invalid amount format"))
+        }
+    }
+
+    deserializer.deserialize_any(AmountVisitor)
+}
+
+/// 可选金额，字段缺失或为 null 时是 None。需要配合 `#[serde(default)]` 使用
+pub fn option_amount_from_str_or_number<'de, D>(deserializer: D) -> Result<Option<Decimal>, D::Error>
+where D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Amount(#[serde(deserialize_with = "amount_from_str_or_number")] Decimal);
+
+    Ok(Option::<Amount>::deserialize(deserializer)?.map(|a| a.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_money_validation() {
+        assert_eq!(Money::parse("12.30", Currency::USD).unwrap().amount(), Decimal::from_str("12.3").unwrap());
+        assert!(Money::parse("0", Currency::USD).is_err());
+        assert!(Money::parse("-5.00", Currency::USD).is_err());
+        assert!(Money::parse("1.005", Currency::USD).is_err());
+        assert!(Money::parse("abc", Currency::USD).is_err());
+        assert!(Money::parse("9999999999.99", Currency::USD).is_ok());
+        assert!(Money::parse("10000000000.00", Currency::USD).is_err());
+    }
+
+    #[test]
+    fn test_round_amount() {
+        assert_eq!(round_amount(Decimal::from_str("2.345").unwrap()), Decimal::from_str("2.35").unwrap());
+        assert_eq!(round_amount(Decimal::from_str("2.355").unwrap()), Decimal::from_str("2.36").unwrap());
+        assert_eq!(round_amount(Decimal::from_str("-1.005").unwrap()), Decimal::from_str("-1.01").unwrap());
+    }
+
+    #[test]
+    fn test_minor_units_are_exact() {
+        // 0.29 * 100 用f64计算会得到 28.999999999999996
+        assert_eq!(Money::parse("0.29", Currency::USD).unwrap().to_minor_units().unwrap(), 29);
+        assert_eq!(Money::parse("1234567.89", Currency::EUR).unwrap().to_minor_units().unwrap(), 123456789);
+    }
+
+    #[test]
+    fn test_money_serde() {
+        let money = Money::parse("12.3", Currency::CNY).unwrap();
+        assert_eq!(serde_json::to_string(&money).unwrap(), r#"{"amount":"12.30","currency":"CNY"}"#);
+
+        let from_str: Money = serde_json::from_str(r#"{"amount":"19.99","currency":"EUR"}"#).unwrap();
+        assert_eq!(from_str, Money::parse("19.99", Currency::EUR).unwrap());
+
+        let from_number: Money = serde_json::from_str(r#"{"amount":0.1}"#).unwrap();
+        assert_eq!(from_number, Money::parse("0.1", Currency::USD).unwrap());
+
+        assert!(serde_json::from_str::<Money>(r#"{"amount":"1.999"}"#).is_err());
+    }
+}
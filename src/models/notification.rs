@@ -6,6 +6,7 @@ use chrono::{DateTime, Utc};
 pub struct Notification {
     pub id: i32,
     pub recipient_user_id: i32,
+    pub category: Option<String>,
     pub message: String,
     pub link_url: Option<String>,
     pub is_read: bool,
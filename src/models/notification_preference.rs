@@ -0,0 +1,179 @@
+// src/models/notification_preference.rs
+// 通知偏好：事件类别 × 通道，加上免打扰时段和Webhook地址
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// 通知的事件类别。事务性类别（付款、争议、系统告警）的站内和邮件通知不能关闭
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationCategory {
+    // 询价、报价、授标
+    Rfq,
+    // 订单、发货、收货、变更单
+    Order,
+    // 框架协议
+    Agreement,
+    // 询价聊天消息
+    Message,
+    // 付款、发票、退款、账期
+    Payment,
+    // 争议
+    Dispute,
+    // 平台管理员告警
+    System,
+}
+
+impl NotificationCategory {
+    pub const ALL: [NotificationCategory; 7] = [
+        NotificationCategory::Rfq, NotificationCategory::Order, NotificationCategory::Agreement, NotificationCategory::Message,
+        NotificationCategory::Payment, NotificationCategory::Dispute, NotificationCategory::System,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationCategory::Rfq => "RFQ",
+            NotificationCategory::Order => "ORDER",
+            NotificationCategory::Agreement => "AGREEMENT",
+            NotificationCategory::Message => "MESSAGE",
+            NotificationCategory::Payment => "PAYMENT",
+            NotificationCategory::Dispute => "DISPUTE",
+            NotificationCategory::System => "SYSTEM",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_uppercase();
+        Self::ALL.into_iter().find(|category| category.as_str() == s)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            NotificationCategory::Rfq => "RFQs, quotes and award results",
+            NotificationCategory::Order => "Order status, shipments, receipts and change orders",
+            NotificationCategory::Agreement => "Blanket agreements and releases",
+            NotificationCategory::Message => "Chat messages on RFQs",
+            NotificationCategory::Payment => "Payments, invoices, refunds and payment terms",
+            NotificationCategory::Dispute => "Disputes and their resolution",
+            NotificationCategory::System => "Platform alerts for administrators",
+        }
+    }
+
+    /// 事务性通知涉及钱和责任，必须送达
+    pub fn is_transactional(&self) -> bool {
+        matches!(self, NotificationCategory::Payment | NotificationCategory::Dispute | NotificationCategory::System)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationChannel {
+    InApp,
+    Email,
+    Webhook,
+    // 不单独发邮件，汇总进每日摘要
+    Digest,
+}
+
+impl NotificationChannel {
+    pub const ALL: [NotificationChannel; 4] =
+        [NotificationChannel::InApp, NotificationChannel::Email, NotificationChannel::Webhook, NotificationChannel::Digest];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationChannel::InApp => "IN_APP",
+            NotificationChannel::Email => "EMAIL",
+            NotificationChannel::Webhook => "WEBHOOK",
+            NotificationChannel::Digest => "DIGEST",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_uppercase();
+        Self::ALL.into_iter().find(|channel| channel.as_str() == s)
+    }
+
+    /// 没设置过偏好时：站内和邮件开，Webhook和摘要关
+    pub fn default_enabled(&self) -> bool {
+        matches!(self, NotificationChannel::InApp | NotificationChannel::Email)
+    }
+
+    /// 事务性类别不能关闭的通道
+    pub fn is_mandatory_for(&self, category: NotificationCategory) -> bool {
+        category.is_transactional() && matches!(self, NotificationChannel::InApp | NotificationChannel::Email)
+    }
+
+    /// 用户的设置（没有就用默认值），事务性类别的必选通道始终开启
+    pub fn is_enabled(&self, category: NotificationCategory, stored: Option<bool>) -> bool {
+        self.is_mandatory_for(category) || stored.unwrap_or_else(|| self.default_enabled())
+    }
+}
+
+/// 一个类别在各通道上的开关
+#[derive(Debug, Serialize)]
+pub struct CategoryPreferences {
+    pub category: &'static str,
+    pub description: &'static str,
+    // 为 true 时 in_app 和 email 不能关闭
+    pub transactional: bool,
+    pub in_app: bool,
+    pub email: bool,
+    pub webhook: bool,
+    pub digest: bool,
+}
+
+#[derive(Debug, Default, Clone, FromRow)]
+pub struct NotificationSettings {
+    pub quiet_hours_start: Option<NaiveTime>,
+    pub quiet_hours_end: Option<NaiveTime>,
+    pub utc_offset_minutes: i32,
+    pub webhook_url: Option<String>,
+    pub webhook_secret: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NotificationPreferences {
+    pub categories: Vec<CategoryPreferences>,
+    pub quiet_hours_start: Option<NaiveTime>,
+    pub quiet_hours_end: Option<NaiveTime>,
+    pub utc_offset_minutes: i32,
+    pub webhook_url: Option<String>,
+    // 接收方用它校验 X-SCCP-Signature
+    pub webhook_secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreferenceUpdate {
+    pub category: String,
+    pub channel: String,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePreferencesDto {
+    pub preferences: Vec<PreferenceUpdate>,
+}
+
+/// 免打扰时段用本地时间，两个都不填表示关闭
+#[derive(Debug, Deserialize)]
+pub struct UpdateNotificationSettingsDto {
+    pub quiet_hours_start: Option<NaiveTime>,
+    pub quiet_hours_end: Option<NaiveTime>,
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+    pub webhook_url: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transactional_channels_cannot_be_disabled() {
+        assert!(NotificationChannel::Email.is_enabled(NotificationCategory::Payment, Some(false)));
+        assert!(!NotificationChannel::Webhook.is_enabled(NotificationCategory::Payment, None));
+        assert!(!NotificationChannel::Email.is_enabled(NotificationCategory::Rfq, Some(false)));
+        assert!(NotificationChannel::InApp.is_enabled(NotificationCategory::Rfq, None));
+        assert!(NotificationChannel::Digest.is_enabled(NotificationCategory::Order, Some(true)));
+        assert_eq!(NotificationCategory::parse(" order "), Some(NotificationCategory::Order));
+        assert_eq!(NotificationChannel::parse("in_app"), Some(NotificationChannel::InApp));
+    }
+}
@@ -1,20 +1,17 @@
 use serde::{Deserialize, Serialize};
 use sqlx::{types::Decimal, FromRow};
-use chrono::{DateTime, Utc};
-
-mod decimal_as_string {
-    use super::*;
-    pub fn serialize<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
-    where S: serde::Serializer,
-    {
-        serializer.serialize_str(&value.to_string())
-    }
-}
+use crate::models::money;
+use chrono::{DateTime, NaiveDate, Utc};
 
 #[derive(Debug, Serialize, FromRow)]
 pub struct PurchaseOrder {
     pub id: i32,
+    pub po_number: Option<String>,
     pub rfq_id: i32,
+    // 再订货时的原订单
+    pub source_order_id: Option<i32>,
+    // 框架协议下达的订单
+    pub blanket_agreement_id: Option<i32>,
     #[sqlx(default)] // 这个字段来自JOIN
     pub rfq_title: String,
     pub buyer_company_id: i32,
@@ -23,16 +20,229 @@ pub struct PurchaseOrder {
     pub supplier_company_id: i32,
     #[sqlx(default)] // 这个字段来自JOIN
     pub supplier_name: String,
-    #[serde(with = "decimal_as_string")]
+    pub quantity: i32,
+    // 总额 = 货款小计 + 运费 + 税额
+    #[serde(with = "money::decimal_as_string")]
+    pub subtotal_amount: Decimal,
+    #[serde(with = "money::decimal_as_string")]
+    pub shipping_amount: Decimal,
+    pub tax_name: Option<String>,
+    #[serde(with = "money::decimal_as_string")]
+    pub tax_rate: Decimal,
+    #[serde(with = "money::decimal_as_string")]
+    pub tax_amount: Decimal,
+    #[serde(with = "money::decimal_as_string")]
     pub total_amount: Decimal,
+    pub currency: String,
+    // 贸易术语，例如 FOB Shanghai
+    pub incoterm: Option<String>,
+    pub incoterm_place: Option<String>,
+    // 账期天数，为空表示在线付款
+    pub payment_terms_days: Option<i32>,
+    pub promised_delivery_date: Option<NaiveDate>,
+    // 已生效的变更单数量，0 表示原始订单
+    pub amendment_number: i32,
+    // ON_TRACK / AT_RISK / LATE，由后台交期检查更新
+    pub delivery_status: String,
+    // 全部发货时记录：ON_TIME / LATE
+    pub delivery_outcome: Option<String>,
+    pub delivered_on: Option<NaiveDate>,
+    pub days_late: Option<i32>,
     pub status: String,
     pub created_at: DateTime<Utc>,
     pub payment_status: String,
+    // 线下付款的汇款凭证号
+    pub remittance_reference: Option<String>,
+    // 累计已退金额
+    #[serde(with = "money::decimal_as_string")]
+    pub refunded_amount: Decimal,
     // --- 新增 ---
     pub quality_rating: Option<u8>,
+    pub communication_rating: Option<u8>,
+    pub review_text: Option<String>,
+    pub rated_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpdateOrderStatusDto {
     pub status: String,
-}
\ No newline at end of file
+    // 可选备注，会写入状态历史
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OrderStatusHistory {
+    pub id: i32,
+    pub order_id: i32,
+    pub from_status: Option<String>,
+    pub to_status: String,
+    pub actor_user_id: Option<i32>,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub actor_name: Option<String>,
+    pub actor_company_id: Option<i32>,
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCancellationRequestDto {
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespondCancellationDto {
+    pub accept: bool,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OrderCancellationRequest {
+    pub id: i32,
+    pub order_id: i32,
+    pub requested_by_company_id: i32,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub requested_by_company_name: String,
+    pub requested_by_user_id: Option<i32>,
+    pub reason: String,
+    pub status: String,
+    pub responded_by_user_id: Option<i32>,
+    pub response_comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub responded_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReorderDto {
+    // 为空时沿用原订单数量
+    pub quantity: Option<i32>,
+}
+
+/// 再订货确认前展示给采购方的条款
+#[derive(Debug, Serialize)]
+pub struct ReorderPreview {
+    pub source_order_id: i32,
+    pub supplier_company_id: i32,
+    pub supplier_name: String,
+    pub rfq_title: String,
+    pub quantity: i32,
+    #[serde(with = "money::unit_price_as_string")]
+    pub unit_price: Decimal,
+    #[serde(with = "money::decimal_as_string")]
+    pub subtotal_amount: Decimal,
+    #[serde(with = "money::decimal_as_string")]
+    pub shipping_amount: Decimal,
+    #[serde(with = "money::decimal_as_string")]
+    pub tax_amount: Decimal,
+    #[serde(with = "money::decimal_as_string")]
+    pub total_amount: Decimal,
+    pub currency: String,
+    pub incoterm: Option<String>,
+    pub promised_delivery_date: NaiveDate,
+}
+
+/// 采购订单状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    PendingConfirmation,
+    InProduction,
+    Shipped,
+    Completed,
+    Cancelled,
+}
+
+/// 状态变更的触发条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionGuard {
+    // 只能由订单的某一方直接触发
+    Party(OrderParty),
+    // 需要双方同意，只能通过取消申请流程完成
+    MutualConsent,
+    // 只能由管理员在争议仲裁中触发
+    Admin,
+    // 只能由系统根据业务数据自动触发，例如发货数量覆盖订单数量、收货检验完毕
+    System,
+}
+
+/// 订单中的一方，用来判断谁可以触发某个状态变更
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderParty {
+    Buyer,
+    Supplier,
+}
+
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::PendingConfirmation => "PENDING_CONFIRMATION",
+            OrderStatus::InProduction => "IN_PRODUCTION",
+            OrderStatus::Shipped => "SHIPPED",
+            OrderStatus::Completed => "COMPLETED",
+            OrderStatus::Cancelled => "CANCELLED",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "PENDING_CONFIRMATION" => Some(OrderStatus::PendingConfirmation),
+            "IN_PRODUCTION" => Some(OrderStatus::InProduction),
+            "SHIPPED" => Some(OrderStatus::Shipped),
+            "COMPLETED" => Some(OrderStatus::Completed),
+            "CANCELLED" => Some(OrderStatus::Cancelled),
+            _ => None,
+        }
+    }
+
+    /// 状态机：返回从当前状态变更到 `to` 需要满足的条件，None 表示不允许这个变更
+    pub fn transition_guard(&self, to: OrderStatus) -> Option<TransitionGuard> {
+        use OrderStatus::*;
+        match (self, to) {
+            // 供应商确认订单并开始生产
+            (PendingConfirmation, InProduction) => Some(TransitionGuard::Party(OrderParty::Supplier)),
+            // 发货记录覆盖全部数量后自动变为已发货
+            (InProduction, Shipped) => Some(TransitionGuard::System),
+            // 采购方收货检验完毕、NCR都关闭后自动完成
+            (Shipped, Completed) => Some(TransitionGuard::System),
+            // 发货前可以取消，但必须一方申请、另一方同意
+            (PendingConfirmation | InProduction, Cancelled) => Some(TransitionGuard::MutualConsent),
+            // 争议仲裁判定返工时，由管理员退回生产
+            (Shipped | Completed, InProduction) => Some(TransitionGuard::Admin),
+            _ => None,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_state_machine() {
+        use OrderStatus::*;
+
+        let supplier = Some(TransitionGuard::Party(OrderParty::Supplier));
+
+        assert_eq!(PendingConfirmation.transition_guard(InProduction), supplier);
+        assert_eq!(InProduction.transition_guard(Shipped), Some(TransitionGuard::System));
+        assert_eq!(Shipped.transition_guard(Completed), Some(TransitionGuard::System));
+
+        // 不能跳过状态，也不能由订单双方自行倒退
+        assert_eq!(PendingConfirmation.transition_guard(Completed), None);
+        assert_eq!(PendingConfirmation.transition_guard(Shipped), None);
+        assert_eq!(Completed.transition_guard(Shipped), None);
+        assert_eq!(InProduction.transition_guard(InProduction), None);
+        assert_eq!(Shipped.transition_guard(InProduction), Some(TransitionGuard::Admin));
+
+        // 取消需要双方同意，发货后不能取消
+        assert_eq!(PendingConfirmation.transition_guard(Cancelled), Some(TransitionGuard::MutualConsent));
+        assert_eq!(InProduction.transition_guard(Cancelled), Some(TransitionGuard::MutualConsent));
+        assert_eq!(Shipped.transition_guard(Cancelled), None);
+        assert_eq!(Cancelled.transition_guard(InProduction), None);
+    }
+
+    #[test]
+    fn test_order_status_round_trip() {
+        for status in [OrderStatus::PendingConfirmation, OrderStatus::InProduction, OrderStatus::Shipped, OrderStatus::Completed, OrderStatus::Cancelled] {
+            assert_eq!(OrderStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(OrderStatus::parse("UNKNOWN"), None);
+    }
+}
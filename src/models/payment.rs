@@ -1,7 +1,30 @@
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize)]
 pub struct CheckoutSessionResponse {
     pub session_id: String,
-}
\ No newline at end of file
+    // 托管支付页面地址，Mock渠道没有
+    pub checkout_url: Option<String>,
+}
+
+/// 支付渠道事件日志，管理员排查用
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct PaymentEventLog {
+    pub id: i32,
+    pub provider: String,
+    pub event_id: String,
+    pub event_type: String,
+    pub payload: String,
+    pub status: String,
+    pub detail: Option<String>,
+    pub attempts: i32,
+    pub received_at: Option<DateTime<Utc>>,
+    pub processed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentEventListParams {
+    pub status: Option<String>,
+}
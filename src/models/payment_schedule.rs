@@ -0,0 +1,112 @@
+// src/models/payment_schedule.rs
+use crate::models::{money, order::OrderStatus};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Decimal, FromRow};
+
+/// 分期付款在订单到达哪个状态后到期
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MilestoneTrigger {
+    // 授标即可付款（定金）
+    Award,
+    Shipped,
+    // 收货检验通过、订单完成
+    Accepted,
+}
+
+impl MilestoneTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MilestoneTrigger::Award => "AWARD",
+            MilestoneTrigger::Shipped => "SHIPPED",
+            MilestoneTrigger::Accepted => "ACCEPTED",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "AWARD" => Some(MilestoneTrigger::Award),
+            "SHIPPED" => Some(MilestoneTrigger::Shipped),
+            "ACCEPTED" => Some(MilestoneTrigger::Accepted),
+            _ => None,
+        }
+    }
+
+    /// 订单处于这个状态时，这一期是否已经可以付款
+    pub fn is_due(&self, order_status: OrderStatus) -> bool {
+        match (self, order_status) {
+            (_, OrderStatus::Cancelled) => false,
+            (MilestoneTrigger::Award, _) => true,
+            (MilestoneTrigger::Shipped, OrderStatus::Shipped | OrderStatus::Completed) => true,
+            (MilestoneTrigger::Accepted, OrderStatus::Completed) => true,
+            _ => false,
+        }
+    }
+
+    /// 订单刚变成这个状态时到期的那一类
+    pub fn reached_by(order_status: OrderStatus) -> Option<Self> {
+        match order_status {
+            OrderStatus::Shipped => Some(MilestoneTrigger::Shipped),
+            OrderStatus::Completed => Some(MilestoneTrigger::Accepted),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MilestoneDto {
+    pub label: String,
+    #[serde(deserialize_with = "money::amount_from_str_or_number")]
+    pub percentage: Decimal,
+    pub trigger: String,
+}
+
+/// 设置（或替换）订单的付款计划，传空数组表示取消分期、恢复整单付款
+#[derive(Debug, Deserialize)]
+pub struct SetPaymentScheduleDto {
+    pub milestones: Vec<MilestoneDto>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PaymentMilestone {
+    pub id: i32,
+    pub order_id: i32,
+    pub sequence: i32,
+    pub label: String,
+    #[serde(with = "money::decimal_as_string")]
+    pub percentage: Decimal,
+    #[serde(with = "money::decimal_as_string")]
+    pub amount: Decimal,
+    pub currency: String,
+    pub due_trigger: String,
+    #[sqlx(skip)]
+    pub is_due: bool,
+    pub payment_status: String,
+    #[serde(with = "money::decimal_as_string")]
+    pub refunded_amount: Decimal,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_milestone_due() {
+        use OrderStatus::*;
+        assert!(MilestoneTrigger::Award.is_due(PendingConfirmation));
+        assert!(!MilestoneTrigger::Shipped.is_due(InProduction));
+        assert!(MilestoneTrigger::Shipped.is_due(Shipped));
+        assert!(MilestoneTrigger::Shipped.is_due(Completed));
+        assert!(!MilestoneTrigger::Accepted.is_due(Shipped));
+        assert!(MilestoneTrigger::Accepted.is_due(Completed));
+        // 取消的订单不再收款
+        assert!(!MilestoneTrigger::Award.is_due(Cancelled));
+
+        assert_eq!(MilestoneTrigger::reached_by(Shipped), Some(MilestoneTrigger::Shipped));
+        assert_eq!(MilestoneTrigger::reached_by(InProduction), None);
+        assert_eq!(MilestoneTrigger::parse("ACCEPTED"), Some(MilestoneTrigger::Accepted));
+        assert_eq!(MilestoneTrigger::parse("DELIVERED"), None);
+    }
+}
@@ -0,0 +1,53 @@
+// src/models/payment_terms.rs
+use crate::models::money;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Decimal, FromRow};
+
+/// 供应商给某个买方设置账期
+#[derive(Debug, Deserialize)]
+pub struct SetPaymentTermsDto {
+    pub buyer_company_id: i32,
+    // 例如 30 表示 net-30
+    pub net_days: u32,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PaymentTerms {
+    pub id: i32,
+    pub buyer_company_id: i32,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub buyer_name: String,
+    pub supplier_company_id: i32,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub supplier_name: String,
+    pub net_days: i32,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 管理员核定买方的信用额度，按买方的报表币种计算，传 null 取消赊账
+#[derive(Debug, Deserialize)]
+pub struct SetCreditLimitDto {
+    #[serde(default, deserialize_with = "money::option_amount_from_str_or_number")]
+    pub credit_limit: Option<Decimal>,
+}
+
+/// 买方的信用额度使用情况
+#[derive(Debug, Serialize)]
+pub struct CreditSummary {
+    pub company_id: i32,
+    pub currency: String,
+    #[serde(with = "money::option_decimal_as_string")]
+    pub credit_limit: Option<Decimal>,
+    // 账期订单中还没付的金额
+    #[serde(with = "money::decimal_as_string")]
+    pub exposure: Decimal,
+    #[serde(with = "money::option_decimal_as_string")]
+    pub available: Option<Decimal>,
+}
+
+/// 线下收款（银行转账等）后手工标记为已付
+#[derive(Debug, Deserialize)]
+pub struct MarkPaidDto {
+    pub remittance_reference: String,
+}
@@ -1,36 +1,101 @@
 use serde::{Deserialize, Serialize};
 use sqlx::{types::Decimal, FromRow};
 use chrono::{DateTime, Utc};
-
-//sqlx竟然没有decimal序列化，只能自己实现一个
-mod decimal_as_string {
-    use super::*;
-    pub fn serialize<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
-    where S: serde::Serializer,
-    {
-        serializer.serialize_str(&value.to_string())
-    }
-}
+use crate::models::money::{self, amount_from_str_or_number, Currency};
 
 #[derive(Debug, Serialize, FromRow)]
 pub struct Quote {
     pub id: i32,
     pub rfq_id: i32,
     pub supplier_company_id: i32,
-    #[serde(with = "decimal_as_string")]
+    #[serde(with = "money::decimal_as_string")]
     pub price: Decimal,
+    pub currency: String,
+    #[serde(with = "money::decimal_as_string")]
+    pub shipping_cost: Decimal,
+    pub incoterm: Option<String>,
+    pub incoterm_place: Option<String>,
     pub lead_time_days: i32,
     pub notes: Option<String>,
+    pub revision: i32,
+    pub expires_at: Option<DateTime<Utc>>,
     pub status: String,
+    // 未中标时由采购方填写的原因，以及可选的匿名价格反馈
+    pub rejection_reason: Option<String>,
+    pub price_feedback: Option<String>,
     pub created_at: DateTime<Utc>,
     // 这个字段通过JOIN查询得到
     #[sqlx(default)]
     pub supplier_company_name: String,
+    // 按采购方所在地税率估算的税额和含税运总价，授标时以订单上的为准
+    #[sqlx(skip)]
+    #[serde(with = "money::decimal_as_string")]
+    pub estimated_tax: Decimal,
+    #[sqlx(skip)]
+    #[serde(with = "money::decimal_as_string")]
+    pub estimated_total: Decimal,
+    // 含税运总价折算到采购方报表币种，用于跨币种比较；缺少汇率时为空
+    #[sqlx(skip)]
+    #[serde(with = "money::option_decimal_as_string")]
+    pub normalized_price: Option<Decimal>,
+    #[sqlx(skip)]
+    pub normalized_currency: String,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateQuoteDto {
-    pub price: f64,
+    // 建议前端以字符串传递，例如 "1234.50"
+    #[serde(deserialize_with = "amount_from_str_or_number")]
+    pub price: Decimal,
+    // 不填默认USD
+    #[serde(default)]
+    pub currency: Currency,
+    // 整单运费，和货款同币种，不填为0
+    #[serde(default, deserialize_with = "money::option_amount_from_str_or_number")]
+    pub shipping_cost: Option<Decimal>,
+    // EXW、FOB、DDP 等贸易术语，以及指定地点
+    pub incoterm: Option<String>,
+    pub incoterm_place: Option<String>,
     pub lead_time_days: i32,
     pub notes: Option<String>,
-}
\ No newline at end of file
+    // 报价有效期，不填则长期有效
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// 供应商“我的报价”列表中的一行
+#[derive(Debug, Serialize, FromRow)]
+pub struct SupplierQuoteSummary {
+    pub id: i32,
+    pub rfq_id: i32,
+    pub rfq_title: String,
+    pub rfq_status: String,
+    #[serde(with = "money::decimal_as_string")]
+    pub price: Decimal,
+    pub currency: String,
+    pub lead_time_days: i32,
+    pub status: String,
+    pub revision: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub price_feedback: Option<String>,
+    // 中标后生成的采购订单
+    pub purchase_order_id: Option<i32>,
+    pub order_status: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SupplierQuotePage {
+    pub items: Vec<SupplierQuoteSummary>,
+    pub total: i64,
+    pub page: u32,
+    pub page_size: u32,
+}
+
+/// 采购方接受报价时可选的请求体，用于通知未中标的供应商
+#[derive(Debug, Deserialize, Default)]
+pub struct AcceptQuoteDto {
+    pub rejection_reason: Option<String>,
+    // 为true时，告诉未中标供应商他们的报价与中标价的百分比差距（不透露中标方和具体价格）
+    #[serde(default)]
+    pub share_price_feedback: bool,
+}
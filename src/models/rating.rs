@@ -1,8 +1,48 @@
 // src/models/rating.rs
-use serde::Deserialize;
+use crate::models::{delivery::DeliveryPerformance, receipt::SupplierQuality};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
 
 #[derive(Debug, Deserialize)]
 pub struct RateOrderDto {
     pub quality_rating: u8,
     pub communication_rating: u8,
-}
\ No newline at end of file
+    // 可选的文字评价
+    pub review: Option<String>,
+}
+
+/// 按月汇总的评分趋势
+#[derive(Debug, Serialize, FromRow)]
+pub struct RatingTrendPoint {
+    pub month: String, // YYYY-MM
+    pub rated_orders: i64,
+    pub average_quality: Option<f64>,
+    pub average_communication: Option<f64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OrderReview {
+    pub order_id: i32,
+    pub buyer_name: String,
+    pub quality_rating: u8,
+    pub communication_rating: u8,
+    pub review_text: String,
+    pub rated_at: DateTime<Utc>,
+}
+
+/// 供应商评分卡
+#[derive(Debug, Serialize)]
+pub struct SupplierScorecard {
+    pub supplier_company_id: i32,
+    pub rated_orders: i64,
+    pub average_quality: Option<f64>,
+    pub average_communication: Option<f64>,
+    // 近12个月，按月从早到晚
+    pub trend: Vec<RatingTrendPoint>,
+    pub recent_reviews: Vec<OrderReview>,
+    // 收货检验和NCR历史
+    pub inspection: SupplierQuality,
+    // 准时交付情况
+    pub delivery: DeliveryPerformance,
+}
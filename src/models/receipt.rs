@@ -0,0 +1,103 @@
+// src/models/receipt.rs
+// 收货、来料检验和不合格品报告(NCR)
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateGoodsReceiptDto {
+    pub shipment_id: Option<i32>,
+    pub received_quantity: i32,
+    pub accepted_quantity: i32,
+    pub notes: Option<String>,
+    // 有拒收时必填，用来创建NCR
+    pub defect_category: Option<String>,
+    pub defect_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NcrResponseDto {
+    pub root_cause: String,
+    pub corrective_action: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CloseNcrDto {
+    pub comment: Option<String>,
+}
+
+pub const DEFECT_CATEGORIES: &[&str] = &[
+    "DIMENSIONAL",
+    "MATERIAL",
+    "SURFACE_FINISH",
+    "FUNCTIONAL",
+    "PACKAGING",
+    "DOCUMENTATION",
+    "OTHER",
+];
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct GoodsReceipt {
+    pub id: i32,
+    pub order_id: i32,
+    pub shipment_id: Option<i32>,
+    pub received_quantity: i32,
+    pub accepted_quantity: i32,
+    pub rejected_quantity: i32,
+    pub notes: Option<String>,
+    pub received_by_user_id: Option<i32>,
+    // 争议判返工时作废，不再计入已收数量
+    pub superseded_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    #[sqlx(default)] // 这个字段来自JOIN，没有拒收时为空
+    pub ncr_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Ncr {
+    pub id: i32,
+    pub order_id: i32,
+    pub goods_receipt_id: i32,
+    pub supplier_company_id: i32,
+    pub defect_category: String,
+    pub description: String,
+    pub rejected_quantity: i32,
+    pub status: String,
+    pub root_cause: Option<String>,
+    pub corrective_action: Option<String>,
+    pub responded_by_user_id: Option<i32>,
+    pub responded_at: Option<DateTime<Utc>>,
+    pub closed_by_user_id: Option<i32>,
+    pub closing_comment: Option<String>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct NcrPhoto {
+    pub id: i32,
+    pub ncr_id: i32,
+    pub uploaded_by_user_id: Option<i32>,
+    pub original_filename: String,
+    #[serde(skip_serializing)] // 照片只能通过下载接口获取
+    pub stored_path: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NcrDetail {
+    #[serde(flatten)]
+    pub ncr: Ncr,
+    pub photos: Vec<NcrPhoto>,
+}
+
+/// 供应商质量表现，来自收货检验和NCR历史
+#[derive(Debug, Serialize, Default)]
+pub struct SupplierQuality {
+    pub received_units: i64,
+    pub rejected_units: i64,
+    pub ncr_count: i64,
+    pub open_ncrs: i64,
+    // 0-100，没有收货记录时为空
+    pub quality_score: Option<f64>,
+}
@@ -0,0 +1,56 @@
+// src/models/reconciliation.rs
+use crate::models::money;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Decimal, FromRow};
+
+/// 一次对账的汇总
+#[derive(Debug, Serialize, FromRow)]
+pub struct ReconciliationRun {
+    pub id: i32,
+    pub provider: String,
+    pub report_date: NaiveDate,
+    pub checked: i32,
+    pub fixed: i32,
+    pub flagged: i32,
+    pub triggered_by_user_id: Option<i32>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// 对账检查过的一笔付款。outcome 为 MISMATCH 的需要管理员处理
+#[derive(Debug, Serialize, FromRow)]
+pub struct ReconciliationItem {
+    pub id: i32,
+    pub run_id: i32,
+    pub order_id: i32,
+    pub invoice_id: Option<i32>,
+    pub milestone_id: Option<i32>,
+    pub stripe_session_id: String,
+    pub local_status: String,
+    pub provider_status: Option<String>,
+    #[serde(with = "money::decimal_as_string")]
+    pub expected_amount: Decimal,
+    #[serde(with = "money::option_decimal_as_string")]
+    pub provider_amount: Option<Decimal>,
+    pub currency: String,
+    pub outcome: String,
+    pub detail: Option<String>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub resolved_by_user_id: Option<i32>,
+    pub resolution_note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReconciliationItemListParams {
+    pub outcome: Option<String>,
+    // 只看还没处理的不一致
+    #[serde(default)]
+    pub unresolved: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResolveReconciliationItemDto {
+    pub note: Option<String>,
+}
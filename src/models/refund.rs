@@ -0,0 +1,54 @@
+// src/models/refund.rs
+use crate::models::money;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Decimal, FromRow};
+
+/// 申请或直接发起退款。不填金额时：关联争议的按仲裁金额，否则退剩余可退的全部金额
+#[derive(Debug, Deserialize)]
+pub struct CreateRefundDto {
+    #[serde(default, deserialize_with = "money::option_amount_from_str_or_number")]
+    pub amount: Option<Decimal>,
+    pub reason: String,
+    // 按发票或分期付款的订单必须指定退哪一笔
+    pub invoice_id: Option<i32>,
+    pub milestone_id: Option<i32>,
+    pub dispute_id: Option<i32>,
+    pub cancellation_request_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespondRefundDto {
+    pub approve: bool,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefundListParams {
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Refund {
+    pub id: i32,
+    pub order_id: i32,
+    pub invoice_id: Option<i32>,
+    pub milestone_id: Option<i32>,
+    pub dispute_id: Option<i32>,
+    pub cancellation_request_id: Option<i32>,
+    #[serde(with = "money::decimal_as_string")]
+    pub amount: Decimal,
+    pub currency: String,
+    pub reason: String,
+    pub status: String,
+    pub provider_refund_id: Option<String>,
+    pub failure_reason: Option<String>,
+    pub requested_by_company_id: i32,
+    #[sqlx(default)] // 这个字段来自JOIN
+    pub requested_by_company_name: String,
+    pub requested_by_user_id: Option<i32>,
+    pub processed_by_user_id: Option<i32>,
+    pub response_comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub processed_at: Option<DateTime<Utc>>,
+}
@@ -32,4 +32,26 @@ pub struct RfqAttachment {
     pub rfq_id: i32,
     pub original_filename: String,
     pub stored_path: String,
-}
\ No newline at end of file
+}
+
+/// 供应商表态：准备报价(INTEND_TO_QUOTE) 或 不参与(DECLINED)
+#[derive(Debug, Deserialize)]
+pub struct RfqResponseDto {
+    pub response: String,
+    // DECLINED 时必填: CAPACITY / CAPABILITY / PRICE / TIMELINE
+    pub reason_code: Option<String>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct RfqSupplierResponse {
+    pub rfq_id: i32,
+    pub supplier_company_id: i32,
+    #[sqlx(default)]
+    pub supplier_company_name: String,
+    pub response: String,
+    pub reason_code: Option<String>,
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,32 @@
+// src/models/shipment.rs
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Shipment {
+    pub id: i32,
+    pub order_id: i32,
+    pub carrier: String,
+    pub tracking_number: String,
+    pub shipped_quantity: i32,
+    pub estimated_arrival: Option<NaiveDate>,
+    pub notes: Option<String>,
+    pub packing_list_filename: Option<String>,
+    #[serde(skip_serializing)] // 装箱单只能通过下载接口获取
+    pub packing_list_path: Option<String>,
+    pub created_by_user_id: Option<i32>,
+    // 争议判返工时作废，不再计入已发数量
+    pub superseded_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// 订单的发货进度
+#[derive(Debug, Serialize)]
+pub struct OrderShipments {
+    pub order_id: i32,
+    pub ordered_quantity: i32,
+    pub shipped_quantity: i64,
+    pub remaining_quantity: i64,
+    pub shipments: Vec<Shipment>,
+}
@@ -0,0 +1,93 @@
+// src/models/tax.rs
+// 税率和贸易术语（Incoterms 2020）
+use crate::models::money;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Decimal, FromRow};
+
+/// 贸易术语，决定运费和风险在哪里从卖方转给买方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Incoterm {
+    Exw,
+    Fca,
+    Cpt,
+    Cip,
+    Dap,
+    Dpu,
+    Ddp,
+    Fas,
+    Fob,
+    Cfr,
+    Cif,
+}
+
+impl Incoterm {
+    pub const ALL: [Incoterm; 11] = [
+        Incoterm::Exw, Incoterm::Fca, Incoterm::Cpt, Incoterm::Cip, Incoterm::Dap, Incoterm::Dpu,
+        Incoterm::Ddp, Incoterm::Fas, Incoterm::Fob, Incoterm::Cfr, Incoterm::Cif,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Incoterm::Exw => "EXW",
+            Incoterm::Fca => "FCA",
+            Incoterm::Cpt => "CPT",
+            Incoterm::Cip => "CIP",
+            Incoterm::Dap => "DAP",
+            Incoterm::Dpu => "DPU",
+            Incoterm::Ddp => "DDP",
+            Incoterm::Fas => "FAS",
+            Incoterm::Fob => "FOB",
+            Incoterm::Cfr => "CFR",
+            Incoterm::Cif => "CIF",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_uppercase();
+        Self::ALL.into_iter().find(|term| term.as_str() == s)
+    }
+
+    /// EXW 由买方到卖方工厂提货，卖方不能再收运费
+    pub fn allows_shipping_charge(&self) -> bool {
+        !matches!(self, Incoterm::Exw)
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TaxRate {
+    pub id: i32,
+    pub country_code: String,
+    // 空字符串表示全国统一税率
+    pub region: String,
+    pub name: String,
+    #[serde(with = "money::decimal_as_string")]
+    pub rate: Decimal,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 管理员新增或修改税率，同一国家/地区只有一条
+#[derive(Debug, Deserialize)]
+pub struct SetTaxRateDto {
+    pub country_code: String,
+    pub region: Option<String>,
+    pub name: String,
+    #[serde(deserialize_with = "money::amount_from_str_or_number")]
+    pub rate: Decimal,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incoterm_round_trip() {
+        for term in Incoterm::ALL {
+            assert_eq!(Incoterm::parse(term.as_str()), Some(term));
+        }
+        assert_eq!(Incoterm::parse(" fob "), Some(Incoterm::Fob));
+        assert_eq!(Incoterm::parse("FOO"), None);
+        assert!(!Incoterm::Exw.allows_shipping_charge());
+        assert!(Incoterm::Ddp.allows_shipping_charge());
+    }
+}
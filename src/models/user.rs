@@ -27,6 +27,12 @@ pub struct ChangePasswordDto {
     pub new_password: String,
 }
 
+/// 修改邮件语言：en 或 zh-CN
+#[derive(Debug, Deserialize)]
+pub struct UpdateLocaleDto {
+    pub locale: String,
+}
+
 
 // --- API 请求体 (DTOs) ---
 
@@ -71,6 +77,7 @@ pub struct UserProfileResponse {
     pub company_id: i32,
     #[sqlx(default)]
     pub company_name: String,
+    pub locale: String,
     pub is_active: bool,
 }
 
@@ -3,7 +3,7 @@ use sqlx::MySqlPool;
 use crate::models::user::UserProfileResponse;
 
 pub async fn list_all_companies(pool: &MySqlPool) -> Result<Vec<CompanyProfile>, AppError> {
-    let companies = sqlx::query_as("SELECT id, name, company_type, city, description, created_at, is_verified FROM companies ORDER BY created_at DESC")
+    let companies = sqlx::query_as("SELECT id, name, company_type, city, country_code, region, description, reporting_currency, created_at, is_verified FROM companies ORDER BY created_at DESC")
         .fetch_all(pool)
         .await?;
     Ok(companies)
@@ -19,7 +19,7 @@ pub async fn verify_company(pool: &MySqlPool, company_id: i32) -> Result<u64, Ap
 
 pub async fn list_all_users(pool: &MySqlPool) -> Result<Vec<UserProfileResponse>, AppError> {
     let users = sqlx::query_as(
-        "SELECT u.id, u.full_name, u.email, u.company_id, u.is_active, c.name as company_name
+        "SELECT u.id, u.full_name, u.email, u.company_id, u.locale, u.is_active, c.name as company_name
          FROM users u JOIN companies c ON u.company_id = c.id ORDER BY u.created_at DESC"
     )
         .fetch_all(pool)
@@ -2,8 +2,10 @@ use crate::{
     errors::AppError,
     models::{analytics::{BuyerStats, SpendingBySupplier, }, user::Claims},
 };
-use sqlx::MySqlPool;
+use sqlx::{types::Decimal, MySqlPool};
+use std::collections::{BTreeSet, HashMap};
 use crate::models::analytics::SupplierStats;
+use crate::services::{delivery_service, fx_service, receipt_service};
 
 pub async fn get_buyer_dashboard_stats(pool: &MySqlPool, claims: &Claims) -> Result<BuyerStats, AppError> {
     // 权限检查
@@ -11,12 +13,12 @@ pub async fn get_buyer_dashboard_stats(pool: &MySqlPool, claims: &Claims) -> Res
         return Err(AppError::BadRequest("Analytics are only available for buyers.".to_string()));
     }
 
-    // 使用SQL聚合函数 COUNT, SUM, COUNT(DISTINCT)
-    let stats = sqlx::query_as(
+    // 使用SQL聚合函数 COUNT, COUNT(DISTINCT)
+    let mut stats: BuyerStats = sqlx::query_as(
         "SELECT
             COUNT(*) as total_orders,
-            COALESCE(SUM(total_amount), 0) as total_spent,
-            COUNT(DISTINCT supplier_company_id) as distinct_suppliers
+            COUNT(DISTINCT supplier_company_id) as distinct_suppliers,
+            COUNT(CASE WHEN refunded_amount > 0 THEN 1 END) as refunded_orders
          FROM purchase_orders
          WHERE buyer_company_id = ?"
     )
@@ -24,6 +26,22 @@ pub async fn get_buyer_dashboard_stats(pool: &MySqlPool, claims: &Claims) -> Res
         .fetch_one(pool)
         .await?;
 
+    // 订单可能是不同币种，先按币种分组求和，再折算到报表币种
+    let amounts = order_amounts_by_currency(pool, "buyer_company_id", claims.company_id, false).await?;
+
+    let refunded_by_currency = refunded_by_currency(pool, "buyer_company_id", claims.company_id).await?;
+
+    let reporting_currency = fx_service::get_reporting_currency(pool, claims.company_id).await?;
+    let fx = fx_service::load_fx_table(pool).await?;
+    // 缺汇率的币种不计入合计，在 unconverted_currencies 里列出来，不让整个看板报错
+    let mut skipped = BTreeSet::new();
+    stats.total_spent = fx_service::sum_convertible(&fx, &column(&amounts, |a| a.total), reporting_currency, &mut skipped);
+    stats.total_shipping = fx_service::sum_convertible(&fx, &column(&amounts, |a| a.shipping), reporting_currency, &mut skipped);
+    stats.total_tax = fx_service::sum_convertible(&fx, &column(&amounts, |a| a.tax), reporting_currency, &mut skipped);
+    stats.total_refunded = fx_service::sum_convertible(&fx, &refunded_by_currency, reporting_currency, &mut skipped);
+    stats.currency = reporting_currency.code().to_string();
+    stats.unconverted_currencies = skipped.into_iter().collect();
+
     Ok(stats)
 }
 
@@ -32,21 +50,43 @@ pub async fn get_buyer_spending_by_supplier(pool: &MySqlPool, claims: &Claims) -
         return Err(AppError::BadRequest("Analytics are only available for buyers.".to_string()));
     }
 
-    // 用 GROUP BY 和 JOIN 来按供应商分组统计支出
-    let spending_data = sqlx::query_as(
+    // 用 GROUP BY 和 JOIN 来按供应商和币种分组统计支出
+    let rows: Vec<(i32, String, String, Decimal)> = sqlx::query_as(
         "SELECT
+            po.supplier_company_id,
             c.name as supplier_name,
+            po.currency,
             SUM(po.total_amount) as total
          FROM purchase_orders po
          JOIN companies c ON po.supplier_company_id = c.id
          WHERE po.buyer_company_id = ?
-         GROUP BY po.supplier_company_id, c.name
-         ORDER BY total DESC"
+         GROUP BY po.supplier_company_id, c.name, po.currency"
     )
         .bind(claims.company_id)
         .fetch_all(pool)
         .await?;
 
+    let reporting_currency = fx_service::get_reporting_currency(pool, claims.company_id).await?;
+    let fx = fx_service::load_fx_table(pool).await?;
+
+    let mut by_supplier: HashMap<i32, SpendingBySupplier> = HashMap::new();
+    for (supplier_id, supplier_name, currency, total) in rows {
+        let entry = by_supplier
+            .entry(supplier_id)
+            .or_insert_with(|| SpendingBySupplier {
+                supplier_name,
+                total: Decimal::ZERO,
+                currency: reporting_currency.code().to_string(),
+                unconverted_currencies: Vec::new(),
+            });
+        let mut skipped = BTreeSet::new();
+        entry.total += fx_service::sum_convertible(&fx, &[(currency, total)], reporting_currency, &mut skipped);
+        entry.unconverted_currencies.extend(skipped);
+    }
+
+    let mut spending_data: Vec<SpendingBySupplier> = by_supplier.into_values().collect();
+    spending_data.sort_by_key(|s| std::cmp::Reverse(s.total));
+
     Ok(spending_data)
 }
 
@@ -56,18 +96,82 @@ pub async fn get_supplier_dashboard_stats(pool: &MySqlPool, claims: &Claims) ->
     }
 
     // 这个真的恶心，必须要SQL查询的时候使其始终返回一行，我还以为类型错了
-    let stats: SupplierStats = sqlx::query_as(
+    let mut stats: SupplierStats = sqlx::query_as(
         "SELECT
             (SELECT COUNT(*) FROM quotes WHERE supplier_company_id = ?) as total_quotes_submitted,
             (SELECT COUNT(*) FROM quotes WHERE supplier_company_id = ? AND status = 'ACCEPTED') as accepted_quotes,
-            (SELECT COALESCE(SUM(po.total_amount), 0) FROM purchase_orders po WHERE po.supplier_company_id = ? AND po.status = 'COMPLETED') as total_revenue
+            (SELECT COUNT(*) FROM rfq_supplier_responses WHERE supplier_company_id = ? AND response = 'INTEND_TO_QUOTE') as intents_to_quote,
+            (SELECT COUNT(*) FROM rfq_supplier_responses WHERE supplier_company_id = ? AND response = 'DECLINED') as declined_rfqs,
+            (SELECT COUNT(*) FROM purchase_orders WHERE supplier_company_id = ? AND refunded_amount > 0) as refunded_orders
         "
     )
+        .bind(claims.company_id)
+        .bind(claims.company_id)
         .bind(claims.company_id)
         .bind(claims.company_id)
         .bind(claims.company_id)
         .fetch_one(pool)
         .await?;
 
+    let amounts = order_amounts_by_currency(pool, "supplier_company_id", claims.company_id, true).await?;
+
+    let refunded_by_currency = refunded_by_currency(pool, "supplier_company_id", claims.company_id).await?;
+
+    let reporting_currency = fx_service::get_reporting_currency(pool, claims.company_id).await?;
+    let fx = fx_service::load_fx_table(pool).await?;
+    let mut skipped = BTreeSet::new();
+    stats.total_revenue = fx_service::sum_convertible(&fx, &column(&amounts, |a| a.total - a.tax), reporting_currency, &mut skipped);
+    stats.tax_collected = fx_service::sum_convertible(&fx, &column(&amounts, |a| a.tax), reporting_currency, &mut skipped);
+    stats.total_refunded = fx_service::sum_convertible(&fx, &refunded_by_currency, reporting_currency, &mut skipped);
+    stats.currency = reporting_currency.code().to_string();
+    stats.unconverted_currencies = skipped.into_iter().collect();
+    stats.quality = receipt_service::get_supplier_quality(pool, claims.company_id).await?;
+    stats.delivery = delivery_service::get_supplier_delivery_performance(pool, claims.company_id).await?;
+
     Ok(stats)
-}
\ No newline at end of file
+}
+
+/// 某个币种下订单金额的合计
+#[derive(sqlx::FromRow)]
+struct OrderAmounts {
+    currency: String,
+    shipping: Decimal,
+    tax: Decimal,
+    total: Decimal,
+}
+
+// 取出某一项，交给 sum_convertible 折算
+fn column(amounts: &[OrderAmounts], pick: impl Fn(&OrderAmounts) -> Decimal) -> Vec<(String, Decimal)> {
+    amounts.iter().map(|a| (a.currency.clone(), pick(a))).collect()
+}
+
+// 按币种汇总订单的运费、税额和总额，company_column 是 buyer_company_id 或 supplier_company_id
+async fn order_amounts_by_currency(
+    pool: &MySqlPool,
+    company_column: &str,
+    company_id: i32,
+    completed_only: bool,
+) -> Result<Vec<OrderAmounts>, AppError> {
+    let rows = sqlx::query_as(&format!(
+        "SELECT currency, SUM(shipping_amount) AS shipping, SUM(tax_amount) AS tax, SUM(total_amount) AS total
+         FROM purchase_orders WHERE {} = ?{} GROUP BY currency",
+        company_column,
+        if completed_only { " AND status = 'COMPLETED'" } else { "" }
+    ))
+        .bind(company_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+// 按币种汇总已退金额，company_column 是 buyer_company_id 或 supplier_company_id
+async fn refunded_by_currency(pool: &MySqlPool, company_column: &str, company_id: i32) -> Result<Vec<(String, Decimal)>, AppError> {
+    let rows = sqlx::query_as(&format!(
+        "SELECT currency, SUM(refunded_amount) FROM purchase_orders WHERE {} = ? AND refunded_amount > 0 GROUP BY currency",
+        company_column
+    ))
+        .bind(company_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
@@ -0,0 +1,398 @@
+// src/services/blanket_agreement_service.rs
+// 框架协议：一方基于历史订单提出单价、总数量和有效期，另一方同意后生效；
+// 采购方在有效期内分批下达订单（release），每批生成一张采购订单，并跟踪剩余数量
+use crate::{
+    errors::AppError,
+    models::{
+        blanket_agreement::{
+            BlanketAgreement, BlanketAgreementDetail, CreateBlanketAgreementDto, CreateReleaseDto, RespondBlanketAgreementDto,
+        },
+        money::{self, Currency, Money},
+        user::Claims,
+    },
+    services::{
+        chat_server::ChatServer,
+        document_service, notification_service,
+        order_service::{self, NewPurchaseOrder},
+    },
+};
+use crate::models::notification_preference::NotificationCategory;
+use actix::Addr;
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use sqlx::{types::Decimal, MySqlPool};
+use std::str::FromStr;
+
+/// 协议最长有效期
+const MAX_AGREEMENT_DAYS: i64 = 3 * 366;
+
+const AGREEMENT_SELECT: &str =
+    "SELECT ba.*, b.name as buyer_name, s.name as supplier_name, r.title as rfq_title,
+            CAST(COALESCE(rel.released, 0) AS SIGNED) as released_quantity,
+            CAST(ba.total_quantity - COALESCE(rel.released, 0) AS SIGNED) as remaining_quantity
+     FROM blanket_agreements ba
+     JOIN companies b ON ba.buyer_company_id = b.id
+     JOIN companies s ON ba.supplier_company_id = s.id
+     JOIN rfqs r ON ba.rfq_id = r.id
+     LEFT JOIN (SELECT blanket_agreement_id, SUM(quantity) as released FROM purchase_orders
+                WHERE blanket_agreement_id IS NOT NULL AND status <> 'CANCELLED' GROUP BY blanket_agreement_id) rel
+            ON rel.blanket_agreement_id = ba.id";
+
+/// 校验协议条款
+fn check_agreement_terms(
+    unit_price: Decimal,
+    total_quantity: i32,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    today: NaiveDate,
+) -> Result<(), AppError> {
+    if unit_price <= Decimal::ZERO || unit_price.normalize().scale() > 4 {
+        return Err(AppError::BadRequest("Unit price must be positive with at most 4 decimals.".to_string()));
+    }
+    if total_quantity <= 0 {
+        return Err(AppError::BadRequest("Total quantity must be greater than zero.".to_string()));
+    }
+    if end_date < start_date || end_date < today {
+        return Err(AppError::BadRequest("The agreement period must end on or after its start and not in the past.".to_string()));
+    }
+    if (end_date - start_date).num_days() > MAX_AGREEMENT_DAYS {
+        return Err(AppError::BadRequest("A blanket agreement can run for at most 3 years.".to_string()));
+    }
+    Ok(())
+}
+
+/// 校验一批下达数量，返回本批的金额
+fn check_release(
+    agreement: &BlanketAgreement,
+    quantity: i32,
+    today: NaiveDate,
+) -> Result<Decimal, AppError> {
+    if agreement.status != "ACTIVE" {
+        return Err(AppError::BadRequest("Releases can only be made against an active agreement.".to_string()));
+    }
+    if today < agreement.start_date || today > agreement.end_date {
+        return Err(AppError::BadRequest(format!(
+            "Releases are only possible between {} and {}.",
+            agreement.start_date, agreement.end_date
+        )));
+    }
+    if quantity <= 0 {
+        return Err(AppError::BadRequest("Release quantity must be greater than zero.".to_string()));
+    }
+    if i64::from(quantity) > agreement.remaining_quantity {
+        return Err(AppError::BadRequest(format!(
+            "Release quantity exceeds the remaining balance of {} units.",
+            agreement.remaining_quantity.max(0)
+        )));
+    }
+    Ok(money::round_amount(agreement.unit_price * Decimal::from(quantity)))
+}
+
+fn trimmed_text(value: Option<String>, field: &str) -> Result<Option<String>, AppError> {
+    let value = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    if value.as_ref().is_some_and(|v| v.chars().count() > 1000) {
+        return Err(AppError::BadRequest(format!("{} must be at most 1000 characters.", field)));
+    }
+    Ok(value)
+}
+
+/// 读取当前公司作为一方的协议
+async fn fetch_agreement(pool: &MySqlPool, agreement_id: i32, claims: &Claims) -> Result<BlanketAgreement, AppError> {
+    let sql = format!("{} WHERE ba.id = ? AND (ba.buyer_company_id = ? OR ba.supplier_company_id = ?)", AGREEMENT_SELECT);
+    let agreement = sqlx::query_as(&sql)
+        .bind(agreement_id)
+        .bind(claims.company_id)
+        .bind(claims.company_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Blanket agreement not found or you are not authorized to view it.".to_string()))?;
+    Ok(agreement)
+}
+
+fn counterparty_of(agreement: &BlanketAgreement, company_id: i32) -> i32 {
+    if company_id == agreement.buyer_company_id { agreement.supplier_company_id } else { agreement.buyer_company_id }
+}
+
+/// 订单任一方基于历史订单提出框架协议
+pub async fn propose_agreement(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    dto: CreateBlanketAgreementDto,
+    claims: &Claims,
+) -> Result<i32, AppError> {
+    let notes = trimmed_text(dto.notes, "Notes")?;
+    check_agreement_terms(dto.unit_price, dto.total_quantity, dto.start_date, dto.end_date, Utc::now().date_naive())?;
+
+    let source: Option<(i32, i32, i32, i32, String, String)> = sqlx::query_as(
+        "SELECT po.buyer_company_id, po.supplier_company_id, po.rfq_id, po.quote_id, po.currency, r.title
+         FROM purchase_orders po JOIN rfqs r ON po.rfq_id = r.id
+         WHERE po.id = ? AND (po.buyer_company_id = ? OR po.supplier_company_id = ?)"
+    )
+        .bind(dto.source_order_id)
+        .bind(claims.company_id)
+        .bind(claims.company_id)
+        .fetch_optional(pool)
+        .await?;
+    let (buyer_company_id, supplier_company_id, rfq_id, quote_id, currency, rfq_title) = source
+        .ok_or_else(|| AppError::BadRequest("Source order not found or you are not a party to it.".to_string()))?;
+    // 协议总金额不能超过单笔金额上限
+    Money::new(money::round_amount(dto.unit_price * Decimal::from(dto.total_quantity)), Currency::from_str(&currency)?)?;
+
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query(
+        "INSERT INTO blanket_agreements (buyer_company_id, supplier_company_id, source_order_id, rfq_id, quote_id, unit_price, currency,
+                                         total_quantity, start_date, end_date, notes, proposed_by_company_id, proposed_by_user_id)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+        .bind(buyer_company_id)
+        .bind(supplier_company_id)
+        .bind(dto.source_order_id)
+        .bind(rfq_id)
+        .bind(quote_id)
+        .bind(dto.unit_price)
+        .bind(&currency)
+        .bind(dto.total_quantity)
+        .bind(dto.start_date)
+        .bind(dto.end_date)
+        .bind(&notes)
+        .bind(claims.company_id)
+        .bind(claims.sub)
+        .execute(&mut *tx)
+        .await?;
+    let agreement_id = result.last_insert_id() as i32;
+    let agreement_number = format!("BA-{}-{:06}", Utc::now().year(), agreement_id);
+    sqlx::query("UPDATE blanket_agreements SET agreement_number = ? WHERE id = ?")
+        .bind(&agreement_number)
+        .bind(agreement_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    let counterparty = if claims.company_id == buyer_company_id { supplier_company_id } else { buyer_company_id };
+    let subject = format!("Blanket agreement {} proposed", agreement_number);
+    let message = format!(
+        "A blanket agreement for '{}' has been proposed: {} units at {:.4} {} per unit, from {} to {}. Please accept or reject it.",
+        rfq_title, dto.total_quantity, dto.unit_price, currency, dto.start_date, dto.end_date
+    );
+    notification_service::notify_company(pool, chat_server, counterparty, NotificationCategory::Agreement, &subject, &message, "/orders").await;
+
+    Ok(agreement_id)
+}
+
+pub async fn get_agreements(pool: &MySqlPool, claims: &Claims) -> Result<Vec<BlanketAgreement>, AppError> {
+    let sql = format!(
+        "{} WHERE ba.buyer_company_id = ? OR ba.supplier_company_id = ? ORDER BY ba.created_at DESC, ba.id DESC",
+        AGREEMENT_SELECT
+    );
+    let agreements = sqlx::query_as(&sql)
+        .bind(claims.company_id)
+        .bind(claims.company_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(agreements)
+}
+
+pub async fn get_agreement_detail(pool: &MySqlPool, agreement_id: i32, claims: &Claims) -> Result<BlanketAgreementDetail, AppError> {
+    let agreement = fetch_agreement(pool, agreement_id, claims).await?;
+    let releases = sqlx::query_as(
+        "SELECT po.*, r.title as rfq_title, b.name as buyer_name, s.name as supplier_name
+         FROM purchase_orders po
+         JOIN rfqs r ON po.rfq_id = r.id
+         JOIN companies b ON po.buyer_company_id = b.id
+         JOIN companies s ON po.supplier_company_id = s.id
+         WHERE po.blanket_agreement_id = ? ORDER BY po.created_at ASC, po.id ASC"
+    )
+        .bind(agreement_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(BlanketAgreementDetail { agreement, releases })
+}
+
+/// 另一方同意或拒绝协议
+pub async fn respond_to_agreement(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    agreement_id: i32,
+    dto: RespondBlanketAgreementDto,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    let comment = trimmed_text(dto.comment, "Comment")?;
+    let agreement = fetch_agreement(pool, agreement_id, claims).await?;
+    if agreement.proposed_by_company_id == claims.company_id {
+        return Err(AppError::BadRequest("You cannot respond to your own proposal.".to_string()));
+    }
+    if dto.accept && agreement.end_date < Utc::now().date_naive() {
+        return Err(AppError::BadRequest("The agreement period has already ended.".to_string()));
+    }
+
+    let result = sqlx::query(
+        "UPDATE blanket_agreements
+         SET status = ?, responded_by_user_id = ?, response_comment = ?, responded_at = CURRENT_TIMESTAMP
+         WHERE id = ? AND status = 'PROPOSED'"
+    )
+        .bind(if dto.accept { "ACTIVE" } else { "REJECTED" })
+        .bind(claims.sub)
+        .bind(&comment)
+        .bind(agreement_id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest("This agreement has already been answered.".to_string()));
+    }
+
+    let number = agreement.agreement_number.unwrap_or_default();
+    let (subject, message) = if dto.accept {
+        (
+            format!("Blanket agreement {} accepted", number),
+            format!("Blanket agreement {} for '{}' is now active until {}.", number, agreement.rfq_title, agreement.end_date),
+        )
+    } else {
+        (
+            format!("Blanket agreement {} rejected", number),
+            match &comment {
+                Some(c) => format!("Blanket agreement {} for '{}' was rejected: {}", number, agreement.rfq_title, c),
+                None => format!("Blanket agreement {} for '{}' was rejected.", number, agreement.rfq_title),
+            },
+        )
+    };
+    notification_service::notify_company(pool, chat_server, agreement.proposed_by_company_id, NotificationCategory::Agreement, &subject, &message, "/orders").await;
+
+    Ok(())
+}
+
+/// 任一方关闭协议，之后不能再下达订单。已下达的订单不受影响
+pub async fn close_agreement(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    agreement_id: i32,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    let agreement = fetch_agreement(pool, agreement_id, claims).await?;
+    let result = sqlx::query(
+        "UPDATE blanket_agreements SET status = 'CLOSED', closed_at = CURRENT_TIMESTAMP
+         WHERE id = ? AND status IN ('PROPOSED', 'ACTIVE')"
+    )
+        .bind(agreement_id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest("This agreement is already closed or rejected.".to_string()));
+    }
+
+    let number = agreement.agreement_number.clone().unwrap_or_default();
+    let subject = format!("Blanket agreement {} closed", number);
+    let message = format!(
+        "Blanket agreement {} for '{}' has been closed with {} of {} units released.",
+        number, agreement.rfq_title, agreement.released_quantity, agreement.total_quantity
+    );
+    notification_service::notify_company(pool, chat_server, counterparty_of(&agreement, claims.company_id), NotificationCategory::Agreement, &subject, &message, "/orders").await;
+
+    Ok(())
+}
+
+/// 采购方在协议下下达一批订单，生成的采购订单仍需供应商确认。数量用完后协议自动关闭
+pub async fn create_release(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    agreement_id: i32,
+    dto: CreateReleaseDto,
+    claims: &Claims,
+) -> Result<(i32, String), AppError> {
+    let today = Utc::now().date_naive();
+    if dto.delivery_date.is_some_and(|d| d < today) {
+        return Err(AppError::BadRequest("The delivery date cannot be in the past.".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+    // 锁住协议行，防止并发下达超出总数量
+    let locked: Option<(i32,)> = sqlx::query_as("SELECT id FROM blanket_agreements WHERE id = ? AND buyer_company_id = ? FOR UPDATE")
+        .bind(agreement_id)
+        .bind(claims.company_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if locked.is_none() {
+        return Err(AppError::BadRequest("Blanket agreement not found or only the buyer can release orders.".to_string()));
+    }
+    let sql = format!("{} WHERE ba.id = ?", AGREEMENT_SELECT);
+    let agreement: BlanketAgreement = sqlx::query_as(&sql)
+        .bind(agreement_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let subtotal = check_release(&agreement, dto.quantity, today)?;
+    Money::new(subtotal, Currency::from_str(&agreement.currency)?)?;
+
+    // 交期、运费和贸易术语按原报价，每批单独收运费
+    let (lead_time_days, shipping_cost, incoterm, incoterm_place): (i32, Decimal, Option<String>, Option<String>) = sqlx::query_as(
+        "SELECT lead_time_days, shipping_cost, incoterm, incoterm_place FROM quotes WHERE id = ?"
+    )
+        .bind(agreement.quote_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let delivery_date = dto.delivery_date.unwrap_or_else(|| today + Duration::days(i64::from(lead_time_days)));
+
+    let number = agreement.agreement_number.clone().unwrap_or_default();
+    let comment = format!("Release against blanket agreement {}", number);
+    let (order_id, po_number) = order_service::create_purchase_order(
+        &mut tx,
+        NewPurchaseOrder {
+            quote_id: agreement.quote_id,
+            rfq_id: agreement.rfq_id,
+            source_order_id: None,
+            blanket_agreement_id: Some(agreement.id),
+            buyer_company_id: agreement.buyer_company_id,
+            supplier_company_id: agreement.supplier_company_id,
+            quantity: dto.quantity,
+            subtotal,
+            shipping_amount: shipping_cost,
+            incoterm: incoterm.as_deref(),
+            incoterm_place: incoterm_place.as_deref(),
+            currency: &agreement.currency,
+            promised_delivery_date: delivery_date,
+            comment: &comment,
+        },
+        claims,
+    )
+        .await?;
+
+    let remaining = agreement.remaining_quantity - i64::from(dto.quantity);
+    if remaining == 0 {
+        sqlx::query("UPDATE blanket_agreements SET status = 'CLOSED', closed_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(agreement.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    // PO文件生成失败不影响下达，之后在订单文件列表中可以补生成
+    if let Err(e) = document_service::ensure_purchase_order_pdf(pool, order_id).await {
+        log::error!("Failed to generate purchase order PDF for order #{}: {:?}", order_id, e);
+    }
+
+    let subject = format!("New purchase order {} under {}", po_number, number);
+    let message = format!(
+        "Purchase order {} releases {} units of '{}' under blanket agreement {} ({} units remaining), delivery by {}. Please confirm the order.",
+        po_number, dto.quantity, agreement.rfq_title, number, remaining, delivery_date
+    );
+    notification_service::notify_company(pool, chat_server, agreement.supplier_company_id, NotificationCategory::Agreement, &subject, &message, "/orders").await;
+
+    Ok((order_id, po_number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_agreement_terms() {
+        let today = date(2026, 10, 19);
+        let price = Decimal::from_str("2.5").unwrap();
+        assert!(check_agreement_terms(price, 1000, today, date(2027, 10, 18), today).is_ok());
+        assert!(check_agreement_terms(price, 0, today, date(2027, 10, 18), today).is_err());
+        assert!(check_agreement_terms(Decimal::from_str("2.12345").unwrap(), 10, today, date(2027, 1, 1), today).is_err());
+        assert!(check_agreement_terms(price, 10, date(2027, 1, 1), date(2026, 12, 1), today).is_err());
+        assert!(check_agreement_terms(price, 10, today, date(2030, 1, 1), today).is_err());
+    }
+}
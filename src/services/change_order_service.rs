@@ -0,0 +1,545 @@
+// src/services/change_order_service.rs
+// 订单变更单：授标后任一方可以提出修改数量、单价或交期，另一方同意后生效并生成新版本的PO
+use crate::{
+    errors::AppError,
+    models::{
+        change_order::{ChangeOrder, CreateChangeOrderDto, RespondChangeOrderDto},
+        money::{self, Currency, Money},
+        order::{OrderParty, OrderStatus},
+        user::Claims,
+    },
+    services::{
+        chat_server::ChatServer, delivery_service, document_service, notification_service, order_service,
+        payment_provider::PaymentProvider, payment_schedule_service, payment_service, tax_service,
+    },
+};
+use crate::models::notification_preference::NotificationCategory;
+use actix::Addr;
+use chrono::{NaiveDate, Utc};
+use sqlx::{types::Decimal, FromRow, MySql, MySqlPool, Transaction};
+use std::str::FromStr;
+
+/// 单价最多四位小数，和 change_orders.new_unit_price 一致
+const MAX_UNIT_PRICE_DECIMALS: u32 = 4;
+
+/// 订单当前的商务条款
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct OrderTerms {
+    pub quantity: i32,
+    pub unit_price: Decimal,
+    pub total_amount: Decimal,
+    pub delivery_date: Option<NaiveDate>,
+}
+
+/// 变更受已发生业务的限制
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ChangeLimits {
+    pub shipped_quantity: i64,
+    pub invoiced_quantity: i64,
+    // 已经开过发票，单价不能再改
+    pub has_invoices: bool,
+    // 已经付款（或在退款中），数量和单价都不能再改
+    pub payment_settled: bool,
+}
+
+/// 提出的修改，None 表示不修改该项
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ProposedChange {
+    pub quantity: Option<i32>,
+    pub unit_price: Option<Decimal>,
+    pub delivery_date: Option<NaiveDate>,
+}
+
+impl OrderTerms {
+    fn new(quantity: i32, total_amount: Decimal, delivery_date: Option<NaiveDate>) -> Self {
+        // 报价是整单总价，单价由总价折算
+        let unit_price = if quantity > 0 {
+            (total_amount / Decimal::from(quantity)).round_dp(MAX_UNIT_PRICE_DECIMALS)
+        } else {
+            total_amount
+        };
+        Self { quantity, unit_price, total_amount, delivery_date }
+    }
+}
+
+/// 校验变更并计算生效后的条款。数量和单价都没变时总价保持原值，避免折算单价带来的舍入误差
+pub(crate) fn apply_change(
+    current: &OrderTerms,
+    change: &ProposedChange,
+    limits: &ChangeLimits,
+    today: NaiveDate,
+) -> Result<OrderTerms, AppError> {
+    let quantity = change.quantity.filter(|q| *q != current.quantity);
+    let unit_price = change.unit_price.filter(|p| *p != current.unit_price);
+    let delivery_date = change.delivery_date.filter(|d| Some(*d) != current.delivery_date);
+    if quantity.is_none() && unit_price.is_none() && delivery_date.is_none() {
+        return Err(AppError::BadRequest("The change order does not change anything.".to_string()));
+    }
+
+    if let Some(q) = quantity {
+        if q <= 0 {
+            return Err(AppError::BadRequest("Quantity must be greater than zero.".to_string()));
+        }
+        let floor = limits.shipped_quantity.max(limits.invoiced_quantity);
+        if i64::from(q) < floor {
+            return Err(AppError::BadRequest(format!(
+                "Quantity cannot be lower than the {} units already shipped or invoiced.",
+                floor
+            )));
+        }
+    }
+    if let Some(p) = unit_price {
+        if p <= Decimal::ZERO || p.normalize().scale() > MAX_UNIT_PRICE_DECIMALS {
+            return Err(AppError::BadRequest("Unit price must be positive with at most 4 decimals.".to_string()));
+        }
+        if limits.has_invoices {
+            return Err(AppError::BadRequest("The unit price cannot be changed after invoices have been issued.".to_string()));
+        }
+    }
+    if (quantity.is_some() || unit_price.is_some()) && limits.payment_settled {
+        return Err(AppError::BadRequest("Quantity and price cannot be changed after the order has been paid.".to_string()));
+    }
+    if delivery_date.is_some_and(|d| d < today) {
+        return Err(AppError::BadRequest("The delivery date cannot be in the past.".to_string()));
+    }
+
+    let new_quantity = quantity.unwrap_or(current.quantity);
+    let new_unit_price = unit_price.unwrap_or(current.unit_price);
+    let total_amount = if quantity.is_none() && unit_price.is_none() {
+        current.total_amount
+    } else {
+        money::round_amount(new_unit_price * Decimal::from(new_quantity))
+    };
+
+    Ok(OrderTerms {
+        quantity: new_quantity,
+        unit_price: new_unit_price,
+        total_amount,
+        delivery_date: delivery_date.or(current.delivery_date),
+    })
+}
+
+#[derive(FromRow)]
+struct TermsRow {
+    quantity: i32,
+    total_amount: Decimal,
+    promised_delivery_date: Option<NaiveDate>,
+    currency: String,
+    payment_status: String,
+    shipped_quantity: Option<i64>,
+    invoiced_quantity: Option<i64>,
+    invoice_count: i64,
+    settled_milestones: i64,
+}
+
+/// 在事务中读取订单当前条款和变更限制
+async fn load_terms(tx: &mut Transaction<'_, MySql>, order_id: i32) -> Result<(OrderTerms, ChangeLimits, String), AppError> {
+    let row: TermsRow = sqlx::query_as(
+        "SELECT po.quantity, po.subtotal_amount AS total_amount, po.promised_delivery_date, po.currency, po.payment_status,
+                (SELECT CAST(SUM(s.shipped_quantity) AS SIGNED) FROM shipments s WHERE s.order_id = po.id AND s.superseded_at IS NULL) as shipped_quantity,
+                (SELECT CAST(SUM(i.quantity) AS SIGNED) FROM invoices i WHERE i.order_id = po.id) as invoiced_quantity,
+                (SELECT COUNT(*) FROM invoices i WHERE i.order_id = po.id) as invoice_count,
+                (SELECT COUNT(*) FROM payment_milestones m WHERE m.order_id = po.id AND m.payment_status NOT IN ('UNPAID', 'FAILED')) as settled_milestones
+         FROM purchase_orders po WHERE po.id = ?"
+    )
+        .bind(order_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+    let limits = ChangeLimits {
+        shipped_quantity: row.shipped_quantity.unwrap_or(0),
+        invoiced_quantity: row.invoiced_quantity.unwrap_or(0),
+        has_invoices: row.invoice_count > 0,
+        // 分期付过任何一期也算已付款
+        payment_settled: (row.payment_status != "UNPAID" && row.payment_status != "FAILED") || row.settled_milestones > 0,
+    };
+    Ok((OrderTerms::new(row.quantity, row.total_amount, row.promised_delivery_date), limits, row.currency))
+}
+
+/// 只有发货前的订单可以变更
+fn ensure_changeable(status: &str) -> Result<OrderStatus, AppError> {
+    OrderStatus::parse(status)
+        .filter(|s| matches!(s, OrderStatus::PendingConfirmation | OrderStatus::InProduction))
+        .ok_or_else(|| AppError::BadRequest(format!("An order in status {} cannot be changed.", status)))
+}
+
+/// 用于通知的变更摘要
+fn describe_change(change: &ChangeOrder) -> String {
+    let mut parts = Vec::new();
+    if let Some(q) = change.new_quantity {
+        parts.push(format!("quantity {} -> {}", change.old_quantity, q));
+    }
+    if let Some(p) = change.new_unit_price {
+        parts.push(format!("unit price {:.4} -> {:.4}", change.old_unit_price, p));
+    }
+    if let Some(d) = change.new_delivery_date {
+        let old = change.old_delivery_date.map(|d| d.to_string()).unwrap_or_else(|| "none".to_string());
+        parts.push(format!("delivery date {} -> {}", old, d));
+    }
+    parts.join(", ")
+}
+
+async fn fetch_change_order(pool: &MySqlPool, change_order_id: i32) -> Result<ChangeOrder, AppError> {
+    let change = sqlx::query_as(
+        "SELECT co.*, c.name as proposed_by_company_name
+         FROM change_orders co JOIN companies c ON co.proposed_by_company_id = c.id
+         WHERE co.id = ?"
+    )
+        .bind(change_order_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(change)
+}
+
+/// 订单一方提出变更，需要另一方同意
+pub async fn propose_change_order(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    order_id: i32,
+    dto: CreateChangeOrderDto,
+    claims: &Claims,
+) -> Result<i32, AppError> {
+    let reason = dto.reason.trim().to_string();
+    if reason.is_empty() || reason.chars().count() > 1000 {
+        return Err(AppError::BadRequest("A reason of at most 1000 characters is required.".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+    let order = order_service::lock_order(&mut tx, order_id).await?;
+    let party = order.party_of(claims.company_id)
+        .ok_or_else(|| AppError::BadRequest("Order not found or you are not authorized to change it.".to_string()))?;
+    ensure_changeable(&order.status)?;
+
+    // 同一订单同时只能有一个待处理的变更单
+    let pending: Option<(i32,)> = sqlx::query_as("SELECT id FROM change_orders WHERE order_id = ? AND status = 'PENDING'")
+        .bind(order.id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if pending.is_some() {
+        return Err(AppError::BadRequest("There is already a pending change order for this order.".to_string()));
+    }
+
+    let (current, limits, currency) = load_terms(&mut tx, order.id).await?;
+    let change = ProposedChange {
+        quantity: dto.new_quantity.filter(|q| *q != current.quantity),
+        unit_price: dto.new_unit_price.filter(|p| *p != current.unit_price),
+        delivery_date: dto.new_delivery_date.filter(|d| Some(*d) != current.delivery_date),
+    };
+    let proposed = apply_change(&current, &change, &limits, Utc::now().date_naive())?;
+    Money::new(proposed.total_amount, Currency::from_str(&currency)?)?;
+
+    let result = sqlx::query(
+        "INSERT INTO change_orders (order_id, proposed_by_company_id, proposed_by_user_id, reason,
+                                    old_quantity, new_quantity, old_unit_price, new_unit_price,
+                                    old_delivery_date, new_delivery_date, old_total_amount)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+        .bind(order.id)
+        .bind(claims.company_id)
+        .bind(claims.sub)
+        .bind(&reason)
+        .bind(current.quantity)
+        .bind(change.quantity)
+        .bind(current.unit_price)
+        .bind(change.unit_price)
+        .bind(current.delivery_date)
+        .bind(change.delivery_date)
+        .bind(current.total_amount)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    let change_order_id = result.last_insert_id() as i32;
+    let created = fetch_change_order(pool, change_order_id).await?;
+    let counterparty = match party {
+        OrderParty::Buyer => order.supplier_company_id,
+        OrderParty::Supplier => order.buyer_company_id,
+    };
+    let subject = format!("Change order proposed for order #{}", order.id);
+    let message = format!("A change to order #{} has been proposed: {}. Please accept or reject it.", order.id, describe_change(&created));
+    let details = format!(
+        "Order: {}\nNew goods subtotal: {:.2} {}\nReason: {}",
+        order.rfq_title, proposed.total_amount, currency, reason
+    );
+    notification_service::notify_company_with_details(pool, chat_server, counterparty, NotificationCategory::Order, &subject, &message, Some(&details), "/orders").await;
+
+    Ok(change_order_id)
+}
+
+pub async fn get_change_orders(pool: &MySqlPool, order_id: i32, claims: &Claims) -> Result<Vec<ChangeOrder>, AppError> {
+    order_service::ensure_order_party(pool, order_id, claims).await?;
+
+    let changes = sqlx::query_as(
+        "SELECT co.*, c.name as proposed_by_company_name
+         FROM change_orders co JOIN companies c ON co.proposed_by_company_id = c.id
+         WHERE co.order_id = ? ORDER BY co.created_at DESC, co.id DESC"
+    )
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(changes)
+}
+
+#[derive(FromRow)]
+struct PendingChange {
+    proposed_by_company_id: i32,
+    new_quantity: Option<i32>,
+    new_unit_price: Option<Decimal>,
+    new_delivery_date: Option<NaiveDate>,
+}
+
+/// 另一方同意或拒绝变更单。同意后更新订单条款、重新计算总价并递增修订号
+pub async fn respond_to_change_order(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    provider: &dyn PaymentProvider,
+    order_id: i32,
+    change_order_id: i32,
+    dto: RespondChangeOrderDto,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    let comment = dto.comment.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+    if comment.as_ref().is_some_and(|c| c.chars().count() > 1000) {
+        return Err(AppError::BadRequest("Comment must be at most 1000 characters.".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+    let order = order_service::lock_order(&mut tx, order_id).await?;
+    if order.party_of(claims.company_id).is_none() {
+        return Err(AppError::BadRequest("Order not found or you are not authorized to update it.".to_string()));
+    }
+
+    let pending: Option<PendingChange> = sqlx::query_as(
+        "SELECT proposed_by_company_id, new_quantity, new_unit_price, new_delivery_date FROM change_orders
+         WHERE id = ? AND order_id = ? AND status = 'PENDING' FOR UPDATE"
+    )
+        .bind(change_order_id)
+        .bind(order.id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let pending = pending
+        .ok_or_else(|| AppError::BadRequest("Change order not found or already answered.".to_string()))?;
+    if pending.proposed_by_company_id == claims.company_id {
+        return Err(AppError::BadRequest("You cannot respond to your own change order.".to_string()));
+    }
+
+    let mut new_terms = None;
+    let mut fully_shipped = false;
+    if dto.accept {
+        // 提出后订单可能已经发货、开票或付款，需要再检查一次
+        let status = ensure_changeable(&order.status)?;
+        let (current, limits, currency) = load_terms(&mut tx, order.id).await?;
+        let change = ProposedChange {
+            quantity: pending.new_quantity,
+            unit_price: pending.new_unit_price,
+            delivery_date: pending.new_delivery_date,
+        };
+        let terms = apply_change(&current, &change, &limits, Utc::now().date_naive())?;
+        // 变更的是货款，运费不变，税额按下单时的税率重算
+        let (shipping_amount, tax_rate, old_total, session_id): (Decimal, Decimal, Decimal, Option<String>) = sqlx::query_as(
+            "SELECT shipping_amount, tax_rate, total_amount, stripe_session_id FROM purchase_orders WHERE id = ?"
+        )
+            .bind(order.id)
+            .fetch_one(&mut *tx)
+            .await?;
+        let totals = tax_service::order_totals(terms.total_amount, shipping_amount, tax_rate);
+        Money::new(totals.total, Currency::from_str(&currency)?)?;
+        // 金额变了的话，按旧金额开的支付会话在渠道作废，买方下次付款按新金额开会话
+        if let Some(session_id) = session_id.filter(|_| totals.total != old_total) {
+            payment_service::expire_open_session(provider, &session_id).await?;
+            sqlx::query("UPDATE purchase_orders SET stripe_session_id = NULL WHERE id = ?")
+                .bind(order.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query(
+            "UPDATE purchase_orders
+             SET quantity = ?, subtotal_amount = ?, tax_amount = ?, total_amount = ?, promised_delivery_date = ?,
+                 amendment_number = amendment_number + 1
+             WHERE id = ?"
+        )
+            .bind(terms.quantity)
+            .bind(totals.subtotal)
+            .bind(totals.tax)
+            .bind(totals.total)
+            .bind(terms.delivery_date)
+            .bind(order.id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "UPDATE change_orders
+             SET new_total_amount = ?, amendment_number = (SELECT amendment_number FROM purchase_orders WHERE id = ?)
+             WHERE id = ?"
+        )
+            .bind(terms.total_amount)
+            .bind(order.id)
+            .bind(change_order_id)
+            .execute(&mut *tx)
+            .await?;
+        payment_schedule_service::rescale_schedule(&mut tx, provider, order.id, totals.total).await?;
+
+        // 数量减到已发货数量时，订单视为已全部发出
+        if status == OrderStatus::InProduction && limits.shipped_quantity >= i64::from(terms.quantity) {
+            delivery_service::record_delivery_outcome(&mut tx, order.id).await?;
+            let comment = format!("All {} units shipped after change order", terms.quantity);
+            order_service::record_transition(
+                &mut tx,
+                order.id,
+                Some(status.as_str()),
+                OrderStatus::Shipped,
+                Some(claims.sub),
+                Some(claims.company_id),
+                Some(&comment),
+            )
+                .await?;
+            fully_shipped = true;
+        }
+        new_terms = Some((totals.total, currency));
+    }
+
+    sqlx::query(
+        "UPDATE change_orders
+         SET status = ?, responded_by_user_id = ?, response_comment = ?, responded_at = CURRENT_TIMESTAMP
+         WHERE id = ?"
+    )
+        .bind(if dto.accept { "ACCEPTED" } else { "REJECTED" })
+        .bind(claims.sub)
+        .bind(&comment)
+        .bind(change_order_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    let answered = fetch_change_order(pool, change_order_id).await?;
+    let (subject, message, details) = match &new_terms {
+        Some((total, currency)) => {
+            // 新版本PO生成失败不影响变更生效，查看订单文件时会补生成
+            if let Err(e) = document_service::ensure_purchase_order_pdf(pool, order.id).await {
+                log::error!("Failed to generate amended purchase order for order #{}: {:?}", order.id, e);
+            }
+            (
+                format!("Order #{} amended", order.id),
+                format!(
+                    "Change order for order #{} was accepted as amendment {}: {}.",
+                    order.id, answered.amendment_number.unwrap_or_default(), describe_change(&answered)
+                ),
+                Some(format!("Order: {}\nNew total including shipping and tax: {:.2} {}", order.rfq_title, total, currency)),
+            )
+        }
+        None => (
+            format!("Change order rejected for order #{}", order.id),
+            format!("The change order for order #{} ({}) was rejected.", order.id, describe_change(&answered)),
+            comment.as_ref().map(|c| format!("Comment: {}", c)),
+        ),
+    };
+    for company_id in [order.buyer_company_id, order.supplier_company_id] {
+        notification_service::notify_company_with_details(pool, chat_server, company_id, NotificationCategory::Order, &subject, &message, details.as_deref(), "/orders").await;
+    }
+    if fully_shipped {
+        order_service::notify_status_change(pool, chat_server, &order, OrderStatus::Shipped).await;
+    }
+
+    Ok(())
+}
+
+/// 提出方在对方答复前撤回变更单
+pub async fn withdraw_change_order(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    order_id: i32,
+    change_order_id: i32,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    let result = sqlx::query(
+        "UPDATE change_orders SET status = 'WITHDRAWN', responded_by_user_id = ?, responded_at = CURRENT_TIMESTAMP
+         WHERE id = ? AND order_id = ? AND proposed_by_company_id = ? AND status = 'PENDING'"
+    )
+        .bind(claims.sub)
+        .bind(change_order_id)
+        .bind(order_id)
+        .bind(claims.company_id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest("Change order not found or can no longer be withdrawn.".to_string()));
+    }
+
+    let (buyer_company_id, supplier_company_id): (i32, i32) =
+        sqlx::query_as("SELECT buyer_company_id, supplier_company_id FROM purchase_orders WHERE id = ?")
+            .bind(order_id)
+            .fetch_one(pool)
+            .await?;
+    let counterparty = if claims.company_id == buyer_company_id { supplier_company_id } else { buyer_company_id };
+    let subject = format!("Change order withdrawn for order #{}", order_id);
+    let message = format!("The pending change order for order #{} has been withdrawn.", order_id);
+    notification_service::notify_company(pool, chat_server, counterparty, NotificationCategory::Order, &subject, &message, "/orders").await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 19).unwrap()
+    }
+
+    fn current() -> OrderTerms {
+        OrderTerms::new(3, d("100.00"), NaiveDate::from_ymd_opt(2026, 11, 30))
+    }
+
+    #[test]
+    fn test_total_is_recalculated_only_for_quantity_or_price() {
+        let terms = current();
+        assert_eq!(terms.unit_price, d("33.3333"));
+
+        // 只改交期，总价保持原值
+        let date = NaiveDate::from_ymd_opt(2026, 12, 15);
+        let change = ProposedChange { quantity: None, unit_price: None, delivery_date: date };
+        let next = apply_change(&terms, &change, &ChangeLimits::default(), today()).unwrap();
+        assert_eq!(next.total_amount, d("100.00"));
+        assert_eq!(next.delivery_date, date);
+
+        let change = ProposedChange { quantity: Some(6), unit_price: None, delivery_date: None };
+        let next = apply_change(&terms, &change, &ChangeLimits::default(), today()).unwrap();
+        assert_eq!(next.total_amount, d("200.00"));
+
+        let change = ProposedChange { quantity: Some(10), unit_price: Some(d("9.995")), delivery_date: None };
+        let next = apply_change(&terms, &change, &ChangeLimits::default(), today()).unwrap();
+        assert_eq!(next.total_amount, d("99.95"));
+    }
+
+    #[test]
+    fn test_change_limits() {
+        let terms = current();
+        let no_change = ProposedChange { quantity: Some(3), unit_price: None, delivery_date: terms.delivery_date };
+        assert!(apply_change(&terms, &no_change, &ChangeLimits::default(), today()).is_err());
+
+        let shipped = ChangeLimits { shipped_quantity: 2, ..Default::default() };
+        let lower = ProposedChange { quantity: Some(1), unit_price: None, delivery_date: None };
+        assert!(apply_change(&terms, &lower, &shipped, today()).is_err());
+        let to_shipped = ProposedChange { quantity: Some(2), unit_price: None, delivery_date: None };
+        assert!(apply_change(&terms, &to_shipped, &shipped, today()).is_ok());
+
+        let invoiced = ChangeLimits { invoiced_quantity: 1, has_invoices: true, ..Default::default() };
+        let price = ProposedChange { quantity: None, unit_price: Some(d("30")), delivery_date: None };
+        assert!(apply_change(&terms, &price, &invoiced, today()).is_err());
+        assert!(apply_change(&terms, &to_shipped, &invoiced, today()).is_ok());
+
+        let paid = ChangeLimits { payment_settled: true, ..Default::default() };
+        assert!(apply_change(&terms, &to_shipped, &paid, today()).is_err());
+        let later = ProposedChange { quantity: None, unit_price: None, delivery_date: NaiveDate::from_ymd_opt(2027, 1, 10) };
+        assert!(apply_change(&terms, &later, &paid, today()).is_ok());
+
+        let past = ProposedChange { quantity: None, unit_price: None, delivery_date: NaiveDate::from_ymd_opt(2026, 10, 1) };
+        assert!(apply_change(&terms, &past, &ChangeLimits::default(), today()).is_err());
+        let bad_price = ProposedChange { quantity: None, unit_price: Some(d("1.23456")), delivery_date: None };
+        assert!(apply_change(&terms, &bad_price, &ChangeLimits::default(), today()).is_err());
+    }
+}
@@ -3,14 +3,18 @@
 use crate::{
     errors::AppError,
     models::{company::{CompanyProfile, UpdateCompanyDto}, user::Claims},
+    services::{rating_service, tax_service},
 };
 use sqlx::MySqlPool;
 
 pub async fn get_company_by_id(pool: &MySqlPool, company_id: i32) -> Result<CompanyProfile, AppError> {
-    let profile = sqlx::query_as("SELECT id, name, company_type, city, description, is_verified, created_at FROM companies WHERE id = ?")
+    let mut profile: CompanyProfile = sqlx::query_as("SELECT id, name, company_type, city, country_code, region, description, reporting_currency, is_verified, created_at FROM companies WHERE id = ?")
         .bind(company_id)
         .fetch_one(pool)
         .await?;
+    if profile.company_type == "SUPPLIER" {
+        profile.scorecard = Some(rating_service::get_supplier_scorecard(pool, company_id).await?);
+    }
     Ok(profile)
 }
 
@@ -25,8 +29,24 @@ pub async fn update_company_profile(
         return Err(AppError::BadRequest("You are not authorized to edit this company profile.".to_string()));
     }
 
-    let result = sqlx::query("UPDATE companies SET description = ? WHERE id = ?")
+    let location = dto.country_code
+        .map(|country_code| tax_service::normalize_location(&country_code, dto.region))
+        .transpose()?;
+    let (country_code, region) = match location {
+        Some((country_code, region)) => (Some(country_code), Some(region).filter(|r| !r.is_empty())),
+        None => (None, None),
+    };
+
+    let result = sqlx::query(
+        "UPDATE companies SET description = ?, reporting_currency = COALESCE(?, reporting_currency),
+                              region = IF(? IS NULL, region, ?), country_code = COALESCE(?, country_code)
+         WHERE id = ?"
+    )
         .bind(dto.description)
+        .bind(dto.reporting_currency.map(|c| c.code()))
+        .bind(&country_code)
+        .bind(&region)
+        .bind(&country_code)
         .bind(company_id)
         .execute(pool)
         .await?;
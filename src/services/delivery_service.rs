@@ -0,0 +1,185 @@
+// src/services/delivery_service.rs
+// 交期跟踪：后台定时检查未发完货的订单，临近承诺交期标记为有风险，超过交期标记为延期并通知双方；
+// 订单全部发货时记录准时/延期结果，用于供应商分析
+use crate::{
+    errors::AppError,
+    models::delivery::{DeliveryPerformance, DeliveryStatus},
+    services::{chat_server::ChatServer, notification_service},
+};
+use crate::models::notification_preference::NotificationCategory;
+use actix::Addr;
+use chrono::{NaiveDate, Utc};
+use sqlx::{FromRow, MySql, MySqlPool, Transaction};
+use std::{env, time::Duration};
+
+/// 距承诺交期不到这么多天还没发完货就算有风险
+const DEFAULT_AT_RISK_DAYS: i64 = 3;
+/// 默认每小时检查一次
+const DEFAULT_CHECK_INTERVAL_SECS: u64 = 3600;
+
+/// 根据承诺交期判断未发完货订单的交期状态
+pub(crate) fn assess_delivery(promised: NaiveDate, today: NaiveDate, at_risk_days: i64) -> DeliveryStatus {
+    let days_left = (promised - today).num_days();
+    if days_left < 0 {
+        DeliveryStatus::Late
+    } else if days_left <= at_risk_days {
+        DeliveryStatus::AtRisk
+    } else {
+        DeliveryStatus::OnTrack
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct OpenOrder {
+    id: i32,
+    po_number: Option<String>,
+    buyer_company_id: i32,
+    supplier_company_id: i32,
+    rfq_title: String,
+    promised_delivery_date: NaiveDate,
+    delivery_status: String,
+}
+
+/// 检查一遍所有未发完货的订单，返回本次状态有变化的订单数
+pub async fn run_delivery_checks(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    today: NaiveDate,
+    at_risk_days: i64,
+) -> Result<usize, AppError> {
+    let orders: Vec<OpenOrder> = sqlx::query_as(
+        "SELECT po.id, po.po_number, po.buyer_company_id, po.supplier_company_id, r.title as rfq_title,
+                po.promised_delivery_date, po.delivery_status
+         FROM purchase_orders po JOIN rfqs r ON po.rfq_id = r.id
+         WHERE po.status IN ('PENDING_CONFIRMATION', 'IN_PRODUCTION') AND po.promised_delivery_date IS NOT NULL"
+    )
+        .fetch_all(pool)
+        .await?;
+
+    let mut changed = 0;
+    for order in orders {
+        let status = assess_delivery(order.promised_delivery_date, today, at_risk_days);
+        if status.as_str() == order.delivery_status {
+            continue;
+        }
+        // 条件更新，避免和变更单或发货同时修改时重复通知
+        let result = sqlx::query("UPDATE purchase_orders SET delivery_status = ? WHERE id = ? AND delivery_status = ?")
+            .bind(status.as_str())
+            .bind(order.id)
+            .bind(&order.delivery_status)
+            .execute(pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            continue;
+        }
+        changed += 1;
+
+        let po = order.po_number.clone().unwrap_or_else(|| format!("#{}", order.id));
+        let (subject, message) = match status {
+            DeliveryStatus::OnTrack => continue,
+            DeliveryStatus::AtRisk => (
+                format!("Order {} is at risk of late delivery", po),
+                format!(
+                    "Order {} ('{}') is due on {} and has not been fully shipped yet.",
+                    po, order.rfq_title, order.promised_delivery_date
+                ),
+            ),
+            DeliveryStatus::Late => (
+                format!("Order {} is late", po),
+                format!(
+                    "Order {} ('{}') has passed its promised delivery date of {} without being fully shipped.",
+                    po, order.rfq_title, order.promised_delivery_date
+                ),
+            ),
+        };
+        for company_id in [order.buyer_company_id, order.supplier_company_id] {
+            notification_service::notify_company(pool, chat_server, company_id, NotificationCategory::Order, &subject, &message, "/orders").await;
+        }
+    }
+
+    Ok(changed)
+}
+
+/// 启动后台交期检查任务。间隔和风险天数可以用 DELIVERY_CHECK_INTERVAL_SECS、DELIVERY_AT_RISK_DAYS 配置
+pub fn spawn_delivery_monitor(pool: MySqlPool, chat_server: Addr<ChatServer>) {
+    let interval_secs = env::var("DELIVERY_CHECK_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_CHECK_INTERVAL_SECS);
+    let at_risk_days = env::var("DELIVERY_AT_RISK_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|v| *v >= 0)
+        .unwrap_or(DEFAULT_AT_RISK_DAYS);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            ticker.tick().await;
+            match run_delivery_checks(&pool, &chat_server, Utc::now().date_naive(), at_risk_days).await {
+                Ok(0) => {}
+                Ok(changed) => log::info!("Delivery check updated {} orders", changed),
+                Err(e) => log::error!("Delivery check failed: {:?}", e),
+            }
+        }
+    });
+}
+
+/// 订单全部发货时记录交期结果，只记录第一次（返工后再次发货不覆盖）
+pub(crate) async fn record_delivery_outcome(tx: &mut Transaction<'_, MySql>, order_id: i32) -> Result<(), AppError> {
+    sqlx::query(
+        "UPDATE purchase_orders
+         SET delivered_on = CURRENT_DATE,
+             days_late = GREATEST(DATEDIFF(CURRENT_DATE, promised_delivery_date), 0),
+             delivery_outcome = IF(CURRENT_DATE <= promised_delivery_date, 'ON_TIME', 'LATE')
+         WHERE id = ? AND promised_delivery_date IS NOT NULL AND delivery_outcome IS NULL"
+    )
+        .bind(order_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+pub(crate) async fn get_supplier_delivery_performance(pool: &MySqlPool, supplier_company_id: i32) -> Result<DeliveryPerformance, AppError> {
+    let (delivered_orders, on_time_orders, average_days_late, currently_late): (i64, Option<i64>, Option<f64>, Option<i64>) = sqlx::query_as(
+        "SELECT COUNT(delivery_outcome),
+                CAST(SUM(delivery_outcome = 'ON_TIME') AS SIGNED),
+                CAST(AVG(days_late) AS DOUBLE),
+                CAST(SUM(status IN ('PENDING_CONFIRMATION', 'IN_PRODUCTION') AND delivery_status = 'LATE') AS SIGNED)
+         FROM purchase_orders WHERE supplier_company_id = ?"
+    )
+        .bind(supplier_company_id)
+        .fetch_one(pool)
+        .await?;
+
+    let on_time_orders = on_time_orders.unwrap_or(0);
+    let on_time_rate = (delivered_orders > 0)
+        .then(|| (on_time_orders as f64 * 10000.0 / delivered_orders as f64).round() / 100.0);
+    Ok(DeliveryPerformance {
+        delivered_orders,
+        on_time_orders,
+        late_orders: delivered_orders - on_time_orders,
+        on_time_rate,
+        average_days_late: average_days_late.map(|v| (v * 100.0).round() / 100.0),
+        currently_late: currently_late.unwrap_or(0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assess_delivery() {
+        let promised = NaiveDate::from_ymd_opt(2026, 11, 10).unwrap();
+        let day = |d: u32| NaiveDate::from_ymd_opt(2026, 11, d).unwrap();
+
+        assert_eq!(assess_delivery(promised, day(1), 3), DeliveryStatus::OnTrack);
+        assert_eq!(assess_delivery(promised, day(6), 3), DeliveryStatus::OnTrack);
+        assert_eq!(assess_delivery(promised, day(7), 3), DeliveryStatus::AtRisk);
+        assert_eq!(assess_delivery(promised, day(10), 3), DeliveryStatus::AtRisk);
+        assert_eq!(assess_delivery(promised, day(11), 3), DeliveryStatus::Late);
+        assert_eq!(assess_delivery(promised, day(10), 0), DeliveryStatus::AtRisk);
+    }
+}
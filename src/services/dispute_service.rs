@@ -0,0 +1,388 @@
+// src/services/dispute_service.rs
+// 订单争议：订单双方发起并补充证据，管理员仲裁，仲裁结果联动订单状态和付款状态
+use crate::{
+    errors::AppError,
+    models::{
+        dispute::{
+            CreateDisputeDto, Dispute, DisputeAttachment, DisputeDetail, DisputeMessage, DisputeMessageDto,
+            DisputeResolution, ResolveDisputeDto,
+        },
+        money::{Currency, Money},
+        order::{OrderStatus, TransitionGuard},
+        user::Claims,
+    },
+    services::{chat_server::ChatServer, notification_service, order_service, refund_service, shipment_service},
+    utils::upload_utils::{self, SavedFile, DOCUMENT_EXTENSIONS, MAX_DOCUMENT_SIZE_BYTES},
+};
+use crate::models::notification_preference::NotificationCategory;
+use actix::Addr;
+use actix_multipart::Multipart;
+use sqlx::MySqlPool;
+use std::str::FromStr;
+
+const DISPUTE_SELECT: &str =
+    "SELECT d.*, r.title as rfq_title, c.name as opened_by_company_name, po.currency
+     FROM disputes d
+     JOIN purchase_orders po ON d.order_id = po.id
+     JOIN rfqs r ON po.rfq_id = r.id
+     JOIN companies c ON d.opened_by_company_id = c.id";
+
+fn required_text(value: &str, max_chars: usize, field: &str) -> Result<String, AppError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(AppError::BadRequest(format!("{} is required.", field)));
+    }
+    if value.chars().count() > max_chars {
+        return Err(AppError::BadRequest(format!("{} must be at most {} characters.", field, max_chars)));
+    }
+    Ok(value.to_string())
+}
+
+/// 读取争议，并确认当前用户是订单的一方或管理员。返回争议和订单双方的公司ID
+async fn load_dispute(pool: &MySqlPool, dispute_id: i32, claims: &Claims) -> Result<(Dispute, i32, i32), AppError> {
+    let dispute: Dispute = sqlx::query_as(&format!("{} WHERE d.id = ?", DISPUTE_SELECT))
+        .bind(dispute_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Dispute not found.".to_string()))?;
+
+    let (buyer_company_id, supplier_company_id): (i32, i32) = sqlx::query_as(
+        "SELECT buyer_company_id, supplier_company_id FROM purchase_orders WHERE id = ?"
+    )
+        .bind(dispute.order_id)
+        .fetch_one(pool)
+        .await?;
+
+    if !claims.is_admin && claims.company_id != buyer_company_id && claims.company_id != supplier_company_id {
+        return Err(AppError::BadRequest("Dispute not found or you are not authorized to view it.".to_string()));
+    }
+    Ok((dispute, buyer_company_id, supplier_company_id))
+}
+
+/// 订单一方发起争议，同一订单同时只能有一个未解决的争议
+pub async fn open_dispute(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    order_id: i32,
+    dto: CreateDisputeDto,
+    claims: &Claims,
+) -> Result<i32, AppError> {
+    let reason = required_text(&dto.reason, 2000, "Reason")?;
+
+    let mut tx = pool.begin().await?;
+    let order = order_service::lock_order(&mut tx, order_id).await?;
+    if order.party_of(claims.company_id).is_none() {
+        return Err(AppError::BadRequest("Order not found or you are not authorized to dispute it.".to_string()));
+    }
+    if order.status == OrderStatus::Cancelled.as_str() {
+        return Err(AppError::BadRequest("A cancelled order cannot be disputed.".to_string()));
+    }
+
+    let open: Option<(i32,)> = sqlx::query_as("SELECT id FROM disputes WHERE order_id = ? AND status = 'OPEN'")
+        .bind(order.id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if open.is_some() {
+        return Err(AppError::BadRequest("There is already an open dispute for this order.".to_string()));
+    }
+
+    let result = sqlx::query(
+        "INSERT INTO disputes (order_id, opened_by_company_id, opened_by_user_id, reason) VALUES (?, ?, ?, ?)"
+    )
+        .bind(order.id)
+        .bind(claims.company_id)
+        .bind(claims.sub)
+        .bind(&reason)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    let dispute_id = result.last_insert_id() as i32;
+    let counterparty = if claims.company_id == order.buyer_company_id {
+        order.supplier_company_id
+    } else {
+        order.buyer_company_id
+    };
+    let message = format!("A dispute has been opened for order #{}.", order.id);
+    let vars = [("order_id", order.id.to_string()), ("rfq_title", order.rfq_title.clone()), ("reason", reason.clone())];
+    notification_service::notify_company_with_template(pool, chat_server, counterparty, NotificationCategory::Dispute, &message, &format!("/disputes/{}", dispute_id), "dispute_opened", &vars).await;
+
+    Ok(dispute_id)
+}
+
+pub async fn get_disputes_for_order(pool: &MySqlPool, order_id: i32, claims: &Claims) -> Result<Vec<Dispute>, AppError> {
+    order_service::ensure_order_party(pool, order_id, claims).await?;
+
+    let disputes = sqlx::query_as(&format!("{} WHERE d.order_id = ? ORDER BY d.created_at DESC", DISPUTE_SELECT))
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(disputes)
+}
+
+pub async fn get_dispute_detail(pool: &MySqlPool, dispute_id: i32, claims: &Claims) -> Result<DisputeDetail, AppError> {
+    let (dispute, _, _) = load_dispute(pool, dispute_id, claims).await?;
+
+    let messages = sqlx::query_as(
+        "SELECT m.*, u.full_name as sender_name
+         FROM dispute_messages m LEFT JOIN users u ON m.sender_user_id = u.id
+         WHERE m.dispute_id = ? ORDER BY m.created_at ASC, m.id ASC"
+    )
+        .bind(dispute_id)
+        .fetch_all(pool)
+        .await?;
+
+    let attachments = sqlx::query_as("SELECT * FROM dispute_attachments WHERE dispute_id = ? ORDER BY id ASC")
+        .bind(dispute_id)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(DisputeDetail { dispute, messages, attachments })
+}
+
+/// 在争议中留言，订单双方和管理员都可以
+pub async fn add_message(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    dispute_id: i32,
+    dto: DisputeMessageDto,
+    claims: &Claims,
+) -> Result<DisputeMessage, AppError> {
+    let content = required_text(&dto.content, 5000, "Message")?;
+    let (dispute, buyer_company_id, supplier_company_id) = load_dispute(pool, dispute_id, claims).await?;
+    if dispute.status != "OPEN" {
+        return Err(AppError::BadRequest("This dispute has already been resolved.".to_string()));
+    }
+
+    // 管理员不一定属于订单的任何一方
+    let is_party = claims.company_id == buyer_company_id || claims.company_id == supplier_company_id;
+    let sender_company_id = is_party.then_some(claims.company_id);
+    let is_admin = claims.is_admin && !is_party;
+
+    let result = sqlx::query(
+        "INSERT INTO dispute_messages (dispute_id, sender_user_id, sender_company_id, is_admin, content) VALUES (?, ?, ?, ?, ?)"
+    )
+        .bind(dispute_id)
+        .bind(claims.sub)
+        .bind(sender_company_id)
+        .bind(is_admin)
+        .bind(&content)
+        .execute(pool)
+        .await?;
+
+    let message: DisputeMessage = sqlx::query_as(
+        "SELECT m.*, u.full_name as sender_name
+         FROM dispute_messages m LEFT JOIN users u ON m.sender_user_id = u.id WHERE m.id = ?"
+    )
+        .bind(result.last_insert_id())
+        .fetch_one(pool)
+        .await?;
+
+    let text = format!("There is a new message in the dispute for order #{}.", dispute.order_id);
+    let vars = [("dispute_id", dispute_id.to_string()), ("order_id", dispute.order_id.to_string())];
+    for company_id in [buyer_company_id, supplier_company_id] {
+        if Some(company_id) != sender_company_id {
+            notification_service::notify_company_with_template(pool, chat_server, company_id, NotificationCategory::Dispute, &text, &format!("/disputes/{}", dispute_id), "dispute_message", &vars).await;
+        }
+    }
+
+    Ok(message)
+}
+
+/// 上传争议证据，返回新增的附件
+pub async fn add_attachments(
+    pool: &MySqlPool,
+    dispute_id: i32,
+    payload: Multipart,
+    claims: &Claims,
+) -> Result<Vec<DisputeAttachment>, AppError> {
+    let (dispute, _, _) = load_dispute(pool, dispute_id, claims).await?;
+    if dispute.status != "OPEN" {
+        return Err(AppError::BadRequest("This dispute has already been resolved.".to_string()));
+    }
+
+    let (_, files) = upload_utils::save_multipart(payload, "disputes", DOCUMENT_EXTENSIONS, MAX_DOCUMENT_SIZE_BYTES).await?;
+    if files.is_empty() {
+        return Err(AppError::BadRequest("No file was uploaded.".to_string()));
+    }
+
+    // 一次上传的附件要么全部登记，要么一个都不登记；失败时删掉已保存的文件
+    let attachments = insert_attachments(pool, dispute_id, &files, claims).await;
+    if attachments.is_err() {
+        upload_utils::remove_saved_files(&files);
+    }
+    attachments
+}
+
+async fn insert_attachments(
+    pool: &MySqlPool,
+    dispute_id: i32,
+    files: &[SavedFile],
+    claims: &Claims,
+) -> Result<Vec<DisputeAttachment>, AppError> {
+    let mut tx = pool.begin().await?;
+    let mut attachments = Vec::with_capacity(files.len());
+    for file in files {
+        let result = sqlx::query(
+            "INSERT INTO dispute_attachments (dispute_id, uploaded_by_user_id, original_filename, stored_path) VALUES (?, ?, ?, ?)"
+        )
+            .bind(dispute_id)
+            .bind(claims.sub)
+            .bind(&file.original_filename)
+            .bind(&file.stored_path)
+            .execute(&mut *tx)
+            .await?;
+
+        let attachment = sqlx::query_as("SELECT * FROM dispute_attachments WHERE id = ?")
+            .bind(result.last_insert_id())
+            .fetch_one(&mut *tx)
+            .await?;
+        attachments.push(attachment);
+    }
+    tx.commit().await?;
+    Ok(attachments)
+}
+
+/// 下载争议证据，只有订单双方和管理员可以下载
+pub async fn download_attachment(pool: &MySqlPool, dispute_id: i32, attachment_id: i32, claims: &Claims) -> Result<(DisputeAttachment, String, Vec<u8>), AppError> {
+    load_dispute(pool, dispute_id, claims).await?;
+    let attachment: DisputeAttachment = sqlx::query_as("SELECT * FROM dispute_attachments WHERE id = ? AND dispute_id = ?")
+        .bind(attachment_id)
+        .bind(dispute_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Attachment not found.".to_string()))?;
+
+    let (content_type, content) = upload_utils::read_saved_file(&attachment.stored_path, &attachment.original_filename).await?;
+    Ok((attachment, content_type, content))
+}
+
+/// 管理员查看所有争议，可按状态筛选
+pub async fn list_disputes(pool: &MySqlPool, status: Option<String>) -> Result<Vec<Dispute>, AppError> {
+    if status.as_deref().is_some_and(|s| s != "OPEN" && s != "RESOLVED") {
+        return Err(AppError::BadRequest("Status must be OPEN or RESOLVED.".to_string()));
+    }
+
+    let disputes = sqlx::query_as(&format!(
+        "{} WHERE (? IS NULL OR d.status = ?) ORDER BY d.created_at DESC",
+        DISPUTE_SELECT
+    ))
+        .bind(&status)
+        .bind(&status)
+        .fetch_all(pool)
+        .await?;
+    Ok(disputes)
+}
+
+/// 管理员仲裁：
+/// - REFUND: 已付款订单转为待退款，记录退款金额（不能超过订单金额）
+/// - REWORK: 已发货/已完成的订单退回生产中，之前的发货和收货记录作废，供应商重新整单发货
+/// - CLOSED: 只关闭争议，不影响订单
+pub async fn resolve_dispute(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    dispute_id: i32,
+    dto: ResolveDisputeDto,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    let resolution = DisputeResolution::parse(&dto.resolution)
+        .ok_or_else(|| AppError::BadRequest("Resolution must be REFUND, REWORK or CLOSED.".to_string()))?;
+    let note = dto.note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    if note.as_ref().is_some_and(|n| n.chars().count() > 2000) {
+        return Err(AppError::BadRequest("Note must be at most 2000 characters.".to_string()));
+    }
+    if resolution != DisputeResolution::Refund && dto.refund_amount.is_some() {
+        return Err(AppError::BadRequest("A refund amount is only allowed for REFUND resolutions.".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+    let dispute: Option<(i32, String)> = sqlx::query_as("SELECT order_id, status FROM disputes WHERE id = ? FOR UPDATE")
+        .bind(dispute_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let (order_id, status) = dispute.ok_or_else(|| AppError::BadRequest("Dispute not found.".to_string()))?;
+    if status != "OPEN" {
+        return Err(AppError::BadRequest("This dispute has already been resolved.".to_string()));
+    }
+    let order = order_service::lock_order(&mut tx, order_id).await?;
+
+    let mut refund_amount = None;
+    let mut new_status = None;
+    match resolution {
+        DisputeResolution::Refund => {
+            let (total_amount, refunded_amount, currency, payment_status): (sqlx::types::Decimal, sqlx::types::Decimal, String, String) =
+                sqlx::query_as("SELECT total_amount, refunded_amount, currency, payment_status FROM purchase_orders WHERE id = ?")
+                    .bind(order.id)
+                    .fetch_one(&mut *tx)
+                    .await?;
+            let amount = dto.refund_amount
+                .ok_or_else(|| AppError::BadRequest("A refund amount is required.".to_string()))?;
+            let amount = Money::new(amount, Currency::from_str(&currency)?)?;
+            if amount.amount() > total_amount - refunded_amount {
+                return Err(AppError::BadRequest("Refund amount cannot exceed the amount paid and not yet refunded.".to_string()));
+            }
+            if payment_status != "PAID" && payment_status != "PARTIALLY_REFUNDED" {
+                return Err(AppError::BadRequest("Only paid orders can be refunded.".to_string()));
+            }
+            order_service::mark_refund_pending(&mut tx, order.id).await?;
+            // 生成关联这个争议的待审批退款
+            let refund_reason = format!("Refund awarded in dispute #{}", dispute_id);
+            refund_service::open_linked_refund(&mut tx, order.id, Some(amount.amount()), &refund_reason, Some(dispute_id), None).await?;
+            refund_amount = Some(amount.amount());
+        }
+        DisputeResolution::Rework => {
+            let from_status = OrderStatus::parse(&order.status)
+                .filter(|s| s.transition_guard(OrderStatus::InProduction) == Some(TransitionGuard::Admin))
+                .ok_or_else(|| AppError::BadRequest(format!("An order in status {} cannot be sent back for rework.", order.status)))?;
+            let comment = format!("Rework ordered in dispute #{}", dispute_id);
+            order_service::record_transition(
+                &mut tx,
+                order.id,
+                Some(from_status.as_str()),
+                OrderStatus::InProduction,
+                Some(claims.sub),
+                None,
+                Some(&comment),
+            )
+                .await?;
+            shipment_service::supersede_shipments(&mut tx, order.id).await?;
+            new_status = Some(OrderStatus::InProduction);
+        }
+        DisputeResolution::Closed => {}
+    }
+
+    sqlx::query(
+        "UPDATE disputes
+         SET status = 'RESOLVED', resolution = ?, resolution_note = ?, refund_amount = ?,
+             resolved_by_user_id = ?, resolved_at = CURRENT_TIMESTAMP
+         WHERE id = ?"
+    )
+        .bind(resolution.as_str())
+        .bind(&note)
+        .bind(refund_amount)
+        .bind(claims.sub)
+        .bind(dispute_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    if let Some(to_status) = new_status {
+        order_service::notify_status_change(pool, chat_server, &order, to_status).await;
+    }
+    let mut message = format!("The dispute for order #{} has been resolved: {}.", order.id, resolution.as_str());
+    if let Some(amount) = refund_amount {
+        message.push_str(&format!(" Refund amount: {:.2}.", amount));
+    }
+    let vars = [
+        ("dispute_id", dispute_id.to_string()),
+        ("order_id", order.id.to_string()),
+        ("resolution", resolution.as_str().to_string()),
+        ("refund_amount", refund_amount.map(|amount| format!("{:.2}", amount)).unwrap_or_default()),
+        ("note", note.clone().unwrap_or_default()),
+    ];
+    for company_id in [order.buyer_company_id, order.supplier_company_id] {
+        notification_service::notify_company_with_template(pool, chat_server, company_id, NotificationCategory::Dispute, &message, &format!("/disputes/{}", dispute_id), "dispute_resolved", &vars).await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,356 @@
+// src/services/document_service.rs
+// 订单正式文件：授标后生成PO PDF，保存到不对外公开的 ./documents 目录，生成后不再修改
+use crate::{
+    errors::AppError,
+    models::{
+        document::{Document, DOCUMENT_TYPE_PURCHASE_ORDER},
+        user::Claims,
+    },
+    services::order_service,
+    utils::pdf_utils::PdfBuilder,
+};
+use actix_web::web;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{types::Decimal, FromRow, MySqlPool};
+use std::fs;
+
+/// 正式文件的存放目录。注意 ./uploads 是公开静态目录，不能放在那里
+const DOCUMENTS_DIR: &str = "./documents";
+
+/// 生成PO需要的全部信息
+#[derive(Debug, FromRow)]
+pub(crate) struct PurchaseOrderPdfData {
+    pub po_number: String,
+    // 0 是原始订单，变更单生效后递增
+    pub amendment_number: i32,
+    pub created_at: DateTime<Utc>,
+    pub promised_delivery_date: Option<NaiveDate>,
+    pub rfq_title: String,
+    pub rfq_description: Option<String>,
+    pub quantity: i32,
+    pub subtotal_amount: Decimal,
+    pub shipping_amount: Decimal,
+    pub tax_name: Option<String>,
+    pub tax_rate: Decimal,
+    pub tax_amount: Decimal,
+    pub total_amount: Decimal,
+    pub currency: String,
+    pub incoterm: Option<String>,
+    pub incoterm_place: Option<String>,
+    pub lead_time_days: i32,
+    pub quote_notes: Option<String>,
+    pub buyer_name: String,
+    pub buyer_city: Option<String>,
+    pub supplier_name: String,
+    pub supplier_city: Option<String>,
+}
+
+pub(crate) fn render_purchase_order(data: &PurchaseOrderPdfData) -> Result<Vec<u8>, AppError> {
+    let mut pdf = PdfBuilder::new(&format!("Purchase Order {}", data.po_number))?;
+
+    pdf.heading("PURCHASE ORDER", 20.0);
+    pdf.space(2.0);
+    pdf.field("PO Number", &data.po_number, 10.0);
+    if data.amendment_number > 0 {
+        pdf.field("Amendment", &data.amendment_number.to_string(), 10.0);
+    }
+    pdf.field("Order Date", &data.created_at.format("%Y-%m-%d").to_string(), 10.0);
+    pdf.field("Currency", &data.currency, 10.0);
+    pdf.rule();
+
+    pdf.heading("Buyer", 12.0);
+    pdf.paragraph(&data.buyer_name, 10.0);
+    if let Some(city) = &data.buyer_city {
+        pdf.paragraph(city, 10.0);
+    }
+    pdf.space(2.0);
+    pdf.heading("Supplier", 12.0);
+    pdf.paragraph(&data.supplier_name, 10.0);
+    if let Some(city) = &data.supplier_city {
+        pdf.paragraph(city, 10.0);
+    }
+    pdf.rule();
+
+    // 报价是整单货款，单价只用于展示
+    let unit_price = if data.quantity > 0 {
+        (data.subtotal_amount / Decimal::from(data.quantity)).round_dp(4)
+    } else {
+        data.subtotal_amount
+    };
+    pdf.heading("Line Items", 12.0);
+    pdf.row(&[(0.0, "#"), (10.0, "Description"), (95.0, "Qty"), (115.0, "Unit Price"), (145.0, "Amount")], 10.0, true);
+    let description: String = data.rfq_title.chars().take(45).collect();
+    pdf.row(
+        &[
+            (0.0, "1"),
+            (10.0, &description),
+            (95.0, &data.quantity.to_string()),
+            (115.0, &unit_price.to_string()),
+            (145.0, &format!("{:.2}", data.subtotal_amount)),
+        ],
+        10.0,
+        false,
+    );
+    if let Some(desc) = data.rfq_description.as_deref().filter(|d| !d.trim().is_empty()) {
+        pdf.paragraph(desc, 9.0);
+    }
+    pdf.rule();
+    pdf.row(&[(115.0, "Subtotal"), (145.0, &format!("{:.2}", data.subtotal_amount))], 10.0, false);
+    if data.shipping_amount > Decimal::ZERO {
+        pdf.row(&[(115.0, "Shipping"), (145.0, &format!("{:.2}", data.shipping_amount))], 10.0, false);
+    }
+    if data.tax_rate > Decimal::ZERO {
+        let label = format!("{} {}%", data.tax_name.as_deref().unwrap_or("Tax"), data.tax_rate.normalize());
+        pdf.row(&[(115.0, &label), (145.0, &format!("{:.2}", data.tax_amount))], 10.0, false);
+    }
+    pdf.row(&[(115.0, "Total"), (145.0, &format!("{:.2} {}", data.total_amount, data.currency))], 11.0, true);
+    pdf.rule();
+
+    pdf.heading("Terms", 12.0);
+    if let Some(incoterm) = &data.incoterm {
+        let place = data.incoterm_place.as_deref().map(|p| format!(" {}", p)).unwrap_or_default();
+        pdf.paragraph(&format!("Delivery terms: {}{} (Incoterms 2020).", incoterm, place), 10.0);
+    }
+    match data.promised_delivery_date {
+        Some(date) => pdf.paragraph(&format!("Delivery: on or before {}.", date), 10.0),
+        None => {
+            let delivery_by = data.created_at + Duration::days(i64::from(data.lead_time_days));
+            pdf.paragraph(
+                &format!(
+                    "Delivery: within {} days of the order date (on or before {}).",
+                    data.lead_time_days,
+                    delivery_by.format("%Y-%m-%d")
+                ),
+                10.0,
+            );
+        }
+    }
+    if data.amendment_number > 0 {
+        pdf.paragraph(
+            &format!("This amendment supersedes all earlier versions of purchase order {}.", data.po_number),
+            10.0,
+        );
+    }
+    pdf.paragraph("Payment: through the platform checkout; amounts are in the currency stated above.", 10.0);
+    pdf.paragraph("Quality: goods are subject to incoming inspection on receipt. Rejected goods are handled through non-conformance reports.", 10.0);
+    if let Some(notes) = data.quote_notes.as_deref().filter(|n| !n.trim().is_empty()) {
+        pdf.space(2.0);
+        pdf.heading("Supplier Notes", 11.0);
+        pdf.paragraph(notes, 9.0);
+    }
+
+    pdf.finish()
+}
+
+async fn load_purchase_order_data(pool: &MySqlPool, order_id: i32) -> Result<PurchaseOrderPdfData, AppError> {
+    let data = sqlx::query_as(
+        "SELECT po.po_number, po.amendment_number, po.created_at, po.promised_delivery_date, r.title as rfq_title, r.description as rfq_description,
+                po.quantity, po.subtotal_amount, po.shipping_amount, po.tax_name, po.tax_rate, po.tax_amount, po.total_amount,
+                po.currency, po.incoterm, po.incoterm_place, q.lead_time_days, q.notes as quote_notes,
+                b.name as buyer_name, b.city as buyer_city, s.name as supplier_name, s.city as supplier_city
+         FROM purchase_orders po
+         JOIN rfqs r ON po.rfq_id = r.id
+         JOIN quotes q ON po.quote_id = q.id
+         JOIN companies b ON po.buyer_company_id = b.id
+         JOIN companies s ON po.supplier_company_id = s.id
+         WHERE po.id = ?"
+    )
+        .bind(order_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Order not found.".to_string()))?;
+    Ok(data)
+}
+
+/// 保存一份新文件并登记。同一订单同类同编号同版本的文件已存在时直接返回已有的（文件不可变）
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn store_document(
+    pool: &MySqlPool,
+    order_id: i32,
+    document_type: &str,
+    document_number: &str,
+    version: i32,
+    filename: &str,
+    content_type: &str,
+    content: Vec<u8>,
+) -> Result<Document, AppError> {
+    let sha256 = hex::encode(Sha256::digest(&content));
+    let size_bytes = i32::try_from(content.len())
+        .map_err(|_| AppError::InternalServerError("Document is too large".to_string()))?;
+    let dir = format!("{}/{}", DOCUMENTS_DIR, order_id);
+    let stored_path = format!("{}/{}-{}", dir, uuid::Uuid::new_v4(), filename);
+
+    let path_clone = stored_path.clone();
+    web::block(move || {
+        fs::create_dir_all(&dir)?;
+        fs::write(path_clone, content)
+    })
+        .await??;
+
+    // 并发生成时由唯一索引兜底，后写入的一方直接使用先写入的记录
+    let result = sqlx::query(
+        "INSERT IGNORE INTO documents (order_id, document_type, document_number, version, filename, stored_path, content_type, size_bytes, sha256)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+        .bind(order_id)
+        .bind(document_type)
+        .bind(document_number)
+        .bind(version)
+        .bind(filename)
+        .bind(&stored_path)
+        .bind(content_type)
+        .bind(size_bytes)
+        .bind(&sha256)
+        .execute(pool)
+        .await;
+    // 登记失败或已有记录时，刚写的文件没人引用，删掉
+    match result {
+        Ok(result) if result.rows_affected() > 0 => {}
+        Ok(_) => {
+            let _ = fs::remove_file(&stored_path);
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&stored_path);
+            return Err(e.into());
+        }
+    }
+
+    let document = sqlx::query_as(
+        "SELECT * FROM documents WHERE order_id = ? AND document_type = ? AND document_number = ? AND version = ?"
+    )
+        .bind(order_id)
+        .bind(document_type)
+        .bind(document_number)
+        .bind(version)
+        .fetch_one(pool)
+        .await?;
+    Ok(document)
+}
+
+async fn find_document(pool: &MySqlPool, order_id: i32, document_type: &str, version: i32) -> Result<Option<Document>, AppError> {
+    let document = sqlx::query_as("SELECT * FROM documents WHERE order_id = ? AND document_type = ? AND version = ?")
+        .bind(order_id)
+        .bind(document_type)
+        .bind(version)
+        .fetch_optional(pool)
+        .await?;
+    Ok(document)
+}
+
+/// 订单当前的PO版本号：原始订单是1，每次变更单生效加1
+async fn current_po_version(pool: &MySqlPool, order_id: i32) -> Result<i32, AppError> {
+    let (amendment_number,): (i32,) = sqlx::query_as("SELECT amendment_number FROM purchase_orders WHERE id = ?")
+        .bind(order_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Order not found.".to_string()))?;
+    Ok(amendment_number + 1)
+}
+
+/// 获取订单当前版本的PO文件，还没有生成过时生成一份。返回文件记录和内容
+pub(crate) async fn ensure_purchase_order_pdf(pool: &MySqlPool, order_id: i32) -> Result<(Document, Vec<u8>), AppError> {
+    let version = current_po_version(pool, order_id).await?;
+    if let Some(document) = find_document(pool, order_id, DOCUMENT_TYPE_PURCHASE_ORDER, version).await? {
+        let content = read_document(&document).await?;
+        return Ok((document, content));
+    }
+
+    let data = load_purchase_order_data(pool, order_id).await?;
+    let content = render_purchase_order(&data)?;
+    let filename = if data.amendment_number > 0 {
+        format!("{}-A{}.pdf", data.po_number, data.amendment_number)
+    } else {
+        format!("{}.pdf", data.po_number)
+    };
+    let document = store_document(
+        pool,
+        order_id,
+        DOCUMENT_TYPE_PURCHASE_ORDER,
+        &data.po_number,
+        data.amendment_number + 1,
+        &filename,
+        "application/pdf",
+        content,
+    )
+        .await?;
+    let content = read_document(&document).await?;
+    Ok((document, content))
+}
+
+/// 读取文件内容并校验哈希，防止文件被改动
+pub(crate) async fn read_document(document: &Document) -> Result<Vec<u8>, AppError> {
+    let path = document.stored_path.clone();
+    let content = web::block(move || fs::read(path)).await??;
+    if hex::encode(Sha256::digest(&content)) != document.sha256 {
+        log::error!("Checksum mismatch for document #{}", document.id);
+        return Err(AppError::InternalServerError("Document integrity check failed.".to_string()));
+    }
+    Ok(content)
+}
+
+pub async fn get_documents_for_order(pool: &MySqlPool, order_id: i32, claims: &Claims) -> Result<Vec<Document>, AppError> {
+    order_service::ensure_order_party(pool, order_id, claims).await?;
+
+    // 旧订单或刚生效的变更单可能还没有当前版本的PO文件，查看时补生成
+    let version = current_po_version(pool, order_id).await?;
+    if find_document(pool, order_id, DOCUMENT_TYPE_PURCHASE_ORDER, version).await?.is_none() {
+        ensure_purchase_order_pdf(pool, order_id).await?;
+    }
+
+    let documents = sqlx::query_as("SELECT * FROM documents WHERE order_id = ? ORDER BY created_at ASC, id ASC")
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(documents)
+}
+
+/// 下载文件，只有订单双方可以下载
+pub async fn download_document(pool: &MySqlPool, document_id: i32, claims: &Claims) -> Result<(Document, Vec<u8>), AppError> {
+    let document: Document = sqlx::query_as("SELECT * FROM documents WHERE id = ?")
+        .bind(document_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Document not found.".to_string()))?;
+    order_service::ensure_order_party(pool, document.order_id, claims).await?;
+
+    let content = read_document(&document).await?;
+    Ok((document, content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_render_purchase_order() {
+        let data = PurchaseOrderPdfData {
+            po_number: "PO-2026-000007".to_string(),
+            amendment_number: 0,
+            created_at: Utc.with_ymd_and_hms(2026, 10, 19, 9, 0, 0).unwrap(),
+            promised_delivery_date: None,
+            rfq_title: "CNC machined aluminium brackets".to_string(),
+            rfq_description: Some("6061-T6, anodized black, per drawing rev B.".to_string()),
+            quantity: 500,
+            subtotal_amount: Decimal::from_str("12500.00").unwrap(),
+            shipping_amount: Decimal::from_str("350.00").unwrap(),
+            tax_name: Some("VAT".to_string()),
+            tax_rate: Decimal::from_str("19.00").unwrap(),
+            tax_amount: Decimal::from_str("2441.50").unwrap(),
+            total_amount: Decimal::from_str("15291.50").unwrap(),
+            currency: "EUR".to_string(),
+            incoterm: Some("FCA".to_string()),
+            incoterm_place: Some("Birmingham".to_string()),
+            lead_time_days: 21,
+            quote_notes: None,
+            buyer_name: "Acme GmbH".to_string(),
+            buyer_city: Some("Berlin".to_string()),
+            supplier_name: "Precision Parts Ltd".to_string(),
+            supplier_city: None,
+        };
+        let bytes = render_purchase_order(&data).unwrap();
+        assert!(bytes.starts_with(b"%PDF"));
+    }
+}
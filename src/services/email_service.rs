@@ -0,0 +1,231 @@
+// src/services/email_service.rs
+// 邮件发件箱：发邮件只是往 email_outbox 里插一行，后台任务按批取出投递。
+// 失败按指数退避重试，重试次数用完或收件地址有问题的转为 DEAD，管理员可以查看并手动重发
+use crate::{
+    errors::AppError,
+    models::email::{EmailAttachment, OutboxEmail, OutboxListParams, RenderedEmail},
+    services::email_transport::{EmailSendError, EmailTransport, OutgoingEmail},
+};
+use chrono::{DateTime, Utc};
+use sqlx::{MySql, MySqlPool, Transaction};
+use std::{env, sync::Arc, time::Duration};
+
+const DEFAULT_OUTBOX_INTERVAL_SECS: u64 = 15;
+const BATCH_SIZE: i64 = 50;
+// 第一次重试等30秒，之后每次翻倍，最多等6小时
+const BASE_RETRY_DELAY_SECS: i64 = 30;
+const MAX_RETRY_DELAY_SECS: i64 = 6 * 60 * 60;
+pub(crate) const MAX_ATTEMPTS: i32 = 8;
+// 取出的邮件先占住一段时间，进程在投递中途挂掉的话过后会被重新投递
+const CLAIM_LEASE_SECS: i64 = 10 * 60;
+const MAX_ERROR_LEN: usize = 1000;
+
+/// 一轮投递的结果
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OutboxRun {
+    pub sent: u32,
+    pub retried: u32,
+    pub dead: u32,
+}
+
+/// 第 attempts 次投递失败后，等多久再试
+pub(crate) fn retry_delay_secs(attempts: i32) -> i64 {
+    let exponent = attempts.clamp(1, 20) - 1;
+    (BASE_RETRY_DELAY_SECS << exponent).min(MAX_RETRY_DELAY_SECS)
+}
+
+/// 放进发件箱，由后台任务投递
+#[cfg_attr(not(test), allow(dead_code))]
+pub async fn queue_email(
+    pool: &MySqlPool,
+    to: &str,
+    email: &RenderedEmail,
+    attachments: Vec<EmailAttachment>,
+) -> Result<i32, AppError> {
+    let mut tx = pool.begin().await?;
+    let email_id = queue_email_after(&mut tx, to, email, attachments, None).await?;
+    tx.commit().await?;
+    Ok(email_id)
+}
+
+/// 在调用方的事务里入队，not_before 之前不投递（免打扰时段）
+pub(crate) async fn queue_email_after(
+    tx: &mut Transaction<'_, MySql>,
+    to: &str,
+    email: &RenderedEmail,
+    attachments: Vec<EmailAttachment>,
+    not_before: Option<DateTime<Utc>>,
+) -> Result<i32, AppError> {
+    let email_id = sqlx::query(
+        "INSERT INTO email_outbox (recipient, subject, body, html_body, next_attempt_at) VALUES (?, ?, ?, ?, COALESCE(?, NOW()))"
+    )
+        .bind(to.trim())
+        .bind(&email.subject)
+        .bind(&email.text)
+        .bind(&email.html)
+        .bind(not_before)
+        .execute(&mut **tx)
+        .await?
+        .last_insert_id() as i32;
+    for attachment in attachments {
+        sqlx::query("INSERT INTO email_outbox_attachments (email_id, filename, content_type, content) VALUES (?, ?, ?, ?)")
+            .bind(email_id)
+            .bind(&attachment.filename)
+            .bind(&attachment.content_type)
+            .bind(&attachment.content)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(email_id)
+}
+
+/// 取出到期的邮件并占住。SKIP LOCKED 让多个实例同时跑也不会重复投递
+async fn claim_due_emails(pool: &MySqlPool) -> Result<Vec<(OutgoingEmail, i32)>, AppError> {
+    let mut tx = pool.begin().await?;
+    let due: Vec<OutboxEmail> = sqlx::query_as(
+        "SELECT * FROM email_outbox WHERE status = 'PENDING' AND next_attempt_at <= NOW()
+         ORDER BY next_attempt_at, id LIMIT ? FOR UPDATE SKIP LOCKED"
+    )
+        .bind(BATCH_SIZE)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let mut claimed = Vec::with_capacity(due.len());
+    for email in due {
+        sqlx::query("UPDATE email_outbox SET attempts = attempts + 1, next_attempt_at = NOW() + INTERVAL ? SECOND WHERE id = ?")
+            .bind(CLAIM_LEASE_SECS)
+            .bind(email.id)
+            .execute(&mut *tx)
+            .await?;
+        let attachments: Vec<EmailAttachment> = sqlx::query_as(
+            "SELECT filename, content_type, content FROM email_outbox_attachments WHERE email_id = ? ORDER BY id"
+        )
+            .bind(email.id)
+            .fetch_all(&mut *tx)
+            .await?;
+        claimed.push((
+            OutgoingEmail { id: email.id, to: email.recipient, subject: email.subject, body: email.body, html: email.html_body, attachments },
+            email.attempts + 1,
+        ));
+    }
+    tx.commit().await?;
+    Ok(claimed)
+}
+
+pub(crate) fn truncate_error(error: &str) -> String {
+    error.chars().take(MAX_ERROR_LEN).collect()
+}
+
+/// 投递一批到期的邮件
+pub async fn deliver_due_emails(pool: &MySqlPool, transport: &dyn EmailTransport) -> Result<OutboxRun, AppError> {
+    let claimed = claim_due_emails(pool).await?;
+    let mut run = OutboxRun::default();
+    for (email, attempts) in claimed {
+        match transport.send(&email).await {
+            Ok(()) => {
+                sqlx::query("UPDATE email_outbox SET status = 'SENT', sent_at = NOW(), last_error = NULL, transport = ? WHERE id = ?")
+                    .bind(transport.name())
+                    .bind(email.id)
+                    .execute(pool)
+                    .await?;
+                run.sent += 1;
+            }
+            Err(EmailSendError::Temporary(error)) if attempts < MAX_ATTEMPTS => {
+                log::warn!("Email #{} failed (attempt {}), will retry: {}", email.id, attempts, error);
+                sqlx::query("UPDATE email_outbox SET next_attempt_at = NOW() + INTERVAL ? SECOND, last_error = ?, transport = ? WHERE id = ?")
+                    .bind(retry_delay_secs(attempts))
+                    .bind(truncate_error(&error))
+                    .bind(transport.name())
+                    .bind(email.id)
+                    .execute(pool)
+                    .await?;
+                run.retried += 1;
+            }
+            Err(EmailSendError::Temporary(error)) | Err(EmailSendError::Permanent(error)) => {
+                log::error!("Email #{} to {} moved to dead letters after {} attempts: {}", email.id, email.to, attempts, error);
+                sqlx::query("UPDATE email_outbox SET status = 'DEAD', last_error = ?, transport = ? WHERE id = ?")
+                    .bind(truncate_error(&error))
+                    .bind(transport.name())
+                    .bind(email.id)
+                    .execute(pool)
+                    .await?;
+                run.dead += 1;
+            }
+        }
+    }
+    Ok(run)
+}
+
+/// 启动发件箱投递任务。间隔可以用 EMAIL_OUTBOX_INTERVAL_SECS 配置
+pub fn spawn_email_outbox(pool: MySqlPool, transport: Arc<dyn EmailTransport>) {
+    let interval_secs = env::var("EMAIL_OUTBOX_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_OUTBOX_INTERVAL_SECS);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            ticker.tick().await;
+            match deliver_due_emails(&pool, transport.as_ref()).await {
+                Ok(run) if run != OutboxRun::default() => log::info!(
+                    "Email outbox via {}: {} sent, {} to retry, {} dead",
+                    transport.name(), run.sent, run.retried, run.dead
+                ),
+                Ok(_) => {}
+                Err(e) => log::error!("Email outbox delivery failed: {:?}", e),
+            }
+        }
+    });
+}
+
+/// 发件箱列表，可按状态筛选，管理员用来看死信
+pub async fn list_outbox(pool: &MySqlPool, params: OutboxListParams) -> Result<Vec<OutboxEmail>, AppError> {
+    let status = params.status.map(|s| s.trim().to_uppercase());
+    if let Some(status) = &status
+        && !["PENDING", "SENT", "DEAD"].contains(&status.as_str())
+    {
+        return Err(AppError::BadRequest("Status must be one of PENDING, SENT or DEAD.".to_string()));
+    }
+    let emails = sqlx::query_as(
+        "SELECT * FROM email_outbox WHERE (? IS NULL OR status = ?) ORDER BY id DESC LIMIT 200"
+    )
+        .bind(&status)
+        .bind(&status)
+        .fetch_all(pool)
+        .await?;
+    Ok(emails)
+}
+
+/// 把死信放回队列，重新开始计算重试次数
+pub async fn retry_email(pool: &MySqlPool, email_id: i32) -> Result<OutboxEmail, AppError> {
+    let result = sqlx::query(
+        "UPDATE email_outbox SET status = 'PENDING', attempts = 0, next_attempt_at = NOW() WHERE id = ? AND status = 'DEAD'"
+    )
+        .bind(email_id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest("Only dead-lettered emails can be retried.".to_string()));
+    }
+    let email = sqlx::query_as("SELECT * FROM email_outbox WHERE id = ?")
+        .bind(email_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(email)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_backs_off() {
+        assert_eq!(retry_delay_secs(1), 30);
+        assert_eq!(retry_delay_secs(2), 60);
+        assert_eq!(retry_delay_secs(5), 480);
+        assert_eq!(retry_delay_secs(MAX_ATTEMPTS), 3840);
+        assert_eq!(retry_delay_secs(30), MAX_RETRY_DELAY_SECS);
+    }
+}
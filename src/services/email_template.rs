@@ -0,0 +1,480 @@
+// src/services/email_template.rs
+// 邮件模板：每个事件一个模板，分中英文，各有纯文本和HTML两个版本。
+// 语法很简单：{{name}} 替换变量（HTML里会转义），{{#name}}...{{/name}} 只在变量有值时输出
+use crate::{
+    errors::AppError,
+    models::email::{EmailTemplateInfo, Locale, RenderedEmail},
+};
+
+struct TemplateText {
+    subject: &'static str,
+    text: &'static str,
+    html: &'static str,
+}
+
+struct EmailTemplate {
+    name: &'static str,
+    description: &'static str,
+    // 预览用的示例数据，同时也是模板用到的全部变量
+    sample: &'static [(&'static str, &'static str)],
+    en: TemplateText,
+    zh_cn: TemplateText,
+}
+
+impl EmailTemplate {
+    fn text(&self, locale: Locale) -> &TemplateText {
+        match locale {
+            Locale::En => &self.en,
+            Locale::ZhCn => &self.zh_cn,
+        }
+    }
+}
+
+const TEMPLATES: &[EmailTemplate] = &[
+    EmailTemplate {
+        name: "notification",
+        description: "Generic notification mirrored from an in-app notification",
+        sample: &[
+            ("subject", "Cancellation requested for order #42"),
+            ("message", "A cancellation has been requested for order #42 ('CNC machined aluminium brackets')."),
+            ("details", "Reason: The project was put on hold by our customer."),
+        ],
+        en: TemplateText {
+            subject: "{{subject}}",
+            text: "Hello,\n\n{{message}}{{#details}}\n\n{{details}}{{/details}}\n\nPlease log in to your SCCP account for details.",
+            html: "<p>Hello,</p><p>{{message}}</p>{{#details}}<blockquote>{{details}}</blockquote>{{/details}}<p>Please log in to your SCCP account for details.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "{{subject}}",
+            text: "您好，\n\n{{message}}{{#details}}\n\n{{details}}{{/details}}\n\n详情请登录 SCCP 账户查看。",
+            html: "<p>您好，</p><p>{{message}}</p>{{#details}}<blockquote>{{details}}</blockquote>{{/details}}<p>详情请登录 SCCP 账户查看。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "quote_received",
+        description: "Buyer: a supplier submitted a quote on their RFQ",
+        sample: &[("rfq_title", "CNC machined aluminium brackets")],
+        en: TemplateText {
+            subject: "New Quote Received: {{rfq_title}}",
+            text: "Hello,\n\nA new quote has been submitted for your RFQ '{{rfq_title}}'.\n\nPlease log in to your SCCP account to review it.",
+            html: "<p>Hello,</p><p>A new quote has been submitted for your RFQ <strong>{{rfq_title}}</strong>.</p><p>Please log in to your SCCP account to review it.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "收到新报价：{{rfq_title}}",
+            text: "您好，\n\n您的询价“{{rfq_title}}”收到了一份新报价。\n\n请登录 SCCP 账户查看。",
+            html: "<p>您好，</p><p>您的询价<strong>“{{rfq_title}}”</strong>收到了一份新报价。</p><p>请登录 SCCP 账户查看。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "quote_accepted",
+        description: "Supplier: their quote won and a purchase order was created",
+        sample: &[("rfq_title", "CNC machined aluminium brackets"), ("po_number", "PO-2026-0042"), ("has_attachment", "yes")],
+        en: TemplateText {
+            subject: "Your Quote for '{{rfq_title}}' has been Accepted!",
+            text: "Congratulations! Your quote has been accepted and Purchase Order {{po_number}} has been generated.{{#has_attachment}} The purchase order document is attached.{{/has_attachment}} Please log in to view your orders.",
+            html: "<p>Congratulations! Your quote for <strong>{{rfq_title}}</strong> has been accepted and Purchase Order <strong>{{po_number}}</strong> has been generated.</p>{{#has_attachment}}<p>The purchase order document is attached.</p>{{/has_attachment}}<p>Please log in to view your orders.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "您对“{{rfq_title}}”的报价已中标",
+            text: "恭喜！您的报价已被接受，已生成采购订单 {{po_number}}。{{#has_attachment}}采购订单文件见附件。{{/has_attachment}}请登录查看您的订单。",
+            html: "<p>恭喜！您对<strong>“{{rfq_title}}”</strong>的报价已被接受，已生成采购订单 <strong>{{po_number}}</strong>。</p>{{#has_attachment}}<p>采购订单文件见附件。</p>{{/has_attachment}}<p>请登录查看您的订单。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "quote_not_selected",
+        description: "Supplier: the RFQ was awarded to someone else",
+        sample: &[
+            ("rfq_title", "CNC machined aluminium brackets"),
+            ("reason", "Lead time too long"),
+            ("price_feedback", "Your quote was 12% above the winning quote."),
+        ],
+        en: TemplateText {
+            subject: "Update on your quote for '{{rfq_title}}'",
+            text: "Hello,\n\nThank you for quoting on '{{rfq_title}}'. The buyer has awarded this RFQ to another supplier.{{#reason}}\n\nReason given by the buyer: {{reason}}{{/reason}}{{#price_feedback}}\n\nPrice feedback: {{price_feedback}}{{/price_feedback}}\n\nWe look forward to your quotes on future RFQs.",
+            html: "<p>Hello,</p><p>Thank you for quoting on <strong>{{rfq_title}}</strong>. The buyer has awarded this RFQ to another supplier.</p>{{#reason}}<p>Reason given by the buyer: {{reason}}</p>{{/reason}}{{#price_feedback}}<p>Price feedback: {{price_feedback}}</p>{{/price_feedback}}<p>We look forward to your quotes on future RFQs.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "您对“{{rfq_title}}”的报价结果",
+            text: "您好，\n\n感谢您参与“{{rfq_title}}”的报价。采购方已将此询价授予其他供应商。{{#reason}}\n\n采购方给出的原因：{{reason}}{{/reason}}{{#price_feedback}}\n\n价格反馈：{{price_feedback}}{{/price_feedback}}\n\n期待您参与今后的询价。",
+            html: "<p>您好，</p><p>感谢您参与<strong>“{{rfq_title}}”</strong>的报价。采购方已将此询价授予其他供应商。</p>{{#reason}}<p>采购方给出的原因：{{reason}}</p>{{/reason}}{{#price_feedback}}<p>价格反馈：{{price_feedback}}</p>{{/price_feedback}}<p>期待您参与今后的询价。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "order_shipped",
+        description: "Buyer: a shipment was recorded against their order",
+        sample: &[
+            ("order_id", "42"),
+            ("rfq_title", "CNC machined aluminium brackets"),
+            ("quantity", "200"),
+            ("carrier", "DHL"),
+            ("tracking_number", "JD014600003812345678"),
+            ("shipped_total", "600"),
+            ("ordered", "1000"),
+            ("estimated_arrival", "2026-11-02"),
+        ],
+        en: TemplateText {
+            subject: "Shipment for order #{{order_id}}",
+            text: "Hello,\n\n{{quantity}} units of order #{{order_id}} ('{{rfq_title}}') have been shipped via {{carrier}} (tracking number {{tracking_number}}). {{shipped_total}} of {{ordered}} units shipped so far.{{#estimated_arrival}}\n\nEstimated arrival: {{estimated_arrival}}.{{/estimated_arrival}}\n\nPlease log in to your SCCP account for details.",
+            html: "<p>Hello,</p><p>{{quantity}} units of order #{{order_id}} (<strong>{{rfq_title}}</strong>) have been shipped via {{carrier}}.</p><table><tr><td>Tracking number</td><td><strong>{{tracking_number}}</strong></td></tr><tr><td>Shipped so far</td><td>{{shipped_total}} / {{ordered}}</td></tr>{{#estimated_arrival}}<tr><td>Estimated arrival</td><td>{{estimated_arrival}}</td></tr>{{/estimated_arrival}}</table><p>Please log in to your SCCP account for details.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "订单 #{{order_id}} 已发货",
+            text: "您好，\n\n订单 #{{order_id}}（“{{rfq_title}}”）有 {{quantity}} 件已通过 {{carrier}} 发出，运单号 {{tracking_number}}。目前已发货 {{shipped_total}} / {{ordered}} 件。{{#estimated_arrival}}\n\n预计到达：{{estimated_arrival}}。{{/estimated_arrival}}\n\n详情请登录 SCCP 账户查看。",
+            html: "<p>您好，</p><p>订单 #{{order_id}}（<strong>“{{rfq_title}}”</strong>）有 {{quantity}} 件已通过 {{carrier}} 发出。</p><table><tr><td>运单号</td><td><strong>{{tracking_number}}</strong></td></tr><tr><td>已发货</td><td>{{shipped_total}} / {{ordered}}</td></tr>{{#estimated_arrival}}<tr><td>预计到达</td><td>{{estimated_arrival}}</td></tr>{{/estimated_arrival}}</table><p>详情请登录 SCCP 账户查看。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "invoice_issued",
+        description: "Buyer: the supplier issued an invoice on their order",
+        sample: &[
+            ("invoice_number", "INV-2026-0042"),
+            ("order_id", "42"),
+            ("rfq_title", "CNC machined aluminium brackets"),
+            ("amount", "1380.50 EUR"),
+            ("due_date", "2026-11-18"),
+        ],
+        en: TemplateText {
+            subject: "Invoice {{invoice_number}} for order #{{order_id}}",
+            text: "Hello,\n\nInvoice {{invoice_number}} for order #{{order_id}} ('{{rfq_title}}') has been issued.\n\nAmount due: {{amount}}\nDue date: {{due_date}}\n\nThe invoice PDF is available in your SCCP account.",
+            html: "<p>Hello,</p><p>Invoice <strong>{{invoice_number}}</strong> for order #{{order_id}} (<strong>{{rfq_title}}</strong>) has been issued.</p><table><tr><td>Amount due</td><td><strong>{{amount}}</strong></td></tr><tr><td>Due date</td><td>{{due_date}}</td></tr></table><p>The invoice PDF is available in your SCCP account.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "订单 #{{order_id}} 的发票 {{invoice_number}}",
+            text: "您好，\n\n订单 #{{order_id}}（“{{rfq_title}}”）的发票 {{invoice_number}} 已开具。\n\n应付金额：{{amount}}\n付款期限：{{due_date}}\n\n发票 PDF 请登录 SCCP 账户下载。",
+            html: "<p>您好，</p><p>订单 #{{order_id}}（<strong>“{{rfq_title}}”</strong>）的发票 <strong>{{invoice_number}}</strong> 已开具。</p><table><tr><td>应付金额</td><td><strong>{{amount}}</strong></td></tr><tr><td>付款期限</td><td>{{due_date}}</td></tr></table><p>发票 PDF 请登录 SCCP 账户下载。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "invoice_due_soon",
+        description: "Buyer: reminder that an unpaid invoice is due soon",
+        sample: &[("invoice_number", "INV-2026-0042"), ("order_id", "42"), ("amount", "1380.50 EUR"), ("due_date", "2026-11-18")],
+        en: TemplateText {
+            subject: "Invoice {{invoice_number}} is due on {{due_date}}",
+            text: "Hello,\n\nThis is a reminder that invoice {{invoice_number}} for order #{{order_id}} ({{amount}}) is due on {{due_date}}.\n\nPlease log in to your SCCP account to pay it.",
+            html: "<p>Hello,</p><p>This is a reminder that invoice <strong>{{invoice_number}}</strong> for order #{{order_id}} ({{amount}}) is due on <strong>{{due_date}}</strong>.</p><p>Please log in to your SCCP account to pay it.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "发票 {{invoice_number}} 将于 {{due_date}} 到期",
+            text: "您好，\n\n提醒您：订单 #{{order_id}} 的发票 {{invoice_number}}（{{amount}}）将于 {{due_date}} 到期。\n\n请登录 SCCP 账户付款。",
+            html: "<p>您好，</p><p>提醒您：订单 #{{order_id}} 的发票 <strong>{{invoice_number}}</strong>（{{amount}}）将于 <strong>{{due_date}}</strong> 到期。</p><p>请登录 SCCP 账户付款。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "invoice_overdue",
+        description: "Buyer and supplier: an invoice is past its due date and still unpaid",
+        sample: &[
+            ("invoice_number", "INV-2026-0042"),
+            ("order_id", "42"),
+            ("amount", "1380.50 EUR"),
+            ("due_date", "2026-11-18"),
+            ("days_overdue", "7"),
+        ],
+        en: TemplateText {
+            subject: "Invoice {{invoice_number}} is overdue",
+            text: "Hello,\n\nInvoice {{invoice_number}} for order #{{order_id}} ({{amount}}) was due on {{due_date}} and is {{days_overdue}} day(s) overdue.\n\nPlease log in to your SCCP account for details.",
+            html: "<p>Hello,</p><p>Invoice <strong>{{invoice_number}}</strong> for order #{{order_id}} ({{amount}}) was due on {{due_date}} and is <strong>{{days_overdue}} day(s) overdue</strong>.</p><p>Please log in to your SCCP account for details.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "发票 {{invoice_number}} 已逾期",
+            text: "您好，\n\n订单 #{{order_id}} 的发票 {{invoice_number}}（{{amount}}）付款期限为 {{due_date}}，已逾期 {{days_overdue}} 天。\n\n详情请登录 SCCP 账户查看。",
+            html: "<p>您好，</p><p>订单 #{{order_id}} 的发票 <strong>{{invoice_number}}</strong>（{{amount}}）付款期限为 {{due_date}}，<strong>已逾期 {{days_overdue}} 天</strong>。</p><p>详情请登录 SCCP 账户查看。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "refund_requested",
+        description: "Supplier: the buyer requested a refund that needs their approval",
+        sample: &[("order_id", "42"), ("amount", "250.00 EUR"), ("reason", "Ten brackets arrived with scratched surfaces.")],
+        en: TemplateText {
+            subject: "Refund requested for order #{{order_id}}",
+            text: "Hello,\n\nThe buyer requested a refund of {{amount}} for order #{{order_id}}.\n\nReason: {{reason}}\n\nPlease log in to your SCCP account to approve or reject it.",
+            html: "<p>Hello,</p><p>The buyer requested a refund of <strong>{{amount}}</strong> for order #{{order_id}}.</p><blockquote>{{reason}}</blockquote><p>Please log in to your SCCP account to approve or reject it.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "订单 #{{order_id}} 的退款申请",
+            text: "您好，\n\n采购方为订单 #{{order_id}} 申请退款 {{amount}}。\n\n原因：{{reason}}\n\n请登录 SCCP 账户批准或拒绝。",
+            html: "<p>您好，</p><p>采购方为订单 #{{order_id}} 申请退款 <strong>{{amount}}</strong>。</p><blockquote>{{reason}}</blockquote><p>请登录 SCCP 账户批准或拒绝。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "refund_rejected",
+        description: "Buyer: the supplier rejected their refund request",
+        sample: &[("order_id", "42"), ("comment", "The scratches are within the agreed cosmetic tolerance.")],
+        en: TemplateText {
+            subject: "Refund request rejected for order #{{order_id}}",
+            text: "Hello,\n\nYour refund request for order #{{order_id}} was rejected by the supplier.{{#comment}}\n\nComment: {{comment}}{{/comment}}\n\nYou can open a dispute from your SCCP account if you disagree.",
+            html: "<p>Hello,</p><p>Your refund request for order #{{order_id}} was rejected by the supplier.</p>{{#comment}}<blockquote>{{comment}}</blockquote>{{/comment}}<p>You can open a dispute from your SCCP account if you disagree.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "订单 #{{order_id}} 的退款申请被拒绝",
+            text: "您好，\n\n供应商拒绝了您对订单 #{{order_id}} 的退款申请。{{#comment}}\n\n说明：{{comment}}{{/comment}}\n\n如有异议，可以在 SCCP 账户中发起争议。",
+            html: "<p>您好，</p><p>供应商拒绝了您对订单 #{{order_id}} 的退款申请。</p>{{#comment}}<blockquote>{{comment}}</blockquote>{{/comment}}<p>如有异议，可以在 SCCP 账户中发起争议。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "refund_issued",
+        description: "Buyer and supplier: a refund was submitted to the payment provider",
+        sample: &[("order_id", "42"), ("amount", "250.00 EUR"), ("pending", "yes")],
+        en: TemplateText {
+            subject: "Refund issued for order #{{order_id}}",
+            text: "Hello,\n\nA refund of {{amount}} for order #{{order_id}} has been issued.{{#pending}} The payment provider is still processing it.{{/pending}}\n\nPlease log in to your SCCP account for details.",
+            html: "<p>Hello,</p><p>A refund of <strong>{{amount}}</strong> for order #{{order_id}} has been issued.{{#pending}} The payment provider is still processing it.{{/pending}}</p><p>Please log in to your SCCP account for details.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "订单 #{{order_id}} 已退款",
+            text: "您好，\n\n订单 #{{order_id}} 的退款 {{amount}} 已发起。{{#pending}}支付渠道仍在处理中。{{/pending}}\n\n详情请登录 SCCP 账户查看。",
+            html: "<p>您好，</p><p>订单 #{{order_id}} 的退款 <strong>{{amount}}</strong> 已发起。{{#pending}}支付渠道仍在处理中。{{/pending}}</p><p>详情请登录 SCCP 账户查看。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "cancellation_requested",
+        description: "Counterparty: the other side asked to cancel the order",
+        sample: &[("order_id", "42"), ("rfq_title", "CNC machined aluminium brackets"), ("reason", "The project was put on hold by our customer.")],
+        en: TemplateText {
+            subject: "Cancellation requested for order #{{order_id}}",
+            text: "Hello,\n\nA cancellation has been requested for order #{{order_id}} ('{{rfq_title}}').\n\nReason: {{reason}}\n\nPlease log in to your SCCP account to accept or reject the request.",
+            html: "<p>Hello,</p><p>A cancellation has been requested for order #{{order_id}} (<strong>{{rfq_title}}</strong>).</p><blockquote>{{reason}}</blockquote><p>Please log in to your SCCP account to accept or reject the request.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "订单 #{{order_id}} 的取消申请",
+            text: "您好，\n\n对方申请取消订单 #{{order_id}}（“{{rfq_title}}”）。\n\n原因：{{reason}}\n\n请登录 SCCP 账户同意或拒绝。",
+            html: "<p>您好，</p><p>对方申请取消订单 #{{order_id}}（<strong>“{{rfq_title}}”</strong>）。</p><blockquote>{{reason}}</blockquote><p>请登录 SCCP 账户同意或拒绝。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "cancellation_rejected",
+        description: "Requesting party: their cancellation request was rejected",
+        sample: &[("order_id", "42"), ("comment", "Production has already started; we can reduce the quantity instead.")],
+        en: TemplateText {
+            subject: "Cancellation rejected for order #{{order_id}}",
+            text: "Hello,\n\nYour cancellation request for order #{{order_id}} was rejected.{{#comment}}\n\nComment: {{comment}}{{/comment}}\n\nYou can open a dispute from your SCCP account if you disagree.",
+            html: "<p>Hello,</p><p>Your cancellation request for order #{{order_id}} was rejected.</p>{{#comment}}<blockquote>{{comment}}</blockquote>{{/comment}}<p>You can open a dispute from your SCCP account if you disagree.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "订单 #{{order_id}} 的取消申请被拒绝",
+            text: "您好，\n\n您对订单 #{{order_id}} 的取消申请被拒绝。{{#comment}}\n\n说明：{{comment}}{{/comment}}\n\n如有异议，可以在 SCCP 账户中发起争议。",
+            html: "<p>您好，</p><p>您对订单 #{{order_id}} 的取消申请被拒绝。</p>{{#comment}}<blockquote>{{comment}}</blockquote>{{/comment}}<p>如有异议，可以在 SCCP 账户中发起争议。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "dispute_opened",
+        description: "Counterparty: a dispute was opened on the order",
+        sample: &[("order_id", "42"), ("rfq_title", "CNC machined aluminium brackets"), ("reason", "Ten brackets arrived with scratched surfaces.")],
+        en: TemplateText {
+            subject: "Dispute opened for order #{{order_id}}",
+            text: "Hello,\n\nA dispute has been opened for order #{{order_id}} ('{{rfq_title}}').\n\nReason: {{reason}}\n\nPlease log in to your SCCP account to respond. A platform administrator will review the case.",
+            html: "<p>Hello,</p><p>A dispute has been opened for order #{{order_id}} (<strong>{{rfq_title}}</strong>).</p><blockquote>{{reason}}</blockquote><p>Please log in to your SCCP account to respond. A platform administrator will review the case.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "订单 #{{order_id}} 发起了争议",
+            text: "您好，\n\n对方就订单 #{{order_id}}（“{{rfq_title}}”）发起了争议。\n\n原因：{{reason}}\n\n请登录 SCCP 账户回复，平台管理员会介入处理。",
+            html: "<p>您好，</p><p>对方就订单 #{{order_id}}（<strong>“{{rfq_title}}”</strong>）发起了争议。</p><blockquote>{{reason}}</blockquote><p>请登录 SCCP 账户回复，平台管理员会介入处理。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "dispute_message",
+        description: "Dispute parties: someone posted a new message in the dispute",
+        sample: &[("dispute_id", "7"), ("order_id", "42")],
+        en: TemplateText {
+            subject: "New message in dispute #{{dispute_id}}",
+            text: "Hello,\n\nThere is a new message in dispute #{{dispute_id}} for order #{{order_id}}.\n\nPlease log in to your SCCP account to read it.",
+            html: "<p>Hello,</p><p>There is a new message in dispute #{{dispute_id}} for order #{{order_id}}.</p><p>Please log in to your SCCP account to read it.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "争议 #{{dispute_id}} 有新消息",
+            text: "您好，\n\n订单 #{{order_id}} 的争议 #{{dispute_id}} 有一条新消息。\n\n请登录 SCCP 账户查看。",
+            html: "<p>您好，</p><p>订单 #{{order_id}} 的争议 #{{dispute_id}} 有一条新消息。</p><p>请登录 SCCP 账户查看。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "dispute_resolved",
+        description: "Buyer and supplier: an administrator resolved the dispute",
+        sample: &[
+            ("dispute_id", "7"),
+            ("order_id", "42"),
+            ("resolution", "REFUND"),
+            ("refund_amount", "250.00"),
+            ("note", "Partial refund for the ten scratched brackets."),
+        ],
+        en: TemplateText {
+            subject: "Dispute #{{dispute_id}} resolved",
+            text: "Hello,\n\nThe dispute for order #{{order_id}} has been resolved: {{resolution}}.{{#refund_amount}}\n\nRefund amount: {{refund_amount}}{{/refund_amount}}{{#note}}\n\nNote: {{note}}{{/note}}\n\nPlease log in to your SCCP account for details.",
+            html: "<p>Hello,</p><p>The dispute for order #{{order_id}} has been resolved: <strong>{{resolution}}</strong>.</p>{{#refund_amount}}<p>Refund amount: {{refund_amount}}</p>{{/refund_amount}}{{#note}}<blockquote>{{note}}</blockquote>{{/note}}<p>Please log in to your SCCP account for details.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "争议 #{{dispute_id}} 已处理",
+            text: "您好，\n\n订单 #{{order_id}} 的争议已处理，结果：{{resolution}}。{{#refund_amount}}\n\n退款金额：{{refund_amount}}{{/refund_amount}}{{#note}}\n\n备注：{{note}}{{/note}}\n\n详情请登录 SCCP 账户查看。",
+            html: "<p>您好，</p><p>订单 #{{order_id}} 的争议已处理，结果：<strong>{{resolution}}</strong>。</p>{{#refund_amount}}<p>退款金额：{{refund_amount}}</p>{{/refund_amount}}{{#note}}<blockquote>{{note}}</blockquote>{{/note}}<p>详情请登录 SCCP 账户查看。</p>",
+        },
+    },
+    EmailTemplate {
+        name: "digest",
+        description: "Daily summary of notifications the user moved to the digest channel",
+        sample: &[
+            ("count", "2"),
+            ("items", "- [ORDER] 200 units of order #42 ('CNC machined aluminium brackets') have been shipped via DHL.\n- [RFQ] A supplier revised their quote for 'CNC machined aluminium brackets' (revision 2)"),
+        ],
+        en: TemplateText {
+            subject: "Your SCCP digest: {{count}} updates",
+            text: "Hello,\n\nHere is what happened since your last digest:\n\n{{items}}\n\nPlease log in to your SCCP account for details.",
+            html: "<p>Hello,</p><p>Here is what happened since your last digest:</p><p>{{items}}</p><p>Please log in to your SCCP account for details.</p>",
+        },
+        zh_cn: TemplateText {
+            subject: "SCCP 通知摘要：{{count}} 条更新",
+            text: "您好，\n\n以下是自上次摘要以来的更新：\n\n{{items}}\n\n详情请登录 SCCP 账户查看。",
+            html: "<p>您好，</p><p>以下是自上次摘要以来的更新：</p><p>{{items}}</p><p>详情请登录 SCCP 账户查看。</p>",
+        },
+    },
+];
+
+fn footer(locale: Locale) -> &'static str {
+    match locale {
+        Locale::En => "This is an automated message from SCCP. Please do not reply to this email.",
+        Locale::ZhCn => "此邮件由 SCCP 平台自动发送，请勿直接回复。",
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            '\n' => escaped.push_str("<br>"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn template_error(message: String) -> AppError {
+    AppError::InternalServerError(message)
+}
+
+fn render_str(template: &str, vars: &[(&str, String)], html: bool) -> Result<String, AppError> {
+    let lookup = |name: &str| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.as_str());
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or_else(|| template_error("Unclosed tag in email template".to_string()))?;
+        let tag = after[..end].trim();
+        rest = &after[end + 2..];
+
+        if let Some(name) = tag.strip_prefix('#') {
+            let close = format!("{{{{/{}}}}}", name);
+            let block_end = rest.find(&close)
+                .ok_or_else(|| template_error(format!("Unclosed section '{}' in email template", name)))?;
+            let block = &rest[..block_end];
+            rest = &rest[block_end + close.len()..];
+            if lookup(name).is_some_and(|value| !value.is_empty()) {
+                out.push_str(&render_str(block, vars, html)?);
+            }
+        } else {
+            let value = lookup(tag).ok_or_else(|| template_error(format!("Missing email template variable '{}'", tag)))?;
+            if html {
+                out.push_str(&escape_html(value));
+            } else {
+                out.push_str(value);
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn find_template(name: &str) -> Result<&'static EmailTemplate, AppError> {
+    TEMPLATES.iter()
+        .find(|template| template.name == name)
+        .ok_or_else(|| AppError::BadRequest(format!("Unknown email template '{}'.", name)))
+}
+
+/// 按收件人的语言渲染模板
+pub(crate) fn render(name: &str, locale: Locale, vars: &[(&str, String)]) -> Result<RenderedEmail, AppError> {
+    let text = find_template(name)?.text(locale);
+    let subject = render_str(text.subject, vars, false)?;
+    let body = render_str(text.text, vars, false)?;
+    let html_body = render_str(text.html, vars, true)?;
+    Ok(RenderedEmail {
+        text: format!("{}\n\n--\n{}", body, footer(locale)),
+        html: format!(
+            "<!DOCTYPE html><html lang=\"{}\"><head><meta charset=\"utf-8\"><title>{}</title></head>\
+             <body style=\"font-family: Arial, sans-serif; color: #222; line-height: 1.5;\">{}\
+             <hr style=\"border: none; border-top: 1px solid #ddd;\"><p style=\"color: #888; font-size: 12px;\">{}</p></body></html>",
+            locale.as_str(), escape_html(&subject), html_body, footer(locale)
+        ),
+        subject,
+    })
+}
+
+pub fn list_templates() -> Vec<EmailTemplateInfo> {
+    TEMPLATES.iter()
+        .map(|template| EmailTemplateInfo {
+            name: template.name,
+            description: template.description,
+            variables: template.sample.iter().map(|(key, _)| *key).collect(),
+            locales: Locale::ALL.iter().map(Locale::as_str).collect(),
+        })
+        .collect()
+}
+
+/// 管理员预览：用模板自带的示例数据渲染
+pub fn preview_template(name: &str, locale: Option<String>) -> Result<RenderedEmail, AppError> {
+    let locale = match locale {
+        Some(locale) => Locale::parse(&locale)
+            .ok_or_else(|| AppError::BadRequest(format!("Unsupported locale '{}'.", locale)))?,
+        None => Locale::default(),
+    };
+    let template = find_template(name)?;
+    let vars: Vec<(&str, String)> = template.sample.iter().map(|(key, value)| (*key, value.to_string())).collect();
+    render(name, locale, &vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&'static str, &str)]) -> Vec<(&'static str, String)> {
+        pairs.iter().map(|(key, value)| (*key, value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_render_str_sections_and_escaping() {
+        let template = "Hi {{name}}.{{#note}} Note: {{note}}{{/note}}";
+        assert_eq!(render_str(template, &vars(&[("name", "Ann"), ("note", "")]), false).unwrap(), "Hi Ann.");
+        assert_eq!(
+            render_str(template, &vars(&[("name", "<b>"), ("note", "a & b")]), true).unwrap(),
+            "Hi &lt;b&gt;. Note: a &amp; b"
+        );
+        assert!(render_str("Hi {{name}}", &[], false).is_err());
+        assert!(render_str("{{#open}}never closed", &vars(&[("open", "x")]), false).is_err());
+    }
+
+    #[test]
+    fn test_all_templates_render_in_every_locale() {
+        for info in list_templates() {
+            for locale in Locale::ALL {
+                let email = preview_template(info.name, Some(locale.as_str().to_string())).unwrap();
+                assert!(!email.subject.contains("{{"), "{} {}", info.name, locale.as_str());
+                assert!(!email.text.contains("{{") && !email.html.contains("{{"));
+                assert!(email.html.contains(&format!("lang=\"{}\"", locale.as_str())));
+            }
+        }
+        assert!(preview_template("missing", None).is_err());
+        assert!(preview_template("notification", Some("fr".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_localized_quote_not_selected() {
+        let email = render(
+            "quote_not_selected",
+            Locale::ZhCn,
+            &vars(&[("rfq_title", "Brackets"), ("reason", ""), ("price_feedback", "")]),
+        ).unwrap();
+        assert_eq!(email.subject, "您对“Brackets”的报价结果");
+        assert!(!email.text.contains("原因"));
+    }
+}
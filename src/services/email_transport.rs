@@ -0,0 +1,284 @@
+// src/services/email_transport.rs
+// 邮件通道抽象：发件箱任务只依赖 EmailTransport，线上用SMTP，本地开发写文件/打印到终端，测试用内存通道
+use crate::models::email::EmailAttachment;
+use async_trait::async_trait;
+use lettre::{
+    message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart},
+    transport::smtp::{
+        authentication::Credentials,
+        client::{Tls, TlsParameters},
+    },
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use std::{collections::HashMap, env, path::PathBuf, sync::{Arc, Mutex}};
+
+/// 要投递的一封邮件
+#[derive(Debug, Clone)]
+pub struct OutgoingEmail {
+    pub id: i32,
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    // 有HTML版本时发 multipart/alternative，客户端自己挑
+    pub html: Option<String>,
+    pub attachments: Vec<EmailAttachment>,
+}
+
+/// 投递失败。Permanent（地址不合法、服务器拒收）不再重试，直接进死信
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmailSendError {
+    Temporary(String),
+    Permanent(String),
+}
+
+#[async_trait]
+pub trait EmailTransport: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, email: &OutgoingEmail) -> Result<(), EmailSendError>;
+}
+
+/// 按 EMAIL_TRANSPORT 选择邮件通道：smtp（默认）、file 或 memory
+pub fn transport_from_env() -> Arc<dyn EmailTransport> {
+    match env::var("EMAIL_TRANSPORT").unwrap_or_default().to_lowercase().as_str() {
+        "file" => {
+            let dir = env::var("EMAIL_FILE_DIR").ok().map(PathBuf::from);
+            log::warn!("Using the file email transport; emails are written to {}.", dir.as_ref().map_or("stdout".to_string(), |d| d.display().to_string()));
+            Arc::new(FileEmailTransport::new(dir))
+        }
+        "memory" => {
+            log::warn!("Using the in-memory email transport; no emails will leave this process.");
+            Arc::new(MemoryEmailTransport::default())
+        }
+        _ => Arc::new(SmtpEmailTransport::from_env()),
+    }
+}
+
+fn sender_from_env() -> String {
+    env::var("SMTP_FROM").unwrap_or_else(|_| "SCCP <no-reply@sccp.local>".to_string())
+}
+
+/// 组装MIME邮件，没有HTML和附件时保持纯文本邮件
+fn build_message(from: &Mailbox, email: &OutgoingEmail) -> Result<Message, EmailSendError> {
+    let to: Mailbox = email.to.parse()
+        .map_err(|_| EmailSendError::Permanent(format!("Invalid recipient address '{}'", email.to)))?;
+    let builder = Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(email.subject.clone());
+
+    let content = email.html.as_ref()
+        .map(|html| MultiPart::alternative_plain_html(email.body.clone(), html.clone()));
+    match (content, email.attachments.is_empty()) {
+        (None, true) => builder.body(email.body.clone()),
+        (Some(content), true) => builder.multipart(content),
+        (content, false) => {
+            let mut multipart = match content {
+                Some(content) => MultiPart::mixed().multipart(content),
+                None => MultiPart::mixed().singlepart(SinglePart::plain(email.body.clone())),
+            };
+            for attachment in &email.attachments {
+                let content_type = ContentType::parse(&attachment.content_type)
+                    .map_err(|_| EmailSendError::Permanent(format!("Invalid attachment content type '{}'", attachment.content_type)))?;
+                multipart = multipart.singlepart(Attachment::new(attachment.filename.clone()).body(attachment.content.clone(), content_type));
+            }
+            builder.multipart(multipart)
+        }
+    }
+        .map_err(|e| EmailSendError::Permanent(format!("Failed to build email: {}", e)))
+}
+
+// ---------------- SMTP ----------------
+
+struct SmtpConfig {
+    from: Mailbox,
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+/// SMTP 配置缺失时不在启动时崩溃，投递时返回临时错误，邮件留在发件箱里等配置好再发
+pub struct SmtpEmailTransport {
+    config: Result<SmtpConfig, String>,
+}
+
+impl SmtpEmailTransport {
+    pub fn from_env() -> Self {
+        let config = Self::config_from_env();
+        if let Err(e) = &config {
+            log::error!("SMTP email transport is not configured: {}", e);
+        }
+        Self { config }
+    }
+
+    fn config_from_env() -> Result<SmtpConfig, String> {
+        let smtp_env = |name: &str| env::var(name).map_err(|_| format!("{} must be set", name));
+        let from = smtp_env("SMTP_FROM")?
+            .parse::<Mailbox>()
+            .map_err(|_| "SMTP_FROM is not a valid email address".to_string())?;
+        let smtp_user = smtp_env("SMTP_USER")?;
+        let smtp_pass = smtp_env("SMTP_PASS")?;
+        let smtp_host = smtp_env("SMTP_HOST")?;
+        let smtp_port = smtp_env("SMTP_PORT")?
+            .parse::<u16>()
+            .map_err(|_| "SMTP_PORT must be a valid number".to_string())?;
+
+        let tls_parameters = TlsParameters::new(smtp_host.clone())
+            .map_err(|e| format!("Failed to create TLS parameters: {}", e))?;
+        let mailer = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&smtp_host)
+            .port(smtp_port)
+            .credentials(Credentials::new(smtp_user, smtp_pass))
+            .tls(Tls::Opportunistic(tls_parameters))
+            .build();
+        Ok(SmtpConfig { from, mailer })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpEmailTransport {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    async fn send(&self, email: &OutgoingEmail) -> Result<(), EmailSendError> {
+        let config = self.config.as_ref().map_err(|e| EmailSendError::Temporary(e.clone()))?;
+        let message = build_message(&config.from, email)?;
+        config.mailer.send(message).await.map(|_| ()).map_err(|e| {
+            // 5xx 是服务器明确拒收，重试也没用
+            if e.is_permanent() {
+                EmailSendError::Permanent(e.to_string())
+            } else {
+                EmailSendError::Temporary(e.to_string())
+            }
+        })
+    }
+}
+
+// ---------------- 文件/终端（本地开发） ----------------
+
+/// 把完整的邮件写成 .eml 文件，没配置目录时打印到终端
+pub struct FileEmailTransport {
+    dir: Option<PathBuf>,
+    from: Mailbox,
+}
+
+impl FileEmailTransport {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        let from = sender_from_env()
+            .parse()
+            .unwrap_or_else(|_| "no-reply@sccp.local".parse().expect("static address is valid"));
+        Self { dir, from }
+    }
+}
+
+#[async_trait]
+impl EmailTransport for FileEmailTransport {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn send(&self, email: &OutgoingEmail) -> Result<(), EmailSendError> {
+        let message = build_message(&self.from, email)?;
+        match &self.dir {
+            Some(dir) => {
+                tokio::fs::create_dir_all(dir).await
+                    .map_err(|e| EmailSendError::Temporary(format!("Failed to create {}: {}", dir.display(), e)))?;
+                let path = dir.join(format!("email-{}.eml", email.id));
+                tokio::fs::write(&path, message.formatted()).await
+                    .map_err(|e| EmailSendError::Temporary(format!("Failed to write {}: {}", path.display(), e)))?;
+            }
+            None => println!("----- email #{} -----\n{}", email.id, String::from_utf8_lossy(&message.formatted())),
+        }
+        Ok(())
+    }
+}
+
+// ---------------- 内存（测试） ----------------
+
+/// 只把邮件记在内存里，测试可以检查发了什么，也可以让发给某个地址的接下来几次投递失败
+#[derive(Default)]
+pub struct MemoryEmailTransport {
+    sent: Mutex<Vec<OutgoingEmail>>,
+    failures: Mutex<HashMap<String, Vec<EmailSendError>>>,
+}
+
+// 测试里用来检查和控制投递结果
+#[cfg_attr(not(test), allow(dead_code))]
+impl MemoryEmailTransport {
+    pub fn sent(&self) -> Vec<OutgoingEmail> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// 接下来发给 to 的投递按顺序返回这些错误
+    pub fn fail_next(&self, to: &str, errors: Vec<EmailSendError>) {
+        self.failures.lock().unwrap().entry(to.to_string()).or_default().extend(errors);
+    }
+}
+
+#[async_trait]
+impl EmailTransport for MemoryEmailTransport {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn send(&self, email: &OutgoingEmail) -> Result<(), EmailSendError> {
+        {
+            let mut failures = self.failures.lock().unwrap();
+            if let Some(errors) = failures.get_mut(&email.to)
+                && !errors.is_empty()
+            {
+                return Err(errors.remove(0));
+            }
+        }
+        self.sent.lock().unwrap().push(email.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email(to: &str) -> OutgoingEmail {
+        OutgoingEmail {
+            id: 7,
+            to: to.to_string(),
+            subject: "Hello".to_string(),
+            body: "Body".to_string(),
+            html: Some("<p>Body</p>".to_string()),
+            attachments: vec![EmailAttachment {
+                filename: "po.pdf".to_string(),
+                content_type: "application/pdf".to_string(),
+                content: b"%PDF".to_vec(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_build_message() {
+        let from: Mailbox = "no-reply@sccp.local".parse().unwrap();
+        let message = build_message(&from, &email("buyer@example.com")).unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("To: buyer@example.com"));
+        assert!(formatted.contains("po.pdf"));
+        assert!(formatted.contains("multipart/alternative") && formatted.contains("<p>Body</p>"));
+        assert!(matches!(build_message(&from, &email("not an address")), Err(EmailSendError::Permanent(_))));
+    }
+
+    #[actix_web::test]
+    async fn test_memory_transport_failures() {
+        let transport = MemoryEmailTransport::default();
+        transport.fail_next("a@example.com", vec![EmailSendError::Temporary("down".to_string())]);
+        assert!(transport.send(&email("a@example.com")).await.is_err());
+        assert!(transport.send(&email("a@example.com")).await.is_ok());
+        assert_eq!(transport.sent().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_file_transport_writes_eml() {
+        let dir = std::env::temp_dir().join(format!("sccp-mail-{}", uuid::Uuid::new_v4().simple()));
+        let transport = FileEmailTransport::new(Some(dir.clone()));
+        transport.send(&email("a@example.com")).await.unwrap();
+        let written = std::fs::read_to_string(dir.join("email-7.eml")).unwrap();
+        assert!(written.contains("Subject: Hello"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+// src/services/fx_service.rs
+use crate::{
+    errors::AppError,
+    models::{fx::{deserialize_rates, FxRate, FxTable}, money::Currency},
+};
+use sqlx::{types::Decimal, MySqlExecutor, MySqlPool};
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+
+pub async fn list_rates(pool: &MySqlPool) -> Result<Vec<FxRate>, AppError> {
+    let rates = sqlx::query_as("SELECT currency, usd_rate, source, updated_at FROM fx_rates ORDER BY currency")
+        .fetch_all(pool)
+        .await?;
+    Ok(rates)
+}
+
+/// 从数据库加载完整汇率表，也可以在事务里调用
+pub async fn load_fx_table<'e>(executor: impl MySqlExecutor<'e>) -> Result<FxTable, AppError> {
+    let rows: Vec<(String, Decimal)> = sqlx::query_as("SELECT currency, usd_rate FROM fx_rates")
+        .fetch_all(executor)
+        .await?;
+
+    let mut rates = HashMap::new();
+    for (code, rate) in rows {
+        match Currency::from_str(&code) {
+            Ok(currency) => {
+                rates.insert(currency, rate);
+            }
+            Err(_) => log::warn!("Ignoring FX rate for unsupported currency {}", code),
+        }
+    }
+    Ok(FxTable::new(rates))
+}
+
+/// 批量写入汇率（管理员接口和启动时的汇率文件共用）
+pub async fn upsert_rates(
+    pool: &MySqlPool,
+    rates: &HashMap<Currency, Decimal>,
+    source: &str,
+) -> Result<u64, AppError> {
+    if rates.get(&Currency::USD).is_some_and(|r| *r != Decimal::ONE) {
+        return Err(AppError::BadRequest("USD is the base currency and its rate must be 1.".to_string()));
+    }
+    if rates.values().any(|r| *r <= Decimal::ZERO) {
+        return Err(AppError::BadRequest("FX rates must be greater than zero.".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+    for (currency, rate) in rates {
+        sqlx::query(
+            "INSERT INTO fx_rates (currency, usd_rate, source) VALUES (?, ?, ?)
+             ON DUPLICATE KEY UPDATE usd_rate = VALUES(usd_rate), source = VALUES(source)",
+        )
+            .bind(currency.code())
+            .bind(rate)
+            .bind(source)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(rates.len() as u64)
+}
+
+/// 启动时从 FX_RATES_FILE 指向的JSON文件加载汇率，格式: { "CNY": "0.1389", "EUR": "1.085" }
+pub async fn load_rates_from_file(pool: &MySqlPool, path: &str) -> Result<u64, AppError> {
+    let content = std::fs::read_to_string(path)?;
+    let mut deserializer = serde_json::Deserializer::from_str(&content);
+    let rates = deserialize_rates(&mut deserializer)
+        .map_err(|e| AppError::BadRequest(format!("Invalid FX rates file {}: {}", path, e)))?;
+
+    upsert_rates(pool, &rates, "FILE").await
+}
+
+/// 查询公司的报表币种，分析和报价比较都折算到这个币种
+pub async fn get_reporting_currency(pool: &MySqlPool, company_id: i32) -> Result<Currency, AppError> {
+    let (code,): (String,) = sqlx::query_as("SELECT reporting_currency FROM companies WHERE id = ?")
+        .bind(company_id)
+        .fetch_one(pool)
+        .await?;
+    Currency::from_str(&code)
+}
+
+/// 把按币种分组的金额汇总为目标币种下的总额
+pub fn sum_in_currency(
+    fx: &FxTable,
+    amounts: &[(String, Decimal)],
+    target: Currency,
+) -> Result<Decimal, AppError> {
+    let mut total = Decimal::ZERO;
+    for (code, amount) in amounts {
+        total += fx.convert(*amount, Currency::from_str(code)?, target)?;
+    }
+    Ok(total)
+}
+
+/// 同上，但缺汇率的币种不会让整个请求失败：这部分金额不计入合计，币种记到 skipped 里交给调用方提示
+pub fn sum_convertible(
+    fx: &FxTable,
+    amounts: &[(String, Decimal)],
+    target: Currency,
+    skipped: &mut BTreeSet<String>,
+) -> Decimal {
+    let mut total = Decimal::ZERO;
+    for (code, amount) in amounts {
+        match Currency::from_str(code).and_then(|from| fx.convert(*amount, from, target)) {
+            Ok(converted) => total += converted,
+            Err(_) if amount.is_zero() => {}
+            Err(_) => {
+                skipped.insert(code.clone());
+            }
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sum_convertible_skips_missing_rates() {
+        let fx = FxTable::new(HashMap::from([(Currency::EUR, Decimal::new(110, 2))]));
+        let amounts = vec![
+            ("USD".to_string(), Decimal::new(1000, 2)),
+            ("EUR".to_string(), Decimal::new(1000, 2)),
+            ("CNY".to_string(), Decimal::new(5000, 2)),
+            ("JPY".to_string(), Decimal::ZERO),
+        ];
+        let mut skipped = BTreeSet::new();
+        assert_eq!(sum_convertible(&fx, &amounts, Currency::USD, &mut skipped), Decimal::new(2100, 2));
+        assert_eq!(skipped.into_iter().collect::<Vec<_>>(), vec!["CNY".to_string()]);
+        assert!(sum_in_currency(&fx, &amounts, Currency::USD).is_err());
+    }
+}
@@ -0,0 +1,660 @@
+// src/services/invoice_service.rs
+// 供应商开票：按订单整单或按发货批次开票，公司内连续编号，含税行和付款期限，生成PDF和UBL XML
+use crate::{
+    errors::AppError,
+    models::{
+        document::{Document, DOCUMENT_TYPE_INVOICE_PDF, DOCUMENT_TYPE_INVOICE_UBL},
+        invoice::{CreateInvoiceDto, Invoice, InvoiceDetail, InvoiceLine, InvoiceTaxLine},
+        money::{self, Currency, Money},
+        order::OrderStatus,
+        user::Claims,
+    },
+    services::{chat_server::ChatServer, document_service, notification_service, order_service, payment_provider::PaymentProvider, payment_service},
+    utils::pdf_utils::PdfBuilder,
+};
+use crate::models::notification_preference::NotificationCategory;
+use actix::Addr;
+use chrono::{Duration, NaiveDate, Utc};
+use sqlx::{types::Decimal, FromRow, MySql, MySqlPool, Transaction};
+use std::str::FromStr;
+
+/// 默认付款期限（天）
+const DEFAULT_DUE_DAYS: u32 = 30;
+const MAX_DUE_DAYS: u32 = 365;
+
+/// 开票时用到的订单条款
+#[derive(Debug, FromRow)]
+struct OrderInvoiceTerms {
+    quantity: i32,
+    subtotal_amount: Decimal,
+    shipping_amount: Decimal,
+    tax_name: Option<String>,
+    tax_rate: Decimal,
+    currency: String,
+    incoterm: Option<String>,
+    incoterm_place: Option<String>,
+    payment_status: String,
+    payment_terms_days: Option<i32>,
+    stripe_session_id: Option<String>,
+}
+
+/// 计算好的发票金额
+#[derive(Debug, PartialEq)]
+pub(crate) struct InvoiceAmounts {
+    pub unit_price: Decimal,
+    // 货款行金额
+    pub goods: Decimal,
+    pub shipping: Decimal,
+    // 不含税金额 = 货款 + 运费
+    pub subtotal: Decimal,
+    // (名称, 税率%, 计税金额, 税额)
+    pub taxes: Vec<(String, Decimal, Decimal, Decimal)>,
+    pub tax_total: Decimal,
+    pub total: Decimal,
+}
+
+/// 按数量比例计算本张发票的货款。最后一张发票用订单货款减去已开票货款，避免分摊的舍入误差。
+/// 运费由调用方决定记在哪一张发票上，和货款一起计税
+pub(crate) fn compute_invoice_amounts(
+    order_subtotal: Decimal,
+    order_quantity: i32,
+    invoiced_quantity: i64,
+    invoiced_goods: Decimal,
+    quantity: i32,
+    shipping: Decimal,
+    tax_lines: &[(String, Decimal)],
+) -> Result<InvoiceAmounts, AppError> {
+    let remaining = i64::from(order_quantity) - invoiced_quantity;
+    if quantity <= 0 {
+        return Err(AppError::BadRequest("Invoice quantity must be greater than zero.".to_string()));
+    }
+    if i64::from(quantity) > remaining {
+        return Err(AppError::BadRequest(format!(
+            "Invoice quantity exceeds the {} units not yet invoiced.",
+            remaining.max(0)
+        )));
+    }
+
+    let unit_price = (order_subtotal / Decimal::from(order_quantity)).round_dp(4);
+    let goods = if i64::from(quantity) == remaining {
+        order_subtotal - invoiced_goods
+    } else {
+        money::round_amount(order_subtotal * Decimal::from(quantity) / Decimal::from(order_quantity))
+    };
+    let subtotal = goods + shipping;
+
+    let mut taxes = Vec::with_capacity(tax_lines.len());
+    for (name, rate) in tax_lines {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 50 {
+            return Err(AppError::BadRequest("Tax name must be between 1 and 50 characters.".to_string()));
+        }
+        if *rate < Decimal::ZERO || *rate > Decimal::ONE_HUNDRED || rate.normalize().scale() > 2 {
+            return Err(AppError::BadRequest("Tax rate must be a percentage between 0 and 100 with at most 2 decimals.".to_string()));
+        }
+        let tax = money::round_amount(subtotal * rate / Decimal::ONE_HUNDRED);
+        taxes.push((name.to_string(), *rate, subtotal, tax));
+    }
+    let tax_total: Decimal = taxes.iter().map(|t| t.3).sum();
+
+    Ok(InvoiceAmounts { unit_price, goods, shipping, subtotal, taxes, tax_total, total: subtotal + tax_total })
+}
+
+/// 取下一个发票号，必须在开票事务中调用，序列行会被锁住直到事务结束
+async fn next_invoice_number(tx: &mut Transaction<'_, MySql>, company_id: i32) -> Result<String, AppError> {
+    sqlx::query("INSERT IGNORE INTO invoice_sequences (company_id, next_number) VALUES (?, 1)")
+        .bind(company_id)
+        .execute(&mut **tx)
+        .await?;
+    let (next,): (i32,) = sqlx::query_as("SELECT next_number FROM invoice_sequences WHERE company_id = ? FOR UPDATE")
+        .bind(company_id)
+        .fetch_one(&mut **tx)
+        .await?;
+    sqlx::query("UPDATE invoice_sequences SET next_number = next_number + 1 WHERE company_id = ?")
+        .bind(company_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(format!("INV-{:06}", next))
+}
+
+pub async fn create_invoice(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    provider: &dyn PaymentProvider,
+    order_id: i32,
+    dto: CreateInvoiceDto,
+    claims: &Claims,
+) -> Result<i32, AppError> {
+    let notes = dto.notes.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    if notes.as_ref().is_some_and(|n| n.chars().count() > 1000) {
+        return Err(AppError::BadRequest("Notes must be at most 1000 characters.".to_string()));
+    }
+    let tax_lines: Vec<(String, Decimal)> = dto.tax_lines.into_iter().map(|t| (t.name, t.rate)).collect();
+
+    let mut tx = pool.begin().await?;
+    let order = order_service::lock_order(&mut tx, order_id).await?;
+    if order.supplier_company_id != claims.company_id {
+        return Err(AppError::BadRequest("Order not found or only the supplier can issue invoices.".to_string()));
+    }
+    if order.status == OrderStatus::Cancelled.as_str() {
+        return Err(AppError::BadRequest("A cancelled order cannot be invoiced.".to_string()));
+    }
+
+    let terms: OrderInvoiceTerms = sqlx::query_as(
+        "SELECT quantity, subtotal_amount, shipping_amount, tax_name, tax_rate, currency, incoterm, incoterm_place, payment_status, payment_terms_days,
+                stripe_session_id
+         FROM purchase_orders WHERE id = ?"
+    )
+        .bind(order.id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let currency = terms.currency.clone();
+    // 开票后按发票付款，整单付款的会话还开着的话作废掉，否则买方可能整单和发票各付一次
+    if let Some(session_id) = terms.stripe_session_id.as_deref()
+        && (terms.payment_status == "UNPAID" || terms.payment_status == "FAILED")
+    {
+        payment_service::expire_open_session(provider, session_id).await?;
+        sqlx::query("UPDATE purchase_orders SET stripe_session_id = NULL WHERE id = ?")
+            .bind(order.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    // 账期订单默认按约定的账期天数
+    let due_in_days = dto.due_in_days
+        .or_else(|| terms.payment_terms_days.and_then(|days| u32::try_from(days).ok()))
+        .unwrap_or(DEFAULT_DUE_DAYS);
+    if due_in_days > MAX_DUE_DAYS {
+        return Err(AppError::BadRequest(format!("Payment terms cannot exceed {} days.", MAX_DUE_DAYS)));
+    }
+
+    // 按发货批次开票时数量取该批次的发货数量
+    let quantity = match dto.shipment_id {
+        Some(shipment_id) => {
+            let shipment: Option<(i32, Option<i32>)> = sqlx::query_as(
+                "SELECT s.shipped_quantity, i.id FROM shipments s LEFT JOIN invoices i ON i.shipment_id = s.id
+                 WHERE s.id = ? AND s.order_id = ?"
+            )
+                .bind(shipment_id)
+                .bind(order.id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            let (shipped, invoice_id) = shipment
+                .ok_or_else(|| AppError::BadRequest("Shipment not found for this order.".to_string()))?;
+            if invoice_id.is_some() {
+                return Err(AppError::BadRequest("This shipment has already been invoiced.".to_string()));
+            }
+            if dto.quantity.is_some_and(|q| q != shipped) {
+                return Err(AppError::BadRequest("The invoice quantity must match the shipped quantity.".to_string()));
+            }
+            shipped
+        }
+        None => dto.quantity.unwrap_or(0),
+    };
+
+    let (invoiced_quantity, invoiced_goods, invoiced_shipping): (Option<i64>, Option<Decimal>, Option<Decimal>) = sqlx::query_as(
+        "SELECT CAST(SUM(quantity) AS SIGNED), SUM(subtotal - shipping_total), SUM(shipping_total) FROM invoices WHERE order_id = ?"
+    )
+        .bind(order.id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let invoiced_quantity = invoiced_quantity.unwrap_or(0);
+    // 没有指定数量时开剩余全部数量
+    let quantity = if quantity == 0 && dto.shipment_id.is_none() {
+        (i64::from(terms.quantity) - invoiced_quantity).max(0) as i32
+    } else {
+        quantity
+    };
+    // 运费全部记在第一张发票上；没有填税目时按订单的税率
+    let shipping = (terms.shipping_amount - invoiced_shipping.unwrap_or(Decimal::ZERO)).max(Decimal::ZERO);
+    let tax_lines = if tax_lines.is_empty() && terms.tax_rate > Decimal::ZERO {
+        vec![(terms.tax_name.clone().unwrap_or_else(|| "Tax".to_string()), terms.tax_rate)]
+    } else {
+        tax_lines
+    };
+    let amounts = compute_invoice_amounts(
+        terms.subtotal_amount,
+        terms.quantity,
+        invoiced_quantity,
+        invoiced_goods.unwrap_or(Decimal::ZERO),
+        quantity,
+        shipping,
+        &tax_lines,
+    )?;
+    Money::new(amounts.total, Currency::from_str(&currency)?)?;
+
+    let invoice_number = next_invoice_number(&mut tx, claims.company_id).await?;
+    let issue_date = Utc::now().date_naive();
+    let due_date = issue_date + Duration::days(i64::from(due_in_days));
+    // 订单已经整单付过款的，发票直接记为已付
+    let payment_status = if terms.payment_status == "PAID" { "PAID" } else { "UNPAID" };
+
+    let result = sqlx::query(
+        "INSERT INTO invoices (invoice_number, order_id, shipment_id, supplier_company_id, buyer_company_id, quantity, currency,
+                               subtotal, shipping_total, tax_total, total, issue_date, due_date, notes, payment_status, paid_at, created_by_user_id)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, IF(? = 'PAID', CURRENT_TIMESTAMP, NULL), ?)"
+    )
+        .bind(&invoice_number)
+        .bind(order.id)
+        .bind(dto.shipment_id)
+        .bind(order.supplier_company_id)
+        .bind(order.buyer_company_id)
+        .bind(quantity)
+        .bind(&currency)
+        .bind(amounts.subtotal)
+        .bind(amounts.shipping)
+        .bind(amounts.tax_total)
+        .bind(amounts.total)
+        .bind(issue_date)
+        .bind(due_date)
+        .bind(&notes)
+        .bind(payment_status)
+        .bind(payment_status)
+        .bind(claims.sub)
+        .execute(&mut *tx)
+        .await?;
+    let invoice_id = result.last_insert_id() as i32;
+
+    let description = match dto.shipment_id {
+        Some(shipment_id) => format!("{} (shipment #{})", order.rfq_title, shipment_id),
+        None => order.rfq_title.clone(),
+    };
+    sqlx::query("INSERT INTO invoice_lines (invoice_id, description, quantity, unit_price, line_total) VALUES (?, ?, ?, ?, ?)")
+        .bind(invoice_id)
+        .bind(description.chars().take(500).collect::<String>())
+        .bind(quantity)
+        .bind(amounts.unit_price)
+        .bind(amounts.goods)
+        .execute(&mut *tx)
+        .await?;
+    if amounts.shipping > Decimal::ZERO {
+        let description = match (&terms.incoterm, &terms.incoterm_place) {
+            (Some(incoterm), Some(place)) => format!("Shipping ({} {})", incoterm, place),
+            (Some(incoterm), None) => format!("Shipping ({})", incoterm),
+            _ => "Shipping".to_string(),
+        };
+        sqlx::query("INSERT INTO invoice_lines (invoice_id, description, quantity, unit_price, line_total) VALUES (?, ?, 1, ?, ?)")
+            .bind(invoice_id)
+            .bind(description)
+            .bind(amounts.shipping)
+            .bind(amounts.shipping)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for (name, rate, taxable, tax) in &amounts.taxes {
+        sqlx::query("INSERT INTO invoice_tax_lines (invoice_id, name, rate, taxable_amount, tax_amount) VALUES (?, ?, ?, ?, ?)")
+            .bind(invoice_id)
+            .bind(name)
+            .bind(rate)
+            .bind(taxable)
+            .bind(tax)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    // 文件生成失败不影响开票，查看发票详情时会补生成
+    if let Err(e) = ensure_invoice_documents(pool, invoice_id).await {
+        log::error!("Failed to generate documents for invoice #{}: {:?}", invoice_id, e);
+    }
+
+    let message = format!(
+        "Invoice {} for order #{} has been issued: {:.2} {}, due {}.",
+        invoice_number, order.id, amounts.total, currency, due_date
+    );
+    let vars = [
+        ("invoice_number", invoice_number.clone()),
+        ("order_id", order.id.to_string()),
+        ("rfq_title", order.rfq_title.clone()),
+        ("amount", format!("{:.2} {}", amounts.total, currency)),
+        ("due_date", due_date.to_string()),
+    ];
+    notification_service::notify_company_with_template(pool, chat_server, order.buyer_company_id, NotificationCategory::Payment, &message, "/orders", "invoice_issued", &vars).await;
+
+    Ok(invoice_id)
+}
+
+/// 渲染发票需要的全部信息
+#[derive(Debug, FromRow)]
+pub(crate) struct InvoiceRenderHeader {
+    pub invoice_number: String,
+    pub order_id: i32,
+    pub po_number: Option<String>,
+    pub currency: String,
+    pub subtotal: Decimal,
+    pub tax_total: Decimal,
+    pub total: Decimal,
+    pub issue_date: NaiveDate,
+    pub due_date: NaiveDate,
+    pub notes: Option<String>,
+    pub supplier_name: String,
+    pub supplier_city: Option<String>,
+    pub buyer_name: String,
+    pub buyer_city: Option<String>,
+}
+
+pub(crate) struct InvoiceRenderData {
+    pub header: InvoiceRenderHeader,
+    pub lines: Vec<InvoiceLine>,
+    pub tax_lines: Vec<InvoiceTaxLine>,
+}
+
+pub(crate) fn render_invoice_pdf(data: &InvoiceRenderData) -> Result<Vec<u8>, AppError> {
+    let h = &data.header;
+    let mut pdf = PdfBuilder::new(&format!("Invoice {}", h.invoice_number))?;
+
+    pdf.heading("INVOICE", 20.0);
+    pdf.space(2.0);
+    pdf.field("Invoice Number", &h.invoice_number, 10.0);
+    pdf.field("Issue Date", &h.issue_date.to_string(), 10.0);
+    pdf.field("Due Date", &h.due_date.to_string(), 10.0);
+    pdf.field("Purchase Order", h.po_number.as_deref().unwrap_or("-"), 10.0);
+    pdf.field("Currency", &h.currency, 10.0);
+    pdf.rule();
+
+    pdf.heading("From", 12.0);
+    pdf.paragraph(&h.supplier_name, 10.0);
+    if let Some(city) = &h.supplier_city {
+        pdf.paragraph(city, 10.0);
+    }
+    pdf.space(2.0);
+    pdf.heading("Bill To", 12.0);
+    pdf.paragraph(&h.buyer_name, 10.0);
+    if let Some(city) = &h.buyer_city {
+        pdf.paragraph(city, 10.0);
+    }
+    pdf.rule();
+
+    pdf.row(&[(0.0, "#"), (10.0, "Description"), (95.0, "Qty"), (115.0, "Unit Price"), (145.0, "Amount")], 10.0, true);
+    for (i, line) in data.lines.iter().enumerate() {
+        let description: String = line.description.chars().take(45).collect();
+        pdf.row(
+            &[
+                (0.0, &(i + 1).to_string()),
+                (10.0, &description),
+                (95.0, &line.quantity.to_string()),
+                (115.0, &format!("{:.4}", line.unit_price)),
+                (145.0, &format!("{:.2}", line.line_total)),
+            ],
+            10.0,
+            false,
+        );
+    }
+    pdf.rule();
+    pdf.row(&[(115.0, "Subtotal"), (145.0, &format!("{:.2}", h.subtotal))], 10.0, false);
+    for tax in &data.tax_lines {
+        let label = format!("{} ({:.2}%)", tax.name, tax.rate);
+        pdf.row(&[(95.0, &label), (145.0, &format!("{:.2}", tax.tax_amount))], 10.0, false);
+    }
+    pdf.row(&[(115.0, "Total"), (145.0, &format!("{:.2} {}", h.total, h.currency))], 11.0, true);
+    pdf.rule();
+
+    pdf.paragraph(&format!("Payment due by {}. Please reference {} with your payment.", h.due_date, h.invoice_number), 10.0);
+    if let Some(notes) = &h.notes {
+        pdf.space(2.0);
+        pdf.paragraph(notes, 9.0);
+    }
+
+    pdf.finish()
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// 生成 UBL 2.1 发票 XML
+pub(crate) fn render_invoice_ubl(data: &InvoiceRenderData) -> String {
+    let h = &data.header;
+    let cur = xml_escape(&h.currency);
+    let amount = |value: &Decimal| format!("<cbc:{{}} currencyID=\"{}\">{:.2}</cbc:{{}}>", cur, value);
+    let tag = |name: &str, value: &Decimal| amount(value).replace("{}", name);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<Invoice xmlns=\"urn:oasis:names:specification:ubl:schema:xsd:Invoice-2\" ");
+    xml.push_str("xmlns:cac=\"urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2\" ");
+    xml.push_str("xmlns:cbc=\"urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2\">\n");
+    xml.push_str("  <cbc:UBLVersionID>2.1</cbc:UBLVersionID>\n");
+    xml.push_str(&format!("  <cbc:ID>{}</cbc:ID>\n", xml_escape(&h.invoice_number)));
+    xml.push_str(&format!("  <cbc:IssueDate>{}</cbc:IssueDate>\n", h.issue_date));
+    xml.push_str(&format!("  <cbc:DueDate>{}</cbc:DueDate>\n", h.due_date));
+    xml.push_str("  <cbc:InvoiceTypeCode>380</cbc:InvoiceTypeCode>\n");
+    if let Some(notes) = &h.notes {
+        xml.push_str(&format!("  <cbc:Note>{}</cbc:Note>\n", xml_escape(notes)));
+    }
+    xml.push_str(&format!("  <cbc:DocumentCurrencyCode>{}</cbc:DocumentCurrencyCode>\n", cur));
+    let order_ref = h.po_number.clone().unwrap_or_else(|| h.order_id.to_string());
+    xml.push_str(&format!("  <cac:OrderReference><cbc:ID>{}</cbc:ID></cac:OrderReference>\n", xml_escape(&order_ref)));
+
+    for (role, name, city) in [
+        ("AccountingSupplierParty", &h.supplier_name, &h.supplier_city),
+        ("AccountingCustomerParty", &h.buyer_name, &h.buyer_city),
+    ] {
+        xml.push_str(&format!("  <cac:{}>\n    <cac:Party>\n", role));
+        xml.push_str(&format!("      <cac:PartyName><cbc:Name>{}</cbc:Name></cac:PartyName>\n", xml_escape(name)));
+        if let Some(city) = city {
+            xml.push_str(&format!("      <cac:PostalAddress><cbc:CityName>{}</cbc:CityName></cac:PostalAddress>\n", xml_escape(city)));
+        }
+        xml.push_str(&format!("    </cac:Party>\n  </cac:{}>\n", role));
+    }
+
+    xml.push_str("  <cac:PaymentMeans>\n    <cbc:PaymentMeansCode>30</cbc:PaymentMeansCode>\n");
+    xml.push_str(&format!("    <cbc:PaymentDueDate>{}</cbc:PaymentDueDate>\n  </cac:PaymentMeans>\n", h.due_date));
+
+    xml.push_str(&format!("  <cac:TaxTotal>\n    {}\n", tag("TaxAmount", &h.tax_total)));
+    for tax in &data.tax_lines {
+        xml.push_str("    <cac:TaxSubtotal>\n");
+        xml.push_str(&format!("      {}\n", tag("TaxableAmount", &tax.taxable_amount)));
+        xml.push_str(&format!("      {}\n", tag("TaxAmount", &tax.tax_amount)));
+        xml.push_str("      <cac:TaxCategory>\n");
+        xml.push_str(&format!("        <cbc:Name>{}</cbc:Name>\n", xml_escape(&tax.name)));
+        xml.push_str(&format!("        <cbc:Percent>{:.2}</cbc:Percent>\n", tax.rate));
+        xml.push_str("        <cac:TaxScheme><cbc:ID>VAT</cbc:ID></cac:TaxScheme>\n");
+        xml.push_str("      </cac:TaxCategory>\n    </cac:TaxSubtotal>\n");
+    }
+    xml.push_str("  </cac:TaxTotal>\n");
+
+    xml.push_str("  <cac:LegalMonetaryTotal>\n");
+    xml.push_str(&format!("    {}\n", tag("LineExtensionAmount", &h.subtotal)));
+    xml.push_str(&format!("    {}\n", tag("TaxExclusiveAmount", &h.subtotal)));
+    xml.push_str(&format!("    {}\n", tag("TaxInclusiveAmount", &h.total)));
+    xml.push_str(&format!("    {}\n", tag("PayableAmount", &h.total)));
+    xml.push_str("  </cac:LegalMonetaryTotal>\n");
+
+    for (i, line) in data.lines.iter().enumerate() {
+        xml.push_str("  <cac:InvoiceLine>\n");
+        xml.push_str(&format!("    <cbc:ID>{}</cbc:ID>\n", i + 1));
+        xml.push_str(&format!("    <cbc:InvoicedQuantity unitCode=\"C62\">{}</cbc:InvoicedQuantity>\n", line.quantity));
+        xml.push_str(&format!("    {}\n", tag("LineExtensionAmount", &line.line_total)));
+        xml.push_str(&format!("    <cac:Item><cbc:Name>{}</cbc:Name></cac:Item>\n", xml_escape(&line.description)));
+        xml.push_str(&format!(
+            "    <cac:Price><cbc:PriceAmount currencyID=\"{}\">{:.4}</cbc:PriceAmount></cac:Price>\n",
+            cur, line.unit_price
+        ));
+        xml.push_str("  </cac:InvoiceLine>\n");
+    }
+    xml.push_str("</Invoice>\n");
+    xml
+}
+
+async fn load_invoice_render_data(pool: &MySqlPool, invoice_id: i32) -> Result<InvoiceRenderData, AppError> {
+    let header: InvoiceRenderHeader = sqlx::query_as(
+        "SELECT i.invoice_number, i.order_id, po.po_number, i.currency, i.subtotal, i.tax_total, i.total,
+                i.issue_date, i.due_date, i.notes,
+                s.name as supplier_name, s.city as supplier_city, b.name as buyer_name, b.city as buyer_city
+         FROM invoices i
+         JOIN purchase_orders po ON i.order_id = po.id
+         JOIN companies s ON i.supplier_company_id = s.id
+         JOIN companies b ON i.buyer_company_id = b.id
+         WHERE i.id = ?"
+    )
+        .bind(invoice_id)
+        .fetch_one(pool)
+        .await?;
+    let lines = sqlx::query_as("SELECT * FROM invoice_lines WHERE invoice_id = ? ORDER BY id ASC")
+        .bind(invoice_id)
+        .fetch_all(pool)
+        .await?;
+    let tax_lines = sqlx::query_as("SELECT * FROM invoice_tax_lines WHERE invoice_id = ? ORDER BY id ASC")
+        .bind(invoice_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(InvoiceRenderData { header, lines, tax_lines })
+}
+
+/// 生成发票的PDF和UBL文件（已存在的不会重新生成）
+async fn ensure_invoice_documents(pool: &MySqlPool, invoice_id: i32) -> Result<Vec<Document>, AppError> {
+    let data = load_invoice_render_data(pool, invoice_id).await?;
+    let h = &data.header;
+    let pdf = render_invoice_pdf(&data)?;
+    let ubl = render_invoice_ubl(&data).into_bytes();
+
+    let pdf_doc = document_service::store_document(
+        pool,
+        h.order_id,
+        DOCUMENT_TYPE_INVOICE_PDF,
+        &h.invoice_number,
+        1,
+        &format!("{}.pdf", h.invoice_number),
+        "application/pdf",
+        pdf,
+    )
+        .await?;
+    let ubl_doc = document_service::store_document(
+        pool,
+        h.order_id,
+        DOCUMENT_TYPE_INVOICE_UBL,
+        &h.invoice_number,
+        1,
+        &format!("{}.xml", h.invoice_number),
+        "application/xml",
+        ubl,
+    )
+        .await?;
+    Ok(vec![pdf_doc, ubl_doc])
+}
+
+pub async fn get_invoices_for_order(pool: &MySqlPool, order_id: i32, claims: &Claims) -> Result<Vec<Invoice>, AppError> {
+    order_service::ensure_order_party(pool, order_id, claims).await?;
+
+    let invoices = sqlx::query_as("SELECT * FROM invoices WHERE order_id = ? ORDER BY created_at ASC, id ASC")
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(invoices)
+}
+
+pub async fn get_invoice_detail(pool: &MySqlPool, invoice_id: i32, claims: &Claims) -> Result<InvoiceDetail, AppError> {
+    let invoice: Invoice = sqlx::query_as("SELECT * FROM invoices WHERE id = ?")
+        .bind(invoice_id)
+        .fetch_optional(pool)
+        .await?
+        .filter(|i: &Invoice| i.buyer_company_id == claims.company_id || i.supplier_company_id == claims.company_id)
+        .ok_or_else(|| AppError::BadRequest("Invoice not found or you are not authorized to view it.".to_string()))?;
+
+    let lines = sqlx::query_as("SELECT * FROM invoice_lines WHERE invoice_id = ? ORDER BY id ASC")
+        .bind(invoice_id)
+        .fetch_all(pool)
+        .await?;
+    let tax_lines = sqlx::query_as("SELECT * FROM invoice_tax_lines WHERE invoice_id = ? ORDER BY id ASC")
+        .bind(invoice_id)
+        .fetch_all(pool)
+        .await?;
+    let documents = ensure_invoice_documents(pool, invoice_id).await?;
+
+    Ok(InvoiceDetail { invoice, lines, tax_lines, documents })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_partial_invoices_add_up_to_order_total() {
+        // 100.00 分三次开票 33 + 33 + 34 件
+        let first = compute_invoice_amounts(d("100.00"), 100, 0, Decimal::ZERO, 33, Decimal::ZERO, &[]).unwrap();
+        assert_eq!(first.subtotal, d("33.00"));
+        let total = d("10.00");
+        let a = compute_invoice_amounts(total, 3, 0, Decimal::ZERO, 1, Decimal::ZERO, &[]).unwrap();
+        let b = compute_invoice_amounts(total, 3, 1, a.subtotal, 1, Decimal::ZERO, &[]).unwrap();
+        let c = compute_invoice_amounts(total, 3, 2, a.subtotal + b.subtotal, 1, Decimal::ZERO, &[]).unwrap();
+        assert_eq!(a.subtotal, d("3.33"));
+        assert_eq!(b.subtotal, d("3.33"));
+        assert_eq!(c.subtotal, d("3.34"));
+        assert_eq!(a.subtotal + b.subtotal + c.subtotal, total);
+
+        assert!(compute_invoice_amounts(total, 3, 2, d("6.66"), 2, Decimal::ZERO, &[]).is_err());
+        assert!(compute_invoice_amounts(total, 3, 0, Decimal::ZERO, 0, Decimal::ZERO, &[]).is_err());
+    }
+
+    #[test]
+    fn test_tax_lines() {
+        let taxes = vec![("VAT".to_string(), d("19")), ("Eco fee".to_string(), d("0.5"))];
+        let amounts = compute_invoice_amounts(d("1234.56"), 10, 0, Decimal::ZERO, 10, Decimal::ZERO, &taxes).unwrap();
+        assert_eq!(amounts.taxes[0].3, d("234.57")); // 234.5664
+        assert_eq!(amounts.taxes[1].3, d("6.17")); // 6.1728
+        assert_eq!(amounts.tax_total, d("240.74"));
+        assert_eq!(amounts.total, d("1475.30"));
+
+        // 运费和货款一起计税
+        let vat = [("VAT".to_string(), d("20"))];
+        let amounts = compute_invoice_amounts(d("100.00"), 4, 0, Decimal::ZERO, 2, d("25.00"), &vat).unwrap();
+        assert_eq!((amounts.goods, amounts.subtotal), (d("50.00"), d("75.00")));
+        assert_eq!(amounts.tax_total, d("15.00"));
+        assert_eq!(amounts.total, d("90.00"));
+
+        assert!(compute_invoice_amounts(d("10"), 1, 0, Decimal::ZERO, 1, Decimal::ZERO, &[("VAT".to_string(), d("101"))]).is_err());
+        assert!(compute_invoice_amounts(d("10"), 1, 0, Decimal::ZERO, 1, Decimal::ZERO, &[(" ".to_string(), d("5"))]).is_err());
+    }
+
+    #[test]
+    fn test_render_invoice_ubl() {
+        let data = InvoiceRenderData {
+            header: InvoiceRenderHeader {
+                invoice_number: "INV-000042".to_string(),
+                order_id: 7,
+                po_number: Some("PO-2026-000007".to_string()),
+                currency: "EUR".to_string(),
+                subtotal: d("100.00"),
+                tax_total: d("19.00"),
+                total: d("119.00"),
+                issue_date: NaiveDate::from_ymd_opt(2026, 10, 19).unwrap(),
+                due_date: NaiveDate::from_ymd_opt(2026, 11, 18).unwrap(),
+                notes: None,
+                supplier_name: "Parts & Co <Ltd>".to_string(),
+                supplier_city: None,
+                buyer_name: "Acme".to_string(),
+                buyer_city: Some("Berlin".to_string()),
+            },
+            lines: vec![InvoiceLine {
+                id: 1,
+                invoice_id: 1,
+                description: "Brackets".to_string(),
+                quantity: 10,
+                unit_price: d("10"),
+                line_total: d("100.00"),
+            }],
+            tax_lines: vec![InvoiceTaxLine {
+                id: 1,
+                invoice_id: 1,
+                name: "VAT".to_string(),
+                rate: d("19"),
+                taxable_amount: d("100.00"),
+                tax_amount: d("19.00"),
+            }],
+        };
+        let xml = render_invoice_ubl(&data);
+        assert!(xml.contains("<cbc:ID>INV-000042</cbc:ID>"));
+        assert!(xml.contains("<cbc:PayableAmount currencyID=\"EUR\">119.00</cbc:PayableAmount>"));
+        assert!(xml.contains("<cbc:Name>Parts &amp; Co &lt;Ltd&gt;</cbc:Name>"));
+        assert!(xml.contains("<cbc:Percent>19.00</cbc:Percent>"));
+        assert!(render_invoice_pdf(&data).unwrap().starts_with(b"%PDF"));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     errors::AppError,
-    models::rfq::Rfq,
+    models::{notification_preference::NotificationCategory, rfq::Rfq},
     services::{
         chat_server::ChatServer,
         notification_service::NotificationBuilder,
@@ -10,6 +10,35 @@ use actix::Addr;
 use sqlx::{MySqlPool, Row};
 
 
+// 每个RFQ最多通知的供应商数量
+const MAX_NOTIFIED_SUPPLIERS: usize = 5;
+// 在相同能力的RFQ上每拒绝一次，相当于少命中半个能力标签
+const DECLINE_PENALTY: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq)]
+struct SupplierCandidate {
+    company_id: i32,
+    match_count: i64,
+    decline_count: i64,
+}
+
+impl SupplierCandidate {
+    fn score(&self) -> f64 {
+        self.match_count as f64 - DECLINE_PENALTY * self.decline_count as f64
+    }
+}
+
+/// 按匹配得分排序，得分相同时拒绝次数少的优先
+fn rank_suppliers(mut candidates: Vec<SupplierCandidate>) -> Vec<SupplierCandidate> {
+    candidates.sort_by(|a, b| {
+        b.score()
+            .total_cmp(&a.score())
+            .then(a.decline_count.cmp(&b.decline_count))
+            .then(a.company_id.cmp(&b.company_id))
+    });
+    candidates
+}
+
 fn extract_keywords(text: &str) -> Vec<String> {
     text.to_lowercase()
         .split(|c: char| !c.is_alphanumeric())
@@ -55,26 +84,54 @@ pub async fn find_and_notify_suppliers(
     }
     log::info!("Found matching capability IDs for RFQ #{}: {:?}", rfq.id, matched_cap_ids);
 
+    // 记录本RFQ命中的能力标签，供应商之后的拒绝记录才能按能力统计
+    for cap_id in &matched_cap_ids {
+        sqlx::query("INSERT IGNORE INTO rfq_matched_capabilities (rfq_id, capability_id) VALUES (?, ?)")
+            .bind(rfq.id)
+            .bind(cap_id)
+            .execute(pool)
+            .await?;
+    }
+
+    // 候选供应商：命中的能力数 + 在相同能力的历史RFQ上拒绝报价的次数
+    let placeholders = matched_cap_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
     let query = format!(
-        "SELECT company_id, COUNT(capability_id) as match_count
-         FROM company_capabilities
-         WHERE capability_id IN ({})
-         GROUP BY company_id
-         ORDER BY match_count DESC
-         LIMIT 5",
-        matched_cap_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",")
+        "SELECT cc.company_id, COUNT(cc.capability_id) as match_count,
+            (SELECT COUNT(DISTINCT sr.rfq_id)
+             FROM rfq_supplier_responses sr
+             JOIN rfq_matched_capabilities rmc ON rmc.rfq_id = sr.rfq_id
+             WHERE sr.supplier_company_id = cc.company_id
+               AND sr.response = 'DECLINED'
+               AND sr.rfq_id <> ?
+               AND rmc.capability_id IN ({placeholders})) as decline_count
+         FROM company_capabilities cc
+         WHERE cc.capability_id IN ({placeholders})
+         GROUP BY cc.company_id",
     );
 
-    let mut query_builder = sqlx::query(&query);
+    let mut query_builder = sqlx::query(&query).bind(rfq.id);
+    for id in &matched_cap_ids {
+        query_builder = query_builder.bind(id);
+    }
     for id in &matched_cap_ids {
         query_builder = query_builder.bind(id);
     }
 
-    let matched_suppliers: Vec<(i32,)> = query_builder
+    let candidates: Vec<SupplierCandidate> = query_builder
         .fetch_all(pool)
         .await?
         .into_iter()
-        .map(|row| (row.get("company_id"),))
+        .map(|row| SupplierCandidate {
+            company_id: row.get("company_id"),
+            match_count: row.get("match_count"),
+            decline_count: row.get("decline_count"),
+        })
+        .collect();
+
+    let matched_suppliers: Vec<(i32,)> = rank_suppliers(candidates)
+        .into_iter()
+        .take(MAX_NOTIFIED_SUPPLIERS)
+        .map(|c| (c.company_id,))
         .collect();
 
     if matched_suppliers.is_empty() {
@@ -98,7 +155,7 @@ pub async fn find_and_notify_suppliers(
                 let link = format!("/rfqs/{}", rfq.id);
 
                 // 使用已有的通知服务
-                NotificationBuilder::new(user_id, message)
+                NotificationBuilder::new(user_id, NotificationCategory::Rfq, message)
                     .with_link(link)
                     .send(pool, chat_server)
                     .await?;
@@ -107,4 +164,25 @@ pub async fn find_and_notify_suppliers(
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(company_id: i32, match_count: i64, decline_count: i64) -> SupplierCandidate {
+        SupplierCandidate { company_id, match_count, decline_count }
+    }
+
+    #[test]
+    fn test_repeat_decliners_rank_lower() {
+        let ranked = rank_suppliers(vec![
+            candidate(1, 3, 4), // 命中多，但经常拒绝
+            candidate(2, 2, 0),
+            candidate(3, 3, 0),
+            candidate(4, 2, 1),
+        ]);
+        let order: Vec<i32> = ranked.iter().map(|c| c.company_id).collect();
+        assert_eq!(order, vec![3, 2, 4, 1]);
+    }
+}
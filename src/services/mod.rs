@@ -8,7 +8,28 @@ pub(crate) mod company_service;
 pub(crate) mod user_service;
 pub(crate) mod analytics_service;
 pub(crate) mod payment_service;
+pub(crate) mod fx_service;
 pub(crate) mod admin_service;
 pub(crate) mod capability_service;
 pub mod matching_service;
-pub(crate) mod annotation_service;
\ No newline at end of file
+pub(crate) mod annotation_service;
+pub(crate) mod dispute_service;
+pub(crate) mod shipment_service;
+pub(crate) mod receipt_service;
+pub(crate) mod rating_service;
+pub(crate) mod document_service;
+pub(crate) mod invoice_service;
+pub(crate) mod change_order_service;
+pub(crate) mod blanket_agreement_service;
+pub(crate) mod delivery_service;
+pub(crate) mod payment_provider;
+pub(crate) mod refund_service;
+pub(crate) mod payment_schedule_service;
+pub(crate) mod reconciliation_service;
+pub(crate) mod payment_terms_service;
+pub(crate) mod tax_service;
+pub(crate) mod email_transport;
+pub(crate) mod email_service;
+pub(crate) mod email_template;
+pub(crate) mod notification_preference_service;
+pub(crate) mod notification_channel_service;
@@ -0,0 +1,379 @@
+// src/services/notification_channel_service.rs
+// 邮件之外的通知通道：用户自己的Webhook（签名后POST，失败退避重试）和每日摘要邮件
+use crate::{
+    errors::AppError,
+    models::{email::Locale, notification_preference::NotificationCategory},
+    services::{email_service, email_template, notification_preference_service},
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use hyper::{
+    client::{connect::dns::Name, HttpConnector},
+    header::CONTENT_TYPE,
+    service::Service,
+    Body, Client, Request,
+};
+use hyper_tls::HttpsConnector;
+use sha2::Sha256;
+use sqlx::{FromRow, MySqlPool};
+use std::{
+    env,
+    future::Future,
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+type WebhookClient = Client<HttpsConnector<HttpConnector<PublicResolver>>>;
+
+const DEFAULT_WEBHOOK_INTERVAL_SECS: u64 = 15;
+const DEFAULT_DIGEST_INTERVAL_SECS: u64 = 24 * 60 * 60;
+const WEBHOOK_BATCH_SIZE: i64 = 50;
+const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+const WEBHOOK_CLAIM_LEASE_SECS: i64 = 10 * 60;
+// 一封摘要最多列这么多条，剩下的下次再发
+const MAX_DIGEST_ITEMS: i64 = 200;
+
+// ---------------- Webhook ----------------
+
+/// Webhook地址由用户填写，只允许投递到公网地址，防止借服务器访问内网（SSRF）
+pub(crate) fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_private() || v4.is_loopback() || v4.is_link_local() || v4.is_unspecified() || v4.is_broadcast()
+                || v4.is_multicast() || v4.is_documentation()
+                || a == 0
+                // 100.64.0.0/10 运营商级NAT
+                || (a == 100 && (64..128).contains(&b))
+                // 198.18.0.0/15 基准测试
+                || (a == 198 && (18..20).contains(&b))
+                || a >= 240)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback() || v6.is_unspecified() || v6.is_multicast()
+                // fc00::/7 唯一本地地址，fe80::/10 链路本地
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// 解析Webhook主机名，有任何一个地址不是公网地址就拒绝（PermissionDenied），
+/// 避免DNS里混进内网地址
+pub(crate) async fn resolve_public_host(host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    if addrs.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} did not resolve to any address", host)));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} resolves to the non-public address {}", host, addr.ip()),
+        ));
+    }
+    Ok(addrs)
+}
+
+/// 投递时连接用的DNS解析器：只返回公网地址。保存时校验过的域名之后改指内网（DNS rebinding）也连不上
+#[derive(Clone)]
+pub(crate) struct PublicResolver;
+
+impl Service<Name> for PublicResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        Box::pin(async move { Ok(resolve_public_host(name.as_str(), 0).await?.into_iter()) })
+    }
+}
+
+fn webhook_client() -> WebhookClient {
+    let mut http = HttpConnector::new_with_resolver(PublicResolver);
+    http.enforce_http(false);
+    Client::builder().build(HttpsConnector::new_with_connector(http))
+}
+
+/// 投递前重新校验地址：不合法或指向内网的直接进死信，DNS暂时解析失败的稍后重试
+async fn check_webhook_target(url: &str) -> Result<(), (bool, String)> {
+    let uri = notification_preference_service::validate_webhook_url(Some(url.to_string()))
+        .map_err(|_| (true, "Webhook URL is no longer an allowed public https address".to_string()))?
+        .and_then(|url| url.parse::<hyper::Uri>().ok())
+        .ok_or_else(|| (true, "Webhook URL is missing".to_string()))?;
+    let host = uri.host().unwrap_or_default().trim_start_matches('[').trim_end_matches(']');
+    if host.parse::<IpAddr>().is_ok() {
+        // IP字面量已经在 validate_webhook_url 里检查过
+        return Ok(());
+    }
+    resolve_public_host(host, uri.port_u16().unwrap_or(443))
+        .await
+        .map(|_| ())
+        .map_err(|e| (e.kind() == io::ErrorKind::PermissionDenied, e.to_string()))
+}
+
+/// 签名头 X-SCCP-Signature: t=时间戳,v1=HMAC-SHA256(密钥, "时间戳.请求体")
+pub(crate) fn webhook_signature(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+    format!("t={},v1={}", timestamp, hex::encode(mac.finalize().into_bytes()))
+}
+
+pub(crate) async fn queue_webhook(
+    pool: &MySqlPool,
+    user_id: i32,
+    url: &str,
+    category: NotificationCategory,
+    message: &str,
+    link: Option<&str>,
+) -> Result<(), AppError> {
+    let payload = serde_json::json!({
+        "type": "notification",
+        "category": category.as_str(),
+        "message": message,
+        "link": link,
+        "created_at": Utc::now(),
+    });
+    sqlx::query("INSERT INTO notification_webhook_deliveries (user_id, url, payload) VALUES (?, ?, ?)")
+        .bind(user_id)
+        .bind(url)
+        .bind(payload.to_string())
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+#[derive(Debug, FromRow)]
+struct DueWebhook {
+    id: i32,
+    url: String,
+    payload: String,
+    attempts: i32,
+    // 用户删掉Webhook后为空
+    webhook_secret: Option<String>,
+}
+
+async fn claim_due_webhooks(pool: &MySqlPool) -> Result<Vec<DueWebhook>, AppError> {
+    let mut tx = pool.begin().await?;
+    let mut due: Vec<DueWebhook> = sqlx::query_as(
+        "SELECT d.id, d.url, d.payload, d.attempts, s.webhook_secret
+         FROM notification_webhook_deliveries d
+         LEFT JOIN notification_settings s ON s.user_id = d.user_id
+         WHERE d.status = 'PENDING' AND d.next_attempt_at <= NOW()
+         ORDER BY d.next_attempt_at, d.id LIMIT ? FOR UPDATE OF d SKIP LOCKED"
+    )
+        .bind(WEBHOOK_BATCH_SIZE)
+        .fetch_all(&mut *tx)
+        .await?;
+    for webhook in &mut due {
+        webhook.attempts += 1;
+        sqlx::query("UPDATE notification_webhook_deliveries SET attempts = attempts + 1, next_attempt_at = NOW() + INTERVAL ? SECOND WHERE id = ?")
+            .bind(WEBHOOK_CLAIM_LEASE_SECS)
+            .bind(webhook.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(due)
+}
+
+async fn post_webhook(client: &WebhookClient, webhook: &DueWebhook, secret: &str) -> Result<u16, String> {
+    let request = Request::post(&webhook.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header("X-SCCP-Signature", webhook_signature(secret, Utc::now().timestamp(), &webhook.payload))
+        .body(Body::from(webhook.payload.clone()))
+        .map_err(|e| format!("Invalid webhook request: {}", e))?;
+    let response = tokio::time::timeout(Duration::from_secs(WEBHOOK_TIMEOUT_SECS), client.request(request))
+        .await
+        .map_err(|_| "Webhook request timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    Ok(response.status().as_u16())
+}
+
+/// 投递一批到期的Webhook，返回 (成功, 重试, 死信) 数量
+pub async fn deliver_due_webhooks(pool: &MySqlPool, client: &WebhookClient) -> Result<(u32, u32, u32), AppError> {
+    let due = claim_due_webhooks(pool).await?;
+    let (mut sent, mut retried, mut dead) = (0, 0, 0);
+    for webhook in due {
+        // permanent 为 true 时不再重试
+        let (status, result, permanent) = match webhook.webhook_secret.as_deref() {
+            None => (None, Err("Webhook is no longer configured".to_string()), true),
+            Some(secret) => match check_webhook_target(&webhook.url).await {
+                Err((permanent, e)) => (None, Err(e), permanent),
+                Ok(()) => match post_webhook(client, &webhook, secret).await {
+                    Ok(status) if (200..300).contains(&status) => (Some(status), Ok(()), false),
+                    Ok(status) => (Some(status), Err(format!("Webhook endpoint returned HTTP {}", status)), false),
+                    Err(e) => (None, Err(e), false),
+                },
+            },
+        };
+        match result {
+            Ok(()) => {
+                sqlx::query("UPDATE notification_webhook_deliveries SET status = 'SENT', response_status = ?, last_error = NULL WHERE id = ?")
+                    .bind(status)
+                    .bind(webhook.id)
+                    .execute(pool)
+                    .await?;
+                sent += 1;
+            }
+            Err(error) if !permanent && webhook.attempts < email_service::MAX_ATTEMPTS => {
+                sqlx::query(
+                    "UPDATE notification_webhook_deliveries SET next_attempt_at = NOW() + INTERVAL ? SECOND, response_status = ?, last_error = ? WHERE id = ?"
+                )
+                    .bind(email_service::retry_delay_secs(webhook.attempts))
+                    .bind(status)
+                    .bind(email_service::truncate_error(&error))
+                    .bind(webhook.id)
+                    .execute(pool)
+                    .await?;
+                retried += 1;
+            }
+            Err(error) => {
+                log::warn!("Notification webhook #{} to {} dead-lettered: {}", webhook.id, webhook.url, error);
+                sqlx::query("UPDATE notification_webhook_deliveries SET status = 'DEAD', response_status = ?, last_error = ? WHERE id = ?")
+                    .bind(status)
+                    .bind(email_service::truncate_error(&error))
+                    .bind(webhook.id)
+                    .execute(pool)
+                    .await?;
+                dead += 1;
+            }
+        }
+    }
+    Ok((sent, retried, dead))
+}
+
+// ---------------- 每日摘要 ----------------
+
+pub(crate) async fn queue_digest_item(
+    pool: &MySqlPool,
+    user_id: i32,
+    category: NotificationCategory,
+    message: &str,
+    link: Option<&str>,
+) -> Result<(), AppError> {
+    sqlx::query("INSERT INTO notification_digest_items (user_id, category, message, link_url) VALUES (?, ?, ?, ?)")
+        .bind(user_id)
+        .bind(category.as_str())
+        .bind(message)
+        .bind(link)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// 给每个有未发摘要的用户发一封汇总邮件，返回发出的封数
+pub async fn send_digests(pool: &MySqlPool) -> Result<u32, AppError> {
+    let recipients: Vec<(i32, String, String)> = sqlx::query_as(
+        "SELECT DISTINCT u.id, u.email, u.locale FROM notification_digest_items d
+         JOIN users u ON u.id = d.user_id
+         WHERE d.sent_at IS NULL AND u.is_active = TRUE"
+    )
+        .fetch_all(pool)
+        .await?;
+
+    let mut sent = 0;
+    for (user_id, email, locale) in recipients {
+        let items: Vec<(i32, String, String)> = sqlx::query_as(
+            "SELECT id, category, message FROM notification_digest_items WHERE user_id = ? AND sent_at IS NULL ORDER BY id LIMIT ?"
+        )
+            .bind(user_id)
+            .bind(MAX_DIGEST_ITEMS)
+            .fetch_all(pool)
+            .await?;
+        let Some(last_id) = items.last().map(|(id, _, _)| *id) else { continue };
+
+        let lines: Vec<String> = items.iter().map(|(_, category, message)| format!("- [{}] {}", category, message)).collect();
+        let vars = [("count", items.len().to_string()), ("items", lines.join("\n"))];
+        let rendered = email_template::render("digest", Locale::from_stored(&locale), &vars)?;
+        let settings = notification_preference_service::load_settings(pool, user_id).await?;
+        let not_before = notification_preference_service::email_not_before(&settings, Utc::now());
+
+        let mut tx = pool.begin().await?;
+        email_service::queue_email_after(&mut tx, &email, &rendered, Vec::new(), not_before).await?;
+        sqlx::query("UPDATE notification_digest_items SET sent_at = NOW() WHERE user_id = ? AND sent_at IS NULL AND id <= ?")
+            .bind(user_id)
+            .bind(last_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        sent += 1;
+    }
+    Ok(sent)
+}
+
+fn interval_from_env(name: &str, default_secs: u64) -> Duration {
+    let secs = env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(default_secs);
+    Duration::from_secs(secs)
+}
+
+/// 启动Webhook投递和每日摘要任务。间隔可以用 NOTIFICATION_WEBHOOK_INTERVAL_SECS 和 NOTIFICATION_DIGEST_INTERVAL_SECS 配置
+pub fn spawn_notification_channels(pool: MySqlPool) {
+    let webhook_pool = pool.clone();
+    let webhook_interval = interval_from_env("NOTIFICATION_WEBHOOK_INTERVAL_SECS", DEFAULT_WEBHOOK_INTERVAL_SECS);
+    tokio::spawn(async move {
+        let client = webhook_client();
+        let mut ticker = tokio::time::interval(webhook_interval);
+        loop {
+            ticker.tick().await;
+            match deliver_due_webhooks(&webhook_pool, &client).await {
+                Ok((0, 0, 0)) => {}
+                Ok((sent, retried, dead)) => log::info!("Notification webhooks: {} sent, {} to retry, {} dead", sent, retried, dead),
+                Err(e) => log::error!("Notification webhook delivery failed: {:?}", e),
+            }
+        }
+    });
+
+    let digest_interval = interval_from_env("NOTIFICATION_DIGEST_INTERVAL_SECS", DEFAULT_DIGEST_INTERVAL_SECS);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(digest_interval);
+        // 第一次 tick 立即返回，跳过它，避免每次重启都发一封摘要
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            match send_digests(&pool).await {
+                Ok(count) => log::info!("Queued {} notification digests", count),
+                Err(e) => log::error!("Notification digest failed: {:?}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_ip() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{} should not be public", ip);
+        }
+        for ip in ["93.184.216.34", "8.8.8.8", "2606:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{} should be public", ip);
+        }
+    }
+
+    #[test]
+    fn test_webhook_signature() {
+        let signature = webhook_signature("secret", 1_760_000_000, "{\"a\":1}");
+        let (timestamp, digest) = signature.split_once(",v1=").unwrap();
+        assert_eq!(timestamp, "t=1760000000");
+        assert_eq!(digest.len(), 64);
+        assert_ne!(signature, webhook_signature("other", 1_760_000_000, "{\"a\":1}"));
+        assert_eq!(signature, webhook_signature("secret", 1_760_000_000, "{\"a\":1}"));
+    }
+}
@@ -0,0 +1,296 @@
+// src/services/notification_preference_service.rs
+// 通知偏好：用户按类别和通道开关通知，设置免打扰时段和Webhook地址。
+// NotificationBuilder 发送前用 dispatch_plan 决定走哪些通道
+use crate::{
+    errors::AppError,
+    models::{
+        notification_preference::{
+            CategoryPreferences, NotificationCategory, NotificationChannel, NotificationPreferences, NotificationSettings,
+            UpdateNotificationSettingsDto, UpdatePreferencesDto,
+        },
+        user::Claims,
+    },
+    services::notification_channel_service,
+};
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use rand::{distr::Alphanumeric, Rng};
+use sqlx::{MySqlExecutor, MySqlPool};
+use std::net::IpAddr;
+
+// UTC-12 到 UTC+14
+const MIN_UTC_OFFSET_MINUTES: i32 = -12 * 60;
+const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// 现在处于免打扰时段时，返回时段结束的时间（UTC）。时段可以跨午夜，例如 22:00-07:00
+pub(crate) fn quiet_hours_until(
+    now: DateTime<Utc>,
+    start: NaiveTime,
+    end: NaiveTime,
+    utc_offset_minutes: i32,
+) -> Option<DateTime<Utc>> {
+    let offset = Duration::minutes(i64::from(utc_offset_minutes));
+    let local = (now + offset).naive_utc();
+    let time = local.time();
+    let quiet = if start < end {
+        start <= time && time < end
+    } else if start > end {
+        time >= start || time < end
+    } else {
+        false
+    };
+    if !quiet {
+        return None;
+    }
+    let end_date = if time < end { local.date() } else { local.date() + Duration::days(1) };
+    Some(end_date.and_time(end).and_utc() - offset)
+}
+
+/// 这次通知要走哪些通道
+#[derive(Debug, Default)]
+pub(crate) struct DispatchPlan {
+    pub in_app: bool,
+    // 收件地址和语言
+    pub email: Option<(String, String)>,
+    // 免打扰时段内的邮件推迟到时段结束后投递，事务性通知不推迟
+    pub email_not_before: Option<DateTime<Utc>>,
+    // Webhook地址和签名密钥
+    pub webhook: Option<(String, String)>,
+    pub digest: bool,
+}
+
+pub(crate) async fn load_settings<'e>(executor: impl MySqlExecutor<'e>, user_id: i32) -> Result<NotificationSettings, AppError> {
+    let settings = sqlx::query_as(
+        "SELECT quiet_hours_start, quiet_hours_end, utc_offset_minutes, webhook_url, webhook_secret
+         FROM notification_settings WHERE user_id = ?"
+    )
+        .bind(user_id)
+        .fetch_optional(executor)
+        .await?;
+    Ok(settings.unwrap_or_default())
+}
+
+/// 用户的设置下，现在发邮件要推迟到什么时候
+pub(crate) fn email_not_before(settings: &NotificationSettings, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    match (settings.quiet_hours_start, settings.quiet_hours_end) {
+        (Some(start), Some(end)) => quiet_hours_until(now, start, end, settings.utc_offset_minutes),
+        _ => None,
+    }
+}
+
+async fn stored_preferences(
+    pool: &MySqlPool,
+    user_id: i32,
+    category: Option<NotificationCategory>,
+) -> Result<Vec<(String, String, bool)>, AppError> {
+    let rows = sqlx::query_as(
+        "SELECT category, channel, enabled FROM notification_preferences WHERE user_id = ? AND (? IS NULL OR category = ?)"
+    )
+        .bind(user_id)
+        .bind(category.map(|c| c.as_str()))
+        .bind(category.map(|c| c.as_str()))
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+fn stored_value(rows: &[(String, String, bool)], category: NotificationCategory, channel: NotificationChannel) -> Option<bool> {
+    rows.iter()
+        .find(|(c, ch, _)| c == category.as_str() && ch == channel.as_str())
+        .map(|(_, _, enabled)| *enabled)
+}
+
+pub(crate) async fn dispatch_plan(pool: &MySqlPool, user_id: i32, category: NotificationCategory) -> Result<DispatchPlan, AppError> {
+    let rows = stored_preferences(pool, user_id, Some(category)).await?;
+    let enabled = |channel: NotificationChannel| channel.is_enabled(category, stored_value(&rows, category, channel));
+    let settings = load_settings(pool, user_id).await?;
+
+    let mut plan = DispatchPlan { in_app: enabled(NotificationChannel::InApp), digest: enabled(NotificationChannel::Digest), ..Default::default() };
+    // 非事务性类别开了摘要就不再单独发邮件，只汇总进每日摘要；事务性通知照常发
+    if enabled(NotificationChannel::Email) && (category.is_transactional() || !plan.digest) {
+        // 停用的用户不再收邮件
+        plan.email = sqlx::query_as("SELECT email, locale FROM users WHERE id = ? AND is_active = TRUE")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+        if !category.is_transactional() {
+            plan.email_not_before = email_not_before(&settings, Utc::now());
+        }
+    }
+    if enabled(NotificationChannel::Webhook)
+        && let (Some(url), Some(secret)) = (settings.webhook_url, settings.webhook_secret)
+    {
+        plan.webhook = Some((url, secret));
+    }
+    Ok(plan)
+}
+
+pub async fn get_preferences(pool: &MySqlPool, claims: &Claims) -> Result<NotificationPreferences, AppError> {
+    let rows = stored_preferences(pool, claims.sub, None).await?;
+    let settings = load_settings(pool, claims.sub).await?;
+    let enabled = |category, channel: NotificationChannel| channel.is_enabled(category, stored_value(&rows, category, channel));
+    let categories = NotificationCategory::ALL.into_iter()
+        .map(|category| CategoryPreferences {
+            category: category.as_str(),
+            description: category.description(),
+            transactional: category.is_transactional(),
+            in_app: enabled(category, NotificationChannel::InApp),
+            email: enabled(category, NotificationChannel::Email),
+            webhook: enabled(category, NotificationChannel::Webhook),
+            digest: enabled(category, NotificationChannel::Digest),
+        })
+        .collect();
+    Ok(NotificationPreferences {
+        categories,
+        quiet_hours_start: settings.quiet_hours_start,
+        quiet_hours_end: settings.quiet_hours_end,
+        utc_offset_minutes: settings.utc_offset_minutes,
+        webhook_url: settings.webhook_url,
+        webhook_secret: settings.webhook_secret,
+    })
+}
+
+pub async fn update_preferences(pool: &MySqlPool, claims: &Claims, dto: UpdatePreferencesDto) -> Result<NotificationPreferences, AppError> {
+    let mut updates = Vec::with_capacity(dto.preferences.len());
+    for update in dto.preferences {
+        let category = NotificationCategory::parse(&update.category)
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown notification category '{}'.", update.category)))?;
+        let channel = NotificationChannel::parse(&update.channel)
+            .ok_or_else(|| AppError::BadRequest(format!("Unknown notification channel '{}'.", update.channel)))?;
+        if !update.enabled && channel.is_mandatory_for(category) {
+            return Err(AppError::BadRequest(format!(
+                "{} notifications are transactional; {} delivery cannot be disabled.",
+                category.as_str(), channel.as_str()
+            )));
+        }
+        updates.push((category, channel, update.enabled));
+    }
+
+    let mut tx = pool.begin().await?;
+    for (category, channel, enabled) in updates {
+        sqlx::query(
+            "INSERT INTO notification_preferences (user_id, category, channel, enabled) VALUES (?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE enabled = VALUES(enabled)"
+        )
+            .bind(claims.sub)
+            .bind(category.as_str())
+            .bind(channel.as_str())
+            .bind(enabled)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    get_preferences(pool, claims).await
+}
+
+/// 只接受 https 地址；主机是IP字面量时必须是公网地址，域名在保存和每次投递时再解析检查
+pub(crate) fn validate_webhook_url(url: Option<String>) -> Result<Option<String>, AppError> {
+    let Some(url) = url.map(|u| u.trim().to_string()).filter(|u| !u.is_empty()) else {
+        return Ok(None);
+    };
+    let uri = url.parse::<hyper::Uri>().ok()
+        .filter(|uri| url.len() <= 500 && uri.scheme_str() == Some("https") && uri.host().is_some_and(|h| !h.is_empty()))
+        .ok_or_else(|| AppError::BadRequest("Webhook URL must be a valid https URL of at most 500 characters.".to_string()))?;
+    let host = uri.host().unwrap_or_default().trim_start_matches('[').trim_end_matches(']');
+    if host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| !notification_channel_service::is_public_ip(ip))
+    {
+        return Err(AppError::BadRequest("Webhook URL must point to a public address.".to_string()));
+    }
+    Ok(Some(url))
+}
+
+/// 保存前解析域名，指向内网地址的拒绝
+async fn check_webhook_host(url: &str) -> Result<(), AppError> {
+    let Ok(uri) = url.parse::<hyper::Uri>() else { return Ok(()) };
+    let host = uri.host().unwrap_or_default().trim_start_matches('[').trim_end_matches(']');
+    if host.parse::<IpAddr>().is_ok() {
+        return Ok(());
+    }
+    notification_channel_service::resolve_public_host(host, uri.port_u16().unwrap_or(443))
+        .await
+        .map(|_| ())
+        .map_err(|e| AppError::BadRequest(format!("Webhook URL must point to a reachable public address: {}", e)))
+}
+
+/// 免打扰时段、时区和Webhook地址。第一次设置Webhook时生成签名密钥
+pub async fn update_settings(pool: &MySqlPool, claims: &Claims, dto: UpdateNotificationSettingsDto) -> Result<NotificationPreferences, AppError> {
+    if dto.quiet_hours_start.is_some() != dto.quiet_hours_end.is_some() {
+        return Err(AppError::BadRequest("Quiet hours need both a start and an end time.".to_string()));
+    }
+    if !(MIN_UTC_OFFSET_MINUTES..=MAX_UTC_OFFSET_MINUTES).contains(&dto.utc_offset_minutes) {
+        return Err(AppError::BadRequest("UTC offset must be between -720 and 840 minutes.".to_string()));
+    }
+    let webhook_url = validate_webhook_url(dto.webhook_url)?;
+    if let Some(url) = &webhook_url {
+        check_webhook_host(url).await?;
+    }
+    let new_secret: String = rand::rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect();
+
+    sqlx::query(
+        "INSERT INTO notification_settings (user_id, quiet_hours_start, quiet_hours_end, utc_offset_minutes, webhook_url, webhook_secret)
+         VALUES (?, ?, ?, ?, ?, IF(? IS NULL, NULL, ?))
+         ON DUPLICATE KEY UPDATE quiet_hours_start = VALUES(quiet_hours_start), quiet_hours_end = VALUES(quiet_hours_end),
+                                 utc_offset_minutes = VALUES(utc_offset_minutes), webhook_url = VALUES(webhook_url),
+                                 webhook_secret = IF(VALUES(webhook_url) IS NULL, NULL, COALESCE(webhook_secret, VALUES(webhook_secret)))"
+    )
+        .bind(claims.sub)
+        .bind(dto.quiet_hours_start)
+        .bind(dto.quiet_hours_end)
+        .bind(dto.utc_offset_minutes)
+        .bind(&webhook_url)
+        .bind(&webhook_url)
+        .bind(&new_secret)
+        .execute(pool)
+        .await?;
+    get_preferences(pool, claims).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn t(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours_across_midnight() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 19, 23, 30, 0).unwrap();
+        assert_eq!(
+            quiet_hours_until(now, t(22, 0), t(7, 0), 0),
+            Some(Utc.with_ymd_and_hms(2026, 10, 20, 7, 0, 0).unwrap())
+        );
+        let early = Utc.with_ymd_and_hms(2026, 10, 20, 6, 0, 0).unwrap();
+        assert_eq!(quiet_hours_until(early, t(22, 0), t(7, 0), 0), Some(Utc.with_ymd_and_hms(2026, 10, 20, 7, 0, 0).unwrap()));
+        assert_eq!(quiet_hours_until(Utc.with_ymd_and_hms(2026, 10, 20, 12, 0, 0).unwrap(), t(22, 0), t(7, 0), 0), None);
+        assert_eq!(quiet_hours_until(now, t(9, 0), t(9, 0), 0), None);
+    }
+
+    #[test]
+    fn test_quiet_hours_use_local_time() {
+        // UTC 15:00 是北京时间 23:00，处于 22:00-08:00 的免打扰时段，结束时间是北京时间 08:00 即 UTC 00:00
+        let now = Utc.with_ymd_and_hms(2026, 10, 19, 15, 0, 0).unwrap();
+        assert_eq!(
+            quiet_hours_until(now, t(22, 0), t(8, 0), 8 * 60),
+            Some(Utc.with_ymd_and_hms(2026, 10, 20, 0, 0, 0).unwrap())
+        );
+        // 同一时刻在UTC看是 15:00，不在 12:00-14:00 的时段里
+        assert_eq!(quiet_hours_until(now, t(12, 0), t(14, 0), 0), None);
+        assert!(quiet_hours_until(now, t(12, 0), t(16, 0), 0).is_some());
+    }
+
+    #[test]
+    fn test_validate_webhook_url() {
+        assert_eq!(validate_webhook_url(Some(" ".to_string())).unwrap(), None);
+        assert!(validate_webhook_url(Some("ftp://example.com".to_string())).is_err());
+        // 只允许https，不允许内网、回环和链路本地地址
+        for url in ["http://hooks.example.com/sccp", "https://127.0.0.1/hook", "https://10.0.0.5/hook", "https://169.254.169.254/latest", "https://[::1]:8443/", "https://localhost/hook"] {
+            assert!(validate_webhook_url(Some(url.to_string())).is_err(), "{} should be rejected", url);
+        }
+        assert_eq!(
+            validate_webhook_url(Some("https://hooks.example.com/sccp".to_string())).unwrap().as_deref(),
+            Some("https://hooks.example.com/sccp")
+        );
+    }
+}
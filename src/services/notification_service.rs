@@ -1,68 +1,43 @@
-use lettre::{
-    transport::smtp::{
-        authentication::Credentials,
-        client::{Tls, TlsParameters},
-    },
-    Message, SmtpTransport, Transport,
-};
-use std::env;
 use crate::{
     errors::AppError,
-    models::{notification::Notification, user::Claims},
-    services::chat_server::{ChatServer, DirectMessage},
+    models::{
+        email::{EmailAttachment, Locale},
+        notification::Notification,
+        notification_preference::NotificationCategory,
+        user::Claims,
+    },
+    services::{
+        chat_server::{ChatServer, DirectMessage},
+        email_service, email_template, notification_channel_service,
+        notification_preference_service::{self, DispatchPlan},
+    },
 };
 use actix::Addr;
 use sqlx::MySqlPool;
-pub async fn send_email(to: String, subject: String, body: String) -> Result<(), AppError> {
-    let from = env::var("SMTP_FROM").expect("SMTP_FROM must be set");
-    let smtp_user = env::var("SMTP_USER").expect("SMTP_USER must be set");
-    let smtp_pass = env::var("SMTP_PASS").expect("SMTP_PASS must be set");
-    let smtp_host = env::var("SMTP_HOST").expect("SMTP_HOST must be set");
-    let smtp_port_str = env::var("SMTP_PORT").expect("SMTP_PORT must be set");
-    let smtp_port = smtp_port_str.parse::<u16>().expect("SMTP_PORT must be a valid number");
-
-
-    let email = Message::builder()
-        .from(from.parse().unwrap())
-        .to(to.parse().map_err(|_| AppError::InternalServerError("Invalid 'to' email address".to_string()))?)
-        .subject(subject)
-        .body(body)
-        .map_err(|_| AppError::InternalServerError("Failed to build email".to_string()))?;
-
-    let creds = Credentials::new(smtp_user, smtp_pass);
-
-    let tls_parameters = TlsParameters::new(smtp_host.clone())
-        .map_err(|_| AppError::InternalServerError("Failed to create TLS parameters".to_string()))?;
-
-    let mailer = SmtpTransport::builder_dangerous(&smtp_host)
-        .port(smtp_port)
-        .credentials(creds)
-        .tls(Tls::Opportunistic(tls_parameters))
-        .build();
-
-    tokio::spawn(async move {
-        match mailer.send(&email) {
-            Ok(_) => log::info!("Email sent successfully!"),
-            Err(e) => log::error!("Could not send email: {:?}", e),
-        }
-    });
 
-    Ok(())
+struct EmailContent {
+    template: &'static str,
+    vars: Vec<(&'static str, String)>,
+    attachments: Vec<EmailAttachment>,
 }
 
-// 这个结构体将作为创建通知的统一入口
+// 这个结构体将作为创建通知的统一入口，发送前按收件人的偏好决定走哪些通道
 pub struct NotificationBuilder {
     recipient_user_id: i32,
+    category: NotificationCategory,
     message: String,
     link_url: Option<String>,
+    email: Option<EmailContent>,
 }
 
 impl NotificationBuilder {
-    pub fn new(recipient_user_id: i32, message: String) -> Self {
+    pub fn new(recipient_user_id: i32, category: NotificationCategory, message: String) -> Self {
         Self {
             recipient_user_id,
+            category,
             message,
             link_url: None,
+            email: None,
         }
     }
 
@@ -71,18 +46,55 @@ impl NotificationBuilder {
         self
     }
 
-    // 保存到数据库并尝试实时推送，重点
+    /// 邮件内容，按收件人的语言渲染。不设置的话这条通知不发邮件
+    pub fn with_email(mut self, template: &'static str, vars: &[(&'static str, String)]) -> Self {
+        self.email = Some(EmailContent { template, vars: vars.to_vec(), attachments: Vec::new() });
+        self
+    }
+
+    pub fn with_attachments(mut self, attachments: Vec<EmailAttachment>) -> Self {
+        if let Some(email) = self.email.as_mut() {
+            email.attachments = attachments;
+        }
+        self
+    }
+
+    // 邮件、Webhook、摘要失败只记日志；站内通知关闭时返回 None
     pub async fn send(
         self,
         pool: &MySqlPool,
         chat_server: &Addr<ChatServer>,
-    ) -> Result<Notification, AppError> {
+    ) -> Result<Option<Notification>, AppError> {
+        let plan = notification_preference_service::dispatch_plan(pool, self.recipient_user_id, self.category).await?;
+        let link = self.link_url.as_deref();
+
+        if let Some(email) = &self.email
+            && let Some((address, locale)) = &plan.email
+            && let Err(e) = queue_email(pool, &plan, address, locale, email).await
+        {
+            log::error!("Failed to queue email notification to user #{}: {:?}", self.recipient_user_id, e);
+        }
+        if let Some((url, _)) = &plan.webhook
+            && let Err(e) = notification_channel_service::queue_webhook(pool, self.recipient_user_id, url, self.category, &self.message, link).await
+        {
+            log::error!("Failed to queue webhook notification to user #{}: {:?}", self.recipient_user_id, e);
+        }
+        if plan.digest
+            && let Err(e) = notification_channel_service::queue_digest_item(pool, self.recipient_user_id, self.category, &self.message, link).await
+        {
+            log::error!("Failed to queue digest item for user #{}: {:?}", self.recipient_user_id, e);
+        }
+        if !plan.in_app {
+            return Ok(None);
+        }
+
         let mut tx = pool.begin().await?;
 
         let result = sqlx::query(
-            "INSERT INTO notifications (recipient_user_id, message, link_url) VALUES (?, ?, ?)"
+            "INSERT INTO notifications (recipient_user_id, category, message, link_url) VALUES (?, ?, ?, ?)"
         )
             .bind(self.recipient_user_id)
+            .bind(self.category.as_str())
             .bind(&self.message)
             .bind(&self.link_url)
             .execute(&mut *tx)
@@ -103,7 +115,121 @@ impl NotificationBuilder {
             content: notification_json,
         });
 
-        Ok(notification)
+        Ok(Some(notification))
+    }
+}
+
+/// 按收件人的语言渲染邮件模板，放进发件箱由后台任务投递和重试
+async fn queue_email(pool: &MySqlPool, plan: &DispatchPlan, address: &str, locale: &str, email: &EmailContent) -> Result<(), AppError> {
+    let rendered = email_template::render(email.template, Locale::from_stored(locale), &email.vars)?;
+    let mut tx = pool.begin().await?;
+    email_service::queue_email_after(&mut tx, address, &rendered, email.attachments.clone(), plan.email_not_before).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// 通知某个公司的所有在职用户。走哪些通道看每个用户的偏好，失败只记日志，不影响业务流程
+/// 邮件用通用的 notification 模板。发票、退款、争议、取消和付款提醒这些事件有各自的模板，用 notify_company_with_template
+pub async fn notify_company(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    company_id: i32,
+    category: NotificationCategory,
+    subject: &str,
+    message: &str,
+    link: &str,
+) {
+    let vars = [("subject", subject.to_string()), ("message", message.to_string())];
+    notify_company_with_template(pool, chat_server, company_id, category, message, link, "notification", &vars).await;
+}
+
+/// 同上，但邮件用指定的模板，站内通知仍然是 message
+#[allow(clippy::too_many_arguments)]
+pub async fn notify_company_with_template(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    company_id: i32,
+    category: NotificationCategory,
+    message: &str,
+    link: &str,
+    template: &'static str,
+    vars: &[(&'static str, String)],
+) {
+    let users: Vec<(i32,)> = match sqlx::query_as(
+        "SELECT id FROM users WHERE company_id = ? AND is_active = TRUE"
+    )
+        .bind(company_id)
+        .fetch_all(pool)
+        .await
+    {
+        Ok(users) => users,
+        Err(e) => {
+            log::error!("Failed to fetch users of company #{} for notification: {:?}", company_id, e);
+            return;
+        }
+    };
+    notify_users(pool, chat_server, users, category, message, link, template, vars).await;
+}
+
+/// 通知某个公司的所有在职用户，原因、备注这类用户填写的长文本只放在邮件正文里。
+/// notifications.message 只有255个字符，站内通知只留简短的 message，详情点链接查看
+#[allow(clippy::too_many_arguments)]
+pub async fn notify_company_with_details(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    company_id: i32,
+    category: NotificationCategory,
+    subject: &str,
+    message: &str,
+    details: Option<&str>,
+    link: &str,
+) {
+    let vars = [
+        ("subject", subject.to_string()),
+        ("message", message.to_string()),
+        ("details", details.unwrap_or_default().to_string()),
+    ];
+    notify_company_with_template(pool, chat_server, company_id, category, message, link, "notification", &vars).await;
+}
+
+/// 通知所有在职的平台管理员，用于需要人工处理的异常
+pub async fn notify_admins(pool: &MySqlPool, chat_server: &Addr<ChatServer>, subject: &str, message: &str, link: &str) {
+    let users: Vec<(i32,)> = match sqlx::query_as(
+        "SELECT id FROM users WHERE is_admin = TRUE AND is_active = TRUE"
+    )
+        .fetch_all(pool)
+        .await
+    {
+        Ok(users) => users,
+        Err(e) => {
+            log::error!("Failed to fetch admins for notification: {:?}", e);
+            return;
+        }
+    };
+    let vars = [("subject", subject.to_string()), ("message", message.to_string())];
+    notify_users(pool, chat_server, users, NotificationCategory::System, message, link, "notification", &vars).await;
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn notify_users(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    users: Vec<(i32,)>,
+    category: NotificationCategory,
+    message: &str,
+    link: &str,
+    template: &'static str,
+    vars: &[(&'static str, String)],
+) {
+    for (user_id,) in users {
+        if let Err(e) = NotificationBuilder::new(user_id, category, message.to_string())
+            .with_link(link.to_string())
+            .with_email(template, vars)
+            .send(pool, chat_server)
+            .await
+        {
+            log::error!("Failed to send notification to user #{}: {:?}", user_id, e);
+        }
     }
 }
 
@@ -2,9 +2,21 @@
 
 use crate::{
     errors::AppError,
-    models::{order::{PurchaseOrder, UpdateOrderStatusDto}, user::Claims},
+    models::{
+        order::{
+            CreateCancellationRequestDto, OrderCancellationRequest, OrderParty, OrderStatus, OrderStatusHistory,
+            PurchaseOrder, ReorderDto, ReorderPreview, RespondCancellationDto, TransitionGuard, UpdateOrderStatusDto,
+        },
+        money::{self, Currency, Money},
+        user::Claims,
+    },
+    services::{chat_server::ChatServer, notification_service, payment_provider::PaymentProvider, payment_schedule_service, payment_service, payment_terms_service, refund_service, tax_service},
 };
-use sqlx::MySqlPool;use crate::models::rating::RateOrderDto; // <-- 导入
+use crate::models::notification_preference::NotificationCategory;
+use actix::Addr;
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use sqlx::{types::Decimal, MySql, MySqlPool, Transaction};
+use std::str::FromStr;use crate::models::rating::RateOrderDto; // <-- 导入
 pub async fn get_orders_for_user(pool: &MySqlPool, claims: &Claims) -> Result<Vec<PurchaseOrder>, AppError> {
     let sql_query = if claims.company_type == "BUYER" {
         "SELECT po.*, r.title as rfq_title, b.name as buyer_name, s.name as supplier_name
@@ -30,53 +42,475 @@ pub async fn get_orders_for_user(pool: &MySqlPool, claims: &Claims) -> Result<Ve
     Ok(orders)
 }
 
+/// 状态变更前在事务中加锁读取的订单信息
+pub(crate) struct LockedOrder {
+    pub id: i32,
+    pub buyer_company_id: i32,
+    pub supplier_company_id: i32,
+    pub status: String,
+    pub rfq_title: String,
+}
+
+impl LockedOrder {
+    /// 当前公司在订单中的身份，不是订单的任何一方时返回None
+    pub fn party_of(&self, company_id: i32) -> Option<OrderParty> {
+        if company_id == self.buyer_company_id {
+            Some(OrderParty::Buyer)
+        } else if company_id == self.supplier_company_id {
+            Some(OrderParty::Supplier)
+        } else {
+            None
+        }
+    }
+}
+
+pub(crate) async fn lock_order(tx: &mut Transaction<'_, MySql>, order_id: i32) -> Result<LockedOrder, AppError> {
+    let row: Option<(i32, i32, i32, String, String)> = sqlx::query_as(
+        "SELECT po.id, po.buyer_company_id, po.supplier_company_id, po.status, r.title
+         FROM purchase_orders po JOIN rfqs r ON po.rfq_id = r.id
+         WHERE po.id = ? FOR UPDATE"
+    )
+        .bind(order_id)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+    let (id, buyer_company_id, supplier_company_id, status, rfq_title) =
+        row.ok_or_else(|| AppError::BadRequest("Order not found.".to_string()))?;
+    Ok(LockedOrder { id, buyer_company_id, supplier_company_id, status, rfq_title })
+}
+
+/// 新建采购订单需要的信息，授标、再订货、框架协议下达订单都走这里
+pub(crate) struct NewPurchaseOrder<'a> {
+    pub quote_id: i32,
+    pub rfq_id: i32,
+    pub source_order_id: Option<i32>,
+    pub blanket_agreement_id: Option<i32>,
+    pub buyer_company_id: i32,
+    pub supplier_company_id: i32,
+    pub quantity: i32,
+    // 货款小计，不含运费和税
+    pub subtotal: Decimal,
+    pub shipping_amount: Decimal,
+    pub incoterm: Option<&'a str>,
+    pub incoterm_place: Option<&'a str>,
+    pub currency: &'a str,
+    pub promised_delivery_date: NaiveDate,
+    // 写入状态历史的说明
+    pub comment: &'a str,
+}
+
+/// 在事务中创建订单、分配PO编号并写入第一条状态历史。返回订单ID和PO编号
+pub(crate) async fn create_purchase_order(
+    tx: &mut Transaction<'_, MySql>,
+    new: NewPurchaseOrder<'_>,
+    claims: &Claims,
+) -> Result<(i32, String), AppError> {
+    // 按买方所在地的税率计税，税率快照到订单上
+    let tax = tax_service::rate_for_company(&mut **tx, new.buyer_company_id).await?;
+    let totals = tax_service::order_totals(new.subtotal, new.shipping_amount, tax.as_ref().map_or(Decimal::ZERO, |(_, rate)| *rate));
+    Money::new(totals.total, Currency::from_str(new.currency)?)?;
+
+    // 供应商给了账期且买方额度够时，这张订单按账期线下付款
+    let payment_terms_days = payment_terms_service::terms_for_new_order(
+        tx, new.buyer_company_id, new.supplier_company_id, totals.total, new.currency,
+    ).await?;
+    let result = sqlx::query(
+        "INSERT INTO purchase_orders (quote_id, rfq_id, source_order_id, blanket_agreement_id, buyer_company_id, supplier_company_id,
+                                      quantity, subtotal_amount, shipping_amount, tax_name, tax_rate, tax_amount, total_amount,
+                                      currency, incoterm, incoterm_place, promised_delivery_date, payment_terms_days)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+        .bind(new.quote_id)
+        .bind(new.rfq_id)
+        .bind(new.source_order_id)
+        .bind(new.blanket_agreement_id)
+        .bind(new.buyer_company_id)
+        .bind(new.supplier_company_id)
+        .bind(new.quantity)
+        .bind(totals.subtotal)
+        .bind(totals.shipping)
+        .bind(tax.as_ref().map(|(name, _)| name))
+        .bind(tax.as_ref().map_or(Decimal::ZERO, |(_, rate)| *rate))
+        .bind(totals.tax)
+        .bind(totals.total)
+        .bind(new.currency)
+        .bind(new.incoterm)
+        .bind(new.incoterm_place)
+        .bind(new.promised_delivery_date)
+        .bind(payment_terms_days)
+        .execute(&mut **tx)
+        .await?;
+
+    let po_id = result.last_insert_id() as i32;
+    let po_number = format!("PO-{}-{:06}", Utc::now().year(), po_id);
+    sqlx::query("UPDATE purchase_orders SET po_number = ? WHERE id = ?")
+        .bind(&po_number)
+        .bind(po_id)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO order_status_history (order_id, from_status, to_status, actor_user_id, actor_company_id, comment) VALUES (?, NULL, 'PENDING_CONFIRMATION', ?, ?, ?)"
+    )
+        .bind(po_id)
+        .bind(claims.sub)
+        .bind(claims.company_id)
+        .bind(new.comment)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok((po_id, po_number))
+}
+
+/// 在事务中写入新状态和状态历史。权限和状态机检查由调用方负责
+pub(crate) async fn record_transition(
+    tx: &mut Transaction<'_, MySql>,
+    order_id: i32,
+    from_status: Option<&str>,
+    to_status: OrderStatus,
+    actor_user_id: Option<i32>,
+    actor_company_id: Option<i32>,
+    comment: Option<&str>,
+) -> Result<(), AppError> {
+    // 如果状态是COMPLETED，我们同时更新 completed_at 时间戳
+    let sql_query = if to_status == OrderStatus::Completed {
+        "UPDATE purchase_orders SET status = ?, completed_at = CURRENT_TIMESTAMP WHERE id = ?"
+    } else {
+        "UPDATE purchase_orders SET status = ? WHERE id = ?"
+    };
+    sqlx::query(sql_query)
+        .bind(to_status.as_str())
+        .bind(order_id)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO order_status_history (order_id, from_status, to_status, actor_user_id, actor_company_id, comment) VALUES (?, ?, ?, ?, ?, ?)"
+    )
+        .bind(order_id)
+        .bind(from_status)
+        .bind(to_status.as_str())
+        .bind(actor_user_id)
+        .bind(actor_company_id)
+        .bind(comment)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(())
+}
+
+/// 已付款的订单被取消或判定退款时，付款状态改为待退款。返回是否有变更
+pub(crate) async fn mark_refund_pending(tx: &mut Transaction<'_, MySql>, order_id: i32) -> Result<bool, AppError> {
+    let result = sqlx::query(
+        "UPDATE purchase_orders SET payment_status = 'REFUND_PENDING' WHERE id = ? AND payment_status = 'PAID'"
+    )
+        .bind(order_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// 通知订单双方状态已变更
+pub(crate) async fn notify_status_change(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    order: &LockedOrder,
+    to_status: OrderStatus,
+) {
+    let subject = format!("Order #{} status updated", order.id);
+    let message = format!("Order #{} for '{}' is now {}.", order.id, order.rfq_title, to_status.as_str());
+    for company_id in [order.buyer_company_id, order.supplier_company_id] {
+        notification_service::notify_company(pool, chat_server, company_id, NotificationCategory::Order, &subject, &message, "/orders").await;
+    }
+    payment_schedule_service::notify_due_milestones(pool, chat_server, order, to_status).await;
+}
+
 pub async fn update_order_status(
     pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
     order_id: i32,
     dto: UpdateOrderStatusDto,
     claims: &Claims,
-) -> Result<u64, AppError> {
-    // 权限检查：只有供应商才能更新订单状态
-    if claims.company_type != "SUPPLIER" {
-        return Err(AppError::BadRequest("Only suppliers can update order status.".to_string()));
+) -> Result<(), AppError> {
+    // 验证新状态是否合法
+    let to_status = OrderStatus::parse(&dto.status)
+        .ok_or_else(|| AppError::BadRequest("Invalid status provided.".to_string()))?;
+    let comment = dto.comment.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+    if comment.as_ref().is_some_and(|c| c.chars().count() > 500) {
+        return Err(AppError::BadRequest("Comment must be at most 500 characters.".to_string()));
     }
 
-    // 验证新状态是否合法
-    let new_status = dto.status.as_str();
-    if !matches!(new_status, "IN_PRODUCTION" | "SHIPPED" | "COMPLETED") {
-        return Err(AppError::BadRequest("Invalid status provided.".to_string()));
+    let mut tx = pool.begin().await?;
+    let order = lock_order(&mut tx, order_id).await?;
+
+    // 权限检查：必须是订单的采购方或供应商
+    let party = order.party_of(claims.company_id)
+        .ok_or_else(|| AppError::BadRequest("Order not found or you are not authorized to update it.".to_string()))?;
+
+    let from_status = OrderStatus::parse(&order.status)
+        .ok_or_else(|| AppError::BadRequest(format!("Order in status {} cannot be updated.", order.status)))?;
+
+    // 状态机检查：变更是否允许，以及是否由正确的一方触发
+    match from_status.transition_guard(to_status) {
+        None => {
+            return Err(AppError::BadRequest(format!(
+                "Cannot change order status from {} to {}.",
+                from_status.as_str(),
+                to_status.as_str()
+            )));
+        }
+        Some(TransitionGuard::Party(required)) if required != party => {
+            let who = match required {
+                OrderParty::Buyer => "buyer",
+                OrderParty::Supplier => "supplier",
+            };
+            return Err(AppError::BadRequest(format!(
+                "Only the {} can change this order to {}.",
+                who,
+                to_status.as_str()
+            )));
+        }
+        Some(TransitionGuard::MutualConsent) => {
+            return Err(AppError::BadRequest(
+                "Cancelling an order requires a cancellation request accepted by the other party.".to_string(),
+            ));
+        }
+        Some(TransitionGuard::Admin) => {
+            return Err(AppError::BadRequest(
+                "This status change can only be made by an administrator through dispute resolution.".to_string(),
+            ));
+        }
+        Some(TransitionGuard::System) => {
+            return Err(AppError::BadRequest(
+                "This status is set automatically: an order is shipped once shipments cover the full quantity and completed once all goods are received and inspected.".to_string(),
+            ));
+        }
+        Some(TransitionGuard::Party(_)) => {}
+    }
+
+    record_transition(
+        &mut tx,
+        order.id,
+        Some(from_status.as_str()),
+        to_status,
+        Some(claims.sub),
+        Some(claims.company_id),
+        comment.as_deref(),
+    )
+        .await?;
+    tx.commit().await?;
+
+    notify_status_change(pool, chat_server, &order, to_status).await;
+
+    Ok(())
+}
+
+pub(crate) fn trimmed_text(value: Option<String>, max_chars: usize, field: &str) -> Result<Option<String>, AppError> {
+    let value = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    if value.as_ref().is_some_and(|v| v.chars().count() > max_chars) {
+        return Err(AppError::BadRequest(format!("{} must be at most {} characters.", field, max_chars)));
+    }
+    Ok(value)
+}
+
+/// 订单一方发起取消申请，需要另一方同意
+pub async fn request_cancellation(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    order_id: i32,
+    dto: CreateCancellationRequestDto,
+    claims: &Claims,
+) -> Result<i32, AppError> {
+    let reason = trimmed_text(Some(dto.reason), 1000, "Reason")?
+        .ok_or_else(|| AppError::BadRequest("A reason is required to cancel an order.".to_string()))?;
+
+    let mut tx = pool.begin().await?;
+    let order = lock_order(&mut tx, order_id).await?;
+    let party = order.party_of(claims.company_id)
+        .ok_or_else(|| AppError::BadRequest("Order not found or you are not authorized to cancel it.".to_string()))?;
+
+    let status = OrderStatus::parse(&order.status);
+    if status.and_then(|s| s.transition_guard(OrderStatus::Cancelled)) != Some(TransitionGuard::MutualConsent) {
+        return Err(AppError::BadRequest(format!("An order in status {} cannot be cancelled.", order.status)));
+    }
+
+    // 同一订单同时只能有一个待处理的取消申请
+    let pending: Option<(i32,)> = sqlx::query_as(
+        "SELECT id FROM order_cancellation_requests WHERE order_id = ? AND status = 'PENDING'"
+    )
+        .bind(order.id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if pending.is_some() {
+        return Err(AppError::BadRequest("There is already a pending cancellation request for this order.".to_string()));
     }
 
-    // 执行更新，并确保该供应商确实是此订单的供应商
     let result = sqlx::query(
-        "UPDATE purchase_orders SET status = ? WHERE id = ? AND supplier_company_id = ?"
+        "INSERT INTO order_cancellation_requests (order_id, requested_by_company_id, requested_by_user_id, reason) VALUES (?, ?, ?, ?)"
     )
-        .bind(new_status)
-        .bind(order_id)
+        .bind(order.id)
         .bind(claims.company_id)
-        .execute(pool)
+        .bind(claims.sub)
+        .bind(&reason)
+        .execute(&mut *tx)
         .await?;
+    tx.commit().await?;
 
-    // 【新增】如果状态是COMPLETED，我们同时更新 completed_at 时间戳
-    let sql_query = if dto.status == "COMPLETED" {
-        "UPDATE purchase_orders SET status = ?, completed_at = CURRENT_TIMESTAMP WHERE id = ? AND supplier_company_id = ?"
-    } else {
-        "UPDATE purchase_orders SET status = ? WHERE id = ? AND supplier_company_id = ?"
+    let counterparty = match party {
+        OrderParty::Buyer => order.supplier_company_id,
+        OrderParty::Supplier => order.buyer_company_id,
     };
+    let message = format!("A cancellation has been requested for order #{}. Please accept or reject the request.", order.id);
+    let vars = [("order_id", order.id.to_string()), ("rfq_title", order.rfq_title.clone()), ("reason", reason.clone())];
+    notification_service::notify_company_with_template(pool, chat_server, counterparty, NotificationCategory::Order, &message, "/orders", "cancellation_requested", &vars).await;
+
+    Ok(result.last_insert_id() as i32)
+}
 
-    let result = sqlx::query(sql_query)
-        .bind(dto.status)
+pub async fn get_cancellation_requests(
+    pool: &MySqlPool,
+    order_id: i32,
+    claims: &Claims,
+) -> Result<Vec<OrderCancellationRequest>, AppError> {
+    ensure_order_party(pool, order_id, claims).await?;
+
+    let requests = sqlx::query_as(
+        "SELECT cr.*, c.name as requested_by_company_name
+         FROM order_cancellation_requests cr JOIN companies c ON cr.requested_by_company_id = c.id
+         WHERE cr.order_id = ? ORDER BY cr.created_at DESC, cr.id DESC"
+    )
         .bind(order_id)
-        .bind(claims.company_id)
-        .execute(pool)
+        .fetch_all(pool)
         .await?;
+    Ok(requests)
+}
 
-    // 如果没有行被影响，说明订单不存在或该供应商无权修改
-    if result.rows_affected() == 0 {
+/// 另一方同意或拒绝取消申请。同意后订单取消，已付款的订单转为待退款
+pub async fn respond_to_cancellation(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    provider: &dyn PaymentProvider,
+    order_id: i32,
+    request_id: i32,
+    dto: RespondCancellationDto,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    let comment = trimmed_text(dto.comment, 1000, "Comment")?;
+
+    let mut tx = pool.begin().await?;
+    let order = lock_order(&mut tx, order_id).await?;
+    if order.party_of(claims.company_id).is_none() {
         return Err(AppError::BadRequest("Order not found or you are not authorized to update it.".to_string()));
     }
 
-    Ok(result.rows_affected())
+    let request: Option<(i32, String)> = sqlx::query_as(
+        "SELECT requested_by_company_id, reason FROM order_cancellation_requests
+         WHERE id = ? AND order_id = ? AND status = 'PENDING' FOR UPDATE"
+    )
+        .bind(request_id)
+        .bind(order.id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let (requested_by, reason) = request
+        .ok_or_else(|| AppError::BadRequest("Cancellation request not found or already answered.".to_string()))?;
+    if requested_by == claims.company_id {
+        return Err(AppError::BadRequest("You cannot respond to your own cancellation request.".to_string()));
+    }
+
+    let mut refund_pending = false;
+    if dto.accept {
+        // 申请提交后订单可能已经发货，需要再检查一次
+        let from_status = OrderStatus::parse(&order.status)
+            .filter(|s| s.transition_guard(OrderStatus::Cancelled) == Some(TransitionGuard::MutualConsent))
+            .ok_or_else(|| AppError::BadRequest(format!("An order in status {} cannot be cancelled.", order.status)))?;
+        // 先在渠道作废还开着的支付会话，作废不了就不取消
+        payment_service::expire_order_sessions(&mut tx, provider, order.id).await?;
+        // 状态历史的备注最多500字，申请原因可以有1000字，完整原因留在取消申请里
+        let history_comment: String = format!("Cancelled by mutual consent: {}", reason).chars().take(500).collect();
+        record_transition(
+            &mut tx,
+            order.id,
+            Some(from_status.as_str()),
+            OrderStatus::Cancelled,
+            Some(claims.sub),
+            Some(claims.company_id),
+            Some(&history_comment),
+        )
+            .await?;
+        refund_pending = mark_refund_pending(&mut tx, order.id).await?;
+        if refund_pending {
+            // 生成待审批的退款，供应商同意后原路退回
+            // 退款原因最多1000字，完整的取消原因可以通过 cancellation_request_id 查到
+            let refund_reason: String = format!("Order #{} cancelled by mutual consent: {}", order.id, reason).chars().take(1000).collect();
+            refund_service::open_linked_refund(&mut tx, order.id, None, &refund_reason, None, Some(request_id)).await?;
+        }
+    }
+
+    sqlx::query(
+        "UPDATE order_cancellation_requests
+         SET status = ?, responded_by_user_id = ?, response_comment = ?, responded_at = CURRENT_TIMESTAMP
+         WHERE id = ?"
+    )
+        .bind(if dto.accept { "ACCEPTED" } else { "REJECTED" })
+        .bind(claims.sub)
+        .bind(&comment)
+        .bind(request_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    if dto.accept {
+        notify_status_change(pool, chat_server, &order, OrderStatus::Cancelled).await;
+        if refund_pending {
+            let subject = format!("Refund pending for order #{}", order.id);
+            let message = format!("Order #{} was cancelled after payment. The payment will be refunded.", order.id);
+            notification_service::notify_company(pool, chat_server, order.buyer_company_id, NotificationCategory::Order, &subject, &message, "/orders").await;
+            let message = format!("Order #{} was cancelled after payment. Please approve the refund request.", order.id);
+            notification_service::notify_company(pool, chat_server, order.supplier_company_id, NotificationCategory::Order, &subject, &message, "/orders").await;
+        }
+    } else {
+        let message = format!("Your cancellation request for order #{} was rejected.", order.id);
+        let vars = [("order_id", order.id.to_string()), ("comment", comment.clone().unwrap_or_default())];
+        notification_service::notify_company_with_template(pool, chat_server, requested_by, NotificationCategory::Order, &message, "/orders", "cancellation_rejected", &vars).await;
+    }
+
+    Ok(())
+}
+
+/// 校验当前用户的公司是订单的一方
+pub(crate) async fn ensure_order_party(pool: &MySqlPool, order_id: i32, claims: &Claims) -> Result<(), AppError> {
+    let order: Option<(i32,)> = sqlx::query_as(
+        "SELECT id FROM purchase_orders WHERE id = ? AND (buyer_company_id = ? OR supplier_company_id = ?)"
+    )
+        .bind(order_id)
+        .bind(claims.company_id)
+        .bind(claims.company_id)
+        .fetch_optional(pool)
+        .await?;
+    if order.is_none() {
+        return Err(AppError::BadRequest("Order not found or you are not authorized to view it.".to_string()));
+    }
+    Ok(())
+}
+
+/// 订单状态历史，只有订单双方可以查看
+pub async fn get_order_history(
+    pool: &MySqlPool,
+    order_id: i32,
+    claims: &Claims,
+) -> Result<Vec<OrderStatusHistory>, AppError> {
+    ensure_order_party(pool, order_id, claims).await?;
+
+    let history = sqlx::query_as(
+        "SELECT h.*, u.full_name as actor_name
+         FROM order_status_history h LEFT JOIN users u ON h.actor_user_id = u.id
+         WHERE h.order_id = ? ORDER BY h.created_at ASC, h.id ASC"
+    )
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(history)
 }
 
 // --- 【新增】rate_order 函数 ---
@@ -94,15 +528,17 @@ pub async fn rate_order(
     if !(1..=5).contains(&dto.quality_rating) || !(1..=5).contains(&dto.communication_rating) {
         return Err(AppError::BadRequest("Rating must be between 1 and 5.".to_string()));
     }
+    let review = trimmed_text(dto.review, 2000, "Review")?;
 
     // 执行更新，并确保该采购方是此订单的所有者，且订单状态为COMPLETED，且尚未评分
     let result = sqlx::query(
         "UPDATE purchase_orders
-         SET quality_rating = ?, communication_rating = ?
-         WHERE id = ? AND buyer_company_id = ? AND status = 'COMPLETED' AND quality_rating IS NULL"
+         SET quality_rating = ?, communication_rating = ?, review_text = ?, rated_at = CURRENT_TIMESTAMP
+         WHERE id = ? AND buyer_company_id = ? AND status = 'COMPLETED' AND rated_at IS NULL"
     )
         .bind(dto.quality_rating)
         .bind(dto.communication_rating)
+        .bind(&review)
         .bind(order_id)
         .bind(claims.company_id)
         .execute(pool)
@@ -113,4 +549,141 @@ pub async fn rate_order(
     }
 
     Ok(result.rows_affected())
-}
\ No newline at end of file
+}
+/// 再订货的原订单条款
+#[derive(sqlx::FromRow)]
+struct ReorderSource {
+    quote_id: i32,
+    rfq_id: i32,
+    supplier_company_id: i32,
+    supplier_name: String,
+    rfq_title: String,
+    quantity: i32,
+    subtotal_amount: Decimal,
+    shipping_amount: Decimal,
+    currency: String,
+    incoterm: Option<String>,
+    incoterm_place: Option<String>,
+    lead_time_days: i32,
+}
+
+/// 按原订单单价计算新数量的货款，返回 (单价, 货款小计)。数量不变时小计保持原值
+fn reorder_amounts(source_total: Decimal, source_quantity: i32, quantity: i32) -> Result<(Decimal, Decimal), AppError> {
+    if quantity <= 0 || source_quantity <= 0 {
+        return Err(AppError::BadRequest("Quantity must be greater than zero.".to_string()));
+    }
+    let unit_price = (source_total / Decimal::from(source_quantity)).round_dp(4);
+    let total = if quantity == source_quantity {
+        source_total
+    } else {
+        money::round_amount(unit_price * Decimal::from(quantity))
+    };
+    Ok((unit_price, total))
+}
+
+/// 读取可以再订货的原订单（已完成、属于当前采购方）并计算新订单的条款
+async fn load_reorder(pool: &MySqlPool, order_id: i32, quantity: Option<i32>, claims: &Claims) -> Result<(ReorderSource, ReorderPreview), AppError> {
+    let source: ReorderSource = sqlx::query_as(
+        "SELECT po.quote_id, po.rfq_id, po.supplier_company_id, s.name as supplier_name, r.title as rfq_title,
+                po.quantity, po.subtotal_amount, po.shipping_amount, po.currency, po.incoterm, po.incoterm_place, q.lead_time_days
+         FROM purchase_orders po
+         JOIN rfqs r ON po.rfq_id = r.id
+         JOIN quotes q ON po.quote_id = q.id
+         JOIN companies s ON po.supplier_company_id = s.id
+         WHERE po.id = ? AND po.buyer_company_id = ? AND po.status = 'COMPLETED'"
+    )
+        .bind(order_id)
+        .bind(claims.company_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Only your completed orders can be reordered.".to_string()))?;
+
+    let quantity = quantity.unwrap_or(source.quantity);
+    let (unit_price, subtotal) = reorder_amounts(source.subtotal_amount, source.quantity, quantity)?;
+    // 运费和贸易术语沿用原订单，税率按现在的配置
+    let tax_rate = tax_service::rate_for_company(pool, claims.company_id).await?.map_or(Decimal::ZERO, |(_, rate)| rate);
+    let totals = tax_service::order_totals(subtotal, source.shipping_amount, tax_rate);
+    Money::new(totals.total, Currency::from_str(&source.currency)?)?;
+
+    let preview = ReorderPreview {
+        source_order_id: order_id,
+        supplier_company_id: source.supplier_company_id,
+        supplier_name: source.supplier_name.clone(),
+        rfq_title: source.rfq_title.clone(),
+        quantity,
+        unit_price,
+        subtotal_amount: totals.subtotal,
+        shipping_amount: totals.shipping,
+        tax_amount: totals.tax,
+        total_amount: totals.total,
+        currency: source.currency.clone(),
+        incoterm: source.incoterm.clone(),
+        promised_delivery_date: Utc::now().date_naive() + Duration::days(i64::from(source.lead_time_days)),
+    };
+    Ok((source, preview))
+}
+
+/// 再订货前预览新订单的条款，供采购方确认
+pub async fn preview_reorder(pool: &MySqlPool, order_id: i32, dto: ReorderDto, claims: &Claims) -> Result<ReorderPreview, AppError> {
+    let (_, preview) = load_reorder(pool, order_id, dto.quantity, claims).await?;
+    Ok(preview)
+}
+
+/// 采购方确认后按原订单的供应商和条款生成新订单，新订单仍需供应商确认
+pub async fn reorder(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    order_id: i32,
+    dto: ReorderDto,
+    claims: &Claims,
+) -> Result<(i32, String), AppError> {
+    let (source, preview) = load_reorder(pool, order_id, dto.quantity, claims).await?;
+
+    let mut tx = pool.begin().await?;
+    let comment = format!("Reorder of order #{}", order_id);
+    let (new_order_id, po_number) = create_purchase_order(
+        &mut tx,
+        NewPurchaseOrder {
+            quote_id: source.quote_id,
+            rfq_id: source.rfq_id,
+            source_order_id: Some(order_id),
+            blanket_agreement_id: None,
+            buyer_company_id: claims.company_id,
+            supplier_company_id: source.supplier_company_id,
+            quantity: preview.quantity,
+            subtotal: preview.subtotal_amount,
+            shipping_amount: preview.shipping_amount,
+            incoterm: source.incoterm.as_deref(),
+            incoterm_place: source.incoterm_place.as_deref(),
+            currency: &preview.currency,
+            promised_delivery_date: preview.promised_delivery_date,
+            comment: &comment,
+        },
+        claims,
+    )
+        .await?;
+    tx.commit().await?;
+
+    let subject = format!("New purchase order {}", po_number);
+    let message = format!(
+        "Purchase order {} is a reorder of order #{} ('{}'): {} units for {:.2} {}, delivery by {}. Please confirm the order.",
+        po_number, order_id, source.rfq_title, preview.quantity, preview.total_amount, preview.currency, preview.promised_delivery_date
+    );
+    notification_service::notify_company(pool, chat_server, source.supplier_company_id, NotificationCategory::Order, &subject, &message, "/orders").await;
+
+    Ok((new_order_id, po_number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reorder_amounts() {
+        let total = Decimal::from_str("100.00").unwrap();
+        assert_eq!(reorder_amounts(total, 3, 3).unwrap(), (Decimal::from_str("33.3333").unwrap(), total));
+        assert_eq!(reorder_amounts(total, 3, 6).unwrap().1, Decimal::from_str("200.00").unwrap());
+        assert_eq!(reorder_amounts(total, 4, 1).unwrap().1, Decimal::from_str("25.00").unwrap());
+        assert!(reorder_amounts(total, 3, 0).is_err());
+    }
+}
@@ -0,0 +1,681 @@
+// src/services/payment_provider.rs
+// 支付渠道抽象：业务代码只依赖 PaymentProvider，线上用Stripe，本地开发和集成测试用不联网的Mock
+use crate::{
+    errors::AppError,
+    models::money::{Currency, Money},
+};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+};
+use std::str::FromStr;
+use stripe::{
+    CheckoutSession, CheckoutSessionId, CheckoutSessionMode, CheckoutSessionPaymentStatus, Client, CreateCheckoutSession, CreateCheckoutSessionLineItems,
+    CreateCheckoutSessionLineItemsPriceData, CreateCheckoutSessionLineItemsPriceDataProductData, CreateRefund, EventObject, EventType,
+    PaymentIntentId, Refund, RequestStrategy, Webhook,
+};
+
+/// 创建支付会话需要的信息
+#[derive(Debug, Clone)]
+pub struct CheckoutRequest {
+    pub amount: Money,
+    // 显示在支付页面上的名称
+    pub product_name: String,
+    // 金额明细（货款、运费、税），合计等于 amount；为空时整笔显示为一行
+    pub line_items: Vec<CheckoutLineItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckoutLineItem {
+    pub name: String,
+    pub amount: Money,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckoutSessionInfo {
+    pub session_id: String,
+    // 托管支付页面的地址，Mock没有
+    pub url: Option<String>,
+}
+
+/// 渠道那边支付会话的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckoutState {
+    // 还没付款，或付款还在处理中
+    Open,
+    Paid,
+    Expired,
+}
+
+impl CheckoutState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckoutState::Open => "OPEN",
+            CheckoutState::Paid => "PAID",
+            CheckoutState::Expired => "EXPIRED",
+        }
+    }
+}
+
+/// 对账时从渠道查到的支付会话
+#[derive(Debug, Clone)]
+pub struct CheckoutSessionDetails {
+    pub state: CheckoutState,
+    pub payment_reference: Option<String>,
+    // 渠道实际收取的金额（分）
+    pub amount_total: Option<i64>,
+}
+
+/// 退款请求。idempotency_key 保证同一笔退款重试时渠道只退一次
+#[derive(Debug, Clone)]
+pub struct RefundRequest {
+    pub payment_reference: String,
+    pub amount: Money,
+    pub idempotency_key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RefundInfo {
+    pub refund_id: String,
+    // 渠道还在处理，结果稍后通过Webhook通知
+    pub pending: bool,
+}
+
+/// 业务关心的支付事件。payment_reference 是渠道的付款ID（Stripe的PaymentIntent），退款和拒付靠它关联
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentEventKind {
+    CheckoutCompleted { session_id: String, payment_reference: Option<String> },
+    CheckoutFailed { session_id: String },
+    CheckoutExpired { session_id: String },
+    // amount_refunded 是累计退款金额（分）
+    Refunded { payment_reference: String, amount_refunded: i64, fully_refunded: bool },
+    DisputeOpened { payment_reference: String, reason: String },
+    // 其他事件只确认收到
+    Ignored,
+}
+
+/// 验签后的渠道事件
+#[derive(Debug, Clone)]
+pub struct PaymentEvent {
+    pub id: String,
+    pub event_type: String,
+    pub kind: PaymentEventKind,
+}
+
+#[async_trait]
+pub trait PaymentProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn create_checkout_session(&self, request: CheckoutRequest) -> Result<CheckoutSessionInfo, AppError>;
+
+    /// 向渠道查询支付会话的当前状态，Webhook丢失时对账用
+    async fn retrieve_checkout_session(&self, session_id: &str) -> Result<CheckoutSessionDetails, AppError>;
+
+    /// 让还没付款的支付会话失效，返回会话最终的状态。已经付过款的会话无法作废，返回 Paid
+    async fn expire_checkout_session(&self, session_id: &str) -> Result<CheckoutState, AppError>;
+
+    /// 对一笔已完成的付款发起（部分）退款
+    async fn refund(&self, request: RefundRequest) -> Result<RefundInfo, AppError>;
+
+    /// 校验Webhook签名并解析事件，签名不对返回 BadRequest
+    fn parse_webhook(&self, payload: &str, signature: &str) -> Result<PaymentEvent, AppError>;
+}
+
+/// 按 PAYMENT_PROVIDER 选择支付渠道：stripe（默认）或 mock
+pub fn provider_from_env() -> Arc<dyn PaymentProvider> {
+    match env::var("PAYMENT_PROVIDER").unwrap_or_default().to_lowercase().as_str() {
+        "mock" => {
+            log::warn!("Using the mock payment provider; no real payments will be taken.");
+            Arc::new(MockPaymentProvider::from_env())
+        }
+        _ => Arc::new(StripePaymentProvider::from_env()),
+    }
+}
+
+// ---------------- Stripe ----------------
+
+/// Stripe 配置缺失时不在启动时崩溃，而是在调用时返回错误
+pub struct StripePaymentProvider {
+    client: Option<Client>,
+    webhook_secret: Option<String>,
+    frontend_url: Option<String>,
+}
+
+impl StripePaymentProvider {
+    pub fn from_env() -> Self {
+        let provider = Self {
+            client: env::var("STRIPE_SECRET_KEY").ok().map(Client::new),
+            webhook_secret: env::var("STRIPE_WEBHOOK_SECRET").ok(),
+            frontend_url: env::var("FRONTEND_URL").ok(),
+        };
+        if provider.client.is_none() || provider.webhook_secret.is_none() || provider.frontend_url.is_none() {
+            log::warn!("Stripe is not fully configured (STRIPE_SECRET_KEY, STRIPE_WEBHOOK_SECRET, FRONTEND_URL); payments will fail.");
+        }
+        provider
+    }
+}
+
+fn not_configured(name: &str) -> AppError {
+    AppError::InternalServerError(format!("{} is not configured", name))
+}
+
+// 订单币种映射到Stripe币种
+fn stripe_currency(currency: Currency) -> stripe::Currency {
+    match currency {
+        Currency::USD => stripe::Currency::USD,
+        Currency::CNY => stripe::Currency::CNY,
+        Currency::EUR => stripe::Currency::EUR,
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for StripePaymentProvider {
+    fn name(&self) -> &'static str {
+        "stripe"
+    }
+
+    async fn create_checkout_session(&self, request: CheckoutRequest) -> Result<CheckoutSessionInfo, AppError> {
+        let client = self.client.as_ref().ok_or_else(|| not_configured("STRIPE_SECRET_KEY"))?;
+        let frontend_url = self.frontend_url.as_ref().ok_or_else(|| not_configured("FRONTEND_URL"))?;
+        // 金额精确转换为分，不经过浮点数
+        let unit_amount = request.amount.to_minor_units()?;
+
+        let success_url = format!("{}/payment/success?session_id={{CHECKOUT_SESSION_ID}}", frontend_url);
+        let cancel_url = format!("{}/orders", frontend_url);
+
+        let mut params = CreateCheckoutSession::new();
+        params.success_url = Some(&*success_url);
+        params.cancel_url = Some(&*cancel_url);
+        params.mode = Some(CheckoutSessionMode::Payment);
+        let lines = if request.line_items.is_empty() {
+            vec![(request.product_name, unit_amount)]
+        } else {
+            request.line_items.into_iter()
+                .map(|item| Ok((item.name, item.amount.to_minor_units()?)))
+                .collect::<Result<Vec<_>, AppError>>()?
+        };
+        params.line_items = Some(lines.into_iter().map(|(name, unit_amount)| CreateCheckoutSessionLineItems {
+            price_data: Some(CreateCheckoutSessionLineItemsPriceData {
+                currency: stripe_currency(request.amount.currency()),
+                product_data: Some(CreateCheckoutSessionLineItemsPriceDataProductData {
+                    name,
+                    ..Default::default()
+                }),
+                unit_amount: Some(unit_amount),
+                ..Default::default()
+            }),
+            quantity: Some(1),
+            ..Default::default()
+        }).collect());
+
+        let session = CheckoutSession::create(client, params).await
+            .map_err(|e| AppError::InternalServerError(format!("Stripe error: {}", e)))?;
+
+        Ok(CheckoutSessionInfo { session_id: session.id.to_string(), url: session.url })
+    }
+
+    async fn retrieve_checkout_session(&self, session_id: &str) -> Result<CheckoutSessionDetails, AppError> {
+        let client = self.client.as_ref().ok_or_else(|| not_configured("STRIPE_SECRET_KEY"))?;
+        let id = CheckoutSessionId::from_str(session_id)
+            .map_err(|_| AppError::BadRequest("Invalid checkout session id.".to_string()))?;
+        let session = CheckoutSession::retrieve(client, &id, &[]).await
+            .map_err(|e| AppError::InternalServerError(format!("Stripe error: {}", e)))?;
+
+        // 异步支付方式会话已完成但钱还没到，按未付款处理
+        let state = match (session.status, session.payment_status) {
+            (_, CheckoutSessionPaymentStatus::Paid | CheckoutSessionPaymentStatus::NoPaymentRequired) => CheckoutState::Paid,
+            (Some(stripe::CheckoutSessionStatus::Expired), _) => CheckoutState::Expired,
+            _ => CheckoutState::Open,
+        };
+        Ok(CheckoutSessionDetails {
+            state,
+            payment_reference: session.payment_intent.map(|intent| intent.id().to_string()),
+            amount_total: session.amount_total,
+        })
+    }
+
+    async fn expire_checkout_session(&self, session_id: &str) -> Result<CheckoutState, AppError> {
+        let details = self.retrieve_checkout_session(session_id).await?;
+        if details.state != CheckoutState::Open {
+            return Ok(details.state);
+        }
+        let client = self.client.as_ref().ok_or_else(|| not_configured("STRIPE_SECRET_KEY"))?;
+        let id = CheckoutSessionId::from_str(session_id)
+            .map_err(|_| AppError::BadRequest("Invalid checkout session id.".to_string()))?;
+        CheckoutSession::expire(client, &id).await
+            .map_err(|e| AppError::InternalServerError(format!("Stripe error: {}", e)))?;
+        Ok(CheckoutState::Expired)
+    }
+
+    async fn refund(&self, request: RefundRequest) -> Result<RefundInfo, AppError> {
+        let client = self.client.as_ref().ok_or_else(|| not_configured("STRIPE_SECRET_KEY"))?;
+        let payment_intent = PaymentIntentId::from_str(&request.payment_reference)
+            .map_err(|_| AppError::BadRequest("Invalid payment reference.".to_string()))?;
+
+        let mut params = CreateRefund::new();
+        params.payment_intent = Some(payment_intent);
+        params.amount = Some(request.amount.to_minor_units()?);
+
+        let client = client.clone().with_strategy(RequestStrategy::Idempotent(request.idempotency_key));
+        let refund = Refund::create(&client, params).await
+            .map_err(|e| AppError::InternalServerError(format!("Stripe error: {}", e)))?;
+
+        let pending = matches!(refund.status.as_deref(), Some("pending") | Some("requires_action"));
+        if refund.status.as_deref() == Some("failed") || refund.status.as_deref() == Some("canceled") {
+            return Err(AppError::InternalServerError(format!("Stripe refund {} failed", refund.id)));
+        }
+        Ok(RefundInfo { refund_id: refund.id.to_string(), pending })
+    }
+
+    fn parse_webhook(&self, payload: &str, signature: &str) -> Result<PaymentEvent, AppError> {
+        let secret = self.webhook_secret.as_ref().ok_or_else(|| not_configured("STRIPE_WEBHOOK_SECRET"))?;
+        let event = Webhook::construct_event(payload, signature, secret)
+            .map_err(|e| AppError::BadRequest(format!("Invalid Stripe signature: {}", e)))?;
+
+        let kind = match (event.type_, event.data.object) {
+            (EventType::CheckoutSessionCompleted, EventObject::CheckoutSession(session)) => PaymentEventKind::CheckoutCompleted {
+                session_id: session.id.to_string(),
+                payment_reference: session.payment_intent.map(|intent| intent.id().to_string()),
+            },
+            (EventType::CheckoutSessionAsyncPaymentFailed, EventObject::CheckoutSession(session)) => {
+                PaymentEventKind::CheckoutFailed { session_id: session.id.to_string() }
+            }
+            (EventType::CheckoutSessionExpired, EventObject::CheckoutSession(session)) => {
+                PaymentEventKind::CheckoutExpired { session_id: session.id.to_string() }
+            }
+            (EventType::ChargeRefunded, EventObject::Charge(charge)) => match charge.payment_intent {
+                Some(intent) => PaymentEventKind::Refunded {
+                    payment_reference: intent.id().to_string(),
+                    amount_refunded: charge.amount_refunded,
+                    fully_refunded: charge.refunded,
+                },
+                None => PaymentEventKind::Ignored,
+            },
+            (EventType::ChargeDisputeCreated, EventObject::Dispute(dispute)) => match dispute.payment_intent {
+                Some(intent) => PaymentEventKind::DisputeOpened { payment_reference: intent.id().to_string(), reason: dispute.reason },
+                None => PaymentEventKind::Ignored,
+            },
+            _ => PaymentEventKind::Ignored,
+        };
+        Ok(PaymentEvent { id: event.id.to_string(), event_type: event.type_.to_string(), kind })
+    }
+}
+
+// ---------------- Mock ----------------
+
+/// Mock 支付会话
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockSession {
+    pub amount: Money,
+    pub product_name: String,
+    pub line_items: Vec<CheckoutLineItem>,
+    // 模拟付款成功后生成
+    pub payment_reference: Option<String>,
+    pub expired: bool,
+}
+
+/// 不联网的支付渠道。会话只保存在内存里，Webhook 用和Stripe一样的 `t=...,v1=...` HMAC-SHA256 签名
+pub struct MockPaymentProvider {
+    // Mock 渠道的 Webhook 接口谁都能调，没有显式配置密钥时拒绝所有Webhook
+    webhook_secret: Option<String>,
+    sessions: Mutex<HashMap<String, MockSession>>,
+    // 按 idempotency_key 记录退款，重试不会重复退
+    refunds: Mutex<HashMap<String, MockRefund>>,
+}
+
+#[derive(Debug, Clone)]
+struct MockRefund {
+    refund_id: String,
+    payment_reference: String,
+    amount: i64,
+}
+
+/// 签名时间戳允许的误差（秒），和Stripe默认值一致
+const SIGNATURE_TOLERANCE_SECS: i64 = 300;
+
+impl MockPaymentProvider {
+    /// 和 Stripe 一样，缺少 MOCK_WEBHOOK_SECRET 不在启动时崩溃，而是在收到Webhook时返回错误
+    pub fn from_env() -> Self {
+        let secret = env::var("MOCK_WEBHOOK_SECRET").ok().filter(|s| !s.trim().is_empty());
+        if secret.is_none() {
+            log::error!("MOCK_WEBHOOK_SECRET is not set; mock payment webhooks will be rejected.");
+        }
+        Self::with_secret(secret)
+    }
+
+    fn with_secret(webhook_secret: Option<String>) -> Self {
+        Self { webhook_secret, sessions: Mutex::new(HashMap::new()), refunds: Mutex::new(HashMap::new()) }
+    }
+
+    fn verify_signature(&self, payload: &str, signature: &str, now: i64) -> Result<(), AppError> {
+        let secret = self.webhook_secret.as_ref().ok_or_else(|| not_configured("MOCK_WEBHOOK_SECRET"))?;
+        let invalid = || AppError::BadRequest("Invalid webhook signature".to_string());
+        let mut timestamp = None;
+        let mut signatures = Vec::new();
+        for part in signature.split(',') {
+            match part.trim().split_once('=') {
+                Some(("t", t)) => timestamp = t.parse::<i64>().ok(),
+                Some(("v1", v)) => signatures.push(v),
+                _ => {}
+            }
+        }
+        let timestamp = timestamp.ok_or_else(invalid)?;
+        if (now - timestamp).abs() > SIGNATURE_TOLERANCE_SECS {
+            return Err(invalid());
+        }
+        for candidate in signatures {
+            let Ok(bytes) = hex::decode(candidate) else { continue };
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|_| invalid())?;
+            mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+            // verify_slice 是常数时间比较
+            if mac.verify_slice(&bytes).is_ok() {
+                return Ok(());
+            }
+        }
+        Err(invalid())
+    }
+}
+
+// 模拟支付结果的辅助方法，集成测试里用来驱动Webhook
+#[cfg_attr(not(test), allow(dead_code))]
+impl MockPaymentProvider {
+    pub fn new(webhook_secret: &str) -> Self {
+        Self::with_secret(Some(webhook_secret.to_string()))
+    }
+
+    pub fn session(&self, session_id: &str) -> Option<MockSession> {
+        self.sessions.lock().ok()?.get(session_id).cloned()
+    }
+
+    fn hmac_hex(&self, timestamp: i64, payload: &str) -> String {
+        let secret = self.webhook_secret.as_deref().unwrap_or_default();
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// 生成 Stripe-Signature 头
+    pub fn sign(&self, payload: &str, timestamp: i64) -> String {
+        format!("t={},v1={}", timestamp, self.hmac_hex(timestamp, payload))
+    }
+
+    /// 模拟一个渠道事件，返回 (payload, 签名)，可以直接POST到Webhook接口
+    pub fn simulate_event(&self, event_type: &str, object: serde_json::Value) -> (String, String) {
+        let payload = serde_json::json!({
+            "id": format!("evt_mock_{}", uuid::Uuid::new_v4().simple()),
+            "type": event_type,
+            "data": { "object": object },
+        })
+            .to_string();
+        let signature = self.sign(&payload, chrono::Utc::now().timestamp());
+        (payload, signature)
+    }
+
+    /// 模拟买方在支付页面付款成功，同时生成付款ID
+    pub fn complete_checkout(&self, session_id: &str) -> (String, String) {
+        let payment_reference = format!("mock_pi_{}", uuid::Uuid::new_v4().simple());
+        if let Ok(mut sessions) = self.sessions.lock()
+            && let Some(session) = sessions.get_mut(session_id)
+        {
+            session.payment_reference = Some(payment_reference.clone());
+        }
+        self.simulate_event(
+            "checkout.session.completed",
+            serde_json::json!({ "id": session_id, "payment_intent": payment_reference }),
+        )
+    }
+
+    /// 模拟付款失败
+    pub fn fail_checkout(&self, session_id: &str) -> (String, String) {
+        self.simulate_event("checkout.session.async_payment_failed", serde_json::json!({ "id": session_id }))
+    }
+
+    /// 模拟支付会话过期
+    pub fn expire_checkout(&self, session_id: &str) -> (String, String) {
+        if let Ok(mut sessions) = self.sessions.lock()
+            && let Some(session) = sessions.get_mut(session_id)
+        {
+            session.expired = true;
+        }
+        self.simulate_event("checkout.session.expired", serde_json::json!({ "id": session_id }))
+    }
+
+    /// 模拟渠道的退款事件，amount_refunded 为累计退款金额（分）
+    pub fn charge_refunded(&self, payment_reference: &str, amount_refunded: i64, fully_refunded: bool) -> (String, String) {
+        self.simulate_event(
+            "charge.refunded",
+            serde_json::json!({ "payment_intent": payment_reference, "amount_refunded": amount_refunded, "refunded": fully_refunded }),
+        )
+    }
+
+    /// 模拟买方发卡行发起拒付
+    pub fn open_dispute(&self, payment_reference: &str, reason: &str) -> (String, String) {
+        self.simulate_event("charge.dispute.created", serde_json::json!({ "payment_intent": payment_reference, "reason": reason }))
+    }
+}
+
+#[async_trait]
+impl PaymentProvider for MockPaymentProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn create_checkout_session(&self, request: CheckoutRequest) -> Result<CheckoutSessionInfo, AppError> {
+        request.amount.to_minor_units()?;
+        let session_id = format!("mock_cs_{}", uuid::Uuid::new_v4().simple());
+        self.sessions
+            .lock()
+            .map_err(|_| AppError::InternalServerError("Mock payment provider state is poisoned".to_string()))?
+            .insert(session_id.clone(), MockSession {
+                amount: request.amount,
+                product_name: request.product_name,
+                line_items: request.line_items,
+                payment_reference: None,
+                expired: false,
+            });
+        Ok(CheckoutSessionInfo { session_id, url: None })
+    }
+
+    async fn retrieve_checkout_session(&self, session_id: &str) -> Result<CheckoutSessionDetails, AppError> {
+        let session = self.sessions
+            .lock()
+            .map_err(|_| AppError::InternalServerError("Mock payment provider state is poisoned".to_string()))?
+            .get(session_id)
+            .cloned()
+            .ok_or_else(|| AppError::BadRequest("Unknown checkout session.".to_string()))?;
+        let state = match (&session.payment_reference, session.expired) {
+            (Some(_), _) => CheckoutState::Paid,
+            (None, true) => CheckoutState::Expired,
+            (None, false) => CheckoutState::Open,
+        };
+        Ok(CheckoutSessionDetails { state, payment_reference: session.payment_reference, amount_total: Some(session.amount.to_minor_units()?) })
+    }
+
+    async fn expire_checkout_session(&self, session_id: &str) -> Result<CheckoutState, AppError> {
+        let mut sessions = self.sessions
+            .lock()
+            .map_err(|_| AppError::InternalServerError("Mock payment provider state is poisoned".to_string()))?;
+        // 重启后内存里没有的会话当作已过期
+        let Some(session) = sessions.get_mut(session_id) else {
+            return Ok(CheckoutState::Expired);
+        };
+        if session.payment_reference.is_some() {
+            return Ok(CheckoutState::Paid);
+        }
+        session.expired = true;
+        Ok(CheckoutState::Expired)
+    }
+
+    async fn refund(&self, request: RefundRequest) -> Result<RefundInfo, AppError> {
+        let poisoned = || AppError::InternalServerError("Mock payment provider state is poisoned".to_string());
+        let paid = self.sessions
+            .lock()
+            .map_err(|_| poisoned())?
+            .values()
+            .find(|session| session.payment_reference.as_deref() == Some(request.payment_reference.as_str()))
+            .map(|session| session.amount.to_minor_units())
+            .transpose()?
+            .ok_or_else(|| AppError::BadRequest("Unknown payment reference.".to_string()))?;
+
+        let mut refunds = self.refunds.lock().map_err(|_| poisoned())?;
+        if let Some(refund) = refunds.get(&request.idempotency_key) {
+            return Ok(RefundInfo { refund_id: refund.refund_id.clone(), pending: false });
+        }
+        // 和真实渠道一样，累计退款不能超过付款金额
+        let amount = request.amount.to_minor_units()?;
+        let already_refunded: i64 = refunds
+            .values()
+            .filter(|refund| refund.payment_reference == request.payment_reference)
+            .map(|refund| refund.amount)
+            .sum();
+        if already_refunded + amount > paid {
+            return Err(AppError::BadRequest("Refund exceeds the captured amount.".to_string()));
+        }
+
+        let refund_id = format!("mock_re_{}", uuid::Uuid::new_v4().simple());
+        refunds.insert(
+            request.idempotency_key,
+            MockRefund { refund_id: refund_id.clone(), payment_reference: request.payment_reference, amount },
+        );
+        Ok(RefundInfo { refund_id, pending: false })
+    }
+
+    fn parse_webhook(&self, payload: &str, signature: &str) -> Result<PaymentEvent, AppError> {
+        self.verify_signature(payload, signature, chrono::Utc::now().timestamp())?;
+
+        let value: serde_json::Value = serde_json::from_str(payload)
+            .map_err(|_| AppError::BadRequest("Invalid webhook payload".to_string()))?;
+        let id = value["id"].as_str().unwrap_or_default().to_string();
+        let event_type = value["type"].as_str().unwrap_or_default().to_string();
+        let object = &value["data"]["object"];
+        let text = |field: &str| object[field].as_str().map(str::to_string);
+
+        let kind = match (event_type.as_str(), text("id"), text("payment_intent")) {
+            ("checkout.session.completed", Some(session_id), payment_reference) => {
+                PaymentEventKind::CheckoutCompleted { session_id, payment_reference }
+            }
+            ("checkout.session.async_payment_failed", Some(session_id), _) => PaymentEventKind::CheckoutFailed { session_id },
+            ("checkout.session.expired", Some(session_id), _) => PaymentEventKind::CheckoutExpired { session_id },
+            ("charge.refunded", _, Some(payment_reference)) => PaymentEventKind::Refunded {
+                payment_reference,
+                amount_refunded: object["amount_refunded"].as_i64().unwrap_or_default(),
+                fully_refunded: object["refunded"].as_bool().unwrap_or_default(),
+            },
+            ("charge.dispute.created", _, Some(payment_reference)) => {
+                PaymentEventKind::DisputeOpened { payment_reference, reason: text("reason").unwrap_or_default() }
+            }
+            _ => PaymentEventKind::Ignored,
+        };
+        Ok(PaymentEvent { id, event_type, kind })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_mock_checkout_and_signed_webhook() {
+        let provider = MockPaymentProvider::new("whsec_test");
+        let amount = Money::parse("12.50", Currency::EUR).unwrap();
+        let session = provider
+            .create_checkout_session(CheckoutRequest { amount, product_name: "Brackets".to_string(), line_items: Vec::new() })
+            .await
+            .unwrap();
+        assert!(session.session_id.starts_with("mock_cs_"));
+        assert_eq!(provider.session(&session.session_id).unwrap().amount, amount);
+
+        let (payload, signature) = provider.complete_checkout(&session.session_id);
+        let event = provider.parse_webhook(&payload, &signature).unwrap();
+        let payment_reference = provider.session(&session.session_id).unwrap().payment_reference.unwrap();
+        assert_eq!(
+            event.kind,
+            PaymentEventKind::CheckoutCompleted { session_id: session.session_id.clone(), payment_reference: Some(payment_reference.clone()) }
+        );
+        assert!(event.id.starts_with("evt_mock_"));
+
+        let (payload, signature) = provider.fail_checkout(&session.session_id);
+        let event = provider.parse_webhook(&payload, &signature).unwrap();
+        assert_eq!(event.kind, PaymentEventKind::CheckoutFailed { session_id: session.session_id.clone() });
+
+        let (payload, signature) = provider.expire_checkout(&session.session_id);
+        let event = provider.parse_webhook(&payload, &signature).unwrap();
+        assert_eq!(event.kind, PaymentEventKind::CheckoutExpired { session_id: session.session_id });
+
+        let (payload, signature) = provider.charge_refunded(&payment_reference, 500, false);
+        let event = provider.parse_webhook(&payload, &signature).unwrap();
+        assert_eq!(
+            event.kind,
+            PaymentEventKind::Refunded { payment_reference: payment_reference.clone(), amount_refunded: 500, fully_refunded: false }
+        );
+
+        let (payload, signature) = provider.open_dispute(&payment_reference, "fraudulent");
+        let event = provider.parse_webhook(&payload, &signature).unwrap();
+        assert_eq!(event.kind, PaymentEventKind::DisputeOpened { payment_reference, reason: "fraudulent".to_string() });
+    }
+
+    #[actix_web::test]
+    async fn test_mock_expire_checkout_session() {
+        let provider = MockPaymentProvider::new("whsec_test");
+        let amount = Money::parse("40.00", Currency::USD).unwrap();
+        let request = || CheckoutRequest { amount, product_name: "Bolts".to_string(), line_items: Vec::new() };
+        let open = provider.create_checkout_session(request()).await.unwrap();
+        assert_eq!(provider.expire_checkout_session(&open.session_id).await.unwrap(), CheckoutState::Expired);
+        assert_eq!(provider.retrieve_checkout_session(&open.session_id).await.unwrap().state, CheckoutState::Expired);
+
+        // 已经付款的会话不能作废
+        let paid = provider.create_checkout_session(request()).await.unwrap();
+        provider.complete_checkout(&paid.session_id);
+        assert_eq!(provider.expire_checkout_session(&paid.session_id).await.unwrap(), CheckoutState::Paid);
+    }
+
+    #[actix_web::test]
+    async fn test_mock_refunds() {
+        let provider = MockPaymentProvider::new("whsec_test");
+        let amount = Money::parse("100.00", Currency::USD).unwrap();
+        let session = provider
+            .create_checkout_session(CheckoutRequest { amount, product_name: "Gears".to_string(), line_items: Vec::new() })
+            .await
+            .unwrap();
+        provider.complete_checkout(&session.session_id);
+        let payment_reference = provider.session(&session.session_id).unwrap().payment_reference.unwrap();
+        let request = |amount: &str, key: &str| RefundRequest {
+            payment_reference: payment_reference.clone(),
+            amount: Money::parse(amount, Currency::USD).unwrap(),
+            idempotency_key: key.to_string(),
+        };
+
+        let first = PaymentProvider::refund(&provider, request("60.00", "refund-1")).await.unwrap();
+        // 同一个 idempotency_key 重试拿到同一笔退款
+        let retried = PaymentProvider::refund(&provider, request("60.00", "refund-1")).await.unwrap();
+        assert_eq!(first.refund_id, retried.refund_id);
+        // 累计不能超过付款金额
+        assert!(PaymentProvider::refund(&provider, request("40.01", "refund-2")).await.is_err());
+        assert!(PaymentProvider::refund(&provider, request("40.00", "refund-2")).await.is_ok());
+        let unknown = RefundRequest { payment_reference: "unknown".to_string(), ..request("1.00", "refund-3") };
+        assert!(PaymentProvider::refund(&provider, unknown).await.is_err());
+    }
+
+    #[test]
+    fn test_mock_signature_verification() {
+        let provider = MockPaymentProvider::new("whsec_test");
+        let payload = r#"{"id":"evt_1","type":"checkout.session.completed","data":{"object":{"id":"cs_1"}}}"#;
+        let now = 1_800_000_000;
+        let signature = provider.sign(payload, now);
+        assert!(provider.verify_signature(payload, &signature, now).is_ok());
+
+        // 篡改内容、换密钥、过期的签名都要拒绝
+        assert!(provider.verify_signature(&payload.replace("cs_1", "cs_2"), &signature, now).is_err());
+        assert!(MockPaymentProvider::new("other").verify_signature(payload, &signature, now).is_err());
+        assert!(provider.verify_signature(payload, &signature, now + SIGNATURE_TOLERANCE_SECS + 1).is_err());
+        assert!(provider.verify_signature(payload, "garbage", now).is_err());
+
+        // 没有配置密钥时所有Webhook都拒绝
+        let unconfigured = MockPaymentProvider::with_secret(None);
+        assert!(matches!(unconfigured.verify_signature(payload, &unconfigured.sign(payload, now), now), Err(AppError::InternalServerError(_))));
+    }
+}
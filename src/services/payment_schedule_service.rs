@@ -0,0 +1,300 @@
+// src/services/payment_schedule_service.rs
+// 分期付款计划：订单按比例拆成几期（定金、发货款、尾款），每期在订单到达对应状态后单独付款
+use crate::{
+    errors::AppError,
+    models::{
+        money::{self, Currency, Money},
+        order::{OrderParty, OrderStatus},
+        payment_schedule::{MilestoneTrigger, PaymentMilestone, SetPaymentScheduleDto},
+        user::Claims,
+    },
+    services::{
+        chat_server::ChatServer,
+        notification_service,
+        order_service::{self, LockedOrder},
+        payment_provider::PaymentProvider,
+        payment_service,
+    },
+};
+use crate::models::notification_preference::NotificationCategory;
+use actix::Addr;
+use sqlx::{types::Decimal, MySql, MySqlPool, Transaction};
+use std::str::FromStr;
+
+/// 一个订单最多拆成几期
+const MAX_MILESTONES: usize = 10;
+
+/// 按百分比拆分订单总额。每期四舍五入到分，最后一期拿剩下的，保证合计等于总额
+pub(crate) fn split_schedule(total: Decimal, percentages: &[Decimal]) -> Result<Vec<Decimal>, AppError> {
+    if percentages.iter().any(|p| *p <= Decimal::ZERO || p.normalize().scale() > 2) {
+        return Err(AppError::BadRequest("Each percentage must be positive with at most 2 decimals.".to_string()));
+    }
+    if percentages.iter().sum::<Decimal>() != Decimal::ONE_HUNDRED {
+        return Err(AppError::BadRequest("Milestone percentages must add up to 100.".to_string()));
+    }
+    split_proportionally(total, percentages)
+}
+
+/// 按比例拆分金额，比例合计不要求是100
+fn split_proportionally(total: Decimal, weights: &[Decimal]) -> Result<Vec<Decimal>, AppError> {
+    let weight_total: Decimal = weights.iter().sum();
+    let mut amounts = Vec::with_capacity(weights.len());
+    let mut remaining = total;
+    for (i, weight) in weights.iter().enumerate() {
+        let amount = if i + 1 == weights.len() {
+            remaining
+        } else {
+            money::round_amount(total * weight / weight_total)
+        };
+        if amount <= Decimal::ZERO {
+            return Err(AppError::BadRequest("Every milestone must have an amount greater than zero.".to_string()));
+        }
+        remaining -= amount;
+        amounts.push(amount);
+    }
+    Ok(amounts)
+}
+
+/// 订单任一方设置或替换付款计划，传空数组表示取消分期。
+/// 已开票、已付款、或有一期已经开始付款的订单不能再改
+pub async fn set_payment_schedule(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    provider: &dyn PaymentProvider,
+    order_id: i32,
+    dto: SetPaymentScheduleDto,
+    claims: &Claims,
+) -> Result<Vec<PaymentMilestone>, AppError> {
+    if dto.milestones.len() == 1 || dto.milestones.len() > MAX_MILESTONES {
+        return Err(AppError::BadRequest(format!(
+            "A payment schedule needs between 2 and {} milestones; send an empty list to remove it.",
+            MAX_MILESTONES
+        )));
+    }
+    let mut milestones = Vec::with_capacity(dto.milestones.len());
+    for milestone in dto.milestones {
+        let label = order_service::trimmed_text(Some(milestone.label), 100, "Label")?
+            .ok_or_else(|| AppError::BadRequest("Every milestone needs a label.".to_string()))?;
+        let trigger = MilestoneTrigger::parse(&milestone.trigger)
+            .ok_or_else(|| AppError::BadRequest("Trigger must be AWARD, SHIPPED or ACCEPTED.".to_string()))?;
+        milestones.push((label, milestone.percentage, trigger));
+    }
+
+    let mut tx = pool.begin().await?;
+    let order = order_service::lock_order(&mut tx, order_id).await?;
+    let party = order.party_of(claims.company_id)
+        .ok_or_else(|| AppError::BadRequest("Order not found or you are not authorized to update it.".to_string()))?;
+    if matches!(OrderStatus::parse(&order.status), Some(OrderStatus::Cancelled | OrderStatus::Completed)) {
+        return Err(AppError::BadRequest(format!("The payment schedule of a {} order cannot be changed.", order.status)));
+    }
+
+    let (total_amount, currency, payment_status, session_id, invoice_count, started_count): (Decimal, String, String, Option<String>, i64, i64) = sqlx::query_as(
+        "SELECT po.total_amount, po.currency, po.payment_status, po.stripe_session_id,
+                (SELECT COUNT(*) FROM invoices i WHERE i.order_id = po.id),
+                (SELECT COUNT(*) FROM payment_milestones m
+                 WHERE m.order_id = po.id AND (m.payment_status <> 'UNPAID' OR m.stripe_session_id IS NOT NULL))
+         FROM purchase_orders po WHERE po.id = ?"
+    )
+        .bind(order.id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if payment_status != "UNPAID" && payment_status != "FAILED" {
+        return Err(AppError::BadRequest("This order has already been paid.".to_string()));
+    }
+    if invoice_count > 0 {
+        return Err(AppError::BadRequest("This order is invoiced and is paid per invoice.".to_string()));
+    }
+    if started_count > 0 {
+        return Err(AppError::BadRequest("Payment of a milestone has already started; the schedule can no longer be changed.".to_string()));
+    }
+
+    let percentages: Vec<Decimal> = milestones.iter().map(|(_, percentage, _)| *percentage).collect();
+    let amounts = if milestones.is_empty() { Vec::new() } else { split_schedule(total_amount, &percentages)? };
+    let currency_code = Currency::from_str(&currency)?;
+    for amount in &amounts {
+        Money::new(*amount, currency_code)?;
+    }
+
+    // 整单付款的会话在渠道作废，避免和分期重复收款
+    if let Some(session_id) = session_id.as_deref().filter(|_| !milestones.is_empty()) {
+        payment_service::expire_open_session(provider, session_id).await?;
+        sqlx::query("UPDATE purchase_orders SET stripe_session_id = NULL WHERE id = ?")
+            .bind(order.id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    sqlx::query("DELETE FROM payment_milestones WHERE order_id = ?")
+        .bind(order.id)
+        .execute(&mut *tx)
+        .await?;
+    for (sequence, ((label, percentage, trigger), amount)) in milestones.iter().zip(&amounts).enumerate() {
+        sqlx::query(
+            "INSERT INTO payment_milestones (order_id, sequence, label, percentage, amount, currency, due_trigger, created_by_user_id)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+            .bind(order.id)
+            .bind(sequence as i32 + 1)
+            .bind(label)
+            .bind(percentage)
+            .bind(amount)
+            .bind(&currency)
+            .bind(trigger.as_str())
+            .bind(claims.sub)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    let subject = format!("Payment schedule updated for order #{}", order.id);
+    let message = if milestones.is_empty() {
+        format!("The payment schedule of order #{} for '{}' was removed; the order is paid in full.", order.id, order.rfq_title)
+    } else {
+        let parts: Vec<String> = milestones.iter().zip(&amounts)
+            .map(|((label, _, trigger), amount)| format!("{} {} {} on {}", label, amount, currency, trigger.as_str()))
+            .collect();
+        format!("Order #{} for '{}' is now paid in milestones: {}.", order.id, order.rfq_title, parts.join("; "))
+    };
+    let counterparty = match party {
+        OrderParty::Buyer => order.supplier_company_id,
+        OrderParty::Supplier => order.buyer_company_id,
+    };
+    notification_service::notify_company(pool, chat_server, counterparty, NotificationCategory::Payment, &subject, &message, "/orders").await;
+
+    load_schedule(pool, order.id, &order.status).await
+}
+
+/// 订单的付款计划，订单双方和管理员可以查看
+pub async fn get_payment_schedule(pool: &MySqlPool, order_id: i32, claims: &Claims) -> Result<Vec<PaymentMilestone>, AppError> {
+    if !claims.is_admin {
+        order_service::ensure_order_party(pool, order_id, claims).await?;
+    }
+    let (status,): (String,) = sqlx::query_as("SELECT status FROM purchase_orders WHERE id = ?")
+        .bind(order_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Order not found.".to_string()))?;
+    load_schedule(pool, order_id, &status).await
+}
+
+async fn load_schedule(pool: &MySqlPool, order_id: i32, order_status: &str) -> Result<Vec<PaymentMilestone>, AppError> {
+    let mut milestones: Vec<PaymentMilestone> = sqlx::query_as(
+        "SELECT * FROM payment_milestones WHERE order_id = ? ORDER BY sequence"
+    )
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+    let order_status = OrderStatus::parse(order_status);
+    for milestone in &mut milestones {
+        milestone.is_due = match (MilestoneTrigger::parse(&milestone.due_trigger), order_status) {
+            (Some(trigger), Some(status)) => trigger.is_due(status),
+            _ => false,
+        };
+    }
+    Ok(milestones)
+}
+
+/// 变更单改了订单总额后重新拆分计划：已付的分期金额不变，新总额减去已付部分后按未付分期的比例拆分。
+/// 未付分期开着的支付会话是按旧金额开的，在渠道作废，买方下次付款按新金额开会话
+pub(crate) async fn rescale_schedule(
+    tx: &mut Transaction<'_, MySql>,
+    provider: &dyn PaymentProvider,
+    order_id: i32,
+    total_amount: Decimal,
+) -> Result<(), AppError> {
+    let milestones: Vec<(i32, Decimal, Decimal, String, Option<String>)> = sqlx::query_as(
+        "SELECT id, percentage, amount, payment_status, stripe_session_id FROM payment_milestones WHERE order_id = ? ORDER BY sequence"
+    )
+        .bind(order_id)
+        .fetch_all(&mut **tx)
+        .await?;
+    let (unpaid, settled): (Vec<_>, Vec<_>) = milestones.into_iter()
+        .partition(|(_, _, _, status, _)| status == "UNPAID" || status == "FAILED");
+    if unpaid.is_empty() {
+        return Ok(());
+    }
+    let settled_total: Decimal = settled.iter().map(|(_, _, amount, _, _)| *amount).sum();
+    let weights: Vec<Decimal> = unpaid.iter().map(|(_, percentage, _, _, _)| *percentage).collect();
+    let amounts = split_proportionally(total_amount - settled_total, &weights)?;
+
+    for ((milestone_id, _, _, _, session_id), amount) in unpaid.iter().zip(amounts) {
+        if let Some(session_id) = session_id {
+            payment_service::expire_open_session(provider, session_id).await?;
+        }
+        sqlx::query("UPDATE payment_milestones SET amount = ?, stripe_session_id = NULL WHERE id = ?")
+            .bind(amount)
+            .bind(milestone_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+/// 订单状态变化后提醒买方支付刚到期的分期。失败只记日志
+pub(crate) async fn notify_due_milestones(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    order: &LockedOrder,
+    to_status: OrderStatus,
+) {
+    let Some(trigger) = MilestoneTrigger::reached_by(to_status) else {
+        return;
+    };
+    let due: Vec<(String, Decimal, String)> = match sqlx::query_as(
+        "SELECT label, amount, currency FROM payment_milestones
+         WHERE order_id = ? AND due_trigger = ? AND payment_status IN ('UNPAID', 'FAILED')
+         ORDER BY sequence"
+    )
+        .bind(order.id)
+        .bind(trigger.as_str())
+        .fetch_all(pool)
+        .await
+    {
+        Ok(due) => due,
+        Err(e) => {
+            log::error!("Failed to fetch due milestones of order #{}: {:?}", order.id, e);
+            return;
+        }
+    };
+
+    for (label, amount, currency) in due {
+        let subject = format!("Payment due for order #{}", order.id);
+        let message = format!("The milestone '{}' of {} {} for order #{} is now due.", label, amount, currency, order.id);
+        notification_service::notify_company(pool, chat_server, order.buyer_company_id, NotificationCategory::Payment, &subject, &message, "/orders").await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_split_schedule() {
+        assert_eq!(split_schedule(d("1000.00"), &[d("30"), d("70")]).unwrap(), vec![d("300.00"), d("700.00")]);
+        // 舍入误差留给最后一期
+        assert_eq!(
+            split_schedule(d("100.00"), &[d("33.33"), d("33.33"), d("33.34")]).unwrap(),
+            vec![d("33.33"), d("33.33"), d("33.34")]
+        );
+        assert_eq!(split_schedule(d("0.10"), &[d("50"), d("50")]).unwrap(), vec![d("0.05"), d("0.05")]);
+        assert_eq!(split_schedule(d("10.01"), &[d("50"), d("50")]).unwrap(), vec![d("5.01"), d("5.00")]);
+
+        assert!(split_schedule(d("100"), &[d("30"), d("60")]).is_err());
+        assert!(split_schedule(d("100"), &[d("0"), d("100")]).is_err());
+        assert!(split_schedule(d("100"), &[d("33.333"), d("66.667")]).is_err());
+        // 金额太小分不出来
+        assert!(split_schedule(d("0.01"), &[d("50"), d("50")]).is_err());
+    }
+
+    #[test]
+    fn test_split_proportionally() {
+        // 已付30%定金后，剩下的两期按 50:20 拆
+        assert_eq!(split_proportionally(d("140.00"), &[d("50"), d("20")]).unwrap(), vec![d("100.00"), d("40.00")]);
+        assert_eq!(split_proportionally(d("10.00"), &[d("1"), d("1"), d("1")]).unwrap(), vec![d("3.33"), d("3.33"), d("3.34")]);
+        assert!(split_proportionally(d("0.00"), &[d("50"), d("50")]).is_err());
+    }
+}
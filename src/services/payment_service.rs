@@ -1,22 +1,30 @@
+use actix::Addr;
+use crate::models::notification_preference::NotificationCategory;
 use crate::{
     errors::AppError,
-    models::{order::PurchaseOrder, user::Claims},
+    models::{
+        order::{OrderStatus, PurchaseOrder},
+        payment::PaymentEventLog,
+        payment_schedule::MilestoneTrigger,
+        user::Claims,
+    },
+    services::{
+        chat_server::ChatServer,
+        notification_service,
+        payment_provider::{CheckoutLineItem, CheckoutRequest, CheckoutSessionInfo, CheckoutState, PaymentEventKind, PaymentProvider},
+        refund_service,
+    },
 };
-use sqlx::MySqlPool;
-use std::env;
-// 导入 async_stripe 相关模块
-use stripe::{
-    Client,
-    CheckoutSession, CheckoutSessionMode, CreateCheckoutSession, CreateCheckoutSessionLineItems, CreateCheckoutSessionLineItemsPriceData,CreateCheckoutSessionLineItemsPriceDataProductData
-};
-// 导入 ToPrimitive trait 用 .to_f64()
-use num_traits::ToPrimitive;
+use sqlx::{types::Decimal, MySql, MySqlPool, Transaction};
+use std::str::FromStr;
+use crate::models::money::{Currency, Money};
 
 pub async fn create_checkout_session(
     pool: &MySqlPool,
+    provider: &dyn PaymentProvider,
     order_id: i32,
     claims: &Claims,
-) -> Result<String, AppError> {
+) -> Result<CheckoutSessionInfo, AppError> {
     // 验证订单
     let order: PurchaseOrder = sqlx::query_as(
         "SELECT po.*, r.title as rfq_title, b.name as buyer_name, s.name as supplier_name
@@ -32,47 +40,740 @@ pub async fn create_checkout_session(
         .await
         .map_err(|_| AppError::BadRequest("Order not found or you are not authorized.".to_string()))?;
 
-    if order.payment_status == "PAID" {
+    if order.status == "CANCELLED" {
+        return Err(AppError::BadRequest("This order has been cancelled.".to_string()));
+    }
+    if order.payment_status != "UNPAID" && order.payment_status != "FAILED" {
         return Err(AppError::BadRequest("This order has already been paid.".to_string()));
     }
 
-    // 配置Stripe客户端
-    let secret_key = env::var("STRIPE_SECRET_KEY").expect("STRIPE_SECRET_KEY must be set");
-    let client = Client::new(secret_key);
-
-    // 定义URL
-    let frontend_url = env::var("FRONTEND_URL").expect("FRONTEND_URL must be set");
-    let success_url = format!("{}/payment/success?session_id={{CHECKOUT_SESSION_ID}}", frontend_url);
-    let cancel_url = format!("{}/orders", frontend_url);
-
-    // 使用 async-stripe 的API创建Checkout会话
-    let mut params = CreateCheckoutSession::new();
-    params.success_url = Some(&*success_url);
-    params.cancel_url = Some(&*cancel_url);
-    params.mode = Some(CheckoutSessionMode::Payment);
-    params.line_items = Some(vec![CreateCheckoutSessionLineItems {
-        price_data: Some(CreateCheckoutSessionLineItemsPriceData {
-            currency: stripe::Currency::USD, // Or your currency
-            product_data: Some(CreateCheckoutSessionLineItemsPriceDataProductData {
-                name: order.rfq_title.clone(),
-                ..Default::default()
-            }),
-            unit_amount: Some((order.total_amount.to_f64().unwrap_or(0.0) * 100.0) as i64),
-            ..Default::default()
-        }),
-        quantity: Some(1),
-        ..Default::default()
-    }]);
-
-    let session = CheckoutSession::create(&client, params).await
-        .map_err(|e| AppError::InternalServerError(format!("Stripe error: {}", e)))?;
+    // 开过发票的订单按发票逐张付款，有分期计划的按期付款
+    let (invoice_count, milestone_count): (i64, i64) = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM invoices WHERE order_id = ?), (SELECT COUNT(*) FROM payment_milestones WHERE order_id = ?)"
+    )
+        .bind(order_id)
+        .bind(order_id)
+        .fetch_one(pool)
+        .await?;
+    if invoice_count > 0 {
+        return Err(AppError::BadRequest("This order is invoiced; please pay its invoices instead.".to_string()));
+    }
+    if milestone_count > 0 {
+        return Err(AppError::BadRequest("This order has a payment schedule; please pay its milestones instead.".to_string()));
+    }
+
+    let tax_label = format!("{} {}%", order.tax_name.as_deref().unwrap_or("Tax"), order.tax_rate.normalize());
+    let lines = [
+        (order.rfq_title.clone(), order.subtotal_amount),
+        ("Shipping".to_string(), order.shipping_amount),
+        (tax_label, order.tax_amount),
+    ];
+    let session = open_session(provider, order.total_amount, &order.currency, &order.rfq_title, &lines).await?;
 
     // 5. 将会话ID存入数据库
     sqlx::query("UPDATE purchase_orders SET stripe_session_id = ? WHERE id = ?")
-        .bind(session.id.to_string()) // 使用 .to_string() 转换ID
+        .bind(&session.session_id)
+        .bind(order_id)
+        .execute(pool)
+        .await?;
+
+    Ok(session)
+}
+
+#[derive(sqlx::FromRow)]
+struct PayableInvoice {
+    subtotal: Decimal,
+    tax_total: Decimal,
+    total: Decimal,
+    currency: String,
+    payment_status: String,
+    invoice_number: String,
+    // 订单有分期计划
+    scheduled: bool,
+    order_payment_status: String,
+}
+
+/// 为单张发票创建支付会话，只有买方可以付款
+pub async fn create_invoice_checkout_session(
+    pool: &MySqlPool,
+    provider: &dyn PaymentProvider,
+    invoice_id: i32,
+    claims: &Claims,
+) -> Result<CheckoutSessionInfo, AppError> {
+    let invoice: Option<PayableInvoice> = sqlx::query_as(
+        "SELECT i.subtotal, i.tax_total, i.total, i.currency, i.payment_status, i.invoice_number,
+                EXISTS (SELECT 1 FROM payment_milestones m WHERE m.order_id = i.order_id) AS scheduled,
+                po.payment_status AS order_payment_status
+         FROM invoices i JOIN purchase_orders po ON i.order_id = po.id
+         WHERE i.id = ? AND i.buyer_company_id = ?"
+    )
+        .bind(invoice_id)
+        .bind(claims.company_id)
+        .fetch_optional(pool)
+        .await?;
+    let invoice = invoice.ok_or_else(|| AppError::BadRequest("Invoice not found or you are not authorized.".to_string()))?;
+
+    if invoice.payment_status != "UNPAID" && invoice.payment_status != "FAILED" {
+        return Err(AppError::BadRequest("This invoice is not awaiting payment.".to_string()));
+    }
+    // 整单已经付过款的，发票不能再付一次
+    if invoice.order_payment_status != "UNPAID" && invoice.order_payment_status != "FAILED" {
+        return Err(AppError::BadRequest("This order has already been paid.".to_string()));
+    }
+    // 分期计划和按发票付款只能二选一
+    if invoice.scheduled {
+        return Err(AppError::BadRequest("This order has a payment schedule; please pay its milestones instead.".to_string()));
+    }
+
+    let product_name = format!("Invoice {}", invoice.invoice_number);
+    let lines = [(product_name.clone(), invoice.subtotal), ("Tax".to_string(), invoice.tax_total)];
+    let session = open_session(provider, invoice.total, &invoice.currency, &product_name, &lines).await?;
+
+    sqlx::query("UPDATE invoices SET stripe_session_id = ? WHERE id = ?")
+        .bind(&session.session_id)
+        .bind(invoice_id)
+        .execute(pool)
+        .await?;
+
+    Ok(session)
+}
+
+/// 为订单的某一期付款创建支付会话，只有买方可以付款，且订单要到了这一期的触发状态
+pub async fn create_milestone_checkout_session(
+    pool: &MySqlPool,
+    provider: &dyn PaymentProvider,
+    order_id: i32,
+    milestone_id: i32,
+    claims: &Claims,
+) -> Result<CheckoutSessionInfo, AppError> {
+    let milestone: Option<(Decimal, String, String, String, String, String, String)> = sqlx::query_as(
+        "SELECT m.amount, m.currency, m.payment_status, m.label, m.due_trigger, po.status, po.payment_status
+         FROM payment_milestones m JOIN purchase_orders po ON m.order_id = po.id
+         WHERE m.id = ? AND m.order_id = ? AND po.buyer_company_id = ?"
+    )
+        .bind(milestone_id)
         .bind(order_id)
+        .bind(claims.company_id)
+        .fetch_optional(pool)
+        .await?;
+    let (amount, currency, payment_status, label, due_trigger, order_status, order_payment_status) =
+        milestone.ok_or_else(|| AppError::BadRequest("Payment milestone not found or you are not authorized.".to_string()))?;
+
+    if order_status == "CANCELLED" {
+        return Err(AppError::BadRequest("This order has been cancelled.".to_string()));
+    }
+    if payment_status != "UNPAID" && payment_status != "FAILED" {
+        return Err(AppError::BadRequest("This milestone is not awaiting payment.".to_string()));
+    }
+    if order_payment_status != "UNPAID" && order_payment_status != "FAILED" {
+        return Err(AppError::BadRequest("This order has already been paid.".to_string()));
+    }
+    let due = match (MilestoneTrigger::parse(&due_trigger), OrderStatus::parse(&order_status)) {
+        (Some(trigger), Some(status)) => trigger.is_due(status),
+        _ => false,
+    };
+    if !due {
+        return Err(AppError::BadRequest(format!("This milestone is not due until the order is {}.", due_trigger)));
+    }
+
+    let session = open_session(provider, amount, &currency, &format!("{} - order #{}", label, order_id), &[]).await?;
+
+    sqlx::query("UPDATE payment_milestones SET stripe_session_id = ? WHERE id = ?")
+        .bind(&session.session_id)
+        .bind(milestone_id)
         .execute(pool)
         .await?;
 
-    Ok(session.id.to_string())
-}
\ No newline at end of file
+    Ok(session)
+}
+
+/// 作废还没付款的支付会话，金额变了或改用别的方式付款时调用。
+/// 会话已经付过款时报错，等Webhook或对账把这笔付款入账后再操作
+pub(crate) async fn expire_open_session(provider: &dyn PaymentProvider, session_id: &str) -> Result<(), AppError> {
+    match provider.expire_checkout_session(session_id).await? {
+        CheckoutState::Paid => Err(AppError::BadRequest(
+            "A checkout for this payment has already been completed; please wait for the payment to be confirmed.".to_string(),
+        )),
+        CheckoutState::Open | CheckoutState::Expired => Ok(()),
+    }
+}
+
+// lines 是支付页面上的明细，金额为0的行不显示；不传明细时整笔显示为一行
+async fn open_session(
+    provider: &dyn PaymentProvider,
+    total: Decimal,
+    currency: &str,
+    product_name: &str,
+    lines: &[(String, Decimal)],
+) -> Result<CheckoutSessionInfo, AppError> {
+    let currency = Currency::from_str(currency)?;
+    let amount = Money::new(total, currency)?;
+    let line_items = lines.iter()
+        .filter(|(_, amount)| *amount > Decimal::ZERO)
+        .map(|(name, amount)| Ok(CheckoutLineItem { name: name.clone(), amount: Money::new(*amount, currency)? }))
+        .collect::<Result<Vec<_>, AppError>>()?;
+    if !line_items.is_empty() && line_items.iter().map(|item| item.amount.amount()).sum::<Decimal>() != total {
+        return Err(AppError::InternalServerError(format!("Checkout line items of '{}' do not add up to {}", product_name, amount)));
+    }
+    provider
+        .create_checkout_session(CheckoutRequest { amount, product_name: product_name.to_string(), line_items })
+        .await
+}
+
+/// 支付事件处理后要发的通知，事务提交后再发
+pub(crate) struct PaymentNotice {
+    // None 表示通知管理员
+    company_id: Option<i32>,
+    subject: String,
+    message: String,
+}
+
+/// 事件处理结果：matched 为 false 表示没有找到对应的订单或发票
+pub(crate) struct PaymentEffect {
+    matched: bool,
+    notices: Vec<PaymentNotice>,
+}
+
+impl PaymentEffect {
+    fn unmatched() -> Self {
+        Self { matched: false, notices: Vec::new() }
+    }
+}
+
+/// 付款对应的订单，或订单下的某张发票、某一期分期付款
+#[derive(sqlx::FromRow)]
+pub(crate) struct PaymentTarget {
+    pub order_id: i32,
+    pub invoice_id: Option<i32>,
+    pub milestone_id: Option<i32>,
+    // 发票号或分期名称
+    pub part_name: Option<String>,
+    pub buyer_company_id: i32,
+    pub supplier_company_id: i32,
+    pub currency: String,
+    // 这笔付款的金额（订单总额、发票总额或这一期的金额）
+    pub paid_total: Decimal,
+}
+
+impl PaymentTarget {
+    pub(crate) fn label(&self) -> String {
+        let part = self.part_name.as_deref().unwrap_or_default();
+        if self.invoice_id.is_some() {
+            format!("Invoice {} of order #{}", part, self.order_id)
+        } else if self.milestone_id.is_some() {
+            format!("Payment '{}' of order #{}", part, self.order_id)
+        } else {
+            format!("Order #{}", self.order_id)
+        }
+    }
+
+    /// 付款记录所在的表、主键和金额列
+    fn table(&self) -> (&'static str, i32, &'static str) {
+        match (self.invoice_id, self.milestone_id) {
+            (Some(invoice_id), _) => ("invoices", invoice_id, "total"),
+            (None, Some(milestone_id)) => ("payment_milestones", milestone_id, "amount"),
+            (None, None) => ("purchase_orders", self.order_id, "total_amount"),
+        }
+    }
+
+    /// 分开付款（发票、分期）时汇总订单状态要查的表
+    fn parts_table(&self) -> Option<&'static str> {
+        match (self.invoice_id, self.milestone_id) {
+            (Some(_), _) => Some("invoices"),
+            (None, Some(_)) => Some("payment_milestones"),
+            (None, None) => None,
+        }
+    }
+}
+
+/// 验签、落库去重，再处理支付渠道的Webhook事件。
+/// 已处理过的事件直接确认；处理失败会记下原因并返回错误，让渠道稍后重试
+pub async fn process_webhook(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    provider: &dyn PaymentProvider,
+    payload: &str,
+    signature: &str,
+) -> Result<(), AppError> {
+    let event = provider.parse_webhook(payload, signature)?;
+    let mut tx = pool.begin().await?;
+
+    // 先插入事件拿到行锁，同一事件的并发投递会在这里排队
+    sqlx::query(
+        "INSERT INTO payment_events (provider, event_id, event_type, payload) VALUES (?, ?, ?, ?)
+         ON DUPLICATE KEY UPDATE attempts = attempts + 1"
+    )
+        .bind(provider.name())
+        .bind(&event.id)
+        .bind(&event.event_type)
+        .bind(payload)
+        .execute(&mut *tx)
+        .await?;
+    let (log_id, status): (i32, String) = sqlx::query_as(
+        "SELECT id, status FROM payment_events WHERE provider = ? AND event_id = ? FOR UPDATE"
+    )
+        .bind(provider.name())
+        .bind(&event.id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if status == "PROCESSED" || status == "IGNORED" {
+        log::info!("Duplicate {} webhook event {} ignored.", provider.name(), event.id);
+        tx.commit().await?;
+        return Ok(());
+    }
+
+    match apply_event(&mut tx, &event.kind).await {
+        Ok(effect) => {
+            let (status, detail) = if effect.matched {
+                ("PROCESSED", None)
+            } else if event.kind == PaymentEventKind::Ignored {
+                ("IGNORED", Some("Event type is not handled"))
+            } else {
+                ("IGNORED", Some("No matching order or invoice"))
+            };
+            sqlx::query("UPDATE payment_events SET status = ?, detail = ?, processed_at = CURRENT_TIMESTAMP WHERE id = ?")
+                .bind(status)
+                .bind(detail)
+                .bind(log_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            log::info!("{} webhook event {} ({}) {}.", provider.name(), event.id, event.event_type, status.to_lowercase());
+
+            send_notices(pool, chat_server, effect.notices).await;
+            Ok(())
+        }
+        Err(e) => {
+            tx.rollback().await?;
+            log::error!("Failed to process {} webhook event {}: {:?}", provider.name(), event.id, e);
+            let detail: String = format!("{:?}", e).chars().take(1000).collect();
+            sqlx::query(
+                "INSERT INTO payment_events (provider, event_id, event_type, payload, status, detail) VALUES (?, ?, ?, ?, 'FAILED', ?)
+                 ON DUPLICATE KEY UPDATE status = 'FAILED', detail = VALUES(detail), attempts = attempts + 1"
+            )
+                .bind(provider.name())
+                .bind(&event.id)
+                .bind(&event.event_type)
+                .bind(payload)
+                .bind(detail)
+                .execute(pool)
+                .await?;
+            Err(e)
+        }
+    }
+}
+
+async fn apply_event(tx: &mut Transaction<'_, MySql>, kind: &PaymentEventKind) -> Result<PaymentEffect, AppError> {
+    match kind {
+        PaymentEventKind::CheckoutCompleted { session_id, payment_reference } => {
+            handle_checkout_completed(tx, session_id, payment_reference.as_deref()).await
+        }
+        PaymentEventKind::CheckoutFailed { session_id } => handle_checkout_failed(tx, session_id).await,
+        PaymentEventKind::CheckoutExpired { session_id } => handle_checkout_expired(tx, session_id).await,
+        PaymentEventKind::Refunded { payment_reference, amount_refunded, fully_refunded } => {
+            handle_refund(tx, payment_reference, *amount_refunded, *fully_refunded).await
+        }
+        PaymentEventKind::DisputeOpened { payment_reference, reason } => handle_chargeback(tx, payment_reference, reason).await,
+        PaymentEventKind::Ignored => Ok(PaymentEffect::unmatched()),
+    }
+}
+
+// 按支付会话或付款ID找订单；依次查整单付款、发票、分期付款
+pub(crate) async fn find_target(tx: &mut Transaction<'_, MySql>, column: &str, value: &str) -> Result<Option<PaymentTarget>, AppError> {
+    // column 只会是下面两个固定列名
+    debug_assert!(column == "stripe_session_id" || column == "payment_reference");
+    let queries = [
+        "SELECT id AS order_id, NULL AS invoice_id, NULL AS milestone_id, NULL AS part_name,
+                buyer_company_id, supplier_company_id, currency, total_amount AS paid_total
+         FROM purchase_orders WHERE {column} = ? LIMIT 1",
+        "SELECT order_id, id AS invoice_id, NULL AS milestone_id, invoice_number AS part_name,
+                buyer_company_id, supplier_company_id, currency, total AS paid_total
+         FROM invoices WHERE {column} = ? LIMIT 1",
+        "SELECT m.order_id, NULL AS invoice_id, m.id AS milestone_id, m.label AS part_name,
+                po.buyer_company_id, po.supplier_company_id, m.currency, m.amount AS paid_total
+         FROM payment_milestones m JOIN purchase_orders po ON m.order_id = po.id WHERE m.{column} = ? LIMIT 1",
+    ];
+    for query in queries {
+        let target: Option<PaymentTarget> = sqlx::query_as(&query.replace("{column}", column))
+            .bind(value)
+            .fetch_optional(&mut **tx)
+            .await?;
+        if target.is_some() {
+            return Ok(target);
+        }
+    }
+    Ok(None)
+}
+
+// 只更新订单、发票、分期其中一张表
+async fn update_target(tx: &mut Transaction<'_, MySql>, target: &PaymentTarget, set_clause: &str, guard: &str) -> Result<bool, AppError> {
+    let (table, id, _) = target.table();
+    let result = sqlx::query(&format!("UPDATE {} SET {} WHERE id = ? AND {}", table, set_clause, guard))
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// 支付完成：会话可能属于整单付款，也可能属于某张发票。重复的完成事件不会重复处理。
+/// 订单已经取消时不算已付，记为待退款并提醒管理员
+pub(crate) async fn handle_checkout_completed(
+    tx: &mut Transaction<'_, MySql>,
+    session_id: &str,
+    payment_reference: Option<&str>,
+) -> Result<PaymentEffect, AppError> {
+    let Some(target) = find_target(tx, "stripe_session_id", session_id).await? else {
+        return Ok(PaymentEffect::unmatched());
+    };
+
+    let (table, id, _) = target.table();
+    let (paid_at, not_cancelled) = if target.parts_table().is_some() {
+        (", paid_at = COALESCE(paid_at, CURRENT_TIMESTAMP)", "order_id NOT IN (SELECT po.id FROM purchase_orders po WHERE po.status = 'CANCELLED')")
+    } else {
+        ("", "status <> 'CANCELLED'")
+    };
+    let updated = sqlx::query(&format!(
+        "UPDATE {} SET payment_status = 'PAID', payment_reference = COALESCE(?, payment_reference){}
+         WHERE id = ? AND payment_status IN ('UNPAID', 'FAILED') AND {}",
+        table, paid_at, not_cancelled
+    ))
+        .bind(payment_reference)
+        .bind(id)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected() > 0;
+    if !updated {
+        return handle_payment_after_cancellation(tx, &target, payment_reference).await;
+    }
+    roll_up_paid_order(tx, &target).await?;
+
+    let message = format!("{} has been paid by the buyer.", target.label());
+    Ok(PaymentEffect {
+        matched: true,
+        notices: vec![PaymentNotice { company_id: Some(target.supplier_company_id), subject: "Payment received".to_string(), message }],
+    })
+}
+
+// 没改成已付：要么是重复的完成事件，要么是订单取消后买方才付款（会话没能及时作废）。
+// 后一种情况钱已经收了，记下付款ID标成待退款，生成待审批的退款并提醒管理员跟进
+async fn handle_payment_after_cancellation(
+    tx: &mut Transaction<'_, MySql>,
+    target: &PaymentTarget,
+    payment_reference: Option<&str>,
+) -> Result<PaymentEffect, AppError> {
+    let (table, id, _) = target.table();
+    let paid_at = if target.parts_table().is_some() { ", paid_at = COALESCE(paid_at, CURRENT_TIMESTAMP)" } else { "" };
+    let updated = sqlx::query(&format!(
+        "UPDATE {} SET payment_status = 'REFUND_PENDING', payment_reference = COALESCE(?, payment_reference){}
+         WHERE id = ? AND payment_status IN ('UNPAID', 'FAILED')",
+        table, paid_at
+    ))
+        .bind(payment_reference)
+        .bind(id)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected() > 0;
+    if !updated {
+        return Ok(PaymentEffect { matched: true, notices: Vec::new() });
+    }
+
+    let reason = format!("{} was paid after the order was cancelled.", target.label());
+    let refund_id = refund_service::open_late_payment_refund(tx, target, &reason).await?;
+    log::warn!("{} was paid after the order was cancelled (refund request: {:?}).", target.label(), refund_id);
+
+    let admin_message = match refund_id {
+        Some(refund_id) => format!("{} Refund request #{} is waiting for approval.", reason, refund_id),
+        None => format!("{} The payment must be refunded manually.", reason),
+    };
+    let buyer_message = format!("{} was paid after the order was cancelled. The payment will be refunded.", target.label());
+    Ok(PaymentEffect {
+        matched: true,
+        notices: vec![
+            PaymentNotice { company_id: None, subject: "Payment received for a cancelled order".to_string(), message: admin_message },
+            PaymentNotice { company_id: Some(target.buyer_company_id), subject: "Refund pending".to_string(), message: buyer_message },
+        ],
+    })
+}
+
+// 发送事件处理生成的通知
+async fn send_notices(pool: &MySqlPool, chat_server: &Addr<ChatServer>, notices: Vec<PaymentNotice>) {
+    for notice in notices {
+        match notice.company_id {
+            Some(company_id) => notification_service::notify_company(pool, chat_server, company_id, NotificationCategory::Payment, &notice.subject, &notice.message, "/orders").await,
+            None => notification_service::notify_admins(pool, chat_server, &notice.subject, &notice.message, "/admin/refunds").await,
+        }
+    }
+}
+
+// 发票或分期付清后，看订单是否整体付清
+async fn roll_up_paid_order(tx: &mut Transaction<'_, MySql>, target: &PaymentTarget) -> Result<(), AppError> {
+    match target.parts_table() {
+        Some("invoices") => {
+            // 全部数量和运费都开了票且每张都付清，订单才算已付。税额各张发票分别舍入，按不含税金额比较
+            sqlx::query(
+                "UPDATE purchase_orders po SET po.payment_status = 'PAID'
+                 WHERE po.id = ? AND po.payment_status IN ('UNPAID', 'FAILED') AND po.status <> 'CANCELLED'
+                   AND NOT EXISTS (SELECT 1 FROM invoices i WHERE i.order_id = po.id AND i.payment_status <> 'PAID')
+                   AND (SELECT SUM(i.subtotal) FROM invoices i WHERE i.order_id = po.id) >= po.subtotal_amount + po.shipping_amount"
+            )
+                .bind(target.order_id)
+                .execute(&mut **tx)
+                .await?;
+        }
+        Some(_) => {
+            // 分期全部付清订单才算已付
+            sqlx::query(
+                "UPDATE purchase_orders po SET po.payment_status = 'PAID'
+                 WHERE po.id = ? AND po.payment_status IN ('UNPAID', 'FAILED') AND po.status <> 'CANCELLED'
+                   AND NOT EXISTS (SELECT 1 FROM payment_milestones m WHERE m.order_id = po.id AND m.payment_status <> 'PAID')"
+            )
+                .bind(target.order_id)
+                .execute(&mut **tx)
+                .await?;
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// 线下收款（账期订单的银行转账等）手工标记为已付，和在线付款成功一样汇总订单状态。
+/// 还没完成的支付会话先在渠道作废，避免重复收款。返回付款对象，已经不是待付款状态时返回 None
+pub(crate) async fn record_manual_payment(
+    tx: &mut Transaction<'_, MySql>,
+    provider: &dyn PaymentProvider,
+    order_id: i32,
+    invoice_id: Option<i32>,
+    remittance_reference: &str,
+    user_id: i32,
+) -> Result<Option<PaymentTarget>, AppError> {
+    let target: Option<PaymentTarget> = match invoice_id {
+        Some(invoice_id) => sqlx::query_as(
+            "SELECT order_id, id AS invoice_id, NULL AS milestone_id, invoice_number AS part_name,
+                    buyer_company_id, supplier_company_id, currency, total AS paid_total
+             FROM invoices WHERE id = ? AND order_id = ?"
+        )
+            .bind(invoice_id)
+            .bind(order_id),
+        None => sqlx::query_as(
+            "SELECT id AS order_id, NULL AS invoice_id, NULL AS milestone_id, NULL AS part_name,
+                    buyer_company_id, supplier_company_id, currency, total_amount AS paid_total
+             FROM purchase_orders WHERE id = ?"
+        )
+            .bind(order_id),
+    }
+        .fetch_optional(&mut **tx)
+        .await?;
+    let target = target.ok_or_else(|| AppError::BadRequest("Invoice not found for this order.".to_string()))?;
+
+    let (table, id, _) = target.table();
+    // 只清掉数据库里的会话ID不够，买方还能在支付页面上付款，付了也对不上账
+    let (payment_status, session_id): (String, Option<String>) =
+        sqlx::query_as(&format!("SELECT payment_status, stripe_session_id FROM {} WHERE id = ?", table))
+            .bind(id)
+            .fetch_one(&mut **tx)
+            .await?;
+    if let Some(session_id) = session_id.as_deref()
+        && (payment_status == "UNPAID" || payment_status == "FAILED")
+    {
+        expire_open_session(provider, session_id).await?;
+    }
+    let paid_at = if target.parts_table().is_some() { ", paid_at = CURRENT_TIMESTAMP" } else { "" };
+    let updated = sqlx::query(&format!(
+        "UPDATE {} SET payment_status = 'PAID', remittance_reference = ?, marked_paid_by_user_id = ?, stripe_session_id = NULL{}
+         WHERE id = ? AND payment_status IN ('UNPAID', 'FAILED')",
+        table, paid_at
+    ))
+        .bind(remittance_reference)
+        .bind(user_id)
+        .bind(id)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected() > 0;
+    if !updated {
+        return Ok(None);
+    }
+    roll_up_paid_order(tx, &target).await?;
+    Ok(Some(target))
+}
+
+/// 订单取消时作废整单、发票和分期上还没付款的支付会话，否则买方还能在支付页面上给已取消的订单付款。
+/// 任何一个会话作废不了（比如刚好已经付款）都返回错误
+pub(crate) async fn expire_order_sessions(tx: &mut Transaction<'_, MySql>, provider: &dyn PaymentProvider, order_id: i32) -> Result<(), AppError> {
+    for (table, order_column) in [("purchase_orders", "id"), ("invoices", "order_id"), ("payment_milestones", "order_id")] {
+        let sessions: Vec<(i32, String)> = sqlx::query_as(&format!(
+            "SELECT id, stripe_session_id FROM {} WHERE {} = ? AND stripe_session_id IS NOT NULL AND payment_status IN ('UNPAID', 'FAILED')",
+            table, order_column
+        ))
+            .bind(order_id)
+            .fetch_all(&mut **tx)
+            .await?;
+        for (id, session_id) in sessions {
+            expire_open_session(provider, &session_id).await?;
+            sqlx::query(&format!("UPDATE {} SET stripe_session_id = NULL WHERE id = ?", table))
+                .bind(id)
+                .execute(&mut **tx)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// 对账发现渠道已经收款：和收到 checkout.session.completed 一样处理。
+/// 返回这次是否真的改了状态，已经被Webhook处理过时返回 false
+pub(crate) async fn settle_checkout(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    session_id: &str,
+    payment_reference: Option<&str>,
+) -> Result<bool, AppError> {
+    let mut tx = pool.begin().await?;
+    let effect = handle_checkout_completed(&mut tx, session_id, payment_reference).await?;
+    tx.commit().await?;
+    // 只有真正改成已付时才会生成通知
+    let settled = !effect.notices.is_empty();
+    send_notices(pool, chat_server, effect.notices).await;
+    Ok(settled)
+}
+
+/// 对账发现支付会话已过期，和收到 checkout.session.expired 一样清掉会话
+pub(crate) async fn expire_checkout(pool: &MySqlPool, session_id: &str) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+    handle_checkout_expired(&mut tx, session_id).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// 支付失败：只把还没付的订单或发票标成 FAILED，买方可以重新发起支付
+pub(crate) async fn handle_checkout_failed(tx: &mut Transaction<'_, MySql>, session_id: &str) -> Result<PaymentEffect, AppError> {
+    let Some(target) = find_target(tx, "stripe_session_id", session_id).await? else {
+        return Ok(PaymentEffect::unmatched());
+    };
+    if !update_target(tx, &target, "payment_status = 'FAILED'", "payment_status = 'UNPAID'").await? {
+        return Ok(PaymentEffect { matched: true, notices: Vec::new() });
+    }
+
+    let message = format!("The payment for {} failed. Please try again.", target.label());
+    Ok(PaymentEffect {
+        matched: true,
+        notices: vec![PaymentNotice { company_id: Some(target.buyer_company_id), subject: "Payment failed".to_string(), message }],
+    })
+}
+
+/// 会话过期：清掉会话ID，买方下次付款会开新的会话
+pub(crate) async fn handle_checkout_expired(tx: &mut Transaction<'_, MySql>, session_id: &str) -> Result<PaymentEffect, AppError> {
+    let Some(target) = find_target(tx, "stripe_session_id", session_id).await? else {
+        return Ok(PaymentEffect::unmatched());
+    };
+    update_target(tx, &target, "stripe_session_id = NULL", "payment_status IN ('UNPAID', 'FAILED')").await?;
+    Ok(PaymentEffect { matched: true, notices: Vec::new() })
+}
+
+/// 渠道完成退款（可能是部分退款），amount_refunded 是渠道给的累计退款
+pub(crate) async fn handle_refund(
+    tx: &mut Transaction<'_, MySql>,
+    payment_reference: &str,
+    amount_refunded: i64,
+    fully_refunded: bool,
+) -> Result<PaymentEffect, AppError> {
+    let Some(target) = find_target(tx, "payment_reference", payment_reference).await? else {
+        return Ok(PaymentEffect::unmatched());
+    };
+    let refunded_total = if fully_refunded { target.paid_total } else { Decimal::new(amount_refunded, 2) };
+    // 我们自己发起、渠道还在处理的退款到这里就算完成了
+    sqlx::query(
+        "UPDATE refunds SET status = 'SUCCEEDED', processed_at = CURRENT_TIMESTAMP
+         WHERE order_id = ? AND invoice_id <=> ? AND milestone_id <=> ? AND status = 'PROCESSING' AND provider_refund_id IS NOT NULL"
+    )
+        .bind(target.order_id)
+        .bind(target.invoice_id)
+        .bind(target.milestone_id)
+        .execute(&mut **tx)
+        .await?;
+    if !record_refund_total(tx, &target, refunded_total).await? {
+        return Ok(PaymentEffect { matched: true, notices: Vec::new() });
+    }
+
+    let message = format!("{} {} has been refunded in total for {}.", refunded_total, target.currency, target.label());
+    let notices = [target.buyer_company_id, target.supplier_company_id]
+        .into_iter()
+        .map(|company_id| PaymentNotice { company_id: Some(company_id), subject: "Payment refunded".to_string(), message: message.clone() })
+        .collect();
+    Ok(PaymentEffect { matched: true, notices })
+}
+
+/// 更新累计退款金额和付款状态（PARTIALLY_REFUNDED / REFUNDED）。
+/// 只会增加不会减少，所以渠道事件和我们自己的退款记录谁先到都一样；没有变化时返回 false
+pub(crate) async fn record_refund_total(
+    tx: &mut Transaction<'_, MySql>,
+    target: &PaymentTarget,
+    refunded_total: Decimal,
+) -> Result<bool, AppError> {
+    let (table, id, total_column) = target.table();
+    // MySQL 按从左到右执行赋值，payment_status 用的是更新后的 refunded_amount
+    let updated = sqlx::query(&format!(
+        "UPDATE {table} SET refunded_amount = LEAST({total}, GREATEST(refunded_amount, ?)),
+             payment_status = IF(refunded_amount >= {total}, 'REFUNDED', 'PARTIALLY_REFUNDED')
+         WHERE id = ? AND payment_status IN ('PAID', 'REFUND_PENDING', 'PARTIALLY_REFUNDED')
+           AND refunded_amount < LEAST({total}, ?)",
+        table = table,
+        total = total_column,
+    ))
+        .bind(refunded_total)
+        .bind(id)
+        .bind(refunded_total)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected() > 0;
+
+    if let (true, Some(parts)) = (updated, target.parts_table()) {
+        // 发票或分期退款后重新汇总订单：已退金额是各部分之和，全部退完订单才算 REFUNDED
+        sqlx::query(&format!(
+            "UPDATE purchase_orders po SET
+                 po.refunded_amount = (SELECT COALESCE(SUM(p.refunded_amount), 0) FROM {parts} p WHERE p.order_id = po.id),
+                 po.payment_status = IF(po.payment_status IN ('PAID', 'REFUND_PENDING', 'PARTIALLY_REFUNDED'),
+                     IF(NOT EXISTS (SELECT 1 FROM {parts} p WHERE p.order_id = po.id AND p.payment_status <> 'REFUNDED'),
+                        'REFUNDED', 'PARTIALLY_REFUNDED'),
+                     po.payment_status)
+             WHERE po.id = ?",
+            parts = parts,
+        ))
+            .bind(target.order_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(updated)
+}
+
+/// 买方发卡行发起拒付：记下时间并通知双方，资金在拒付处理完之前有风险
+pub(crate) async fn handle_chargeback(tx: &mut Transaction<'_, MySql>, payment_reference: &str, reason: &str) -> Result<PaymentEffect, AppError> {
+    let Some(target) = find_target(tx, "payment_reference", payment_reference).await? else {
+        return Ok(PaymentEffect::unmatched());
+    };
+    if !update_target(tx, &target, "chargeback_opened_at = CURRENT_TIMESTAMP", "chargeback_opened_at IS NULL").await? {
+        return Ok(PaymentEffect { matched: true, notices: Vec::new() });
+    }
+
+    let message = format!("The buyer's bank opened a chargeback on the payment for {} (reason: {}).", target.label(), reason);
+    let notices = [target.supplier_company_id, target.buyer_company_id]
+        .into_iter()
+        .map(|company_id| PaymentNotice { company_id: Some(company_id), subject: "Payment disputed".to_string(), message: message.clone() })
+        .collect();
+    Ok(PaymentEffect { matched: true, notices })
+}
+
+/// 管理员查看最近的支付事件，可按处理状态筛选
+pub async fn list_payment_events(pool: &MySqlPool, status: Option<String>) -> Result<Vec<PaymentEventLog>, AppError> {
+    if status.as_deref().is_some_and(|s| !["RECEIVED", "PROCESSED", "IGNORED", "FAILED"].contains(&s)) {
+        return Err(AppError::BadRequest("Status must be RECEIVED, PROCESSED, IGNORED or FAILED.".to_string()));
+    }
+
+    let events = sqlx::query_as(
+        "SELECT id, provider, event_id, event_type, payload, status, detail, attempts, received_at, processed_at
+         FROM payment_events WHERE (? IS NULL OR status = ?) ORDER BY received_at DESC, id DESC LIMIT 200"
+    )
+        .bind(&status)
+        .bind(&status)
+        .fetch_all(pool)
+        .await?;
+    Ok(events)
+}
@@ -0,0 +1,443 @@
+// src/services/payment_terms_service.rs
+// 账期付款：供应商给买方开 net-30/60 账期，买方的信用额度由管理员核定。账期订单按发票线下付款，
+// 供应商收到汇款后手工标记已付；后台定时提醒即将到期和已逾期的发票
+use crate::{
+    errors::AppError,
+    models::{
+        money::{self, Currency},
+        payment_terms::{CreditSummary, MarkPaidDto, PaymentTerms, SetCreditLimitDto, SetPaymentTermsDto},
+        user::Claims,
+    },
+    services::{chat_server::ChatServer, fx_service, notification_service, order_service, payment_provider::PaymentProvider, payment_service},
+};
+use crate::models::notification_preference::NotificationCategory;
+use actix::Addr;
+use chrono::{Duration, NaiveDate, Utc};
+use sqlx::{types::Decimal, FromRow, MySql, MySqlConnection, MySqlPool, Transaction};
+use std::{env, str::FromStr};
+
+/// 账期最长天数
+const MAX_NET_DAYS: u32 = 180;
+/// 到期前几天开始提醒
+const DUE_SOON_DAYS: i64 = 3;
+/// 逾期后每隔几天再提醒一次
+const OVERDUE_REMINDER_EVERY_DAYS: i64 = 7;
+/// 默认每天检查一次
+const DEFAULT_REMINDER_INTERVAL_SECS: u64 = 86400;
+
+const TERMS_SELECT: &str = "SELECT t.*, b.name AS buyer_name, s.name AS supplier_name
+     FROM payment_terms t
+     JOIN companies b ON t.buyer_company_id = b.id
+     JOIN companies s ON t.supplier_company_id = s.id";
+
+/// 剩余可用额度，没有额度时为 None
+pub(crate) fn credit_available(credit_limit: Option<Decimal>, exposure: Decimal) -> Option<Decimal> {
+    credit_limit.map(|limit| (limit - exposure).max(Decimal::ZERO))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Reminder {
+    DueSoon,
+    Overdue,
+}
+
+/// 判断今天要不要提醒：到期前提醒一次，逾期后马上提醒，之后每隔一段时间再提醒
+pub(crate) fn reminder_due(due_date: NaiveDate, today: NaiveDate, last_sent: Option<NaiveDate>) -> Option<Reminder> {
+    if today > due_date {
+        match last_sent {
+            Some(last) if last > due_date && (today - last).num_days() < OVERDUE_REMINDER_EVERY_DAYS => None,
+            _ => Some(Reminder::Overdue),
+        }
+    } else if (due_date - today).num_days() <= DUE_SOON_DAYS && last_sent.is_none() {
+        Some(Reminder::DueSoon)
+    } else {
+        None
+    }
+}
+
+/// 供应商给某个买方设置账期，双方要有过订单或框架协议
+pub async fn set_payment_terms(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    dto: SetPaymentTermsDto,
+    claims: &Claims,
+) -> Result<PaymentTerms, AppError> {
+    if claims.company_type != "SUPPLIER" {
+        return Err(AppError::BadRequest("Only suppliers can grant payment terms.".to_string()));
+    }
+    if dto.net_days == 0 || dto.net_days > MAX_NET_DAYS {
+        return Err(AppError::BadRequest(format!("Net days must be between 1 and {}.", MAX_NET_DAYS)));
+    }
+    let (relationships,): (i64,) = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM purchase_orders WHERE buyer_company_id = ? AND supplier_company_id = ?)
+              + (SELECT COUNT(*) FROM blanket_agreements WHERE buyer_company_id = ? AND supplier_company_id = ?)"
+    )
+        .bind(dto.buyer_company_id)
+        .bind(claims.company_id)
+        .bind(dto.buyer_company_id)
+        .bind(claims.company_id)
+        .fetch_one(pool)
+        .await?;
+    if relationships == 0 {
+        return Err(AppError::BadRequest("Payment terms can only be granted to buyers you have traded with.".to_string()));
+    }
+
+    sqlx::query(
+        "INSERT INTO payment_terms (buyer_company_id, supplier_company_id, net_days, updated_by_user_id) VALUES (?, ?, ?, ?)
+         ON DUPLICATE KEY UPDATE net_days = VALUES(net_days), updated_by_user_id = VALUES(updated_by_user_id)"
+    )
+        .bind(dto.buyer_company_id)
+        .bind(claims.company_id)
+        .bind(dto.net_days)
+        .bind(claims.sub)
+        .execute(pool)
+        .await?;
+    let terms: PaymentTerms = sqlx::query_as(&format!("{} WHERE t.buyer_company_id = ? AND t.supplier_company_id = ?", TERMS_SELECT))
+        .bind(dto.buyer_company_id)
+        .bind(claims.company_id)
+        .fetch_one(pool)
+        .await?;
+
+    let subject = format!("Payment terms from {}", terms.supplier_name);
+    let message = format!(
+        "{} now offers you net-{} payment terms. New orders are placed on terms while your credit limit allows it.",
+        terms.supplier_name, terms.net_days
+    );
+    notification_service::notify_company(pool, chat_server, terms.buyer_company_id, NotificationCategory::Payment, &subject, &message, "/orders").await;
+    Ok(terms)
+}
+
+/// 供应商取消给买方的账期，已下的账期订单不受影响
+pub async fn remove_payment_terms(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    buyer_company_id: i32,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    let result = sqlx::query("DELETE FROM payment_terms WHERE buyer_company_id = ? AND supplier_company_id = ?")
+        .bind(buyer_company_id)
+        .bind(claims.company_id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest("Payment terms not found.".to_string()));
+    }
+    let subject = "Payment terms withdrawn".to_string();
+    let message = "A supplier has withdrawn your payment terms; new orders with them are paid online.".to_string();
+    notification_service::notify_company(pool, chat_server, buyer_company_id, NotificationCategory::Payment, &subject, &message, "/orders").await;
+    Ok(())
+}
+
+/// 本公司作为买方或供应商的全部账期
+pub async fn list_payment_terms(pool: &MySqlPool, claims: &Claims) -> Result<Vec<PaymentTerms>, AppError> {
+    let terms = sqlx::query_as(&format!(
+        "{} WHERE t.buyer_company_id = ? OR t.supplier_company_id = ? ORDER BY t.updated_at DESC",
+        TERMS_SELECT
+    ))
+        .bind(claims.company_id)
+        .bind(claims.company_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(terms)
+}
+
+// 账期订单中还没付的金额，折算到买方的报表币种。已付的发票从订单金额里扣掉
+async fn credit_exposure(conn: &mut MySqlConnection, buyer_company_id: i32, currency: Currency) -> Result<Decimal, AppError> {
+    let amounts: Vec<(String, Decimal)> = sqlx::query_as(
+        "SELECT po.currency,
+                SUM(po.total_amount - COALESCE((SELECT SUM(i.total) FROM invoices i
+                                                WHERE i.order_id = po.id AND i.payment_status NOT IN ('UNPAID', 'FAILED')), 0))
+         FROM purchase_orders po
+         WHERE po.buyer_company_id = ? AND po.payment_terms_days IS NOT NULL
+           AND po.payment_status IN ('UNPAID', 'FAILED') AND po.status <> 'CANCELLED'
+         GROUP BY po.currency"
+    )
+        .bind(buyer_company_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    if amounts.is_empty() {
+        return Ok(Decimal::ZERO);
+    }
+    let fx = fx_service::load_fx_table(&mut *conn).await?;
+    fx_service::sum_in_currency(&fx, &amounts, currency)
+}
+
+async fn credit_summary(conn: &mut MySqlConnection, company_id: i32) -> Result<CreditSummary, AppError> {
+    let (company_type, credit_limit, reporting_currency): (String, Option<Decimal>, String) = sqlx::query_as(
+        "SELECT company_type, credit_limit, reporting_currency FROM companies WHERE id = ?"
+    )
+        .bind(company_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Company not found.".to_string()))?;
+    if company_type != "BUYER" {
+        return Err(AppError::BadRequest("Credit limits only apply to buyers.".to_string()));
+    }
+    let exposure = credit_exposure(conn, company_id, Currency::from_str(&reporting_currency)?).await?;
+    Ok(CreditSummary {
+        company_id,
+        currency: reporting_currency,
+        credit_limit,
+        exposure,
+        available: credit_available(credit_limit, exposure),
+    })
+}
+
+/// 买方查看自己的额度，管理员可以查看任何买方
+pub async fn get_credit_summary(pool: &MySqlPool, company_id: i32, claims: &Claims) -> Result<CreditSummary, AppError> {
+    if !claims.is_admin && claims.company_id != company_id {
+        return Err(AppError::BadRequest("You can only view your own credit limit.".to_string()));
+    }
+    let mut conn = pool.acquire().await?;
+    credit_summary(&mut conn, company_id).await
+}
+
+/// 管理员核定买方的信用额度
+pub async fn set_credit_limit(pool: &MySqlPool, company_id: i32, dto: SetCreditLimitDto) -> Result<CreditSummary, AppError> {
+    if dto.credit_limit.is_some_and(|limit| limit < Decimal::ZERO || limit.normalize().scale() > 2) {
+        return Err(AppError::BadRequest("Credit limit must be zero or positive with at most 2 decimals.".to_string()));
+    }
+    // 额度没变时 MySQL 返回 0 行，公司是否存在、是不是买方交给 credit_summary 检查
+    sqlx::query("UPDATE companies SET credit_limit = ? WHERE id = ? AND company_type = 'BUYER'")
+        .bind(dto.credit_limit.map(money::round_amount))
+        .bind(company_id)
+        .execute(pool)
+        .await?;
+    let mut conn = pool.acquire().await?;
+    credit_summary(&mut conn, company_id).await
+}
+
+/// 新订单是否按账期付款：双方有账期，且买方剩余额度够这笔订单。返回账期天数。
+/// 锁住买方公司行，避免并发下单一起超出额度
+pub(crate) async fn terms_for_new_order(
+    tx: &mut Transaction<'_, MySql>,
+    buyer_company_id: i32,
+    supplier_company_id: i32,
+    total_amount: Decimal,
+    currency: &str,
+) -> Result<Option<i32>, AppError> {
+    let terms: Option<(i32,)> = sqlx::query_as("SELECT net_days FROM payment_terms WHERE buyer_company_id = ? AND supplier_company_id = ?")
+        .bind(buyer_company_id)
+        .bind(supplier_company_id)
+        .fetch_optional(&mut **tx)
+        .await?;
+    let Some((net_days,)) = terms else {
+        return Ok(None);
+    };
+    let (credit_limit, reporting_currency): (Option<Decimal>, String) = sqlx::query_as(
+        "SELECT credit_limit, reporting_currency FROM companies WHERE id = ? FOR UPDATE"
+    )
+        .bind(buyer_company_id)
+        .fetch_one(&mut **tx)
+        .await?;
+    if credit_limit.is_none() {
+        return Ok(None);
+    }
+
+    let reporting_currency = Currency::from_str(&reporting_currency)?;
+    // 缺汇率时无法判断额度，按在线付款处理
+    let checked = async {
+        let exposure = credit_exposure(tx, buyer_company_id, reporting_currency).await?;
+        let fx = fx_service::load_fx_table(&mut **tx).await?;
+        let amount = fx.convert(total_amount, Currency::from_str(currency)?, reporting_currency)?;
+        Ok::<_, AppError>(credit_available(credit_limit, exposure).is_some_and(|available| amount <= available))
+    }
+        .await;
+    match checked {
+        Ok(true) => Ok(Some(net_days)),
+        Ok(false) => {
+            log::info!("Buyer #{} is over its credit limit; the new order is paid online.", buyer_company_id);
+            Ok(None)
+        }
+        Err(e) => {
+            log::warn!("Could not check the credit limit of buyer #{}: {:?}", buyer_company_id, e);
+            Ok(None)
+        }
+    }
+}
+
+/// 供应商或管理员确认收到线下付款。指定发票时标记该发票，否则标记整单
+pub async fn mark_paid(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    provider: &dyn PaymentProvider,
+    order_id: i32,
+    invoice_id: Option<i32>,
+    dto: MarkPaidDto,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    let reference = order_service::trimmed_text(Some(dto.remittance_reference), 255, "Remittance reference")?
+        .ok_or_else(|| AppError::BadRequest("Remittance reference is required.".to_string()))?;
+
+    let mut tx = pool.begin().await?;
+    let order = order_service::lock_order(&mut tx, order_id).await?;
+    if claims.company_id != order.supplier_company_id && !claims.is_admin {
+        return Err(AppError::BadRequest("Only the supplier or an admin can record a payment.".to_string()));
+    }
+    if invoice_id.is_none() {
+        let (parts,): (i64,) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM invoices WHERE order_id = ?) + (SELECT COUNT(*) FROM payment_milestones WHERE order_id = ?)"
+        )
+            .bind(order.id)
+            .bind(order.id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if parts > 0 {
+            return Err(AppError::BadRequest("This order is paid by invoice or in milestones; record the payment on the invoice instead.".to_string()));
+        }
+    }
+
+    let target = payment_service::record_manual_payment(&mut tx, provider, order.id, invoice_id, &reference, claims.sub)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("This payment is not awaiting payment.".to_string()))?;
+    tx.commit().await?;
+
+    let subject = format!("Payment recorded for order #{}", order.id);
+    let message = format!("{} has been marked as paid (remittance reference {}).", target.label(), reference);
+    notification_service::notify_company(pool, chat_server, order.buyer_company_id, NotificationCategory::Payment, &subject, &message, "/orders").await;
+    Ok(())
+}
+
+/// 按发票标记已付时先找到订单
+pub async fn mark_invoice_paid(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    provider: &dyn PaymentProvider,
+    invoice_id: i32,
+    dto: MarkPaidDto,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    let (order_id,): (i32,) = sqlx::query_as("SELECT order_id FROM invoices WHERE id = ?")
+        .bind(invoice_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Invoice not found.".to_string()))?;
+    mark_paid(pool, chat_server, provider, order_id, Some(invoice_id), dto, claims).await
+}
+
+#[derive(Debug, FromRow)]
+struct DueInvoice {
+    id: i32,
+    invoice_number: String,
+    order_id: i32,
+    buyer_company_id: i32,
+    supplier_company_id: i32,
+    total: Decimal,
+    currency: String,
+    due_date: NaiveDate,
+    last_reminder_sent_on: Option<NaiveDate>,
+}
+
+/// 检查一遍待付款的发票，发出到期和逾期提醒，返回发出的提醒数
+pub async fn run_payment_reminders(pool: &MySqlPool, chat_server: &Addr<ChatServer>, today: NaiveDate) -> Result<usize, AppError> {
+    let invoices: Vec<DueInvoice> = sqlx::query_as(
+        "SELECT id, invoice_number, order_id, buyer_company_id, supplier_company_id, total, currency, due_date, last_reminder_sent_on
+         FROM invoices WHERE payment_status IN ('UNPAID', 'FAILED') AND due_date <= ?"
+    )
+        .bind(today + Duration::days(DUE_SOON_DAYS))
+        .fetch_all(pool)
+        .await?;
+
+    let mut sent = 0;
+    for invoice in invoices {
+        let Some(reminder) = reminder_due(invoice.due_date, today, invoice.last_reminder_sent_on) else {
+            continue;
+        };
+        // 条件更新，多个实例同时跑时只提醒一次
+        let result = sqlx::query("UPDATE invoices SET last_reminder_sent_on = ? WHERE id = ? AND last_reminder_sent_on <=> ?")
+            .bind(today)
+            .bind(invoice.id)
+            .bind(invoice.last_reminder_sent_on)
+            .execute(pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            continue;
+        }
+        sent += 1;
+
+        match reminder {
+            Reminder::DueSoon => {
+                let message = format!(
+                    "Invoice {} for order #{} ({:.2} {}) is due on {}.",
+                    invoice.invoice_number, invoice.order_id, invoice.total, invoice.currency, invoice.due_date
+                );
+                let vars = [
+                    ("invoice_number", invoice.invoice_number.clone()),
+                    ("order_id", invoice.order_id.to_string()),
+                    ("amount", format!("{:.2} {}", invoice.total, invoice.currency)),
+                    ("due_date", invoice.due_date.to_string()),
+                ];
+                notification_service::notify_company_with_template(pool, chat_server, invoice.buyer_company_id, NotificationCategory::Payment, &message, "/orders", "invoice_due_soon", &vars).await;
+            }
+            Reminder::Overdue => {
+                let days = (today - invoice.due_date).num_days();
+                let message = format!(
+                    "Invoice {} for order #{} ({:.2} {}) was due on {} and is {} day(s) overdue.",
+                    invoice.invoice_number, invoice.order_id, invoice.total, invoice.currency, invoice.due_date, days
+                );
+                let vars = [
+                    ("invoice_number", invoice.invoice_number.clone()),
+                    ("order_id", invoice.order_id.to_string()),
+                    ("amount", format!("{:.2} {}", invoice.total, invoice.currency)),
+                    ("due_date", invoice.due_date.to_string()),
+                    ("days_overdue", days.to_string()),
+                ];
+                for company_id in [invoice.buyer_company_id, invoice.supplier_company_id] {
+                    notification_service::notify_company_with_template(pool, chat_server, company_id, NotificationCategory::Payment, &message, "/orders", "invoice_overdue", &vars).await;
+                }
+            }
+        }
+    }
+    Ok(sent)
+}
+
+/// 启动后台付款提醒任务。间隔可以用 PAYMENT_REMINDER_INTERVAL_SECS 配置
+pub fn spawn_payment_reminders(pool: MySqlPool, chat_server: Addr<ChatServer>) {
+    let interval_secs = env::var("PAYMENT_REMINDER_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_REMINDER_INTERVAL_SECS);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        loop {
+            ticker.tick().await;
+            match run_payment_reminders(&pool, &chat_server, Utc::now().date_naive()).await {
+                Ok(0) => {}
+                Ok(sent) => log::info!("Sent {} invoice payment reminders", sent),
+                Err(e) => log::error!("Invoice payment reminders failed: {:?}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credit_available() {
+        let d = |s: &str| Decimal::from_str(s).unwrap();
+        assert_eq!(credit_available(None, d("100")), None);
+        assert_eq!(credit_available(Some(d("5000")), d("1200.50")), Some(d("3799.50")));
+        // 超出额度（比如汇率变了）时可用额度为0
+        assert_eq!(credit_available(Some(d("1000")), d("1500")), Some(Decimal::ZERO));
+    }
+
+    #[test]
+    fn test_reminder_due() {
+        let due = NaiveDate::from_ymd_opt(2026, 11, 30).unwrap();
+        let day = |m: u32, d: u32| NaiveDate::from_ymd_opt(2026, m, d).unwrap();
+
+        assert_eq!(reminder_due(due, day(11, 20), None), None);
+        assert_eq!(reminder_due(due, day(11, 27), None), Some(Reminder::DueSoon));
+        assert_eq!(reminder_due(due, day(11, 30), Some(day(11, 27))), None);
+        // 到期前提醒过，逾期后还要再提醒
+        assert_eq!(reminder_due(due, day(12, 1), Some(day(11, 27))), Some(Reminder::Overdue));
+        assert_eq!(reminder_due(due, day(12, 5), Some(day(12, 1))), None);
+        assert_eq!(reminder_due(due, day(12, 8), Some(day(12, 1))), Some(Reminder::Overdue));
+        // 第一次检查时已经逾期
+        assert_eq!(reminder_due(due, day(12, 15), None), Some(Reminder::Overdue));
+    }
+}
@@ -1,16 +1,43 @@
 // src/services/quote_service.rs
 use crate::{
     errors::AppError,
-    models::{quote::{CreateQuoteDto, Quote}, user::Claims},
+    models::{money::{self, Currency, Money}, quote::{AcceptQuoteDto, CreateQuoteDto, Quote, SupplierQuotePage, SupplierQuoteSummary}, tax::Incoterm, user::Claims},
 };
-use sqlx::{types::Decimal, MySqlPool, Row};
-use std::str::FromStr;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{types::Decimal, MySql, MySqlPool, QueryBuilder, Row};
 use actix::Addr;
 use crate::models::order::PurchaseOrder;
 use crate::services::chat_server::ChatServer;
-use crate::services::notification_service;
+use crate::services::{document_service, fx_service, order_service, tax_service};
+use crate::services::order_service::NewPurchaseOrder;
+use std::str::FromStr;
+use crate::models::{email::EmailAttachment, notification_preference::NotificationCategory};
 use crate::services::notification_service::NotificationBuilder;
 
+/// 校验报价的运费和贸易术语，返回 (运费, 贸易术语, 指定地点)
+fn shipping_terms(
+    shipping_cost: Option<Decimal>,
+    incoterm: Option<String>,
+    incoterm_place: Option<String>,
+) -> Result<(Decimal, Option<Incoterm>, Option<String>), AppError> {
+    let shipping_cost = shipping_cost.unwrap_or(Decimal::ZERO);
+    if shipping_cost < Decimal::ZERO || shipping_cost.normalize().scale() > money::MAX_DECIMAL_PLACES || shipping_cost > money::MAX_AMOUNT {
+        return Err(AppError::BadRequest("Shipping cost must be zero or a positive amount with at most 2 decimals.".to_string()));
+    }
+    let incoterm = incoterm
+        .filter(|t| !t.trim().is_empty())
+        .map(|t| Incoterm::parse(&t).ok_or_else(|| AppError::BadRequest(format!("Unknown Incoterm: {}", t.trim()))))
+        .transpose()?;
+    let incoterm_place = order_service::trimmed_text(incoterm_place, 100, "Incoterm place")?;
+    if incoterm.is_none() && incoterm_place.is_some() {
+        return Err(AppError::BadRequest("A named place needs an Incoterm.".to_string()));
+    }
+    if incoterm.is_some_and(|t| !t.allows_shipping_charge()) && shipping_cost > Decimal::ZERO {
+        return Err(AppError::BadRequest("Under EXW the buyer collects the goods; shipping cannot be charged.".to_string()));
+    }
+    Ok((shipping_cost, incoterm, incoterm_place))
+}
+
 pub async fn create_quote(
     pool: &MySqlPool,
     chat_server: &Addr<ChatServer>,
@@ -22,62 +49,84 @@ pub async fn create_quote(
         return Err(AppError::BadRequest("Only suppliers can create quotes".to_string()));
     }
 
-    let _rfq: (i32,) = sqlx::query_as("SELECT id FROM rfqs WHERE id = ? AND status = 'OPEN'")
+    if dto.expires_at.is_some_and(|t| t <= Utc::now()) {
+        return Err(AppError::BadRequest("Quote expiry must be in the future.".to_string()));
+    }
+
+    let price = Money::new(dto.price, dto.currency)?;
+    let (shipping_cost, incoterm, incoterm_place) = shipping_terms(dto.shipping_cost, dto.incoterm, dto.incoterm_place)?;
+
+    let mut tx = pool.begin().await?;
+
+    // 锁住RFQ行，保证并发提交时“一家供应商一个有效报价”的检查不会被绕过
+    let _rfq: (i32,) = sqlx::query_as("SELECT id FROM rfqs WHERE id = ? AND status = 'OPEN' FOR UPDATE")
         .bind(rfq_id)
-        .fetch_optional(pool)
+        .fetch_optional(&mut *tx)
         .await?
         .ok_or_else(|| AppError::BadRequest("RFQ not found or is not open for quotes".to_string()))?;
 
-    let price_decimal = Decimal::from_str(&dto.price.to_string())
-        .map_err(|_| AppError::BadRequest("Invalid price format".to_string()))?;
+    let existing: Option<(i32,)> = sqlx::query_as(
+        "SELECT id FROM quotes WHERE rfq_id = ? AND supplier_company_id = ? AND status = 'SUBMITTED' LIMIT 1",
+    )
+        .bind(rfq_id)
+        .bind(claims.company_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    if let Some((existing_id,)) = existing {
+        return Err(AppError::BadRequest(format!(
+            "You already have an active quote (#{}) for this RFQ. Please revise it instead.",
+            existing_id
+        )));
+    }
 
     let result = sqlx::query(
-        "INSERT INTO quotes (rfq_id, supplier_company_id, price, lead_time_days, notes) VALUES (?, ?, ?, ?, ?)",
+        "INSERT INTO quotes (rfq_id, supplier_company_id, price, currency, shipping_cost, incoterm, incoterm_place, lead_time_days, notes, expires_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
     )
         .bind(rfq_id)
         .bind(claims.company_id)
-        .bind(price_decimal)
+        .bind(price.amount())
+        .bind(price.currency().code())
+        .bind(shipping_cost)
+        .bind(incoterm.map(|t| t.as_str()))
+        .bind(&incoterm_place)
         .bind(dto.lead_time_days)
         .bind(dto.notes)
-        .execute(pool)
+        .bind(dto.expires_at)
+        .execute(&mut *tx)
         .await?;
 
+    tx.commit().await?;
+
     let quote_id = result.last_insert_id();
 
     // 修好了！同时触发两种通知
 
-    // 1. 查询需要通知的用户ID、邮箱和RFQ标题
+    // 1. 查询需要通知的用户ID和RFQ标题
     // 我们假设一个公司只有一个用户，实际应用中这里可能更复杂
 
-    let rfq_owner_info: Result<(i32, String, String), _> = sqlx::query_as(
-        "SELECT u.id, u.email, r.title FROM rfqs r JOIN users u ON r.buyer_company_id = u.company_id WHERE r.id = ?"
+    let rfq_owner_info: Result<(i32, String), _> = sqlx::query_as(
+        "SELECT u.id, r.title FROM rfqs r JOIN users u ON r.buyer_company_id = u.company_id WHERE r.id = ?"
     )
         .bind(rfq_id)
         .fetch_one(pool)
         .await;
 
-    if let Ok((buyer_user_id, buyer_email, rfq_title)) = rfq_owner_info {
+    if let Ok((buyer_user_id, rfq_title)) = rfq_owner_info {
 
-        let in_app_result = NotificationBuilder::new(
+        let result = NotificationBuilder::new(
             buyer_user_id,
+            NotificationCategory::Rfq,
             format!("You received a new quote for '{}'", &rfq_title)
         )
             .with_link(format!("/rfqs/{}", rfq_id))
+            .with_email("quote_received", &[("rfq_title", rfq_title.clone())])
             .send(pool, chat_server)
             .await;
 
-        if let Err(e) = in_app_result {
-            log::error!("Failed to send in-app notification: {:?}", e);
-        }
-        let subject = format!("New Quote Received: {}", &rfq_title);
-        let body = format!(
-            "Hello,\n\nA new quote has been submitted for your RFQ '{}'.\n\nPlease log in to your SCCP account to review it.",
-            &rfq_title
-        );
-
-        let email_result = notification_service::send_email(buyer_email, subject, body).await;
-        if let Err(e) = email_result {
-            log::error!("Failed to send email notification: {:?}", e);
+        if let Err(e) = result {
+            log::error!("Failed to send quote notification: {:?}", e);
         }
     } else {
         log::error!("Failed to fetch RFQ owner info for notifications for RFQ ID: {}", rfq_id);
@@ -96,26 +145,183 @@ pub async fn get_quotes_for_rfq(pool: &MySqlPool, rfq_id: i32, claims: &Claims)
         return Err(AppError::BadRequest("You are not authorized to view quotes for this RFQ".to_string()));
     }
 
-    let quotes = sqlx::query_as::<_, Quote>(
+    let mut quotes = sqlx::query_as::<_, Quote>(
         "SELECT q.*, c.name as supplier_company_name FROM quotes q JOIN companies c ON q.supplier_company_id = c.id WHERE q.rfq_id = ? ORDER BY q.price ASC"
     )
         .bind(rfq_id)
         .fetch_all(pool)
         .await?;
 
+    // 报价可能是不同币种、不同运费，按含税运总价折算到采购方的报表币种后再排序
+    let reporting_currency = fx_service::get_reporting_currency(pool, claims.company_id).await?;
+    let fx = fx_service::load_fx_table(pool).await?;
+    let tax_rate = tax_service::rate_for_company(pool, claims.company_id).await?.map_or(Decimal::ZERO, |(_, rate)| rate);
+    for quote in quotes.iter_mut() {
+        let totals = tax_service::order_totals(quote.price, quote.shipping_cost, tax_rate);
+        quote.estimated_tax = totals.tax;
+        quote.estimated_total = totals.total;
+        quote.normalized_currency = reporting_currency.code().to_string();
+        quote.normalized_price = Currency::from_str(&quote.currency)
+            .and_then(|from| fx.convert(totals.total, from, reporting_currency))
+            .map_err(|e| log::warn!("Cannot normalize price of quote #{}: {}", quote.id, e))
+            .ok();
+    }
+    // 无法折算的报价排在最后
+    quotes.sort_by_key(|q| (q.normalized_price.is_none(), q.normalized_price));
+
     Ok(quotes)
 }
 
+/// 供应商修改自己仍有效的报价，版本号+1
+pub async fn revise_quote(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    quote_id: i32,
+    dto: CreateQuoteDto,
+    claims: &Claims,
+) -> Result<i32, AppError> {
+    if claims.company_type != "SUPPLIER" {
+        return Err(AppError::BadRequest("Only suppliers can revise quotes".to_string()));
+    }
+    if dto.expires_at.is_some_and(|t| t <= Utc::now()) {
+        return Err(AppError::BadRequest("Quote expiry must be in the future.".to_string()));
+    }
+
+    let price = Money::new(dto.price, dto.currency)?;
+    let (shipping_cost, incoterm, incoterm_place) = shipping_terms(dto.shipping_cost, dto.incoterm, dto.incoterm_place)?;
+
+    let mut tx = pool.begin().await?;
+
+    let quote: Option<(i32, i32)> = sqlx::query_as(
+        "SELECT q.rfq_id, q.revision FROM quotes q JOIN rfqs r ON q.rfq_id = r.id
+         WHERE q.id = ? AND q.supplier_company_id = ? AND q.status = 'SUBMITTED' AND r.status = 'OPEN' FOR UPDATE",
+    )
+        .bind(quote_id)
+        .bind(claims.company_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    let (rfq_id, revision) = quote.ok_or_else(|| {
+        AppError::BadRequest("Quote not found, no longer active, or the RFQ is closed.".to_string())
+    })?;
+    let new_revision = revision + 1;
+
+    sqlx::query(
+        "UPDATE quotes SET price = ?, currency = ?, shipping_cost = ?, incoterm = ?, incoterm_place = ?, lead_time_days = ?, notes = ?,
+                           expires_at = ?, revision = ?
+         WHERE id = ?",
+    )
+        .bind(price.amount())
+        .bind(price.currency().code())
+        .bind(shipping_cost)
+        .bind(incoterm.map(|t| t.as_str()))
+        .bind(&incoterm_place)
+        .bind(dto.lead_time_days)
+        .bind(dto.notes)
+        .bind(dto.expires_at)
+        .bind(new_revision)
+        .bind(quote_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    let rfq_owner_info: Result<(i32, String), _> = sqlx::query_as(
+        "SELECT u.id, r.title FROM rfqs r JOIN users u ON r.buyer_company_id = u.company_id WHERE r.id = ? LIMIT 1"
+    )
+        .bind(rfq_id)
+        .fetch_one(pool)
+        .await;
+
+    if let Ok((buyer_user_id, rfq_title)) = rfq_owner_info {
+        let in_app_result = NotificationBuilder::new(
+            buyer_user_id,
+            NotificationCategory::Rfq,
+            format!("A supplier revised their quote for '{}' (revision {})", &rfq_title, new_revision),
+        )
+            .with_link(format!("/rfqs/{}", rfq_id))
+            .send(pool, chat_server)
+            .await;
+
+        if let Err(e) = in_app_result {
+            log::error!("Failed to send in-app notification: {:?}", e);
+        }
+    }
+
+    Ok(new_revision)
+}
+
+/// 供应商查看自己提交过的所有报价（分页，可按状态过滤）
+pub async fn get_quotes_for_supplier(
+    pool: &MySqlPool,
+    claims: &Claims,
+    status: Option<String>,
+    page: u32,
+    page_size: u32,
+) -> Result<SupplierQuotePage, AppError> {
+    if claims.company_type != "SUPPLIER" {
+        return Err(AppError::BadRequest("Only suppliers can list their quotes".to_string()));
+    }
+
+    let status = status.map(|s| s.trim().to_uppercase()).filter(|s| !s.is_empty());
+    if status.as_deref().is_some_and(|s| !matches!(s, "SUBMITTED" | "ACCEPTED" | "REJECTED")) {
+        return Err(AppError::BadRequest("Invalid status filter.".to_string()));
+    }
+
+    let page = page.max(1);
+    let page_size = page_size.clamp(1, 100);
+
+    // 列表和总数用同样的过滤条件
+    let push_filters = |qb: &mut QueryBuilder<MySql>| {
+        qb.push(" WHERE q.supplier_company_id = ").push_bind(claims.company_id);
+        if let Some(s) = &status {
+            qb.push(" AND q.status = ").push_bind(s.clone());
+        }
+    };
+
+    let mut count_qb: QueryBuilder<MySql> = QueryBuilder::new("SELECT COUNT(*) FROM quotes q");
+    push_filters(&mut count_qb);
+    let (total,): (i64,) = count_qb.build_query_as().fetch_one(pool).await?;
+
+    // 再下单和框架协议下的订单沿用同一报价，只关联中标时生成的那张订单，避免一条报价出多行
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(
+        "SELECT q.id, q.rfq_id, r.title as rfq_title, r.status as rfq_status, q.price, q.currency, q.lead_time_days,
+                q.status, q.revision, q.expires_at, q.price_feedback,
+                po.id as purchase_order_id, po.status as order_status, q.created_at
+         FROM quotes q
+         JOIN rfqs r ON q.rfq_id = r.id
+         LEFT JOIN purchase_orders po ON po.quote_id = q.id AND po.source_order_id IS NULL AND po.blanket_agreement_id IS NULL",
+    );
+    push_filters(&mut qb);
+    qb.push(" ORDER BY q.created_at DESC LIMIT ")
+        .push_bind(page_size)
+        .push(" OFFSET ")
+        .push_bind((page - 1) * page_size);
+
+    let items = qb.build_query_as::<SupplierQuoteSummary>().fetch_all(pool).await?;
+
+    Ok(SupplierQuotePage { items, total, page, page_size })
+}
+
 pub async fn accept_quote(
     pool: &MySqlPool,
     chat_server: &Addr<ChatServer>,
     quote_id: i32,
+    dto: AcceptQuoteDto,
     claims: &Claims,
 ) -> Result<u64, AppError> {
+    let rejection_reason = dto
+        .rejection_reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if rejection_reason.as_ref().is_some_and(|r| r.chars().count() > 500) {
+        return Err(AppError::BadRequest("Rejection reason must be at most 500 characters.".to_string()));
+    }
+
     let mut tx = pool.begin().await?;
 
     let quote_info = sqlx::query(
-        "SELECT q.rfq_id, q.supplier_company_id, q.price, r.buyer_company_id, r.status as rfq_status, r.title as rfq_title
+        "SELECT q.rfq_id, q.supplier_company_id, q.price, q.currency, q.shipping_cost, q.incoterm, q.incoterm_place, q.status as quote_status, q.expires_at, q.lead_time_days, r.buyer_company_id, r.status as rfq_status, r.title as rfq_title, r.quantity
          FROM quotes q JOIN rfqs r ON q.rfq_id = r.id WHERE q.id = ? FOR UPDATE",
     )
         .bind(quote_id)
@@ -125,58 +331,259 @@ pub async fn accept_quote(
     let rfq_id: i32 = quote_info.try_get("rfq_id")?;
     let supplier_company_id: i32 = quote_info.try_get("supplier_company_id")?;
     let price: Decimal = quote_info.try_get("price")?;
+    let currency: String = quote_info.try_get("currency")?;
+    let shipping_cost: Decimal = quote_info.try_get("shipping_cost")?;
+    let incoterm: Option<String> = quote_info.try_get("incoterm")?;
+    let incoterm_place: Option<String> = quote_info.try_get("incoterm_place")?;
     let buyer_company_id: i32 = quote_info.try_get("buyer_company_id")?;
     let rfq_status: String = quote_info.try_get("rfq_status")?;
     let rfq_title: String = quote_info.try_get("rfq_title")?;
+    let quantity: i32 = quote_info.try_get("quantity")?;
+    let quote_status: String = quote_info.try_get("quote_status")?;
+    let expires_at: Option<DateTime<Utc>> = quote_info.try_get("expires_at")?;
+    let lead_time_days: i32 = quote_info.try_get("lead_time_days")?;
 
     if buyer_company_id != claims.company_id || rfq_status != "OPEN" {
         return Err(AppError::BadRequest(
             "Not authorized to accept this quote or RFQ is not open.".to_string(),
         ));
     }
+    if quote_status != "SUBMITTED" {
+        return Err(AppError::BadRequest("This quote is no longer active.".to_string()));
+    }
+    if expires_at.is_some_and(|t| t <= Utc::now()) {
+        return Err(AppError::BadRequest("This quote has expired.".to_string()));
+    }
 
     sqlx::query("UPDATE rfqs SET status = 'AWARDED' WHERE id = ?").bind(rfq_id).execute(&mut *tx).await?;
     sqlx::query("UPDATE quotes SET status = 'ACCEPTED' WHERE id = ?").bind(quote_id).execute(&mut *tx).await?;
 
-    let po_result = sqlx::query(
-        "INSERT INTO purchase_orders (quote_id, rfq_id, buyer_company_id, supplier_company_id, total_amount) VALUES (?, ?, ?, ?, ?)",
+    // 同一个RFQ下其余仍为SUBMITTED的报价全部拒绝，并在同一事务中写入原因和价格反馈
+    let losing_quotes: Vec<(i32, i32, Decimal, String)> = sqlx::query_as(
+        "SELECT id, supplier_company_id, price + shipping_cost, currency FROM quotes WHERE rfq_id = ? AND id <> ? AND status = 'SUBMITTED' FOR UPDATE",
+    )
+        .bind(rfq_id)
+        .bind(quote_id)
+        .fetch_all(&mut *tx)
+        .await?;
+
+    // 价格反馈按货款加运费比较，落选报价要折算到中标报价的币种
+    let fx = if dto.share_price_feedback && !losing_quotes.is_empty() {
+        Some(fx_service::load_fx_table(pool).await?)
+    } else {
+        None
+    };
+    let winning_currency = Currency::from_str(&currency)?;
+
+    let mut rejected = Vec::with_capacity(losing_quotes.len());
+    for (losing_quote_id, losing_supplier_id, losing_price, losing_currency) in losing_quotes {
+        let feedback = fx.as_ref().and_then(|fx| {
+            Currency::from_str(&losing_currency)
+                .and_then(|from| fx.convert(losing_price, from, winning_currency))
+                .ok()
+                .and_then(|comparable| price_feedback_message(price + shipping_cost, comparable))
+        });
+
+        sqlx::query(
+            "UPDATE quotes SET status = 'REJECTED', rejection_reason = ?, price_feedback = ?, rejected_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+            .bind(&rejection_reason)
+            .bind(&feedback)
+            .bind(losing_quote_id)
+            .execute(&mut *tx)
+            .await?;
+
+        rejected.push((losing_supplier_id, feedback));
+    }
+
+    let (po_id, po_number) = order_service::create_purchase_order(
+        &mut tx,
+        NewPurchaseOrder {
+            quote_id,
+            rfq_id,
+            source_order_id: None,
+            blanket_agreement_id: None,
+            buyer_company_id,
+            supplier_company_id,
+            quantity,
+            subtotal: price,
+            shipping_amount: shipping_cost,
+            incoterm: incoterm.as_deref(),
+            incoterm_place: incoterm_place.as_deref(),
+            currency: &currency,
+            promised_delivery_date: Utc::now().date_naive() + Duration::days(i64::from(lead_time_days)),
+            comment: "Purchase order created from accepted quote",
+        },
+        claims,
     )
-        .bind(quote_id).bind(rfq_id).bind(buyer_company_id).bind(supplier_company_id).bind(price)
-        .execute(&mut *tx)
         .await?;
 
-    let po_id = po_result.last_insert_id();
     tx.commit().await?;
 
-    let supplier_user: Result<(i32, String), _> =
-        sqlx::query_as("SELECT id, email FROM users WHERE company_id = ? LIMIT 1")
+    // PO文件生成失败不影响授标，之后在订单文件列表中可以补生成
+    let attachments = match document_service::ensure_purchase_order_pdf(pool, po_id).await {
+        Ok((document, content)) => vec![EmailAttachment {
+            filename: document.filename,
+            content_type: document.content_type,
+            content,
+        }],
+        Err(e) => {
+            log::error!("Failed to generate purchase order PDF for order #{}: {:?}", po_id, e);
+            Vec::new()
+        }
+    };
+
+    let supplier_user: Result<(i32,), _> =
+        sqlx::query_as("SELECT id FROM users WHERE company_id = ? LIMIT 1")
             .bind(supplier_company_id)
             .fetch_one(pool)
             .await;
 
-    if let Ok((supplier_user_id, supplier_email)) = supplier_user {
-
-        let in_app_result = NotificationBuilder::new(
+    if let Ok((supplier_user_id,)) = supplier_user {
+        let vars = [
+            ("rfq_title", rfq_title.clone()),
+            ("po_number", po_number.clone()),
+            ("has_attachment", if attachments.is_empty() { String::new() } else { "yes".to_string() }),
+        ];
+        let result = NotificationBuilder::new(
             supplier_user_id,
+            NotificationCategory::Rfq,
             format!("Congratulations! Your quote for '{}' has been accepted.", &rfq_title),
         )
             .with_link(format!("/orders"))
+            .with_email("quote_accepted", &vars)
+            .with_attachments(attachments)
             .send(pool, chat_server)
             .await;
 
-        if let Err(e) = in_app_result {
-            log::error!("Failed to send in-app notification to supplier: {:?}", e);
+        if let Err(e) = result {
+            log::error!("Failed to send award notification to supplier: {:?}", e);
         }
+    }
+
+    for (losing_supplier_id, feedback) in rejected {
+        notify_unsuccessful_supplier(
+            pool,
+            chat_server,
+            losing_supplier_id,
+            rfq_id,
+            &rfq_title,
+            rejection_reason.as_deref(),
+            feedback.as_deref(),
+        )
+            .await;
+    }
 
-        let subject = format!("Your Quote for '{}' has been Accepted!", &rfq_title);
-        let body = "Congratulations! Your quote has been accepted and a new Purchase Order has been generated. Please log in to view your orders.".to_string();
+    Ok(po_id as u64)
+}
 
-        let email_result = notification_service::send_email(supplier_email, subject, body).await;
-        if let Err(e) = email_result {
-            log::error!("Failed to send email notification to supplier: {:?}", e);
-        }
+// 通知未中标的供应商（站内通知 + 邮件），失败只记日志，不影响授标结果
+async fn notify_unsuccessful_supplier(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    supplier_company_id: i32,
+    rfq_id: i32,
+    rfq_title: &str,
+    rejection_reason: Option<&str>,
+    price_feedback: Option<&str>,
+) {
+    let supplier_user: Result<(i32,), _> =
+        sqlx::query_as("SELECT id FROM users WHERE company_id = ? LIMIT 1")
+            .bind(supplier_company_id)
+            .fetch_one(pool)
+            .await;
+
+    let Ok((supplier_user_id,)) = supplier_user else {
+        log::error!("Failed to fetch user for unsuccessful supplier company #{}", supplier_company_id);
+        return;
+    };
+
+    let vars = [
+        ("rfq_title", rfq_title.to_string()),
+        ("reason", rejection_reason.unwrap_or_default().to_string()),
+        ("price_feedback", price_feedback.unwrap_or_default().to_string()),
+    ];
+    let result = NotificationBuilder::new(
+        supplier_user_id,
+        NotificationCategory::Rfq,
+        format!("Your quote for '{}' was not selected.", rfq_title),
+    )
+        .with_link(format!("/rfqs/{}", rfq_id))
+        .with_email("quote_not_selected", &vars)
+        .send(pool, chat_server)
+        .await;
+
+    if let Err(e) = result {
+        log::error!("Failed to send notification to unsuccessful supplier: {:?}", e);
     }
+}
+
+/// 生成匿名的价格反馈，只给出与中标价的百分比差距
+fn price_feedback_message(winning_price: Decimal, losing_price: Decimal) -> Option<String> {
+    if winning_price <= Decimal::ZERO {
+        return None;
+    }
+
+    // 先用未舍入的差值判断是否在1%以内，舍入只用于显示
+    let diff_percent = (losing_price - winning_price) * Decimal::from(100) / winning_price;
+    let message = if diff_percent.abs() < Decimal::ONE {
+        "Your price was within 1% of the winning bid".to_string()
+    } else if diff_percent > Decimal::ZERO {
+        format!("You were {}% above the winning bid", diff_percent.round())
+    } else {
+        format!("You were {}% below the winning bid", diff_percent.abs().round())
+    };
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_feedback_message() {
+        let winning = Decimal::from_str("1000.00").unwrap();
+
+        assert_eq!(
+            price_feedback_message(winning, Decimal::from_str("1120.00").unwrap()).as_deref(),
+            Some("You were 12% above the winning bid")
+        );
+        assert_eq!(
+            price_feedback_message(winning, Decimal::from_str("950.00").unwrap()).as_deref(),
+            Some("You were 5% below the winning bid")
+        );
+        assert_eq!(
+            price_feedback_message(winning, Decimal::from_str("1003.00").unwrap()).as_deref(),
+            Some("Your price was within 1% of the winning bid")
+        );
+        assert_eq!(price_feedback_message(Decimal::ZERO, winning), None);
+    }
+
+    #[test]
+    fn test_price_feedback_message_one_percent_boundary() {
+        let winning = Decimal::from_str("1000.00").unwrap();
+        let message = |price: &str| price_feedback_message(winning, Decimal::from_str(price).unwrap());
 
-    Ok(po_id)
+        // 0.5% 到 1% 之间舍入后不再是0，但仍在1%以内
+        assert_eq!(message("1008.00").as_deref(), Some("Your price was within 1% of the winning bid"));
+        assert_eq!(message("991.00").as_deref(), Some("Your price was within 1% of the winning bid"));
+        assert_eq!(message("1009.99").as_deref(), Some("Your price was within 1% of the winning bid"));
+        // 正好1%不算在1%以内
+        assert_eq!(message("1010.00").as_deref(), Some("You were 1% above the winning bid"));
+        assert_eq!(message("990.00").as_deref(), Some("You were 1% below the winning bid"));
+    }
+
+    #[test]
+    fn test_shipping_terms() {
+        let d = |s: &str| Decimal::from_str(s).unwrap();
+        let (shipping, incoterm, place) = shipping_terms(Some(d("45.00")), Some("fob".to_string()), Some(" Shanghai ".to_string())).unwrap();
+        assert_eq!((shipping, incoterm, place.as_deref()), (d("45.00"), Some(Incoterm::Fob), Some("Shanghai")));
+        assert_eq!(shipping_terms(None, None, None).unwrap(), (Decimal::ZERO, None, None));
+
+        assert!(shipping_terms(Some(d("10")), Some("EXW".to_string()), None).is_err());
+        assert!(shipping_terms(Some(d("-1")), None, None).is_err());
+        assert!(shipping_terms(None, Some("XYZ".to_string()), None).is_err());
+        assert!(shipping_terms(None, None, Some("Rotterdam".to_string())).is_err());
+    }
 }
 
@@ -0,0 +1,80 @@
+// src/services/rating_service.rs
+// 供应商评分卡：采购方评分汇总、月度趋势、最近的文字评价和收货检验质量
+use crate::{
+    errors::AppError,
+    models::rating::{OrderReview, RatingTrendPoint, SupplierScorecard},
+    services::{delivery_service, receipt_service},
+};
+use sqlx::MySqlPool;
+
+/// 趋势统计的月数
+const TREND_MONTHS: i32 = 12;
+/// 评分卡上展示的最近评价条数
+const RECENT_REVIEWS: i64 = 5;
+
+fn round2(value: Option<f64>) -> Option<f64> {
+    value.map(|v| (v * 100.0).round() / 100.0)
+}
+
+pub async fn get_supplier_scorecard(pool: &MySqlPool, supplier_company_id: i32) -> Result<SupplierScorecard, AppError> {
+    let company: Option<(String,)> = sqlx::query_as("SELECT company_type FROM companies WHERE id = ?")
+        .bind(supplier_company_id)
+        .fetch_optional(pool)
+        .await?;
+    match company {
+        Some((company_type,)) if company_type == "SUPPLIER" => {}
+        _ => return Err(AppError::BadRequest("Supplier not found.".to_string())),
+    }
+
+    let (rated_orders, average_quality, average_communication): (i64, Option<f64>, Option<f64>) = sqlx::query_as(
+        "SELECT COUNT(*), CAST(AVG(quality_rating) AS DOUBLE), CAST(AVG(communication_rating) AS DOUBLE)
+         FROM purchase_orders WHERE supplier_company_id = ? AND rated_at IS NOT NULL"
+    )
+        .bind(supplier_company_id)
+        .fetch_one(pool)
+        .await?;
+
+    let mut trend: Vec<RatingTrendPoint> = sqlx::query_as(
+        "SELECT DATE_FORMAT(rated_at, '%Y-%m') as month,
+                COUNT(*) as rated_orders,
+                CAST(AVG(quality_rating) AS DOUBLE) as average_quality,
+                CAST(AVG(communication_rating) AS DOUBLE) as average_communication
+         FROM purchase_orders
+         WHERE supplier_company_id = ? AND rated_at IS NOT NULL
+           AND rated_at >= DATE_SUB(DATE_FORMAT(CURRENT_DATE, '%Y-%m-01'), INTERVAL ? MONTH)
+         GROUP BY month ORDER BY month ASC"
+    )
+        .bind(supplier_company_id)
+        .bind(TREND_MONTHS - 1)
+        .fetch_all(pool)
+        .await?;
+    for point in &mut trend {
+        point.average_quality = round2(point.average_quality);
+        point.average_communication = round2(point.average_communication);
+    }
+
+    let recent_reviews: Vec<OrderReview> = sqlx::query_as(
+        "SELECT po.id as order_id, b.name as buyer_name, po.quality_rating, po.communication_rating, po.review_text, po.rated_at
+         FROM purchase_orders po JOIN companies b ON po.buyer_company_id = b.id
+         WHERE po.supplier_company_id = ? AND po.rated_at IS NOT NULL AND po.review_text IS NOT NULL
+         ORDER BY po.rated_at DESC LIMIT ?"
+    )
+        .bind(supplier_company_id)
+        .bind(RECENT_REVIEWS)
+        .fetch_all(pool)
+        .await?;
+
+    let inspection = receipt_service::get_supplier_quality(pool, supplier_company_id).await?;
+    let delivery = delivery_service::get_supplier_delivery_performance(pool, supplier_company_id).await?;
+
+    Ok(SupplierScorecard {
+        supplier_company_id,
+        rated_orders,
+        average_quality: round2(average_quality),
+        average_communication: round2(average_communication),
+        trend,
+        recent_reviews,
+        inspection,
+        delivery,
+    })
+}
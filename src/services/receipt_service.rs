@@ -0,0 +1,457 @@
+// src/services/receipt_service.rs
+// 收货和来料检验：采购方登记收货数量和合格/拒收数量，拒收时创建NCR，供应商回复纠正措施，采购方关闭
+// 全部发货数量都已收货且没有未关闭的NCR时，订单自动完成
+use crate::{
+    errors::AppError,
+    models::{
+        order::OrderStatus,
+        receipt::{
+            CloseNcrDto, CreateGoodsReceiptDto, GoodsReceipt, Ncr, NcrDetail, NcrPhoto, NcrResponseDto,
+            SupplierQuality, DEFECT_CATEGORIES,
+        },
+        user::Claims,
+    },
+    services::{chat_server::ChatServer, notification_service, order_service::{self, LockedOrder}},
+    utils::upload_utils::{self, SavedFile, MAX_DOCUMENT_SIZE_BYTES, PHOTO_EXTENSIONS},
+};
+use crate::models::notification_preference::NotificationCategory;
+use actix::Addr;
+use actix_multipart::Multipart;
+use sqlx::{MySql, MySqlPool, Transaction};
+
+/// 每个近12个月内的NCR扣除的质量分
+const NCR_PENALTY: f64 = 2.0;
+
+fn optional_text(value: Option<String>, max_chars: usize, field: &str) -> Result<Option<String>, AppError> {
+    let value = value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    if value.as_ref().is_some_and(|v| v.chars().count() > max_chars) {
+        return Err(AppError::BadRequest(format!("{} must be at most {} characters.", field, max_chars)));
+    }
+    Ok(value)
+}
+
+fn required_text(value: String, max_chars: usize, field: &str) -> Result<String, AppError> {
+    optional_text(Some(value), max_chars, field)?
+        .ok_or_else(|| AppError::BadRequest(format!("{} is required.", field)))
+}
+
+/// 校验收货数量，返回拒收数量
+fn check_receipt_quantities(shipped: i64, already_received: i64, received: i32, accepted: i32) -> Result<i32, AppError> {
+    if received <= 0 {
+        return Err(AppError::BadRequest("Received quantity must be greater than zero.".to_string()));
+    }
+    if accepted < 0 || accepted > received {
+        return Err(AppError::BadRequest("Accepted quantity must be between 0 and the received quantity.".to_string()));
+    }
+    let outstanding = shipped - already_received;
+    if i64::from(received) > outstanding {
+        return Err(AppError::BadRequest(format!(
+            "Received quantity exceeds the {} shipped units not yet received.",
+            outstanding.max(0)
+        )));
+    }
+    Ok(received - accepted)
+}
+
+/// 质量分：合格率(0-100) 减去近期NCR扣分，没有收货记录时为空
+pub(crate) fn quality_score(received_units: i64, rejected_units: i64, recent_ncrs: i64) -> Option<f64> {
+    if received_units <= 0 {
+        return None;
+    }
+    let acceptance = (received_units - rejected_units) as f64 / received_units as f64 * 100.0;
+    let score = (acceptance - recent_ncrs as f64 * NCR_PENALTY).clamp(0.0, 100.0);
+    Some((score * 10.0).round() / 10.0)
+}
+
+pub(crate) async fn get_supplier_quality(pool: &MySqlPool, supplier_company_id: i32) -> Result<SupplierQuality, AppError> {
+    let (received_units, rejected_units): (Option<i64>, Option<i64>) = sqlx::query_as(
+        "SELECT CAST(SUM(gr.received_quantity) AS SIGNED), CAST(SUM(gr.rejected_quantity) AS SIGNED)
+         FROM goods_receipts gr JOIN purchase_orders po ON gr.order_id = po.id
+         WHERE po.supplier_company_id = ?"
+    )
+        .bind(supplier_company_id)
+        .fetch_one(pool)
+        .await?;
+
+    let (ncr_count, open_ncrs, recent_ncrs): (i64, Option<i64>, Option<i64>) = sqlx::query_as(
+        "SELECT COUNT(*),
+                CAST(SUM(status <> 'CLOSED') AS SIGNED),
+                CAST(SUM(created_at >= NOW() - INTERVAL 12 MONTH) AS SIGNED)
+         FROM ncrs WHERE supplier_company_id = ?"
+    )
+        .bind(supplier_company_id)
+        .fetch_one(pool)
+        .await?;
+
+    let received_units = received_units.unwrap_or(0);
+    let rejected_units = rejected_units.unwrap_or(0);
+    Ok(SupplierQuality {
+        received_units,
+        rejected_units,
+        ncr_count,
+        open_ncrs: open_ncrs.unwrap_or(0),
+        quality_score: quality_score(received_units, rejected_units, recent_ncrs.unwrap_or(0)),
+    })
+}
+
+/// 已发货、全部收货且NCR都已关闭时完成订单，返回是否完成
+async fn complete_if_fully_received(
+    tx: &mut Transaction<'_, MySql>,
+    order: &LockedOrder,
+    claims: &Claims,
+) -> Result<bool, AppError> {
+    if order.status != OrderStatus::Shipped.as_str() {
+        return Ok(false);
+    }
+
+    let (ordered, received, unresolved): (i32, Option<i64>, i64) = sqlx::query_as(
+        "SELECT po.quantity,
+                (SELECT CAST(SUM(gr.received_quantity) AS SIGNED) FROM goods_receipts gr WHERE gr.order_id = po.id AND gr.superseded_at IS NULL),
+                (SELECT COUNT(*) FROM ncrs n WHERE n.order_id = po.id AND n.status <> 'CLOSED')
+         FROM purchase_orders po WHERE po.id = ?"
+    )
+        .bind(order.id)
+        .fetch_one(&mut **tx)
+        .await?;
+    if received.unwrap_or(0) < i64::from(ordered) || unresolved > 0 {
+        return Ok(false);
+    }
+
+    order_service::record_transition(
+        tx,
+        order.id,
+        Some(OrderStatus::Shipped.as_str()),
+        OrderStatus::Completed,
+        Some(claims.sub),
+        Some(claims.company_id),
+        Some("All goods received and inspected"),
+    )
+        .await?;
+    Ok(true)
+}
+
+/// 采购方登记收货，有拒收时同时创建NCR。返回收货记录ID
+pub async fn create_goods_receipt(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    order_id: i32,
+    dto: CreateGoodsReceiptDto,
+    claims: &Claims,
+) -> Result<i32, AppError> {
+    let notes = optional_text(dto.notes, 1000, "Notes")?;
+
+    let mut tx = pool.begin().await?;
+    let order = order_service::lock_order(&mut tx, order_id).await?;
+    if order.buyer_company_id != claims.company_id {
+        return Err(AppError::BadRequest("Order not found or only the buyer can record goods receipts.".to_string()));
+    }
+    if order.status != OrderStatus::InProduction.as_str() && order.status != OrderStatus::Shipped.as_str() {
+        return Err(AppError::BadRequest(format!("Goods cannot be received for an order in status {}.", order.status)));
+    }
+
+    if let Some(shipment_id) = dto.shipment_id {
+        let shipment: Option<(i32,)> = sqlx::query_as("SELECT id FROM shipments WHERE id = ? AND order_id = ? AND superseded_at IS NULL")
+            .bind(shipment_id)
+            .bind(order.id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if shipment.is_none() {
+            return Err(AppError::BadRequest("Shipment not found for this order.".to_string()));
+        }
+    }
+
+    let (shipped, received): (Option<i64>, Option<i64>) = sqlx::query_as(
+        "SELECT (SELECT CAST(SUM(shipped_quantity) AS SIGNED) FROM shipments WHERE order_id = ? AND superseded_at IS NULL),
+                (SELECT CAST(SUM(received_quantity) AS SIGNED) FROM goods_receipts WHERE order_id = ? AND superseded_at IS NULL)"
+    )
+        .bind(order.id)
+        .bind(order.id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let rejected = check_receipt_quantities(
+        shipped.unwrap_or(0),
+        received.unwrap_or(0),
+        dto.received_quantity,
+        dto.accepted_quantity,
+    )?;
+
+    // 有拒收必须说明缺陷类别和描述
+    let defect = if rejected > 0 {
+        let category = dto.defect_category.map(|c| c.trim().to_uppercase()).unwrap_or_default();
+        if !DEFECT_CATEGORIES.contains(&category.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "A defect category is required for rejected goods: {}.",
+                DEFECT_CATEGORIES.join(", ")
+            )));
+        }
+        let description = required_text(dto.defect_description.unwrap_or_default(), 2000, "Defect description")?;
+        Some((category, description))
+    } else {
+        None
+    };
+
+    let result = sqlx::query(
+        "INSERT INTO goods_receipts (order_id, shipment_id, received_quantity, accepted_quantity, rejected_quantity, notes, received_by_user_id)
+         VALUES (?, ?, ?, ?, ?, ?, ?)"
+    )
+        .bind(order.id)
+        .bind(dto.shipment_id)
+        .bind(dto.received_quantity)
+        .bind(dto.accepted_quantity)
+        .bind(rejected)
+        .bind(&notes)
+        .bind(claims.sub)
+        .execute(&mut *tx)
+        .await?;
+    let receipt_id = result.last_insert_id() as i32;
+
+    if let Some((category, description)) = &defect {
+        sqlx::query(
+            "INSERT INTO ncrs (order_id, goods_receipt_id, supplier_company_id, defect_category, description, rejected_quantity)
+             VALUES (?, ?, ?, ?, ?, ?)"
+        )
+            .bind(order.id)
+            .bind(receipt_id)
+            .bind(order.supplier_company_id)
+            .bind(category)
+            .bind(description)
+            .bind(rejected)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let completed = complete_if_fully_received(&mut tx, &order, claims).await?;
+    tx.commit().await?;
+
+    let subject = format!("Goods received for order #{}", order.id);
+    let mut message = format!(
+        "The buyer received {} units of order #{} ('{}'): {} accepted, {} rejected.",
+        dto.received_quantity, order.id, order.rfq_title, dto.accepted_quantity, rejected
+    );
+    if let Some((category, _)) = &defect {
+        message.push_str(&format!(" A non-conformance report ({}) requires your corrective action.", category));
+    }
+    notification_service::notify_company(pool, chat_server, order.supplier_company_id, NotificationCategory::Order, &subject, &message, "/orders").await;
+    if completed {
+        order_service::notify_status_change(pool, chat_server, &order, OrderStatus::Completed).await;
+    }
+
+    Ok(receipt_id)
+}
+
+pub async fn get_receipts_for_order(pool: &MySqlPool, order_id: i32, claims: &Claims) -> Result<Vec<GoodsReceipt>, AppError> {
+    order_service::ensure_order_party(pool, order_id, claims).await?;
+
+    let receipts = sqlx::query_as(
+        "SELECT gr.*, n.id as ncr_id
+         FROM goods_receipts gr LEFT JOIN ncrs n ON n.goods_receipt_id = gr.id
+         WHERE gr.order_id = ? ORDER BY gr.created_at ASC, gr.id ASC"
+    )
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(receipts)
+}
+
+pub async fn get_ncrs_for_order(pool: &MySqlPool, order_id: i32, claims: &Claims) -> Result<Vec<Ncr>, AppError> {
+    order_service::ensure_order_party(pool, order_id, claims).await?;
+
+    let ncrs = sqlx::query_as("SELECT * FROM ncrs WHERE order_id = ? ORDER BY created_at DESC")
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(ncrs)
+}
+
+/// 读取NCR并确认当前用户是订单的一方，返回NCR和采购方公司ID
+async fn load_ncr(pool: &MySqlPool, ncr_id: i32, claims: &Claims) -> Result<(Ncr, i32), AppError> {
+    let ncr: Ncr = sqlx::query_as("SELECT * FROM ncrs WHERE id = ?")
+        .bind(ncr_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Non-conformance report not found.".to_string()))?;
+
+    let (buyer_company_id,): (i32,) = sqlx::query_as("SELECT buyer_company_id FROM purchase_orders WHERE id = ?")
+        .bind(ncr.order_id)
+        .fetch_one(pool)
+        .await?;
+    if claims.company_id != buyer_company_id && claims.company_id != ncr.supplier_company_id {
+        return Err(AppError::BadRequest("Non-conformance report not found or you are not authorized to view it.".to_string()));
+    }
+    Ok((ncr, buyer_company_id))
+}
+
+pub async fn get_ncr_detail(pool: &MySqlPool, ncr_id: i32, claims: &Claims) -> Result<NcrDetail, AppError> {
+    let (ncr, _) = load_ncr(pool, ncr_id, claims).await?;
+    let photos = sqlx::query_as("SELECT * FROM ncr_photos WHERE ncr_id = ? ORDER BY id ASC")
+        .bind(ncr_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(NcrDetail { ncr, photos })
+}
+
+/// 下载NCR照片，只有采购方和供应商可以下载
+pub async fn download_ncr_photo(pool: &MySqlPool, ncr_id: i32, photo_id: i32, claims: &Claims) -> Result<(NcrPhoto, String, Vec<u8>), AppError> {
+    load_ncr(pool, ncr_id, claims).await?;
+    let photo: NcrPhoto = sqlx::query_as("SELECT * FROM ncr_photos WHERE id = ? AND ncr_id = ?")
+        .bind(photo_id)
+        .bind(ncr_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Photo not found.".to_string()))?;
+
+    let (content_type, content) = upload_utils::read_saved_file(&photo.stored_path, &photo.original_filename).await?;
+    Ok((photo, content_type, content))
+}
+
+/// 采购方上传缺陷照片
+pub async fn add_ncr_photos(pool: &MySqlPool, ncr_id: i32, payload: Multipart, claims: &Claims) -> Result<Vec<NcrPhoto>, AppError> {
+    let (ncr, buyer_company_id) = load_ncr(pool, ncr_id, claims).await?;
+    if claims.company_id != buyer_company_id {
+        return Err(AppError::BadRequest("Only the buyer can add photos to a non-conformance report.".to_string()));
+    }
+    if ncr.status == "CLOSED" {
+        return Err(AppError::BadRequest("This non-conformance report is closed.".to_string()));
+    }
+
+    let (_, files) = upload_utils::save_multipart(payload, "ncr_photos", PHOTO_EXTENSIONS, MAX_DOCUMENT_SIZE_BYTES).await?;
+    if files.is_empty() {
+        return Err(AppError::BadRequest("No photo was uploaded.".to_string()));
+    }
+
+    // 登记失败时删掉已保存的照片
+    let photos = insert_ncr_photos(pool, ncr_id, &files, claims).await;
+    if photos.is_err() {
+        upload_utils::remove_saved_files(&files);
+    }
+    photos
+}
+
+async fn insert_ncr_photos(pool: &MySqlPool, ncr_id: i32, files: &[SavedFile], claims: &Claims) -> Result<Vec<NcrPhoto>, AppError> {
+    let mut tx = pool.begin().await?;
+    let mut photos = Vec::with_capacity(files.len());
+    for file in files {
+        let result = sqlx::query(
+            "INSERT INTO ncr_photos (ncr_id, uploaded_by_user_id, original_filename, stored_path) VALUES (?, ?, ?, ?)"
+        )
+            .bind(ncr_id)
+            .bind(claims.sub)
+            .bind(&file.original_filename)
+            .bind(&file.stored_path)
+            .execute(&mut *tx)
+            .await?;
+
+        let photo = sqlx::query_as("SELECT * FROM ncr_photos WHERE id = ?")
+            .bind(result.last_insert_id())
+            .fetch_one(&mut *tx)
+            .await?;
+        photos.push(photo);
+    }
+    tx.commit().await?;
+    Ok(photos)
+}
+
+/// 供应商回复根本原因和纠正措施，关闭前可以修改
+pub async fn respond_to_ncr(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    ncr_id: i32,
+    dto: NcrResponseDto,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    let root_cause = required_text(dto.root_cause, 2000, "Root cause")?;
+    let corrective_action = required_text(dto.corrective_action, 2000, "Corrective action")?;
+
+    let (ncr, buyer_company_id) = load_ncr(pool, ncr_id, claims).await?;
+    if claims.company_id != ncr.supplier_company_id {
+        return Err(AppError::BadRequest("Only the supplier can respond to a non-conformance report.".to_string()));
+    }
+
+    let result = sqlx::query(
+        "UPDATE ncrs SET status = 'RESPONDED', root_cause = ?, corrective_action = ?, responded_by_user_id = ?, responded_at = CURRENT_TIMESTAMP
+         WHERE id = ? AND status <> 'CLOSED'"
+    )
+        .bind(&root_cause)
+        .bind(&corrective_action)
+        .bind(claims.sub)
+        .bind(ncr_id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest("This non-conformance report is closed.".to_string()));
+    }
+
+    let subject = format!("Corrective action for NCR #{}", ncr_id);
+    let message = format!("The supplier responded to NCR #{} on order #{}. Please review the corrective action.", ncr_id, ncr.order_id);
+    let details = format!("Root cause: {}\nCorrective action: {}", root_cause, corrective_action);
+    notification_service::notify_company_with_details(pool, chat_server, buyer_company_id, NotificationCategory::Order, &subject, &message, Some(&details), "/orders").await;
+    Ok(())
+}
+
+/// 采购方确认纠正措施并关闭NCR，关闭后可能触发订单完成
+pub async fn close_ncr(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    ncr_id: i32,
+    dto: CloseNcrDto,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    let comment = optional_text(dto.comment, 1000, "Comment")?;
+
+    let (ncr, buyer_company_id) = load_ncr(pool, ncr_id, claims).await?;
+    if claims.company_id != buyer_company_id {
+        return Err(AppError::BadRequest("Only the buyer can close a non-conformance report.".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+    let order = order_service::lock_order(&mut tx, ncr.order_id).await?;
+    let result = sqlx::query(
+        "UPDATE ncrs SET status = 'CLOSED', closed_by_user_id = ?, closing_comment = ?, closed_at = CURRENT_TIMESTAMP
+         WHERE id = ? AND status = 'RESPONDED'"
+    )
+        .bind(claims.sub)
+        .bind(&comment)
+        .bind(ncr_id)
+        .execute(&mut *tx)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest(
+            "Only a non-conformance report with a supplier response can be closed.".to_string(),
+        ));
+    }
+    let completed = complete_if_fully_received(&mut tx, &order, claims).await?;
+    tx.commit().await?;
+
+    let subject = format!("NCR #{} closed", ncr_id);
+    let message = format!("The buyer accepted your corrective action and closed NCR #{} on order #{}.", ncr_id, order.id);
+    notification_service::notify_company(pool, chat_server, ncr.supplier_company_id, NotificationCategory::Order, &subject, &message, "/orders").await;
+    if completed {
+        order_service::notify_status_change(pool, chat_server, &order, OrderStatus::Completed).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receipt_quantities() {
+        // 已发货60件，已收40件，本次最多再收20件
+        assert_eq!(check_receipt_quantities(60, 40, 20, 18).unwrap(), 2);
+        assert_eq!(check_receipt_quantities(60, 0, 60, 60).unwrap(), 0);
+        assert!(check_receipt_quantities(60, 40, 21, 21).is_err());
+        assert!(check_receipt_quantities(60, 0, 10, 11).is_err());
+        assert!(check_receipt_quantities(60, 0, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_quality_score() {
+        assert_eq!(quality_score(0, 0, 0), None);
+        assert_eq!(quality_score(200, 0, 0), Some(100.0));
+        // 合格率95%，近期2个NCR各扣2分
+        assert_eq!(quality_score(200, 10, 2), Some(91.0));
+        // 不会低于0
+        assert_eq!(quality_score(10, 10, 5), Some(0.0));
+    }
+}
@@ -0,0 +1,374 @@
+// src/services/reconciliation_service.rs
+// 支付对账：Webhook 丢了订单会一直停在未付款。定时向支付渠道查询所有有支付会话但还没付款的订单、发票和分期，
+// 能修正的直接修正，对不上的标记给管理员，每天的结果可以导出成CSV报表
+use crate::{
+    errors::AppError,
+    models::{
+        money::{Currency, Money},
+        reconciliation::{ReconciliationItem, ReconciliationRun},
+        user::Claims,
+    },
+    services::{
+        chat_server::ChatServer,
+        notification_service,
+        order_service,
+        payment_provider::{CheckoutSessionDetails, CheckoutState, PaymentProvider},
+        payment_service,
+    },
+};
+use actix::Addr;
+use chrono::{NaiveDate, Utc};
+use sqlx::{types::Decimal, FromRow, MySqlPool};
+use std::{env, str::FromStr, sync::Arc, time::Duration};
+
+/// 默认每天对账一次
+const DEFAULT_RECONCILIATION_INTERVAL_SECS: u64 = 86400;
+
+/// 对账结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ReconcileAction {
+    // 渠道已收款，补记为已付
+    MarkPaid,
+    // 会话已过期，清掉会话让买方重新付款
+    ClearSession,
+    // 买方还没付完，下次再查
+    Wait,
+    // 对不上，需要管理员处理
+    Flag(String),
+}
+
+/// 根据渠道查到的会话决定怎么处理。收款金额和应付金额不一致时不自动修正
+pub(crate) fn decide(expected_minor: i64, details: &CheckoutSessionDetails) -> ReconcileAction {
+    match details.state {
+        CheckoutState::Paid => match details.amount_total {
+            Some(amount) if amount != expected_minor => ReconcileAction::Flag(format!(
+                "Provider collected {} minor units but {} were expected.",
+                amount, expected_minor
+            )),
+            _ => ReconcileAction::MarkPaid,
+        },
+        CheckoutState::Expired => ReconcileAction::ClearSession,
+        CheckoutState::Open => ReconcileAction::Wait,
+    }
+}
+
+/// 有支付会话但还没付款的一笔
+#[derive(Debug, FromRow)]
+struct OpenCheckout {
+    order_id: i32,
+    invoice_id: Option<i32>,
+    milestone_id: Option<i32>,
+    stripe_session_id: String,
+    payment_status: String,
+    amount: Decimal,
+    currency: String,
+}
+
+/// 一笔的对账结果，写入 payment_reconciliation_items
+struct ItemOutcome {
+    provider_status: Option<&'static str>,
+    provider_amount: Option<Decimal>,
+    outcome: &'static str,
+    detail: Option<String>,
+}
+
+impl ItemOutcome {
+    fn flagged(provider_status: Option<&'static str>, provider_amount: Option<Decimal>, detail: String) -> Self {
+        Self { provider_status, provider_amount, outcome: "MISMATCH", detail: Some(detail.chars().take(1000).collect()) }
+    }
+}
+
+/// 对账一遍，返回这次的汇总。triggered_by 为空表示定时任务
+pub async fn run_reconciliation(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    provider: &dyn PaymentProvider,
+    triggered_by: Option<i32>,
+) -> Result<ReconciliationRun, AppError> {
+    let run_id = sqlx::query("INSERT INTO payment_reconciliation_runs (provider, report_date, triggered_by_user_id) VALUES (?, ?, ?)")
+        .bind(provider.name())
+        .bind(Utc::now().date_naive())
+        .bind(triggered_by)
+        .execute(pool)
+        .await?
+        .last_insert_id() as i32;
+
+    let open: Vec<OpenCheckout> = sqlx::query_as(
+        "SELECT id AS order_id, NULL AS invoice_id, NULL AS milestone_id, stripe_session_id, payment_status,
+                total_amount AS amount, currency
+         FROM purchase_orders WHERE stripe_session_id IS NOT NULL AND payment_status IN ('UNPAID', 'FAILED')
+         UNION ALL
+         SELECT order_id, id, NULL, stripe_session_id, payment_status, total, currency
+         FROM invoices WHERE stripe_session_id IS NOT NULL AND payment_status IN ('UNPAID', 'FAILED')
+         UNION ALL
+         SELECT order_id, NULL, id, stripe_session_id, payment_status, amount, currency
+         FROM payment_milestones WHERE stripe_session_id IS NOT NULL AND payment_status IN ('UNPAID', 'FAILED')"
+    )
+        .fetch_all(pool)
+        .await?;
+
+    let (mut fixed, mut flagged) = (0, 0);
+    for checkout in &open {
+        let outcome = reconcile_one(pool, chat_server, provider, checkout).await;
+        match outcome.outcome {
+            "FIXED" | "EXPIRED" => fixed += 1,
+            "MISMATCH" => flagged += 1,
+            _ => {}
+        }
+        sqlx::query(
+            "INSERT INTO payment_reconciliation_items (run_id, order_id, invoice_id, milestone_id, stripe_session_id, local_status,
+                 provider_status, expected_amount, provider_amount, currency, outcome, detail)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+            .bind(run_id)
+            .bind(checkout.order_id)
+            .bind(checkout.invoice_id)
+            .bind(checkout.milestone_id)
+            .bind(&checkout.stripe_session_id)
+            .bind(&checkout.payment_status)
+            .bind(outcome.provider_status)
+            .bind(checkout.amount)
+            .bind(outcome.provider_amount)
+            .bind(&checkout.currency)
+            .bind(outcome.outcome)
+            .bind(&outcome.detail)
+            .execute(pool)
+            .await?;
+    }
+
+    sqlx::query("UPDATE payment_reconciliation_runs SET checked = ?, fixed = ?, flagged = ?, finished_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(open.len() as i32)
+        .bind(fixed)
+        .bind(flagged)
+        .bind(run_id)
+        .execute(pool)
+        .await?;
+
+    if flagged > 0 {
+        let subject = "Payment reconciliation found mismatches".to_string();
+        let message = format!(
+            "Payment reconciliation run #{} found {} payment(s) that do not match the {} records and need review.",
+            run_id, flagged, provider.name()
+        );
+        notification_service::notify_admins(pool, chat_server, &subject, &message, "/admin/reconciliation").await;
+    }
+
+    let run = sqlx::query_as("SELECT * FROM payment_reconciliation_runs WHERE id = ?")
+        .bind(run_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(run)
+}
+
+// 单笔出错只记在这一笔上，不影响其他付款的对账
+async fn reconcile_one(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    provider: &dyn PaymentProvider,
+    checkout: &OpenCheckout,
+) -> ItemOutcome {
+    let expected = match Currency::from_str(&checkout.currency).and_then(|currency| Money::new(checkout.amount, currency)) {
+        Ok(money) => money,
+        Err(e) => return ItemOutcome::flagged(None, None, format!("Invalid local amount: {:?}", e)),
+    };
+    let expected_minor = match expected.to_minor_units() {
+        Ok(minor) => minor,
+        Err(e) => return ItemOutcome::flagged(None, None, format!("Invalid local amount: {:?}", e)),
+    };
+    let details = match provider.retrieve_checkout_session(&checkout.stripe_session_id).await {
+        Ok(details) => details,
+        Err(e) => return ItemOutcome::flagged(None, None, format!("Provider lookup failed: {:?}", e)),
+    };
+    let provider_status = Some(details.state.as_str());
+    let provider_amount = details.amount_total.map(|minor| Decimal::new(minor, 2));
+
+    let result = match decide(expected_minor, &details) {
+        ReconcileAction::MarkPaid => payment_service::settle_checkout(
+            pool,
+            chat_server,
+            &checkout.stripe_session_id,
+            details.payment_reference.as_deref(),
+        )
+            .await
+            .map(|settled| if settled { ("FIXED", Some("Marked as paid from provider records.".to_string())) } else { ("IN_SYNC", None) }),
+        ReconcileAction::ClearSession => payment_service::expire_checkout(pool, &checkout.stripe_session_id)
+            .await
+            .map(|_| ("EXPIRED", Some("Checkout session expired; it was cleared so the buyer can pay again.".to_string()))),
+        ReconcileAction::Wait => Ok(("PENDING", None)),
+        ReconcileAction::Flag(detail) => return ItemOutcome::flagged(provider_status, provider_amount, detail),
+    };
+    match result {
+        Ok((outcome, detail)) => ItemOutcome { provider_status, provider_amount, outcome, detail },
+        Err(e) => ItemOutcome::flagged(provider_status, provider_amount, format!("Failed to update local records: {:?}", e)),
+    }
+}
+
+/// 启动后台对账任务。间隔可以用 PAYMENT_RECONCILIATION_INTERVAL_SECS 配置
+pub fn spawn_reconciliation_job(pool: MySqlPool, chat_server: Addr<ChatServer>, provider: Arc<dyn PaymentProvider>) {
+    let interval_secs = env::var("PAYMENT_RECONCILIATION_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(DEFAULT_RECONCILIATION_INTERVAL_SECS);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            ticker.tick().await;
+            match run_reconciliation(&pool, &chat_server, provider.as_ref(), None).await {
+                Ok(run) => log::info!(
+                    "Payment reconciliation #{} checked {} payments: {} fixed, {} flagged",
+                    run.id, run.checked, run.fixed, run.flagged
+                ),
+                Err(e) => log::error!("Payment reconciliation failed: {:?}", e),
+            }
+        }
+    });
+}
+
+/// 最近的对账记录
+pub async fn list_runs(pool: &MySqlPool) -> Result<Vec<ReconciliationRun>, AppError> {
+    let runs = sqlx::query_as("SELECT * FROM payment_reconciliation_runs ORDER BY started_at DESC, id DESC LIMIT 100")
+        .fetch_all(pool)
+        .await?;
+    Ok(runs)
+}
+
+/// 对账明细，可按结果筛选，或只看还没处理的不一致
+pub async fn list_items(pool: &MySqlPool, outcome: Option<String>, unresolved: bool) -> Result<Vec<ReconciliationItem>, AppError> {
+    if outcome.as_deref().is_some_and(|o| !["FIXED", "EXPIRED", "PENDING", "IN_SYNC", "MISMATCH"].contains(&o)) {
+        return Err(AppError::BadRequest("Outcome must be FIXED, EXPIRED, PENDING, IN_SYNC or MISMATCH.".to_string()));
+    }
+    let items = sqlx::query_as(
+        "SELECT * FROM payment_reconciliation_items
+         WHERE (? IS NULL OR outcome = ?) AND (? = FALSE OR (outcome = 'MISMATCH' AND resolved_at IS NULL))
+         ORDER BY created_at DESC, id DESC LIMIT 500"
+    )
+        .bind(&outcome)
+        .bind(&outcome)
+        .bind(unresolved)
+        .fetch_all(pool)
+        .await?;
+    Ok(items)
+}
+
+/// 管理员处理完一条不一致后标记为已处理
+pub async fn resolve_item(pool: &MySqlPool, item_id: i32, note: Option<String>, claims: &Claims) -> Result<(), AppError> {
+    let note = order_service::trimmed_text(note, 1000, "Note")?;
+    let result = sqlx::query(
+        "UPDATE payment_reconciliation_items SET resolved_at = CURRENT_TIMESTAMP, resolved_by_user_id = ?, resolution_note = ?
+         WHERE id = ? AND outcome = 'MISMATCH' AND resolved_at IS NULL"
+    )
+        .bind(claims.sub)
+        .bind(&note)
+        .bind(item_id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest("Mismatch not found or already resolved.".to_string()));
+    }
+    Ok(())
+}
+
+/// 某一天所有对账明细的CSV报表
+pub async fn daily_report_csv(pool: &MySqlPool, report_date: NaiveDate) -> Result<String, AppError> {
+    let items: Vec<ReconciliationItem> = sqlx::query_as(
+        "SELECT i.* FROM payment_reconciliation_items i JOIN payment_reconciliation_runs r ON i.run_id = r.id
+         WHERE r.report_date = ? ORDER BY i.run_id, i.id"
+    )
+        .bind(report_date)
+        .fetch_all(pool)
+        .await?;
+    Ok(render_csv(&items))
+}
+
+pub(crate) fn render_csv(items: &[ReconciliationItem]) -> String {
+    let mut csv = String::from(
+        "run_id,checked_at,order_id,invoice_id,milestone_id,session_id,local_status,provider_status,expected_amount,provider_amount,currency,outcome,detail,resolved_at,resolution_note\r\n",
+    );
+    for item in items {
+        let fields = [
+            item.run_id.to_string(),
+            item.created_at.to_rfc3339(),
+            item.order_id.to_string(),
+            item.invoice_id.map(|id| id.to_string()).unwrap_or_default(),
+            item.milestone_id.map(|id| id.to_string()).unwrap_or_default(),
+            item.stripe_session_id.clone(),
+            item.local_status.clone(),
+            item.provider_status.clone().unwrap_or_default(),
+            format!("{:.2}", item.expected_amount),
+            item.provider_amount.map(|a| format!("{:.2}", a)).unwrap_or_default(),
+            item.currency.clone(),
+            item.outcome.clone(),
+            item.detail.clone().unwrap_or_default(),
+            item.resolved_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            item.resolution_note.clone().unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+// 含逗号、引号、换行的字段加引号；以 = + - @ 开头的加单引号，防止在表格软件里被当成公式
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) { format!("'{}", value) } else { value.to_string() };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    fn details(state: CheckoutState, amount_total: Option<i64>) -> CheckoutSessionDetails {
+        CheckoutSessionDetails { state, payment_reference: Some("pi_1".to_string()), amount_total }
+    }
+
+    #[test]
+    fn test_decide() {
+        assert_eq!(decide(12550, &details(CheckoutState::Paid, Some(12550))), ReconcileAction::MarkPaid);
+        assert_eq!(decide(12550, &details(CheckoutState::Paid, None)), ReconcileAction::MarkPaid);
+        // 金额不一致不自动修正
+        assert!(matches!(decide(12550, &details(CheckoutState::Paid, Some(10000))), ReconcileAction::Flag(_)));
+        assert_eq!(decide(12550, &details(CheckoutState::Expired, None)), ReconcileAction::ClearSession);
+        assert_eq!(decide(12550, &details(CheckoutState::Open, Some(12550))), ReconcileAction::Wait);
+    }
+
+    #[test]
+    fn test_csv_report() {
+        let created_at = DateTime::parse_from_rfc3339("2026-10-19T08:00:00Z").unwrap().with_timezone(&Utc);
+        let item = ReconciliationItem {
+            id: 1,
+            run_id: 7,
+            order_id: 42,
+            invoice_id: None,
+            milestone_id: Some(3),
+            stripe_session_id: "cs_test_1".to_string(),
+            local_status: "UNPAID".to_string(),
+            provider_status: Some("PAID".to_string()),
+            expected_amount: Decimal::new(12550, 2),
+            provider_amount: Some(Decimal::new(10000, 2)),
+            currency: "EUR".to_string(),
+            outcome: "MISMATCH".to_string(),
+            detail: Some("Amount differs, \"check\" it".to_string()),
+            resolved_at: None,
+            resolved_by_user_id: None,
+            resolution_note: Some("=HYPERLINK()".to_string()),
+            created_at,
+        };
+        let csv = render_csv(&[item]);
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("run_id,checked_at,order_id"));
+        assert_eq!(
+            lines[1],
+            "7,2026-10-19T08:00:00+00:00,42,,3,cs_test_1,UNPAID,PAID,125.50,100.00,EUR,MISMATCH,\"Amount differs, \"\"check\"\" it\",,'=HYPERLINK()"
+        );
+    }
+}
@@ -0,0 +1,475 @@
+// src/services/refund_service.rs
+// 退款：买方申请，供应商或管理员审批后通过支付渠道退款；供应商和管理员也可以直接退
+use crate::{
+    errors::AppError,
+    models::{
+        money::{Currency, Money},
+        refund::{CreateRefundDto, Refund, RespondRefundDto},
+        user::Claims,
+    },
+    services::{
+        chat_server::ChatServer,
+        notification_service,
+        order_service::{self, LockedOrder},
+        payment_provider::{PaymentProvider, RefundRequest},
+        payment_service::{self, PaymentTarget},
+    },
+};
+use crate::models::notification_preference::NotificationCategory;
+use actix::Addr;
+use sqlx::{types::Decimal, MySql, MySqlPool, Transaction};
+use std::str::FromStr;
+
+const REFUND_SELECT: &str = "SELECT rf.*, c.name AS requested_by_company_name
+     FROM refunds rf JOIN companies c ON rf.requested_by_company_id = c.id";
+
+/// 被退款的那笔付款：整单付款、某张发票或某一期分期付款
+#[derive(sqlx::FromRow)]
+struct RefundSource {
+    paid_total: Decimal,
+    refunded_amount: Decimal,
+    currency: String,
+    payment_status: String,
+    payment_reference: Option<String>,
+}
+
+impl RefundSource {
+    fn ensure_refundable(&self) -> Result<(), AppError> {
+        if !matches!(self.payment_status.as_str(), "PAID" | "REFUND_PENDING" | "PARTIALLY_REFUNDED") {
+            return Err(AppError::BadRequest("Only paid payments can be refunded.".to_string()));
+        }
+        if self.payment_reference.is_none() {
+            return Err(AppError::BadRequest("This payment has no provider reference and must be refunded manually.".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// 退款金额不能超过：付款金额 - 已退金额 - 还在申请中/处理中的退款
+pub(crate) fn refundable_amount(paid_total: Decimal, refunded_amount: Decimal, reserved: Decimal) -> Decimal {
+    (paid_total - refunded_amount - reserved).max(Decimal::ZERO)
+}
+
+async fn load_source(
+    tx: &mut Transaction<'_, MySql>,
+    order_id: i32,
+    invoice_id: Option<i32>,
+    milestone_id: Option<i32>,
+) -> Result<RefundSource, AppError> {
+    let source = match (invoice_id, milestone_id) {
+        (Some(_), Some(_)) => return Err(AppError::BadRequest("Choose either an invoice or a milestone, not both.".to_string())),
+        (Some(invoice_id), None) => sqlx::query_as(
+            "SELECT total AS paid_total, refunded_amount, currency, payment_status, payment_reference
+             FROM invoices WHERE id = ? AND order_id = ? FOR UPDATE"
+        )
+            .bind(invoice_id)
+            .bind(order_id)
+            .fetch_optional(&mut **tx)
+            .await?
+            .ok_or_else(|| AppError::BadRequest("Invoice not found for this order.".to_string()))?,
+        (None, Some(milestone_id)) => sqlx::query_as(
+            "SELECT amount AS paid_total, refunded_amount, currency, payment_status, payment_reference
+             FROM payment_milestones WHERE id = ? AND order_id = ? FOR UPDATE"
+        )
+            .bind(milestone_id)
+            .bind(order_id)
+            .fetch_optional(&mut **tx)
+            .await?
+            .ok_or_else(|| AppError::BadRequest("Payment milestone not found for this order.".to_string()))?,
+        (None, None) => sqlx::query_as(
+            "SELECT total_amount AS paid_total, refunded_amount, currency, payment_status, payment_reference
+             FROM purchase_orders WHERE id = ?"
+        )
+            .bind(order_id)
+            .fetch_one(&mut **tx)
+            .await?,
+    };
+    Ok(source)
+}
+
+async fn reserved_amount(
+    tx: &mut Transaction<'_, MySql>,
+    order_id: i32,
+    invoice_id: Option<i32>,
+    milestone_id: Option<i32>,
+    exclude_refund_id: Option<i32>,
+) -> Result<Decimal, AppError> {
+    let (reserved,): (Option<Decimal>,) = sqlx::query_as(
+        "SELECT SUM(amount) FROM refunds
+         WHERE order_id = ? AND invoice_id <=> ? AND milestone_id <=> ? AND status IN ('REQUESTED', 'PROCESSING') AND id <> ?"
+    )
+        .bind(order_id)
+        .bind(invoice_id)
+        .bind(milestone_id)
+        .bind(exclude_refund_id.unwrap_or(0))
+        .fetch_one(&mut **tx)
+        .await?;
+    Ok(reserved.unwrap_or(Decimal::ZERO))
+}
+
+// 按发票或分期付款的订单，退款要指定退哪一笔
+async fn is_paid_in_parts(tx: &mut Transaction<'_, MySql>, order_id: i32) -> Result<bool, AppError> {
+    let (count,): (i64,) = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM invoices WHERE order_id = ?) + (SELECT COUNT(*) FROM payment_milestones WHERE order_id = ?)"
+    )
+        .bind(order_id)
+        .bind(order_id)
+        .fetch_one(&mut **tx)
+        .await?;
+    Ok(count > 0)
+}
+
+/// 订单一方申请退款（买方）或直接退款（供应商、管理员）
+pub async fn create_refund(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    provider: &dyn PaymentProvider,
+    order_id: i32,
+    dto: CreateRefundDto,
+    claims: &Claims,
+) -> Result<Refund, AppError> {
+    let reason = order_service::trimmed_text(Some(dto.reason), 1000, "Reason")?
+        .ok_or_else(|| AppError::BadRequest("Reason is required.".to_string()))?;
+
+    let mut tx = pool.begin().await?;
+    let order = order_service::lock_order(&mut tx, order_id).await?;
+    let is_buyer = claims.company_id == order.buyer_company_id;
+    let is_supplier = claims.company_id == order.supplier_company_id;
+    if !is_buyer && !is_supplier && !claims.is_admin {
+        return Err(AppError::BadRequest("Order not found or you are not authorized to refund it.".to_string()));
+    }
+
+    if dto.invoice_id.is_none() && dto.milestone_id.is_none() && is_paid_in_parts(&mut tx, order.id).await? {
+        return Err(AppError::BadRequest("This order is paid by invoice or in milestones; please choose the payment to refund.".to_string()));
+    }
+    let source = load_source(&mut tx, order.id, dto.invoice_id, dto.milestone_id).await?;
+    source.ensure_refundable()?;
+
+    // 关联的争议必须已按退款仲裁，取消申请必须已同意
+    let mut dispute_amount = None;
+    if let Some(dispute_id) = dto.dispute_id {
+        let dispute: Option<(String, Option<String>, Option<Decimal>)> = sqlx::query_as(
+            "SELECT status, resolution, refund_amount FROM disputes WHERE id = ? AND order_id = ?"
+        )
+            .bind(dispute_id)
+            .bind(order.id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        match dispute {
+            Some((status, Some(resolution), amount)) if status == "RESOLVED" && resolution == "REFUND" => dispute_amount = amount,
+            Some(_) => return Err(AppError::BadRequest("The dispute was not resolved with a refund.".to_string())),
+            None => return Err(AppError::BadRequest("Dispute not found for this order.".to_string())),
+        }
+    }
+    if let Some(cancellation_id) = dto.cancellation_request_id {
+        let cancellation: Option<(String,)> = sqlx::query_as(
+            "SELECT status FROM order_cancellation_requests WHERE id = ? AND order_id = ?"
+        )
+            .bind(cancellation_id)
+            .bind(order.id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        match cancellation {
+            Some((status,)) if status == "ACCEPTED" => {}
+            Some(_) => return Err(AppError::BadRequest("The cancellation request has not been accepted.".to_string())),
+            None => return Err(AppError::BadRequest("Cancellation request not found for this order.".to_string())),
+        }
+    }
+
+    let reserved = reserved_amount(&mut tx, order.id, dto.invoice_id, dto.milestone_id, None).await?;
+    let available = refundable_amount(source.paid_total, source.refunded_amount, reserved);
+    let amount = dto.amount.or(dispute_amount).unwrap_or(available);
+    let amount = Money::new(amount, Currency::from_str(&source.currency)?)?;
+    if amount.amount() > available {
+        return Err(AppError::BadRequest(format!("At most {} {} can be refunded.", available, source.currency)));
+    }
+
+    // 买方只能申请，供应商和管理员直接退款
+    let status = if is_buyer && !claims.is_admin { "REQUESTED" } else { "PROCESSING" };
+    let refund_id = sqlx::query(
+        "INSERT INTO refunds (order_id, invoice_id, milestone_id, dispute_id, cancellation_request_id, amount, currency, reason, status,
+                              requested_by_company_id, requested_by_user_id, processed_by_user_id)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+        .bind(order.id)
+        .bind(dto.invoice_id)
+        .bind(dto.milestone_id)
+        .bind(dto.dispute_id)
+        .bind(dto.cancellation_request_id)
+        .bind(amount.amount())
+        .bind(&source.currency)
+        .bind(&reason)
+        .bind(status)
+        .bind(claims.company_id)
+        .bind(claims.sub)
+        .bind(if status == "PROCESSING" { Some(claims.sub) } else { None })
+        .execute(&mut *tx)
+        .await?
+        .last_insert_id() as i32;
+    tx.commit().await?;
+
+    if status == "REQUESTED" {
+        let message = format!("The buyer requested a refund of {} for order #{}. Please approve or reject it.", amount, order.id);
+        let vars = [("order_id", order.id.to_string()), ("amount", amount.to_string()), ("reason", reason.clone())];
+        notification_service::notify_company_with_template(pool, chat_server, order.supplier_company_id, NotificationCategory::Payment, &message, "/orders", "refund_requested", &vars).await;
+        return get_refund(pool, refund_id).await;
+    }
+    execute_refund(pool, chat_server, provider, &order, refund_id).await
+}
+
+/// 供应商或管理员审批买方的退款申请，同意后立即通过支付渠道退款
+pub async fn respond_to_refund(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    provider: &dyn PaymentProvider,
+    order_id: i32,
+    refund_id: i32,
+    dto: RespondRefundDto,
+    claims: &Claims,
+) -> Result<Refund, AppError> {
+    let comment = order_service::trimmed_text(dto.comment, 1000, "Comment")?;
+
+    let mut tx = pool.begin().await?;
+    let order = order_service::lock_order(&mut tx, order_id).await?;
+    if claims.company_id != order.supplier_company_id && !claims.is_admin {
+        return Err(AppError::BadRequest("Only the supplier or an admin can answer refund requests.".to_string()));
+    }
+    let request: Option<(Option<i32>, Option<i32>, Decimal)> = sqlx::query_as(
+        "SELECT invoice_id, milestone_id, amount FROM refunds WHERE id = ? AND order_id = ? AND status = 'REQUESTED' FOR UPDATE"
+    )
+        .bind(refund_id)
+        .bind(order.id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let (invoice_id, milestone_id, amount) = request
+        .ok_or_else(|| AppError::BadRequest("Refund request not found or already answered.".to_string()))?;
+
+    if dto.approve {
+        // 申请之后付款状态可能变了（比如在渠道后台退过款），再检查一次
+        let source = load_source(&mut tx, order.id, invoice_id, milestone_id).await?;
+        source.ensure_refundable()?;
+        let reserved = reserved_amount(&mut tx, order.id, invoice_id, milestone_id, Some(refund_id)).await?;
+        if amount > refundable_amount(source.paid_total, source.refunded_amount, reserved) {
+            return Err(AppError::BadRequest("The requested amount exceeds what can still be refunded.".to_string()));
+        }
+    }
+
+    sqlx::query(
+        "UPDATE refunds SET status = ?, processed_by_user_id = ?, response_comment = ?,
+             processed_at = IF(? = 'REJECTED', CURRENT_TIMESTAMP, NULL)
+         WHERE id = ?"
+    )
+        .bind(if dto.approve { "PROCESSING" } else { "REJECTED" })
+        .bind(claims.sub)
+        .bind(&comment)
+        .bind(if dto.approve { "PROCESSING" } else { "REJECTED" })
+        .bind(refund_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    if !dto.approve {
+        let message = format!("Your refund request for order #{} was rejected.", order.id);
+        let vars = [("order_id", order.id.to_string()), ("comment", comment.clone().unwrap_or_default())];
+        notification_service::notify_company_with_template(pool, chat_server, order.buyer_company_id, NotificationCategory::Payment, &message, "/orders", "refund_rejected", &vars).await;
+        return get_refund(pool, refund_id).await;
+    }
+    execute_refund(pool, chat_server, provider, &order, refund_id).await
+}
+
+// 调支付渠道退款。渠道调用不放在事务里，失败时把退款标成 FAILED
+async fn execute_refund(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    provider: &dyn PaymentProvider,
+    order: &LockedOrder,
+    refund_id: i32,
+) -> Result<Refund, AppError> {
+    let refund = get_refund(pool, refund_id).await?;
+    let payment_reference: Option<String> = match (refund.invoice_id, refund.milestone_id) {
+        (Some(invoice_id), _) => sqlx::query_scalar("SELECT payment_reference FROM invoices WHERE id = ?").bind(invoice_id),
+        (None, Some(milestone_id)) => sqlx::query_scalar("SELECT payment_reference FROM payment_milestones WHERE id = ?").bind(milestone_id),
+        (None, None) => sqlx::query_scalar("SELECT payment_reference FROM purchase_orders WHERE id = ?").bind(refund.order_id),
+    }
+        .fetch_one(pool)
+        .await?;
+    let payment_reference = payment_reference
+        .ok_or_else(|| AppError::BadRequest("This payment has no provider reference and must be refunded manually.".to_string()))?;
+    let amount = Money::new(refund.amount, Currency::from_str(&refund.currency)?)?;
+
+    let request = RefundRequest {
+        payment_reference: payment_reference.clone(),
+        amount,
+        idempotency_key: format!("refund-{}", refund.id),
+    };
+    let info = match provider.refund(request).await {
+        Ok(info) => info,
+        Err(e) => {
+            let failure: String = format!("{:?}", e).chars().take(1000).collect();
+            sqlx::query("UPDATE refunds SET status = 'FAILED', failure_reason = ?, processed_at = CURRENT_TIMESTAMP WHERE id = ?")
+                .bind(failure)
+                .bind(refund.id)
+                .execute(pool)
+                .await?;
+            log::error!("Refund #{} for order #{} failed: {:?}", refund.id, refund.order_id, e);
+            return Err(e);
+        }
+    };
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE refunds SET status = ?, provider_refund_id = ?, processed_at = IF(? = 'SUCCEEDED', CURRENT_TIMESTAMP, NULL)
+         WHERE id = ?"
+    )
+        .bind(if info.pending { "PROCESSING" } else { "SUCCEEDED" })
+        .bind(&info.refund_id)
+        .bind(if info.pending { "PROCESSING" } else { "SUCCEEDED" })
+        .bind(refund.id)
+        .execute(&mut *tx)
+        .await?;
+    if !info.pending {
+        // 用成功退款的合计更新订单，和渠道的退款事件一样都是“累计值”，谁先到都不会重复计算
+        let (succeeded,): (Option<Decimal>,) = sqlx::query_as(
+            "SELECT SUM(amount) FROM refunds WHERE order_id = ? AND invoice_id <=> ? AND milestone_id <=> ? AND status = 'SUCCEEDED'"
+        )
+            .bind(refund.order_id)
+            .bind(refund.invoice_id)
+            .bind(refund.milestone_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if let Some(target) = payment_service::find_target(&mut tx, "payment_reference", &payment_reference).await? {
+            payment_service::record_refund_total(&mut tx, &target, succeeded.unwrap_or(Decimal::ZERO)).await?;
+        }
+    }
+    tx.commit().await?;
+
+    let message = if info.pending {
+        format!("A refund of {} for order #{} has been submitted and is being processed.", amount, order.id)
+    } else {
+        format!("A refund of {} for order #{} has been issued.", amount, order.id)
+    };
+    let vars = [
+        ("order_id", order.id.to_string()),
+        ("amount", amount.to_string()),
+        ("pending", if info.pending { "yes" } else { "" }.to_string()),
+    ];
+    for company_id in [order.buyer_company_id, order.supplier_company_id] {
+        notification_service::notify_company_with_template(pool, chat_server, company_id, NotificationCategory::Payment, &message, "/orders", "refund_issued", &vars).await;
+    }
+    get_refund(pool, refund.id).await
+}
+
+/// 取消或争议仲裁后自动生成一条待审批的退款，供应商或管理员同意后退款。
+/// 只处理整单付款且有渠道付款ID的订单，其他情况仍然只标记待退款
+pub(crate) async fn open_linked_refund(
+    tx: &mut Transaction<'_, MySql>,
+    order_id: i32,
+    amount: Option<Decimal>,
+    reason: &str,
+    dispute_id: Option<i32>,
+    cancellation_request_id: Option<i32>,
+) -> Result<Option<i32>, AppError> {
+    if is_paid_in_parts(tx, order_id).await? {
+        return Ok(None);
+    }
+    open_requested_refund(tx, order_id, None, None, amount, reason, dispute_id, cancellation_request_id).await
+}
+
+/// 订单取消后才到账的付款（整单、发票或分期）全额生成一条待审批的退款
+pub(crate) async fn open_late_payment_refund(tx: &mut Transaction<'_, MySql>, target: &PaymentTarget, reason: &str) -> Result<Option<i32>, AppError> {
+    open_requested_refund(tx, target.order_id, target.invoice_id, target.milestone_id, None, reason, None, None).await
+}
+
+// 付款不能退（没付清、没有渠道付款ID）或已经没有可退金额时不生成退款
+#[allow(clippy::too_many_arguments)]
+async fn open_requested_refund(
+    tx: &mut Transaction<'_, MySql>,
+    order_id: i32,
+    invoice_id: Option<i32>,
+    milestone_id: Option<i32>,
+    amount: Option<Decimal>,
+    reason: &str,
+    dispute_id: Option<i32>,
+    cancellation_request_id: Option<i32>,
+) -> Result<Option<i32>, AppError> {
+    let source = load_source(tx, order_id, invoice_id, milestone_id).await?;
+    if source.ensure_refundable().is_err() {
+        return Ok(None);
+    }
+    let reserved = reserved_amount(tx, order_id, invoice_id, milestone_id, None).await?;
+    let available = refundable_amount(source.paid_total, source.refunded_amount, reserved);
+    let amount = amount.unwrap_or(available).min(available);
+    if amount <= Decimal::ZERO {
+        return Ok(None);
+    }
+
+    let refund_id = sqlx::query(
+        "INSERT INTO refunds (order_id, invoice_id, milestone_id, dispute_id, cancellation_request_id, amount, currency, reason, status, requested_by_company_id)
+         SELECT id, ?, ?, ?, ?, ?, ?, ?, 'REQUESTED', buyer_company_id FROM purchase_orders WHERE id = ?"
+    )
+        .bind(invoice_id)
+        .bind(milestone_id)
+        .bind(dispute_id)
+        .bind(cancellation_request_id)
+        .bind(amount)
+        .bind(&source.currency)
+        .bind(reason)
+        .bind(order_id)
+        .execute(&mut **tx)
+        .await?
+        .last_insert_id() as i32;
+    Ok(Some(refund_id))
+}
+
+async fn get_refund(pool: &MySqlPool, refund_id: i32) -> Result<Refund, AppError> {
+    let refund = sqlx::query_as(&format!("{} WHERE rf.id = ?", REFUND_SELECT))
+        .bind(refund_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(refund)
+}
+
+/// 订单的退款记录，订单双方和管理员可以查看
+pub async fn get_refunds_for_order(pool: &MySqlPool, order_id: i32, claims: &Claims) -> Result<Vec<Refund>, AppError> {
+    if !claims.is_admin {
+        order_service::ensure_order_party(pool, order_id, claims).await?;
+    }
+
+    let refunds = sqlx::query_as(&format!("{} WHERE rf.order_id = ? ORDER BY rf.created_at DESC, rf.id DESC", REFUND_SELECT))
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(refunds)
+}
+
+/// 管理员查看所有退款，可按状态筛选
+pub async fn list_refunds(pool: &MySqlPool, status: Option<String>) -> Result<Vec<Refund>, AppError> {
+    if status.as_deref().is_some_and(|s| !["REQUESTED", "PROCESSING", "SUCCEEDED", "FAILED", "REJECTED"].contains(&s)) {
+        return Err(AppError::BadRequest("Status must be REQUESTED, PROCESSING, SUCCEEDED, FAILED or REJECTED.".to_string()));
+    }
+
+    let refunds = sqlx::query_as(&format!(
+        "{} WHERE (? IS NULL OR rf.status = ?) ORDER BY rf.created_at DESC, rf.id DESC",
+        REFUND_SELECT
+    ))
+        .bind(&status)
+        .bind(&status)
+        .fetch_all(pool)
+        .await?;
+    Ok(refunds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refundable_amount() {
+        let d = |s: &str| Decimal::from_str(s).unwrap();
+        assert_eq!(refundable_amount(d("125.50"), d("0"), d("0")), d("125.50"));
+        // 已退和申请中的金额都要扣掉
+        assert_eq!(refundable_amount(d("125.50"), d("50.00"), d("25.50")), d("50.00"));
+        assert_eq!(refundable_amount(d("125.50"), d("125.50"), d("0")), Decimal::ZERO);
+        assert_eq!(refundable_amount(d("100"), d("80"), d("30")), Decimal::ZERO);
+    }
+}
@@ -1,7 +1,7 @@
 // src/services/rfq_service.rs
 use crate::{
     errors::AppError,
-    models::{rfq::{CreateRfqDto, Rfq}, user::Claims},
+    models::{rfq::{CreateRfqDto, Rfq, RfqResponseDto, RfqSupplierResponse}, user::Claims},
 };
 use sqlx::{MySql, MySqlPool, QueryBuilder};
 use actix_multipart::Field;
@@ -15,6 +15,8 @@ use crate::models::rfq::RfqAttachment;
 use crate::models::chat::ChatMessage;
 use crate::services::chat_server::ChatServer;
 use crate::services::matching_service;
+use crate::services::notification_service::NotificationBuilder;
+use crate::models::notification_preference::NotificationCategory;
 
 
 // 允许的上传附件后缀。根据业务需要可在此处扩展类型。
@@ -325,4 +327,122 @@ pub async fn upload_attachment_for_rfq(
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// 供应商对RFQ表态：准备报价或不参与，可以重复提交覆盖之前的表态
+pub async fn respond_to_rfq(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    rfq_id: i32,
+    dto: RfqResponseDto,
+    claims: &Claims,
+) -> Result<(), AppError> {
+    if claims.company_type != "SUPPLIER" {
+        return Err(AppError::BadRequest("Only suppliers can respond to RFQs".to_string()));
+    }
+
+    let response = dto.response.trim().to_uppercase();
+    let reason_code = dto.reason_code.map(|r| r.trim().to_uppercase()).filter(|r| !r.is_empty());
+    match response.as_str() {
+        "INTEND_TO_QUOTE" => {
+            if reason_code.is_some() {
+                return Err(AppError::BadRequest("A reason code is only allowed when declining.".to_string()));
+            }
+        }
+        "DECLINED" => match reason_code.as_deref() {
+            Some("CAPACITY" | "CAPABILITY" | "PRICE" | "TIMELINE") => {}
+            Some(_) => return Err(AppError::BadRequest("Invalid reason code.".to_string())),
+            None => return Err(AppError::BadRequest("A reason code is required when declining.".to_string())),
+        },
+        _ => return Err(AppError::BadRequest("Response must be INTEND_TO_QUOTE or DECLINED.".to_string())),
+    }
+    let comment = dto.comment.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+    if comment.as_ref().is_some_and(|c| c.chars().count() > 500) {
+        return Err(AppError::BadRequest("Comment must be at most 500 characters.".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    // 和提交报价一样锁住RFQ行，避免拒绝报价和提交报价并发时两边的检查都通过
+    let rfq: Option<(String,)> = sqlx::query_as("SELECT title FROM rfqs WHERE id = ? AND status = 'OPEN' FOR UPDATE")
+        .bind(rfq_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let (rfq_title,) = rfq.ok_or_else(|| AppError::BadRequest("RFQ not found or is not open".to_string()))?;
+
+    if response == "DECLINED" {
+        let active_quote: Option<(i32,)> = sqlx::query_as(
+            "SELECT id FROM quotes WHERE rfq_id = ? AND supplier_company_id = ? AND status = 'SUBMITTED' LIMIT 1",
+        )
+            .bind(rfq_id)
+            .bind(claims.company_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if active_quote.is_some() {
+            return Err(AppError::BadRequest("You already have an active quote for this RFQ.".to_string()));
+        }
+    }
+
+    sqlx::query(
+        "INSERT INTO rfq_supplier_responses (rfq_id, supplier_company_id, response, reason_code, comment) VALUES (?, ?, ?, ?, ?)
+         ON DUPLICATE KEY UPDATE response = VALUES(response), reason_code = VALUES(reason_code), comment = VALUES(comment)",
+    )
+        .bind(rfq_id)
+        .bind(claims.company_id)
+        .bind(&response)
+        .bind(&reason_code)
+        .bind(&comment)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    // 通知采购方，失败只记日志
+    let buyer_user: Result<(i32,), _> = sqlx::query_as(
+        "SELECT u.id FROM rfqs r JOIN users u ON r.buyer_company_id = u.company_id WHERE r.id = ? LIMIT 1",
+    )
+        .bind(rfq_id)
+        .fetch_one(pool)
+        .await;
+    if let Ok((buyer_user_id,)) = buyer_user {
+        let message = if response == "DECLINED" {
+            format!("A supplier declined to quote on '{}'", rfq_title)
+        } else {
+            format!("A supplier intends to quote on '{}'", rfq_title)
+        };
+        if let Err(e) = NotificationBuilder::new(buyer_user_id, NotificationCategory::Rfq, message)
+            .with_link(format!("/rfqs/{}", rfq_id))
+            .send(pool, chat_server)
+            .await
+        {
+            log::error!("Failed to send in-app notification: {:?}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// 采购方查看某个RFQ下所有供应商的表态
+pub async fn get_responses_for_rfq(
+    pool: &MySqlPool,
+    rfq_id: i32,
+    claims: &Claims,
+) -> Result<Vec<RfqSupplierResponse>, AppError> {
+    let rfq_owner: (i32,) = sqlx::query_as("SELECT buyer_company_id FROM rfqs WHERE id = ?")
+        .bind(rfq_id)
+        .fetch_one(pool)
+        .await?;
+
+    if rfq_owner.0 != claims.company_id {
+        return Err(AppError::BadRequest("You are not authorized to view responses for this RFQ".to_string()));
+    }
+
+    let responses = sqlx::query_as(
+        "SELECT r.*, c.name as supplier_company_name
+         FROM rfq_supplier_responses r JOIN companies c ON r.supplier_company_id = c.id
+         WHERE r.rfq_id = ? ORDER BY r.created_at DESC",
+    )
+        .bind(rfq_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(responses)
+}
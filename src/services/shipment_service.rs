@@ -0,0 +1,269 @@
+// src/services/shipment_service.rs
+// 分批发货：供应商登记每批的承运商、运单号、数量和装箱单，数量全部发出后订单自动变为已发货
+use crate::{
+    errors::AppError,
+    models::{order::OrderStatus, shipment::{OrderShipments, Shipment}, user::Claims},
+    services::{chat_server::ChatServer, delivery_service, notification_service, order_service::{self, LockedOrder}},
+    utils::upload_utils::{self, SavedFile, DOCUMENT_EXTENSIONS, MAX_DOCUMENT_SIZE_BYTES},
+};
+use crate::models::notification_preference::NotificationCategory;
+use actix::Addr;
+use actix_multipart::Multipart;
+use chrono::NaiveDate;
+use sqlx::{MySql, MySqlPool, Transaction};
+use std::collections::HashMap;
+
+struct NewShipment {
+    carrier: String,
+    tracking_number: String,
+    quantity: i32,
+    estimated_arrival: Option<NaiveDate>,
+    notes: Option<String>,
+}
+
+fn text_field(fields: &HashMap<String, String>, name: &str, max_chars: usize) -> Result<Option<String>, AppError> {
+    let value = fields.get(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    if value.as_ref().is_some_and(|v| v.chars().count() > max_chars) {
+        return Err(AppError::BadRequest(format!("{} must be at most {} characters.", name, max_chars)));
+    }
+    Ok(value)
+}
+
+fn parse_shipment_fields(fields: &HashMap<String, String>) -> Result<NewShipment, AppError> {
+    let carrier = text_field(fields, "carrier", 100)?
+        .ok_or_else(|| AppError::BadRequest("carrier is required.".to_string()))?;
+    let tracking_number = text_field(fields, "tracking_number", 100)?
+        .ok_or_else(|| AppError::BadRequest("tracking_number is required.".to_string()))?;
+    let quantity = text_field(fields, "quantity", 20)?
+        .ok_or_else(|| AppError::BadRequest("quantity is required.".to_string()))?
+        .parse::<i32>()
+        .map_err(|_| AppError::BadRequest("quantity must be a whole number.".to_string()))?;
+    let estimated_arrival = text_field(fields, "estimated_arrival", 10)?
+        .map(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d"))
+        .transpose()
+        .map_err(|_| AppError::BadRequest("estimated_arrival must be a date in YYYY-MM-DD format.".to_string()))?;
+    let notes = text_field(fields, "notes", 1000)?;
+
+    Ok(NewShipment { carrier, tracking_number, quantity, estimated_arrival, notes })
+}
+
+/// 校验本批发货数量，返回发货后订单是否已全部发出
+fn check_shipment_quantity(ordered: i32, already_shipped: i64, quantity: i32) -> Result<bool, AppError> {
+    if quantity <= 0 {
+        return Err(AppError::BadRequest("Shipped quantity must be greater than zero.".to_string()));
+    }
+    let remaining = i64::from(ordered) - already_shipped;
+    if i64::from(quantity) > remaining {
+        return Err(AppError::BadRequest(format!(
+            "Shipped quantity exceeds the remaining quantity of {}.",
+            remaining.max(0)
+        )));
+    }
+    Ok(i64::from(quantity) == remaining)
+}
+
+/// 供应商登记一批发货。multipart 字段：carrier, tracking_number, quantity, estimated_arrival, notes，
+/// 可以附带一个装箱单文件
+pub async fn create_shipment(
+    pool: &MySqlPool,
+    chat_server: &Addr<ChatServer>,
+    order_id: i32,
+    payload: Multipart,
+    claims: &Claims,
+) -> Result<i32, AppError> {
+    // 先做权限检查，避免给无关的人保存文件
+    let order: Option<(i32,)> = sqlx::query_as("SELECT id FROM purchase_orders WHERE id = ? AND supplier_company_id = ?")
+        .bind(order_id)
+        .bind(claims.company_id)
+        .fetch_optional(pool)
+        .await?;
+    if order.is_none() {
+        return Err(AppError::BadRequest("Order not found or only the supplier can record shipments.".to_string()));
+    }
+
+    let (fields, files) = upload_utils::save_multipart(payload, "packing_lists", DOCUMENT_EXTENSIONS, MAX_DOCUMENT_SIZE_BYTES).await?;
+    // 登记失败时装箱单没有记录引用，删掉
+    let recorded = record_shipment(pool, order_id, &fields, &files, claims).await;
+    if recorded.is_err() {
+        upload_utils::remove_saved_files(&files);
+    }
+    let RecordedShipment { order, new, ordered, already_shipped, fully_shipped, shipment_id } = recorded?;
+
+    // 每一批都通知采购方
+    let shipped_total = already_shipped + i64::from(new.quantity);
+    let mut message = format!(
+        "{} units of order #{} ('{}') have been shipped via {} (tracking number {}). {} of {} units shipped so far.",
+        new.quantity, order.id, order.rfq_title, new.carrier, new.tracking_number, shipped_total, ordered
+    );
+    if let Some(eta) = new.estimated_arrival {
+        message.push_str(&format!(" Estimated arrival: {}.", eta));
+    }
+    let vars = [
+        ("order_id", order.id.to_string()),
+        ("rfq_title", order.rfq_title.clone()),
+        ("quantity", new.quantity.to_string()),
+        ("carrier", new.carrier.clone()),
+        ("tracking_number", new.tracking_number.clone()),
+        ("shipped_total", shipped_total.to_string()),
+        ("ordered", ordered.to_string()),
+        ("estimated_arrival", new.estimated_arrival.map(|eta| eta.to_string()).unwrap_or_default()),
+    ];
+    notification_service::notify_company_with_template(pool, chat_server, order.buyer_company_id, NotificationCategory::Order, &message, "/orders", "order_shipped", &vars).await;
+    if fully_shipped {
+        order_service::notify_status_change(pool, chat_server, &order, OrderStatus::Shipped).await;
+    }
+
+    Ok(shipment_id)
+}
+
+// 登记成功后发通知要用到的数据
+struct RecordedShipment {
+    order: LockedOrder,
+    new: NewShipment,
+    ordered: i32,
+    already_shipped: i64,
+    fully_shipped: bool,
+    shipment_id: i32,
+}
+
+/// 在事务里登记这批发货，数量全部发出时订单转为已发货
+async fn record_shipment(
+    pool: &MySqlPool,
+    order_id: i32,
+    fields: &HashMap<String, String>,
+    files: &[SavedFile],
+    claims: &Claims,
+) -> Result<RecordedShipment, AppError> {
+    if files.len() > 1 {
+        return Err(AppError::BadRequest("Only one packing list can be attached per shipment.".to_string()));
+    }
+    let packing_list = files.first();
+    let new = parse_shipment_fields(fields)?;
+
+    let mut tx = pool.begin().await?;
+    let order = order_service::lock_order(&mut tx, order_id).await?;
+    if order.status != OrderStatus::InProduction.as_str() {
+        return Err(AppError::BadRequest(format!("Shipments cannot be recorded for an order in status {}.", order.status)));
+    }
+
+    let (ordered, already_shipped): (i32, Option<i64>) = sqlx::query_as(
+        "SELECT po.quantity, (SELECT CAST(SUM(s.shipped_quantity) AS SIGNED) FROM shipments s WHERE s.order_id = po.id AND s.superseded_at IS NULL)
+         FROM purchase_orders po WHERE po.id = ?"
+    )
+        .bind(order.id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let already_shipped = already_shipped.unwrap_or(0);
+    let fully_shipped = check_shipment_quantity(ordered, already_shipped, new.quantity)?;
+
+    let result = sqlx::query(
+        "INSERT INTO shipments (order_id, carrier, tracking_number, shipped_quantity, estimated_arrival, notes, packing_list_filename, packing_list_path, created_by_user_id)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+        .bind(order.id)
+        .bind(&new.carrier)
+        .bind(&new.tracking_number)
+        .bind(new.quantity)
+        .bind(new.estimated_arrival)
+        .bind(&new.notes)
+        .bind(packing_list.map(|f| &f.original_filename))
+        .bind(packing_list.map(|f| &f.stored_path))
+        .bind(claims.sub)
+        .execute(&mut *tx)
+        .await?;
+
+    if fully_shipped {
+        delivery_service::record_delivery_outcome(&mut tx, order.id).await?;
+        let comment = format!("All {} units shipped", ordered);
+        order_service::record_transition(
+            &mut tx,
+            order.id,
+            Some(OrderStatus::InProduction.as_str()),
+            OrderStatus::Shipped,
+            Some(claims.sub),
+            Some(claims.company_id),
+            Some(&comment),
+        )
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(RecordedShipment { order, new, ordered, already_shipped, fully_shipped, shipment_id: result.last_insert_id() as i32 })
+}
+
+pub async fn get_shipments_for_order(pool: &MySqlPool, order_id: i32, claims: &Claims) -> Result<OrderShipments, AppError> {
+    let ordered: Option<(i32,)> = sqlx::query_as(
+        "SELECT quantity FROM purchase_orders WHERE id = ? AND (buyer_company_id = ? OR supplier_company_id = ?)"
+    )
+        .bind(order_id)
+        .bind(claims.company_id)
+        .bind(claims.company_id)
+        .fetch_optional(pool)
+        .await?;
+    let (ordered_quantity,) = ordered
+        .ok_or_else(|| AppError::BadRequest("Order not found or you are not authorized to view it.".to_string()))?;
+
+    let shipments: Vec<Shipment> = sqlx::query_as("SELECT * FROM shipments WHERE order_id = ? ORDER BY created_at ASC, id ASC")
+        .bind(order_id)
+        .fetch_all(pool)
+        .await?;
+
+    let shipped_quantity: i64 = shipments.iter()
+        .filter(|s| s.superseded_at.is_none())
+        .map(|s| i64::from(s.shipped_quantity))
+        .sum();
+    Ok(OrderShipments {
+        order_id,
+        ordered_quantity,
+        shipped_quantity,
+        remaining_quantity: (i64::from(ordered_quantity) - shipped_quantity).max(0),
+        shipments,
+    })
+}
+
+/// 下载某批发货的装箱单，只有订单双方可以下载
+pub async fn download_packing_list(pool: &MySqlPool, order_id: i32, shipment_id: i32, claims: &Claims) -> Result<(String, String, Vec<u8>), AppError> {
+    order_service::ensure_order_party(pool, order_id, claims).await?;
+    let shipment: Option<Shipment> = sqlx::query_as("SELECT * FROM shipments WHERE id = ? AND order_id = ?")
+        .bind(shipment_id)
+        .bind(order_id)
+        .fetch_optional(pool)
+        .await?;
+    let Some(Shipment { packing_list_filename: Some(filename), packing_list_path: Some(path), .. }) = shipment else {
+        return Err(AppError::BadRequest("Packing list not found.".to_string()));
+    };
+
+    let (content_type, content) = upload_utils::read_saved_file(&path, &filename).await?;
+    Ok((filename, content_type, content))
+}
+
+/// 争议判返工：作废之前的发货和收货记录，供应商重新生产后按整单数量重新发货
+pub(crate) async fn supersede_shipments(tx: &mut Transaction<'_, MySql>, order_id: i32) -> Result<(), AppError> {
+    sqlx::query("UPDATE shipments SET superseded_at = NOW() WHERE order_id = ? AND superseded_at IS NULL")
+        .bind(order_id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query("UPDATE goods_receipts SET superseded_at = NOW() WHERE order_id = ? AND superseded_at IS NULL")
+        .bind(order_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_and_final_shipments() {
+        // 100件分三批：40 + 35 + 25
+        assert!(!check_shipment_quantity(100, 0, 40).unwrap());
+        assert!(!check_shipment_quantity(100, 40, 35).unwrap());
+        assert!(check_shipment_quantity(100, 75, 25).unwrap());
+
+        // 不能超发，也不能发0件
+        assert!(check_shipment_quantity(100, 75, 26).is_err());
+        assert!(check_shipment_quantity(100, 0, 0).is_err());
+        assert!(check_shipment_quantity(100, 100, 1).is_err());
+    }
+}
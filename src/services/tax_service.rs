@@ -0,0 +1,132 @@
+// src/services/tax_service.rs
+// 订单税费：税率按买方所在国家/地区由管理员配置，下单时快照到订单上
+use crate::{
+    errors::AppError,
+    models::{money, tax::{SetTaxRateDto, TaxRate}, user::Claims},
+};
+use sqlx::{types::Decimal, MySqlExecutor, MySqlPool};
+
+/// 订单金额拆分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct OrderTotals {
+    pub subtotal: Decimal,
+    pub shipping: Decimal,
+    pub tax: Decimal,
+    pub total: Decimal,
+}
+
+/// 运费也要计税，税额四舍五入到分
+pub(crate) fn order_totals(subtotal: Decimal, shipping: Decimal, tax_rate: Decimal) -> OrderTotals {
+    let tax = money::round_amount((subtotal + shipping) * tax_rate / Decimal::ONE_HUNDRED);
+    OrderTotals { subtotal, shipping, tax, total: subtotal + shipping + tax }
+}
+
+/// 校验并规范化国家代码（两位字母，转大写）和地区
+pub(crate) fn normalize_location(country_code: &str, region: Option<String>) -> Result<(String, String), AppError> {
+    let country_code = country_code.trim().to_uppercase();
+    if country_code.len() != 2 || !country_code.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(AppError::BadRequest("Country code must be a two-letter ISO code.".to_string()));
+    }
+    let region = region.map(|r| r.trim().to_string()).unwrap_or_default();
+    if region.chars().count() > 100 {
+        return Err(AppError::BadRequest("Region must be at most 100 characters.".to_string()));
+    }
+    Ok((country_code, region))
+}
+
+/// 买方适用的税率：地区税率优先，其次是全国税率。没有配置或公司没填国家时不计税
+pub(crate) async fn rate_for_company<'e>(
+    executor: impl MySqlExecutor<'e>,
+    company_id: i32,
+) -> Result<Option<(String, Decimal)>, AppError> {
+    let rate = sqlx::query_as(
+        "SELECT t.name, t.rate FROM companies c
+         JOIN tax_rates t ON t.country_code = c.country_code AND t.region IN (COALESCE(c.region, ''), '')
+         WHERE c.id = ?
+         ORDER BY t.region = '' LIMIT 1"
+    )
+        .bind(company_id)
+        .fetch_optional(executor)
+        .await?;
+    Ok(rate)
+}
+
+pub async fn list_tax_rates(pool: &MySqlPool) -> Result<Vec<TaxRate>, AppError> {
+    let rates = sqlx::query_as("SELECT * FROM tax_rates ORDER BY country_code, region")
+        .fetch_all(pool)
+        .await?;
+    Ok(rates)
+}
+
+/// 新增或修改一个国家/地区的税率，只影响之后的报价和订单
+pub async fn set_tax_rate(pool: &MySqlPool, dto: SetTaxRateDto, claims: &Claims) -> Result<TaxRate, AppError> {
+    let (country_code, region) = normalize_location(&dto.country_code, dto.region)?;
+    let name = dto.name.trim();
+    if name.is_empty() || name.chars().count() > 50 {
+        return Err(AppError::BadRequest("Tax name must be between 1 and 50 characters.".to_string()));
+    }
+    if dto.rate < Decimal::ZERO || dto.rate > Decimal::ONE_HUNDRED || dto.rate.normalize().scale() > 2 {
+        return Err(AppError::BadRequest("Tax rate must be a percentage between 0 and 100 with at most 2 decimals.".to_string()));
+    }
+
+    sqlx::query(
+        "INSERT INTO tax_rates (country_code, region, name, rate, updated_by_user_id) VALUES (?, ?, ?, ?, ?)
+         ON DUPLICATE KEY UPDATE name = VALUES(name), rate = VALUES(rate), updated_by_user_id = VALUES(updated_by_user_id)"
+    )
+        .bind(&country_code)
+        .bind(&region)
+        .bind(name)
+        .bind(dto.rate)
+        .bind(claims.sub)
+        .execute(pool)
+        .await?;
+    let rate = sqlx::query_as("SELECT * FROM tax_rates WHERE country_code = ? AND region = ?")
+        .bind(&country_code)
+        .bind(&region)
+        .fetch_one(pool)
+        .await?;
+    Ok(rate)
+}
+
+pub async fn delete_tax_rate(pool: &MySqlPool, tax_rate_id: i32) -> Result<(), AppError> {
+    let result = sqlx::query("DELETE FROM tax_rates WHERE id = ?")
+        .bind(tax_rate_id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(AppError::BadRequest("Tax rate not found.".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn d(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_order_totals() {
+        let totals = order_totals(d("1000.00"), d("50.00"), d("19"));
+        assert_eq!(totals.tax, d("199.50"));
+        assert_eq!(totals.total, d("1249.50"));
+        // 税额按商业惯例四舍五入
+        assert_eq!(order_totals(d("10.05"), Decimal::ZERO, d("10")).tax, d("1.01"));
+        let untaxed = order_totals(d("125.50"), Decimal::ZERO, Decimal::ZERO);
+        assert_eq!((untaxed.tax, untaxed.total), (Decimal::ZERO, d("125.50")));
+    }
+
+    #[test]
+    fn test_normalize_location() {
+        assert_eq!(normalize_location(" de ", None).unwrap(), ("DE".to_string(), String::new()));
+        assert_eq!(
+            normalize_location("US", Some(" CA ".to_string())).unwrap(),
+            ("US".to_string(), "CA".to_string())
+        );
+        assert!(normalize_location("USA", None).is_err());
+        assert!(normalize_location("1A", None).is_err());
+    }
+}
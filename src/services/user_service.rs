@@ -1,6 +1,6 @@
 use crate::{
     errors::AppError,
-    models::user::{ChangePasswordDto, Claims, User, UserProfileResponse},
+    models::{email::Locale, user::{ChangePasswordDto, Claims, UpdateLocaleDto, User, UserProfileResponse}},
     utils::auth_utils,
 };
 use sqlx::MySqlPool;
@@ -8,7 +8,7 @@ use sqlx::MySqlPool;
 pub async fn get_my_profile(pool: &MySqlPool, claims: &Claims) -> Result<UserProfileResponse, AppError> {
 
     let profile = sqlx::query_as(
-        "SELECT u.id, u.full_name, u.email, u.company_id, u.is_active, c.name as company_name
+        "SELECT u.id, u.full_name, u.email, u.company_id, u.locale, u.is_active, c.name as company_name
          FROM users u
          JOIN companies c ON u.company_id = c.id
          WHERE u.id = ?"
@@ -59,4 +59,16 @@ pub async fn change_password(
         .await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// 设置邮件语言，之后的邮件按这个语言渲染
+pub async fn update_locale(pool: &MySqlPool, claims: &Claims, dto: UpdateLocaleDto) -> Result<UserProfileResponse, AppError> {
+    let locale = Locale::parse(&dto.locale)
+        .ok_or_else(|| AppError::BadRequest("Locale must be one of: en, zh-CN.".to_string()))?;
+    sqlx::query("UPDATE users SET locale = ? WHERE id = ?")
+        .bind(locale.as_str())
+        .bind(claims.sub)
+        .execute(pool)
+        .await?;
+    get_my_profile(pool, claims).await
+}
@@ -0,0 +1,96 @@
+#![cfg(test)]
+
+use crate::{
+    config,
+    models::email::{Locale, RenderedEmail},
+    services::{
+        email_service::{self, MAX_ATTEMPTS},
+        email_transport::{EmailSendError, MemoryEmailTransport},
+        email_template,
+    },
+};
+use sqlx::MySqlPool;
+
+fn unique_address() -> String {
+    format!("outbox_{}@example.com", uuid::Uuid::new_v4().simple())
+}
+
+fn plain_email(subject: &str) -> RenderedEmail {
+    RenderedEmail { subject: subject.to_string(), text: "Body".to_string(), html: "<p>Body</p>".to_string() }
+}
+
+async fn email_state(pool: &MySqlPool, email_id: i32) -> (String, i32, i64) {
+    sqlx::query_as("SELECT status, attempts, CAST(next_attempt_at > NOW() AS SIGNED) FROM email_outbox WHERE id = ?")
+        .bind(email_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+// 让退避中的邮件立即到期，不用真的等
+async fn make_due(pool: &MySqlPool, email_id: i32) {
+    sqlx::query("UPDATE email_outbox SET next_attempt_at = NOW() WHERE id = ?")
+        .bind(email_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+#[actix_web::test]
+async fn test_outbox_retries_then_sends() {
+    let pool = config::configure_test_db().await;
+    let transport = MemoryEmailTransport::default();
+    let to = unique_address();
+
+    let vars = [("subject", "Outbox test".to_string()), ("message", "Hello".to_string())];
+    let rendered = email_template::render("notification", Locale::ZhCn, &vars).unwrap();
+    let email_id = email_service::queue_email(&pool, &to, &rendered, Vec::new()).await.unwrap();
+
+    // 第一次失败：留在队列里，推迟到退避时间之后
+    transport.fail_next(&to, vec![EmailSendError::Temporary("connection refused".to_string())]);
+    email_service::deliver_due_emails(&pool, &transport).await.unwrap();
+    assert_eq!(email_state(&pool, email_id).await, ("PENDING".to_string(), 1, 1));
+
+    // 还没到期的不会被重发
+    email_service::deliver_due_emails(&pool, &transport).await.unwrap();
+    assert!(transport.sent().iter().all(|e| e.id != email_id));
+
+    make_due(&pool, email_id).await;
+    email_service::deliver_due_emails(&pool, &transport).await.unwrap();
+    assert_eq!(email_state(&pool, email_id).await.0, "SENT");
+    let sent: Vec<_> = transport.sent().into_iter().filter(|e| e.id == email_id).collect();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].subject, "Outbox test");
+    assert!(sent[0].body.contains("您好"));
+    assert!(sent[0].html.as_deref().is_some_and(|html| html.contains("<p>Hello</p>")));
+}
+
+#[actix_web::test]
+async fn test_outbox_dead_letters_and_admin_retry() {
+    let pool = config::configure_test_db().await;
+    let transport = MemoryEmailTransport::default();
+
+    // 服务器拒收：不重试，直接进死信
+    let rejected = unique_address();
+    let rejected_id = email_service::queue_email(&pool, &rejected, &plain_email("Rejected"), Vec::new()).await.unwrap();
+    transport.fail_next(&rejected, vec![EmailSendError::Permanent("550 mailbox unavailable".to_string())]);
+    email_service::deliver_due_emails(&pool, &transport).await.unwrap();
+    assert_eq!(email_state(&pool, rejected_id).await.0, "DEAD");
+
+    // 一直临时失败：重试次数用完后进死信
+    let flaky = unique_address();
+    let flaky_id = email_service::queue_email(&pool, &flaky, &plain_email("Flaky"), Vec::new()).await.unwrap();
+    transport.fail_next(&flaky, vec![EmailSendError::Temporary("timeout".to_string()); MAX_ATTEMPTS as usize]);
+    for _ in 0..MAX_ATTEMPTS {
+        make_due(&pool, flaky_id).await;
+        email_service::deliver_due_emails(&pool, &transport).await.unwrap();
+    }
+    assert_eq!(email_state(&pool, flaky_id).await.0, "DEAD");
+
+    // 管理员重发后重新排队并投递成功
+    let retried = email_service::retry_email(&pool, flaky_id).await.unwrap();
+    assert_eq!((retried.status.as_str(), retried.attempts), ("PENDING", 0));
+    assert!(email_service::retry_email(&pool, flaky_id).await.is_err());
+    email_service::deliver_due_emails(&pool, &transport).await.unwrap();
+    assert_eq!(email_state(&pool, flaky_id).await.0, "SENT");
+}
@@ -1,2 +1,6 @@
 pub mod auth_test;
-mod rfq_test;
\ No newline at end of file
+mod rfq_test;
+mod payment_test;
+mod email_test;
+mod shipment_test;
+mod notification_test;
@@ -0,0 +1,99 @@
+#![cfg(test)]
+
+use super::payment_test::register;
+use crate::{
+    config,
+    models::{
+        notification_preference::{NotificationCategory, PreferenceUpdate, UpdatePreferencesDto},
+        user::Claims,
+    },
+    services::{chat_server::ChatServer, notification_preference_service, notification_service::NotificationBuilder},
+};
+use actix::Actor;
+use sqlx::MySqlPool;
+
+async fn buyer_claims(pool: &MySqlPool) -> Claims {
+    let (_, company_id) = register(pool, "BUYER").await;
+    let (user_id,): (i32,) = sqlx::query_as("SELECT id FROM users WHERE company_id = ?")
+        .bind(company_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    Claims { sub: user_id, company_id, company_type: "BUYER".to_string(), is_admin: false, exp: 0 }
+}
+
+async fn set_channel(pool: &MySqlPool, claims: &Claims, category: &str, channel: &str, enabled: bool) {
+    let dto = UpdatePreferencesDto {
+        preferences: vec![PreferenceUpdate { category: category.to_string(), channel: channel.to_string(), enabled }],
+    };
+    notification_preference_service::update_preferences(pool, claims, dto).await.unwrap();
+}
+
+async fn send(pool: &MySqlPool, claims: &Claims, category: NotificationCategory, message: &str) {
+    let chat_server = ChatServer::default().start();
+    let vars = [("subject", message.to_string()), ("message", message.to_string())];
+    NotificationBuilder::new(claims.sub, category, message.to_string())
+        .with_email("notification", &vars)
+        .send(pool, &chat_server)
+        .await
+        .unwrap();
+}
+
+async fn queued_emails(pool: &MySqlPool, claims: &Claims) -> i64 {
+    let (count,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM email_outbox WHERE recipient = (SELECT email FROM users WHERE id = ?)"
+    )
+        .bind(claims.sub)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    count
+}
+
+async fn digest_items(pool: &MySqlPool, claims: &Claims) -> i64 {
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM notification_digest_items WHERE user_id = ?")
+        .bind(claims.sub)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    count
+}
+
+#[actix_web::test]
+async fn test_disabled_email_is_not_queued() {
+    let pool = config::configure_test_db().await;
+    let claims = buyer_claims(&pool).await;
+
+    set_channel(&pool, &claims, "ORDER", "EMAIL", false).await;
+    send(&pool, &claims, NotificationCategory::Order, "Order shipped").await;
+    assert_eq!(queued_emails(&pool, &claims).await, 0);
+
+    // 事务性类别的邮件关不掉
+    assert!(notification_preference_service::update_preferences(
+        &pool,
+        &claims,
+        UpdatePreferencesDto {
+            preferences: vec![PreferenceUpdate { category: "PAYMENT".to_string(), channel: "EMAIL".to_string(), enabled: false }],
+        },
+    ).await.is_err());
+    send(&pool, &claims, NotificationCategory::Payment, "Invoice issued").await;
+    assert_eq!(queued_emails(&pool, &claims).await, 1);
+}
+
+#[actix_web::test]
+async fn test_digest_replaces_immediate_email() {
+    let pool = config::configure_test_db().await;
+    let claims = buyer_claims(&pool).await;
+
+    // 普通类别开了摘要：只进摘要，不单独发邮件
+    set_channel(&pool, &claims, "ORDER", "DIGEST", true).await;
+    send(&pool, &claims, NotificationCategory::Order, "Order shipped").await;
+    assert_eq!(queued_emails(&pool, &claims).await, 0);
+    assert_eq!(digest_items(&pool, &claims).await, 1);
+
+    // 事务性类别开了摘要也照常发邮件
+    set_channel(&pool, &claims, "PAYMENT", "DIGEST", true).await;
+    send(&pool, &claims, NotificationCategory::Payment, "Invoice issued").await;
+    assert_eq!(queued_emails(&pool, &claims).await, 1);
+    assert_eq!(digest_items(&pool, &claims).await, 2);
+}
@@ -0,0 +1,638 @@
+#![cfg(test)]
+
+use crate::{
+    api, config,
+    models::user::{LoginResponse, RegisterDto},
+    services::{chat_server::ChatServer, payment_provider::{MockPaymentProvider, PaymentProvider}, reconciliation_service},
+};
+use actix::Actor;
+use actix_web::{dev::{ServiceFactory, ServiceRequest, ServiceResponse}, test, web, App, http::header};
+use serde_json::json;
+use sqlx::{types::Decimal, MySqlPool};
+use std::{str::FromStr, sync::Arc};
+
+// 用Mock渠道跑完整的 下单 -> 支付 -> Webhook 流程，不需要Stripe账号
+pub(super) struct PaymentFixture {
+    pub(super) buyer_token: String,
+    pub(super) supplier_token: String,
+    pub(super) order_id: i32,
+    pub(super) buyer_company_id: i32,
+    pub(super) supplier_company_id: i32,
+}
+
+pub(super) async fn register(pool: &MySqlPool, company_type: &str) -> (String, i32) {
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    let email = format!("{}_{}@example.com", company_type.to_lowercase(), suffix);
+    let company_name = format!("Payment Test {} {}", company_type, suffix);
+
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(pool.clone()))
+            .configure(api::config)
+    ).await;
+
+    let register_dto = RegisterDto {
+        company_name: company_name.clone(),
+        company_type: company_type.to_string(),
+        city: "City".to_string(),
+        email: email.clone(),
+        password: "password123".to_string(),
+        full_name: "Payment Test User".to_string(),
+    };
+    let req = test::TestRequest::post().uri("/api/auth/register").set_json(&register_dto).to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(json!({ "email": email, "password": "password123" }))
+        .to_request();
+    let login_resp: LoginResponse = test::read_body_json(test::call_service(&app, req).await).await;
+
+    let (company_id,): (i32,) = sqlx::query_as("SELECT id FROM companies WHERE name = ?")
+        .bind(&company_name)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    (login_resp.token, company_id)
+}
+
+// 挂上连接池、ChatServer和支付渠道的完整应用
+pub(super) fn init_app(
+    pool: &MySqlPool,
+    provider: Arc<dyn PaymentProvider>,
+) -> App<impl ServiceFactory<ServiceRequest, Config = (), Response = ServiceResponse, Error = actix_web::Error, InitError = ()> + use<>> {
+    App::new()
+        .app_data(web::Data::new(pool.clone()))
+        .app_data(web::Data::new(ChatServer::default().start()))
+        .app_data(web::Data::from(provider))
+        .configure(api::config)
+}
+
+// 直接插入询价、报价和订单，避开授标时发邮件
+pub(super) async fn setup_order(pool: &MySqlPool) -> PaymentFixture {
+    let (buyer_token, buyer_company_id) = register(pool, "BUYER").await;
+    let (supplier_token, supplier_company_id) = register(pool, "SUPPLIER").await;
+
+    let rfq_id = sqlx::query("INSERT INTO rfqs (buyer_company_id, title, quantity, status) VALUES (?, 'Payment test RFQ', 10, 'AWARDED')")
+        .bind(buyer_company_id)
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id();
+    let quote_id = sqlx::query(
+        "INSERT INTO quotes (rfq_id, supplier_company_id, price, currency, lead_time_days, status) VALUES (?, ?, 125.50, 'EUR', 14, 'ACCEPTED')"
+    )
+        .bind(rfq_id)
+        .bind(supplier_company_id)
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id();
+    let order_id = sqlx::query(
+        "INSERT INTO purchase_orders (quote_id, rfq_id, buyer_company_id, supplier_company_id, quantity, total_amount, currency)
+         VALUES (?, ?, ?, ?, 10, 125.50, 'EUR')"
+    )
+        .bind(quote_id)
+        .bind(rfq_id)
+        .bind(buyer_company_id)
+        .bind(supplier_company_id)
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_id();
+
+    PaymentFixture { buyer_token, supplier_token, order_id: order_id as i32, buyer_company_id, supplier_company_id }
+}
+
+// 订单对公司是普通外键，删掉才能让下次 configure_test_db 清理公司
+pub(super) async fn cleanup(pool: &MySqlPool, fixture: &PaymentFixture) {
+    sqlx::query("DELETE FROM purchase_orders WHERE buyer_company_id = ? OR supplier_company_id = ?")
+        .bind(fixture.buyer_company_id)
+        .bind(fixture.supplier_company_id)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+async fn payment_status(pool: &MySqlPool, order_id: i32) -> String {
+    let (status,): (String,) = sqlx::query_as("SELECT payment_status FROM purchase_orders WHERE id = ?")
+        .bind(order_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    status
+}
+
+#[actix_web::test]
+async fn test_mock_checkout_completes_order_payment() {
+    let pool = config::configure_test_db().await;
+    let fixture = setup_order(&pool).await;
+    let mock = Arc::new(MockPaymentProvider::new("whsec_test"));
+    let app = test::init_service(init_app(&pool, mock.clone())).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/create-checkout-session", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let session_id = body["session_id"].as_str().unwrap().to_string();
+
+    // Mock 渠道记下了精确金额
+    let session = mock.session(&session_id).unwrap();
+    assert_eq!(session.amount.to_minor_units().unwrap(), 12550);
+
+    let (payload, signature) = mock.complete_checkout(&session_id);
+    let req = test::TestRequest::post()
+        .uri("/api/stripe/webhook")
+        .insert_header(("Stripe-Signature", signature.clone()))
+        .set_payload(payload.clone())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(payment_status(&pool, fixture.order_id).await, "PAID");
+
+    // 渠道重复投递同一事件：确认收到，但只处理一次
+    let req = test::TestRequest::post()
+        .uri("/api/stripe/webhook")
+        .insert_header(("Stripe-Signature", signature))
+        .set_payload(payload.clone())
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    let event_id = serde_json::from_str::<serde_json::Value>(&payload).unwrap()["id"].as_str().unwrap().to_string();
+    let (status, attempts): (String, i32) = sqlx::query_as("SELECT status, attempts FROM payment_events WHERE provider = 'mock' AND event_id = ?")
+        .bind(&event_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "PROCESSED");
+    assert_eq!(attempts, 2);
+
+    // 先部分退款，再全额退款
+    let payment_reference = mock.session(&session_id).unwrap().payment_reference.unwrap();
+    for (amount, fully_refunded, expected) in [(5000, false, "PARTIALLY_REFUNDED"), (12550, true, "REFUNDED")] {
+        let (payload, signature) = mock.charge_refunded(&payment_reference, amount, fully_refunded);
+        let req = test::TestRequest::post()
+            .uri("/api/stripe/webhook")
+            .insert_header(("Stripe-Signature", signature))
+            .set_payload(payload)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+        assert_eq!(payment_status(&pool, fixture.order_id).await, expected);
+    }
+
+    cleanup(&pool, &fixture).await;
+}
+
+#[actix_web::test]
+async fn test_mock_checkout_failure_and_bad_signature() {
+    let pool = config::configure_test_db().await;
+    let fixture = setup_order(&pool).await;
+    let mock = Arc::new(MockPaymentProvider::new("whsec_test"));
+    let app = test::init_service(init_app(&pool, mock.clone())).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/create-checkout-session", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let session_id = body["session_id"].as_str().unwrap().to_string();
+
+    // 别的密钥签出来的事件必须拒绝，状态不变
+    let (payload, signature) = MockPaymentProvider::new("whsec_other").complete_checkout(&session_id);
+    let req = test::TestRequest::post()
+        .uri("/api/stripe/webhook")
+        .insert_header(("Stripe-Signature", signature))
+        .set_payload(payload)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+    assert_eq!(payment_status(&pool, fixture.order_id).await, "UNPAID");
+
+    let (payload, signature) = mock.fail_checkout(&session_id);
+    let req = test::TestRequest::post()
+        .uri("/api/stripe/webhook")
+        .insert_header(("Stripe-Signature", signature))
+        .set_payload(payload)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(payment_status(&pool, fixture.order_id).await, "FAILED");
+
+    cleanup(&pool, &fixture).await;
+}
+
+#[actix_web::test]
+async fn test_partial_and_requested_refunds() {
+    let pool = config::configure_test_db().await;
+    let fixture = setup_order(&pool).await;
+    let mock = Arc::new(MockPaymentProvider::new("whsec_test"));
+    let app = test::init_service(init_app(&pool, mock.clone())).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/create-checkout-session", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let (payload, signature) = mock.complete_checkout(body["session_id"].as_str().unwrap());
+    let req = test::TestRequest::post()
+        .uri("/api/stripe/webhook")
+        .insert_header(("Stripe-Signature", signature))
+        .set_payload(payload)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+    // 供应商直接部分退款
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/refunds", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.supplier_token)))
+        .set_json(json!({ "amount": "50.00", "reason": "Two parts short" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let refund: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(refund["status"], "SUCCEEDED");
+    assert_eq!(payment_status(&pool, fixture.order_id).await, "PARTIALLY_REFUNDED");
+
+    // 买方申请退剩下的全部金额，超额申请被拒
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/refunds", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .set_json(json!({ "amount": "80.00", "reason": "Too much" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/refunds", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .set_json(json!({ "reason": "Order no longer needed" }))
+        .to_request();
+    let refund: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(refund["status"], "REQUESTED");
+    assert_eq!(refund["amount"], "75.50");
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/orders/{}/refunds/{}", fixture.order_id, refund["id"]))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.supplier_token)))
+        .set_json(json!({ "approve": true }))
+        .to_request();
+    let refund: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(refund["status"], "SUCCEEDED");
+    assert_eq!(payment_status(&pool, fixture.order_id).await, "REFUNDED");
+
+    let (refunded,): (sqlx::types::Decimal,) = sqlx::query_as("SELECT refunded_amount FROM purchase_orders WHERE id = ?")
+        .bind(fixture.order_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(refunded.to_string(), "125.50");
+
+    cleanup(&pool, &fixture).await;
+}
+
+#[actix_web::test]
+async fn test_milestone_schedule_payments() {
+    let pool = config::configure_test_db().await;
+    let fixture = setup_order(&pool).await;
+    let mock = Arc::new(MockPaymentProvider::new("whsec_test"));
+    let app = test::init_service(init_app(&pool, mock.clone())).await;
+
+    // 30% 定金授标后付，70% 发货后付
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/orders/{}/payment-schedule", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.supplier_token)))
+        .set_json(json!({ "milestones": [
+            { "label": "Deposit", "percentage": 30, "trigger": "AWARD" },
+            { "label": "Balance", "percentage": "70", "trigger": "SHIPPED" }
+        ] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let schedule: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(schedule[0]["amount"], "37.65");
+    assert_eq!(schedule[1]["amount"], "87.85");
+    assert_eq!(schedule[0]["is_due"], true);
+    assert_eq!(schedule[1]["is_due"], false);
+    let deposit_id = schedule[0]["id"].as_i64().unwrap();
+    let balance_id = schedule[1]["id"].as_i64().unwrap();
+
+    // 有分期计划时不能整单付款
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/create-checkout-session", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    let checkout = |milestone_id: i64| test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/payment-milestones/{}/create-checkout-session", fixture.order_id, milestone_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .to_request();
+
+    // 尾款还没到期
+    assert_eq!(test::call_service(&app, checkout(balance_id)).await.status(), 400);
+
+    let pay = |session_id: String| {
+        let (payload, signature) = mock.complete_checkout(&session_id);
+        test::TestRequest::post()
+            .uri("/api/stripe/webhook")
+            .insert_header(("Stripe-Signature", signature))
+            .set_payload(payload)
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, checkout(deposit_id)).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let session_id = body["session_id"].as_str().unwrap().to_string();
+    assert_eq!(mock.session(&session_id).unwrap().amount.to_minor_units().unwrap(), 3765);
+    assert_eq!(test::call_service(&app, pay(session_id)).await.status(), 200);
+    // 只付了定金，订单还没付清
+    assert_eq!(payment_status(&pool, fixture.order_id).await, "UNPAID");
+
+    // 开始付款后计划不能再改
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/orders/{}/payment-schedule", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .set_json(json!({ "milestones": [] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+    sqlx::query("UPDATE purchase_orders SET status = 'SHIPPED' WHERE id = ?")
+        .bind(fixture.order_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let resp = test::call_service(&app, checkout(balance_id)).await;
+    assert_eq!(resp.status(), 200);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let session_id = body["session_id"].as_str().unwrap().to_string();
+    assert_eq!(test::call_service(&app, pay(session_id)).await.status(), 200);
+    assert_eq!(payment_status(&pool, fixture.order_id).await, "PAID");
+
+    cleanup(&pool, &fixture).await;
+}
+
+#[actix_web::test]
+async fn test_reconciliation_recovers_lost_webhook() {
+    let pool = config::configure_test_db().await;
+    let paid = setup_order(&pool).await;
+    let unknown = setup_order(&pool).await;
+    let mock = Arc::new(MockPaymentProvider::new("whsec_test"));
+    let chat_server = ChatServer::default().start();
+    let app = test::init_service(init_app(&pool, mock.clone())).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/create-checkout-session", paid.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", paid.buyer_token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let session_id = body["session_id"].as_str().unwrap().to_string();
+    // 买方付了款，但Webhook没有送到
+    mock.complete_checkout(&session_id);
+    assert_eq!(payment_status(&pool, paid.order_id).await, "UNPAID");
+
+    // 渠道里查不到的会话要标记给管理员
+    sqlx::query("UPDATE purchase_orders SET stripe_session_id = 'mock_cs_missing' WHERE id = ?")
+        .bind(unknown.order_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let run = reconciliation_service::run_reconciliation(&pool, &chat_server, mock.as_ref(), None).await.unwrap();
+    assert!(run.fixed >= 1 && run.flagged >= 1);
+    assert_eq!(payment_status(&pool, paid.order_id).await, "PAID");
+
+    let outcome = |order_id: i32| {
+        let pool = pool.clone();
+        async move {
+            let (outcome,): (String,) = sqlx::query_as("SELECT outcome FROM payment_reconciliation_items WHERE run_id = ? AND order_id = ?")
+                .bind(run.id)
+                .bind(order_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            outcome
+        }
+    };
+    assert_eq!(outcome(paid.order_id).await, "FIXED");
+    assert_eq!(outcome(unknown.order_id).await, "MISMATCH");
+
+    let csv = reconciliation_service::daily_report_csv(&pool, run.report_date).await.unwrap();
+    assert!(csv.contains(&session_id));
+    assert!(csv.contains("mock_cs_missing"));
+
+    // 再跑一次：已付的不会再查，不会重复补记
+    let run = reconciliation_service::run_reconciliation(&pool, &chat_server, mock.as_ref(), None).await.unwrap();
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM payment_reconciliation_items WHERE run_id = ? AND order_id = ?")
+        .bind(run.id)
+        .bind(paid.order_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+
+    cleanup(&pool, &paid).await;
+    cleanup(&pool, &unknown).await;
+}
+
+#[actix_web::test]
+async fn test_net_terms_credit_and_mark_paid() {
+    let pool = config::configure_test_db().await;
+    let fixture = setup_order(&pool).await;
+    let mock = Arc::new(MockPaymentProvider::new("whsec_test"));
+    let app = test::init_service(init_app(&pool, mock.clone())).await;
+
+    let req = test::TestRequest::put()
+        .uri("/api/payment-terms")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.supplier_token)))
+        .set_json(json!({ "buyer_company_id": fixture.buyer_company_id, "net_days": 30 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let terms: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(terms["net_days"], 30);
+
+    // 额度由管理员核定，这里直接写库；订单改成账期订单
+    sqlx::query("UPDATE companies SET credit_limit = 1000.00, reporting_currency = 'EUR' WHERE id = ?")
+        .bind(fixture.buyer_company_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE purchase_orders SET payment_terms_days = 30 WHERE id = ?")
+        .bind(fixture.order_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let credit = |token: String| {
+        let req = test::TestRequest::get()
+            .uri("/api/payment-terms/credit")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .to_request();
+        test::call_service(&app, req)
+    };
+    let body: serde_json::Value = test::read_body_json(credit(fixture.buyer_token.clone()).await).await;
+    assert_eq!(body["exposure"], "125.50");
+    assert_eq!(body["available"], "874.50");
+
+    // 买方打开过在线支付页面，线下付款后这个会话要作废
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/create-checkout-session", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let session_id = body["session_id"].as_str().unwrap().to_string();
+
+    // 只有供应商能确认收款
+    let mark_paid = |token: &str| {
+        test::TestRequest::post()
+            .uri(&format!("/api/orders/{}/mark-paid", fixture.order_id))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .set_json(json!({ "remittance_reference": "BANK-TRF-0042" }))
+            .to_request()
+    };
+    let resp = test::call_service(&app, mark_paid(&fixture.buyer_token)).await;
+    assert_eq!(resp.status(), 400);
+    let resp = test::call_service(&app, mark_paid(&fixture.supplier_token)).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(payment_status(&pool, fixture.order_id).await, "PAID");
+    let (reference,): (Option<String>,) = sqlx::query_as("SELECT remittance_reference FROM purchase_orders WHERE id = ?")
+        .bind(fixture.order_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(reference.as_deref(), Some("BANK-TRF-0042"));
+    assert!(mock.session(&session_id).unwrap().expired);
+
+    // 付清后额度释放，不能重复标记
+    let body: serde_json::Value = test::read_body_json(credit(fixture.buyer_token.clone()).await).await;
+    assert_eq!(body["exposure"], "0.00");
+    let resp = test::call_service(&app, mark_paid(&fixture.supplier_token)).await;
+    assert_eq!(resp.status(), 400);
+
+    cleanup(&pool, &fixture).await;
+}
+
+#[actix_web::test]
+async fn test_invoicing_expires_open_order_checkout() {
+    let pool = config::configure_test_db().await;
+    let fixture = setup_order(&pool).await;
+    let mock = Arc::new(MockPaymentProvider::new("whsec_test"));
+    let app = test::init_service(init_app(&pool, mock.clone())).await;
+
+    let checkout_order = || test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/create-checkout-session", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, checkout_order()).await).await;
+    let order_session = body["session_id"].as_str().unwrap().to_string();
+
+    // 开票后整单付款的会话作废，只能按发票付款
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/invoices", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.supplier_token)))
+        .set_json(json!({}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(mock.session(&order_session).unwrap().expired);
+    let (session,): (Option<String>,) = sqlx::query_as("SELECT stripe_session_id FROM purchase_orders WHERE id = ?")
+        .bind(fixture.order_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(session, None);
+    assert_eq!(test::call_service(&app, checkout_order()).await.status(), 400);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/invoices/{}/create-checkout-session", body["invoice_id"]))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+    cleanup(&pool, &fixture).await;
+}
+
+#[actix_web::test]
+async fn test_accepted_cancellation_expires_open_checkout() {
+    let pool = config::configure_test_db().await;
+    let fixture = setup_order(&pool).await;
+    let mock = Arc::new(MockPaymentProvider::new("whsec_test"));
+    let app = test::init_service(init_app(&pool, mock.clone())).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/create-checkout-session", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let order_session = body["session_id"].as_str().unwrap().to_string();
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/cancellation-requests", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .set_json(json!({ "reason": "Project cancelled" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 201);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+
+    // 同意取消后支付页面不能再付款
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/orders/{}/cancellation-requests/{}", fixture.order_id, body["request_id"]))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.supplier_token)))
+        .set_json(json!({ "accept": true }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    assert!(mock.session(&order_session).unwrap().expired);
+    let (status, session): (String, Option<String>) = sqlx::query_as("SELECT status, stripe_session_id FROM purchase_orders WHERE id = ?")
+        .bind(fixture.order_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "CANCELLED");
+    assert_eq!(session, None);
+
+    cleanup(&pool, &fixture).await;
+}
+
+#[actix_web::test]
+async fn test_payment_after_cancellation_opens_refund() {
+    let pool = config::configure_test_db().await;
+    let fixture = setup_order(&pool).await;
+    let mock = Arc::new(MockPaymentProvider::new("whsec_test"));
+    let app = test::init_service(init_app(&pool, mock.clone())).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/create-checkout-session", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let session_id = body["session_id"].as_str().unwrap().to_string();
+
+    // 会话没能作废，订单取消后买方才付款
+    sqlx::query("UPDATE purchase_orders SET status = 'CANCELLED' WHERE id = ?")
+        .bind(fixture.order_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (payload, signature) = mock.complete_checkout(&session_id);
+    let req = test::TestRequest::post()
+        .uri("/api/stripe/webhook")
+        .insert_header(("Stripe-Signature", signature))
+        .set_payload(payload)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), 200);
+    assert_eq!(payment_status(&pool, fixture.order_id).await, "REFUND_PENDING");
+
+    let (amount, status): (Decimal, String) = sqlx::query_as("SELECT amount, status FROM refunds WHERE order_id = ?")
+        .bind(fixture.order_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(amount, Decimal::from_str("125.50").unwrap());
+    assert_eq!(status, "REQUESTED");
+
+    cleanup(&pool, &fixture).await;
+}
@@ -0,0 +1,94 @@
+#![cfg(test)]
+
+use super::payment_test::{cleanup, init_app, setup_order};
+use crate::{
+    config,
+    models::{dispute::ResolveDisputeDto, user::Claims},
+    services::{chat_server::ChatServer, dispute_service, payment_provider::MockPaymentProvider},
+};
+use actix::Actor;
+use actix_web::{test, http::header};
+use sqlx::MySqlPool;
+use std::sync::Arc;
+
+const BOUNDARY: &str = "sccp-shipment-test";
+
+// 手工拼 multipart 表单，只有文本字段
+fn shipment_form(quantity: i32, tracking_number: &str) -> String {
+    let fields = [("carrier", "DHL".to_string()), ("tracking_number", tracking_number.to_string()), ("quantity", quantity.to_string())];
+    let mut body = String::new();
+    for (name, value) in fields {
+        body.push_str(&format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", BOUNDARY, name, value));
+    }
+    body.push_str(&format!("--{}--\r\n", BOUNDARY));
+    body
+}
+
+async fn order_status(pool: &MySqlPool, order_id: i32) -> String {
+    let (status,): (String,) = sqlx::query_as("SELECT status FROM purchase_orders WHERE id = ?")
+        .bind(order_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    status
+}
+
+#[actix_web::test]
+async fn test_rework_allows_reshipping() {
+    let pool = config::configure_test_db().await;
+    let fixture = setup_order(&pool).await;
+    let chat_server = ChatServer::default().start();
+    sqlx::query("UPDATE purchase_orders SET status = 'IN_PRODUCTION' WHERE id = ?")
+        .bind(fixture.order_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let app = test::init_service(init_app(&pool, Arc::new(MockPaymentProvider::new("whsec_test")))).await;
+    let ship = |quantity: i32, tracking_number: &str| test::TestRequest::post()
+        .uri(&format!("/api/orders/{}/shipments", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.supplier_token)))
+        .insert_header((header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", BOUNDARY)))
+        .set_payload(shipment_form(quantity, tracking_number))
+        .to_request();
+
+    // 10件一次发完
+    assert_eq!(test::call_service(&app, ship(10, "FIRST-1")).await.status(), 201);
+    assert_eq!(order_status(&pool, fixture.order_id).await, "SHIPPED");
+
+    // 采购方发起争议，管理员判返工
+    let (buyer_user_id,): (i32,) = sqlx::query_as("SELECT id FROM users WHERE company_id = ?")
+        .bind(fixture.buyer_company_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let dispute_id = sqlx::query("INSERT INTO disputes (order_id, opened_by_company_id, opened_by_user_id, reason) VALUES (?, ?, ?, 'Parts out of tolerance')")
+        .bind(fixture.order_id)
+        .bind(fixture.buyer_company_id)
+        .bind(buyer_user_id)
+        .execute(&pool)
+        .await
+        .unwrap()
+        .last_insert_id() as i32;
+    let admin = Claims { sub: buyer_user_id, company_id: fixture.buyer_company_id, company_type: "BUYER".to_string(), is_admin: true, exp: 0 };
+    let dto = ResolveDisputeDto { resolution: "REWORK".to_string(), note: None, refund_amount: None };
+    dispute_service::resolve_dispute(&pool, &chat_server, dispute_id, dto, &admin).await.unwrap();
+    assert_eq!(order_status(&pool, fixture.order_id).await, "IN_PRODUCTION");
+
+    // 返工前的发货作废，可以重新分批发满10件，但不能超发
+    assert_eq!(test::call_service(&app, ship(11, "REWORK-0")).await.status(), 400);
+    assert_eq!(test::call_service(&app, ship(4, "REWORK-1")).await.status(), 201);
+    assert_eq!(order_status(&pool, fixture.order_id).await, "IN_PRODUCTION");
+    assert_eq!(test::call_service(&app, ship(6, "REWORK-2")).await.status(), 201);
+    assert_eq!(order_status(&pool, fixture.order_id).await, "SHIPPED");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/orders/{}/shipments", fixture.order_id))
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", fixture.buyer_token)))
+        .to_request();
+    let shipments: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(shipments["shipped_quantity"], 10);
+    assert_eq!(shipments["shipments"].as_array().unwrap().len(), 3);
+
+    cleanup(&pool, &fixture).await;
+}
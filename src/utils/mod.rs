@@ -1 +1,3 @@
-pub mod auth_utils;
\ No newline at end of file
+pub mod auth_utils;
+pub mod upload_utils;
+pub mod pdf_utils;
\ No newline at end of file
@@ -0,0 +1,167 @@
+// src/utils/pdf_utils.rs
+// 简单的文字排版PDF生成器，用于PO、发票等业务文件。使用内置Helvetica字体，不需要字体文件
+use crate::errors::AppError;
+use printpdf::{BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Point};
+
+const PAGE_WIDTH: f32 = 210.0; // A4
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+
+/// 内置字体只支持 WinAnsi 编码，超出范围的字符替换为 '?'
+fn printable(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\n' | '\r' | '\t' => ' ',
+            c if (c as u32) < 0x20 || (c as u32) > 0xFF => '?',
+            c => c,
+        })
+        .collect()
+}
+
+/// 按最大字符数折行，尽量在空格处断开
+fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut current = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word = word.to_string();
+            // 单个词超过一行时硬切
+            while word.chars().count() > max_chars {
+                if !current.is_empty() {
+                    lines.push(std::mem::take(&mut current));
+                }
+                let head: String = word.chars().take(max_chars).collect();
+                word = word.chars().skip(max_chars).collect();
+                lines.push(head);
+            }
+            if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > max_chars {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(&word);
+        }
+        lines.push(current);
+    }
+    lines
+}
+
+pub struct PdfBuilder {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    // 当前行的纵坐标(mm)，从页面顶部往下写
+    y: f32,
+}
+
+impl PdfBuilder {
+    pub fn new(title: &str) -> Result<Self, AppError> {
+        let (doc, page, layer) = PdfDocument::new(printable(title), Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        let regular = doc
+            .add_builtin_font(BuiltinFont::Helvetica)
+            .map_err(|e| AppError::InternalServerError(format!("Failed to load PDF font: {}", e)))?;
+        let bold = doc
+            .add_builtin_font(BuiltinFont::HelveticaBold)
+            .map_err(|e| AppError::InternalServerError(format!("Failed to load PDF font: {}", e)))?;
+        let layer = doc.get_page(page).get_layer(layer);
+        Ok(Self { doc, layer, regular, bold, y: PAGE_HEIGHT - MARGIN })
+    }
+
+    fn ensure_space(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+    }
+
+    fn line_height(size: f32) -> f32 {
+        size * 0.5
+    }
+
+    pub fn heading(&mut self, text: &str, size: f32) {
+        let height = Self::line_height(size) + 2.0;
+        self.ensure_space(height);
+        self.y -= height;
+        self.layer.use_text(printable(text), size, Mm(MARGIN), Mm(self.y), &self.bold);
+    }
+
+    /// 普通段落，自动折行
+    pub fn paragraph(&mut self, text: &str, size: f32) {
+        let max_chars = ((PAGE_WIDTH - 2.0 * MARGIN) / (size * 0.19)) as usize;
+        for line in wrap(text, max_chars.max(10)) {
+            self.ensure_space(Self::line_height(size));
+            self.y -= Self::line_height(size);
+            self.layer.use_text(printable(&line), size, Mm(MARGIN), Mm(self.y), &self.regular);
+        }
+    }
+
+    /// "标签: 值" 形式的一行
+    pub fn field(&mut self, label: &str, value: &str, size: f32) {
+        self.ensure_space(Self::line_height(size));
+        self.y -= Self::line_height(size);
+        self.layer.use_text(printable(label), size, Mm(MARGIN), Mm(self.y), &self.bold);
+        self.layer.use_text(printable(value), size, Mm(MARGIN + 45.0), Mm(self.y), &self.regular);
+    }
+
+    /// 表格行：columns 为 (距左边距的mm, 文本)
+    pub fn row(&mut self, columns: &[(f32, &str)], size: f32, bold: bool) {
+        self.ensure_space(Self::line_height(size));
+        self.y -= Self::line_height(size);
+        let font = if bold { &self.bold } else { &self.regular };
+        for (x, text) in columns {
+            self.layer.use_text(printable(text), size, Mm(MARGIN + x), Mm(self.y), font);
+        }
+    }
+
+    /// 横线分隔
+    pub fn rule(&mut self) {
+        self.ensure_space(4.0);
+        self.y -= 2.0;
+        let line = Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(self.y)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(self.y)), false),
+            ],
+            is_closed: false,
+        };
+        self.layer.add_line(line);
+        self.y -= 2.0;
+    }
+
+    pub fn space(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, AppError> {
+        self.doc
+            .save_to_bytes()
+            .map_err(|e| AppError::InternalServerError(format!("Failed to render PDF: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_and_printable() {
+        assert_eq!(wrap("one two three four", 9), vec!["one two", "three", "four"]);
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(printable("Müller 零件\n"), "Müller ?? ");
+    }
+
+    #[test]
+    fn test_renders_pdf() {
+        let mut pdf = PdfBuilder::new("Test").unwrap();
+        pdf.heading("PURCHASE ORDER", 18.0);
+        pdf.rule();
+        for i in 0..200 {
+            pdf.field("Line", &i.to_string(), 10.0);
+        }
+        let bytes = pdf.finish().unwrap();
+        assert!(bytes.starts_with(b"%PDF"));
+    }
+}
@@ -0,0 +1,127 @@
+// src/utils/upload_utils.rs
+use crate::errors::AppError;
+use actix_files::file_extension_to_mime;
+use actix_multipart::Multipart;
+use actix_web::web;
+use futures_util::stream::StreamExt;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use uuid::Uuid;
+
+/// 证据、照片、装箱单等业务附件允许的后缀
+pub const DOCUMENT_EXTENSIONS: &[&str] = &["pdf", "png", "jpg", "jpeg", "xlsx", "xls", "csv", "doc", "docx", "txt"];
+/// 照片类附件允许的后缀
+pub const PHOTO_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp"];
+/// 单个业务附件最大体积
+pub const MAX_DOCUMENT_SIZE_BYTES: usize = 20 * 1024 * 1024; // 20 MB
+/// 业务附件的存放目录。只能通过校验权限的下载接口读取，不能放在公开的 ./uploads 下
+const PRIVATE_UPLOADS_DIR: &str = "./private_uploads";
+
+#[derive(Debug)]
+pub struct SavedFile {
+    pub original_filename: String,
+    pub stored_path: String,
+}
+
+/// 删除已经保存的文件。上传后登记失败时调用，避免留下没有记录引用的文件
+pub fn remove_saved_files(files: &[SavedFile]) {
+    for file in files {
+        if let Err(e) = fs::remove_file(&file.stored_path) {
+            log::warn!("Failed to remove orphaned upload {}: {}", file.stored_path, e);
+        }
+    }
+}
+
+/// 解析multipart请求：文件保存到 ./private_uploads/{subdir}/，普通文本字段按字段名返回。
+/// 中途出错时已经保存的文件都会删掉
+pub async fn save_multipart(
+    payload: Multipart,
+    subdir: &str,
+    allowed_extensions: &[&str],
+    max_size: usize,
+) -> Result<(HashMap<String, String>, Vec<SavedFile>), AppError> {
+    let dir = format!("{}/{}", PRIVATE_UPLOADS_DIR, subdir);
+    fs::create_dir_all(&dir)?;
+
+    let mut fields = HashMap::new();
+    let mut files = Vec::new();
+    match read_fields(payload, &dir, allowed_extensions, max_size, &mut fields, &mut files).await {
+        Ok(()) => Ok((fields, files)),
+        Err(e) => {
+            remove_saved_files(&files);
+            Err(e)
+        }
+    }
+}
+
+/// 读取已保存的附件，返回内容和按后缀推断的Content-Type
+pub async fn read_saved_file(stored_path: &str, original_filename: &str) -> Result<(String, Vec<u8>), AppError> {
+    let path = stored_path.to_string();
+    let content = web::block(move || fs::read(path)).await??;
+    let ext = original_filename.rsplit_once('.').map(|(_, ext)| ext).unwrap_or_default();
+    Ok((file_extension_to_mime(ext).to_string(), content))
+}
+
+async fn read_fields(
+    mut payload: Multipart,
+    dir: &str,
+    allowed_extensions: &[&str],
+    max_size: usize,
+    fields: &mut HashMap<String, String>,
+    files: &mut Vec<SavedFile>,
+) -> Result<(), AppError> {
+    while let Some(field_result) = payload.next().await {
+        let mut field = field_result?;
+
+        let field_name = field.name().unwrap_or_default().to_string();
+        let filename = field
+            .content_disposition()
+            .and_then(|cd| cd.get_filename().map(|s| s.to_string()));
+
+        let Some(filename) = filename else {
+            // 文本字段
+            let mut data = Vec::new();
+            while let Some(chunk) = field.next().await {
+                data.extend_from_slice(&chunk?);
+            }
+            let value = String::from_utf8(data)
+                .map_err(|_| AppError::BadRequest("Invalid UTF-8 in form fields".to_string()))?;
+            fields.insert(field_name, value);
+            continue;
+        };
+
+        // 扩展名校验
+        let ext = filename
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_lowercase())
+            .ok_or_else(|| AppError::BadRequest("Attachment must have a file extension".to_string()))?;
+        if !allowed_extensions.contains(&ext.as_str()) {
+            return Err(AppError::BadRequest(format!("Unsupported attachment type: {}", ext)));
+        }
+
+        // 只保留文件名本身，防止路径穿越
+        let safe_name = filename.rsplit(['/', '\\']).next().unwrap_or("file").to_string();
+        let filepath = format!("{}/{}-{}", dir, Uuid::new_v4(), safe_name);
+
+        let filepath_clone = filepath.clone();
+        let mut f = web::block(move || fs::File::create(filepath_clone)).await??;
+        // 先记下路径再写入，写到一半出错时这个文件也会被删掉
+        files.push(SavedFile { original_filename: safe_name, stored_path: filepath });
+
+        let mut total_size: usize = 0;
+        while let Some(chunk) = field.next().await {
+            let data = chunk?;
+            total_size += data.len();
+            if total_size > max_size {
+                return Err(AppError::BadRequest(format!(
+                    "Attachment exceeds maximum size of {} bytes",
+                    max_size
+                )));
+            }
+            f = web::block(move || f.write_all(&data).map(|_| f)).await??;
+        }
+    }
+
+    Ok(())
+}